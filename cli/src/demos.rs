@@ -13,27 +13,20 @@
 // License for the specific language governing permissions and limitations
 // under the License.
 
-//! Exposes EndBASIC demos as an overlay to the store.
+//! Exposes EndBASIC demos as a read-only store.
 
 use endbasic_std::store::{Metadata, Store};
 use std::collections::{BTreeMap, HashMap};
 use std::io;
 use std::str;
 
-/// Wraps a `Store` and exposes a bunch of read-only demo files.
+/// A read-only `Store` that exposes a bunch of demo files.
 ///
-/// All demo file names are case insensitive.  However, this preserves the case sensitiveness
-/// behavior of the underlying store for any files that are passed through.
-///
-/// This takes ownership of any file names that start with `DEMO:`, which means any such files in
-/// the underlying store become invisible.  This should not be a problem in practice because most
-/// file systems deny the `:` character in file names.
-pub struct DemoStoreOverlay<S: Store> {
+/// All demo file names are case insensitive.  This store is meant to be mounted as the `DEMO`
+/// drive so that the demos can be referenced as `DEMO:NAME.BAS`.
+pub struct DemoStore {
     /// The demos to expose, expressed as a mapping of names to (metadata, content) pairs.
     demos: HashMap<&'static str, (Metadata, String)>,
-
-    /// The wrapped store.
-    delegate: S,
 }
 
 /// Converts the raw bytes of a demo file into the program string to expose.
@@ -52,9 +45,9 @@ fn process_demo(bytes: &[u8]) -> String {
     }
 }
 
-impl<S: Store> DemoStoreOverlay<S> {
-    /// Creates a new demo store that wraps the `delegate` store.
-    pub fn new(delegate: S) -> Self {
+impl Default for DemoStore {
+    /// Creates a new demo store with all the built-in demos.
+    fn default() -> Self {
         let mut demos = HashMap::default();
         {
             let content = process_demo(include_bytes!("../examples/guess.bas"));
@@ -62,7 +55,7 @@ impl<S: Store> DemoStoreOverlay<S> {
                 date: time::OffsetDateTime::from_unix_timestamp(1608693152),
                 length: content.len() as u64,
             };
-            demos.insert("DEMO:GUESS.BAS", (metadata, content));
+            demos.insert("GUESS.BAS", (metadata, content));
         }
         {
             let content = process_demo(include_bytes!("../examples/hello.bas"));
@@ -70,7 +63,7 @@ impl<S: Store> DemoStoreOverlay<S> {
                 date: time::OffsetDateTime::from_unix_timestamp(1608646800),
                 length: content.len() as u64,
             };
-            demos.insert("DEMO:HELLO.BAS", (metadata, content));
+            demos.insert("HELLO.BAS", (metadata, content));
         }
         {
             let content = process_demo(include_bytes!("../examples/tour.bas"));
@@ -78,45 +71,19 @@ impl<S: Store> DemoStoreOverlay<S> {
                 date: time::OffsetDateTime::from_unix_timestamp(1608774770),
                 length: content.len() as u64,
             };
-            demos.insert("DEMO:TOUR.BAS", (metadata, content));
+            demos.insert("TOUR.BAS", (metadata, content));
         }
-        Self { demos, delegate }
-    }
-
-    /// Disowns and returns the underlying delegate store.
-    pub fn unmount(self) -> S {
-        self.delegate
+        Self { demos }
     }
 }
 
-impl<S: Store> Store for DemoStoreOverlay<S> {
-    fn delete(&mut self, name: &str) -> io::Result<()> {
-        let uc_name = name.to_ascii_uppercase();
-        match self.demos.get(&uc_name.as_ref()) {
-            Some(_) => {
-                Err(io::Error::new(io::ErrorKind::PermissionDenied, "Demo files are read-only"))
-            }
-            _ if uc_name.starts_with("DEMO:") => {
-                Err(io::Error::new(io::ErrorKind::PermissionDenied, "Demo files are read-only"))
-            }
-            _ => self.delegate.delete(name),
-        }
+impl Store for DemoStore {
+    fn delete(&mut self, _name: &str) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "Demo files are read-only"))
     }
 
     fn enumerate(&self) -> io::Result<BTreeMap<String, Metadata>> {
-        let mut entries = self.delegate.enumerate()?;
-
-        // TODO(https://github.com/rust-lang/rust/issues/70530): Use drain_filter when available.
-        let mut hidden_names = vec![];
-        for (name, _) in entries.iter() {
-            if name.to_ascii_uppercase().starts_with("DEMO:") {
-                hidden_names.push(name.to_owned());
-            }
-        }
-        for name in hidden_names {
-            entries.remove(&name);
-        }
-
+        let mut entries = BTreeMap::default();
         for (name, (metadata, _content)) in self.demos.iter() {
             entries.insert(name.to_string(), metadata.clone());
         }
@@ -130,134 +97,61 @@ impl<S: Store> Store for DemoStoreOverlay<S> {
                 let (_metadata, content) = value;
                 Ok(content.to_string())
             }
-            _ if uc_name.starts_with("DEMO:") => {
-                Err(io::Error::new(io::ErrorKind::NotFound, "Non-existing demo file"))
-            }
-            _ => self.delegate.get(name),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "Non-existing demo file")),
         }
     }
 
-    fn put(&mut self, name: &str, content: &str) -> io::Result<()> {
-        let uc_name = name.to_ascii_uppercase();
-        match self.demos.get(&uc_name.as_ref()) {
-            Some(_) => {
-                Err(io::Error::new(io::ErrorKind::PermissionDenied, "Demo files are read-only"))
-            }
-            _ if uc_name.starts_with("DEMO:") => {
-                Err(io::Error::new(io::ErrorKind::PermissionDenied, "Demo files are read-only"))
-            }
-            _ => self.delegate.put(name, content),
-        }
+    fn put(&mut self, _name: &str, _content: &str) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "Demo files are read-only"))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use endbasic_std::store::InMemoryStore;
 
     #[test]
-    fn test_demo_store_overlay_delete() {
-        let mut store = InMemoryStore::default();
-        store.put("delete.bas", "underlying file").unwrap();
-        store.put("keep.bas", "underlying file").unwrap();
-        store.put("demo:unknown.bas", "should not be touched").unwrap();
-        let store = {
-            let mut store = DemoStoreOverlay::new(store);
-
-            store.delete("delete.bas").unwrap();
-            assert_eq!(io::ErrorKind::NotFound, store.delete("KEEP.Bas").unwrap_err().kind());
-
-            assert_eq!(
-                io::ErrorKind::PermissionDenied,
-                store.delete("demo:hello.bas").unwrap_err().kind()
-            );
-            assert_eq!(
-                io::ErrorKind::PermissionDenied,
-                store.delete("DEMO:Hello.BAS").unwrap_err().kind()
-            );
-
-            assert_eq!(
-                io::ErrorKind::PermissionDenied,
-                store.delete("demo:unknown.bas").unwrap_err().kind()
-            );
-
-            store.unmount()
-        };
-        assert_eq!(io::ErrorKind::NotFound, store.get("delete.bas").unwrap_err().kind());
-        assert_eq!("underlying file", store.get("keep.bas").unwrap());
-        assert_eq!(io::ErrorKind::NotFound, store.get("demo:hello.bas").unwrap_err().kind());
-        assert_eq!("should not be touched", store.get("demo:unknown.bas").unwrap());
+    fn test_demo_store_delete() {
+        let mut store = DemoStore::default();
+        assert_eq!(io::ErrorKind::PermissionDenied, store.delete("hello.bas").unwrap_err().kind());
+        assert_eq!(io::ErrorKind::PermissionDenied, store.delete("Hello.BAS").unwrap_err().kind());
+        assert_eq!(
+            io::ErrorKind::PermissionDenied,
+            store.delete("unknown.bas").unwrap_err().kind()
+        );
+        assert!(store.get("hello.bas").is_ok());
     }
 
     #[test]
-    fn test_demo_store_overlay_enumerate() {
-        let mut store = InMemoryStore::default();
-        store.put("under.bas", "underlying file").unwrap();
-        store.put("demo:hidden.bas", "will be clobbered").unwrap();
-        let store = DemoStoreOverlay::new(store);
-
+    fn test_demo_store_enumerate() {
+        let store = DemoStore::default();
         let entries = store.enumerate().unwrap();
-        assert!(entries.contains_key("under.bas"));
-        assert!(entries.contains_key("DEMO:GUESS.BAS"));
-        assert!(entries.contains_key("DEMO:HELLO.BAS"));
-        assert!(entries.contains_key("DEMO:TOUR.BAS"));
-        assert!(!entries.contains_key("DEMO:HIDDEN.BAS"));
-        assert!(!entries.contains_key("demo:hidden.bas"));
+        assert_eq!(vec!["GUESS.BAS", "HELLO.BAS", "TOUR.BAS"], entries.keys().collect::<Vec<_>>());
     }
 
     #[test]
-    fn test_demo_store_overlay_get() {
-        let mut store = InMemoryStore::default();
-        store.put("under.bas", "underlying file").unwrap();
-        store.put("demo:hidden.bas", "will be clobbered").unwrap();
-        let store = DemoStoreOverlay::new(store);
-
-        assert_eq!("underlying file", store.get("under.bas").unwrap());
-        assert_eq!(io::ErrorKind::NotFound, store.get("Under.bas").unwrap_err().kind());
-
+    fn test_demo_store_get() {
+        let store = DemoStore::default();
         assert_eq!(
             process_demo(include_bytes!("../examples/hello.bas")),
-            store.get("demo:hello.bas").unwrap()
+            store.get("hello.bas").unwrap()
         );
         assert_eq!(
             process_demo(include_bytes!("../examples/hello.bas")),
-            store.get("Demo:Hello.Bas").unwrap()
+            store.get("Hello.Bas").unwrap()
         );
-
-        assert_eq!(io::ErrorKind::NotFound, store.get("demo:hidden.bas").unwrap_err().kind());
-        assert_eq!(io::ErrorKind::NotFound, store.get("demo:unknown.bas").unwrap_err().kind());
         assert_eq!(io::ErrorKind::NotFound, store.get("unknown.bas").unwrap_err().kind());
     }
 
     #[test]
-    fn test_demo_store_overlay_put() {
-        let mut store = InMemoryStore::default();
-        store.put("modify.bas", "previous contents").unwrap();
-        store.put("avoid.bas", "previous contents").unwrap();
-        let store = {
-            let mut store = DemoStoreOverlay::new(store);
-
-            store.put("modify.bas", "new contents").unwrap();
-
-            assert_eq!(
-                io::ErrorKind::PermissionDenied,
-                store.put("demo:hello.bas", "").unwrap_err().kind()
-            );
-            assert_eq!(
-                io::ErrorKind::PermissionDenied,
-                store.put("DEMO:Hello.BAS", "").unwrap_err().kind()
-            );
-
-            assert_eq!(
-                io::ErrorKind::PermissionDenied,
-                store.put("demo:unknown.bas", "").unwrap_err().kind()
-            );
-
-            store.unmount()
-        };
-        assert_eq!(io::ErrorKind::NotFound, store.get("demo:unknown.bas").unwrap_err().kind());
-        assert_eq!("new contents", store.get("modify.bas").unwrap());
-        assert_eq!("previous contents", store.get("avoid.bas").unwrap());
+    fn test_demo_store_put() {
+        let mut store = DemoStore::default();
+        assert_eq!(io::ErrorKind::PermissionDenied, store.put("hello.bas", "").unwrap_err().kind());
+        assert_eq!(io::ErrorKind::PermissionDenied, store.put("HELLO.BAS", "").unwrap_err().kind());
+        assert_eq!(
+            io::ErrorKind::PermissionDenied,
+            store.put("unknown.bas", "").unwrap_err().kind()
+        );
+        assert_eq!(io::ErrorKind::NotFound, store.get("unknown.bas").unwrap_err().kind());
     }
 }
@@ -22,7 +22,9 @@
 #![warn(unsafe_code)]
 
 use endbasic_core::exec::{Machine, StopReason};
-use endbasic_std::console::{self, Console};
+use endbasic_std::completion::MachineCompleter;
+use endbasic_std::console::{self, Console, History};
+use endbasic_std::format_error;
 use endbasic_std::store::Store;
 use futures_lite::future::block_on;
 use std::cell::RefCell;
@@ -58,7 +60,8 @@ pub fn try_load_autoexec(
             match block_on(machine.exec(&mut code.as_bytes())) {
                 Ok(_) => Ok(()),
                 Err(e) => {
-                    console.borrow_mut().print(&format!("AUTOEXEC.BAS failed: {}", e))?;
+                    let message = format_error(&e.in_program());
+                    console.borrow_mut().print(&format!("AUTOEXEC.BAS failed: {}", message))?;
                     Ok(())
                 }
             }
@@ -74,9 +77,17 @@ pub fn try_load_autoexec(
 ///
 /// The `console` provided here is used for the REPL prompt interaction and should match the
 /// console that's in use by the machine (if any).  They don't necessarily have to match though.
+///
+/// The `store` is used to complete file names while typing and should also match the store in use
+/// by the machine.
+///
+/// The `history` records the lines entered by the user and should match the history given to the
+/// machine for its `HISTORY` command.  Lines of the form `!n` run entry `n` of the history again.
 pub async fn run_repl_loop(
     machine: &mut Machine,
     console: Rc<RefCell<dyn Console>>,
+    store: Rc<RefCell<dyn Store>>,
+    history: Rc<RefCell<History>>,
 ) -> io::Result<i32> {
     let mut stop_reason = StopReason::Eof;
     while stop_reason == StopReason::Eof {
@@ -85,16 +96,34 @@ pub async fn run_repl_loop(
             if console.is_interactive() {
                 console.print("Ready")?;
             }
-            console::read_line(&mut *console, "", "").await
+            let completer = MachineCompleter::new(machine, store.clone());
+            let mut history = history.borrow_mut();
+            match console::read_line(&mut *console, "", "", Some(&mut history), Some(&completer))
+                .await
+            {
+                Ok(line) => match history.expand(line.clone()) {
+                    Ok(expanded) => {
+                        if expanded != line {
+                            // Show the recalled line as if the user had typed it.
+                            console.print(&expanded)?;
+                        }
+                        Ok(expanded)
+                    }
+                    Err(e) => {
+                        console.print(&format!("ERROR: {}", e))?;
+                        continue;
+                    }
+                },
+                Err(e) => Err(e),
+            }
         };
 
         match line {
             Ok(line) => match machine.exec(&mut line.as_bytes()).await {
+                // END only terminates the program that is running, not the interpreter.
+                Ok(StopReason::End(_)) => (),
                 Ok(reason) => stop_reason = reason,
-                Err(e) => {
-                    let mut console = console.borrow_mut();
-                    console.print(format!("ERROR: {}", e).as_str())?;
-                }
+                Err(e) => console.borrow_mut().print(&format_error(&e))?,
             },
             Err(e) => {
                 if e.kind() == io::ErrorKind::Interrupted {
@@ -147,7 +176,7 @@ mod tests {
             .expect_var("after", 5)
             .expect_prints([
                 "Loading AUTOEXEC.BAS...",
-                "AUTOEXEC.BAS failed: Undefined variable undef",
+                "AUTOEXEC.BAS failed: ERROR at line 1: Undefined variable undef",
             ])
             .expect_file("AUTOEXEC.BAS", autoexec)
             .check();
@@ -22,31 +22,20 @@
 #![warn(unsafe_code)]
 
 use anyhow::{anyhow, Result};
-use endbasic_std::store::{FileStore, Store};
+use endbasic_core::exec::{InterruptSource, Machine};
+use endbasic_std::console::History;
+use endbasic_std::store::{DriveCommand, DriveStore, FileStore, InMemoryStore, Store};
 use endbasic_std::terminal::TerminalConsole;
 use futures_lite::future::block_on;
 use getopts::Options;
 use std::cell::RefCell;
 use std::env;
 use std::fs::File;
-use std::io;
 use std::path::{Path, PathBuf};
 use std::process;
 use std::rc::Rc;
-
-/// Errors caused by the user when invoking this binary (invalid options or arguments).
-#[derive(Debug, thiserror::Error)]
-#[error("{message}")]
-struct UsageError {
-    message: String,
-}
-
-impl UsageError {
-    /// Creates a new usage error with `message`.
-    fn new<T: Into<String>>(message: T) -> Self {
-        Self { message: message.into() }
-    }
-}
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 /// Consumes and returns the program name from `env::Args`.
 ///
@@ -67,7 +56,7 @@ fn program_name(mut args: env::Args, default_name: &'static str) -> (String, env
 
 /// Prints usage information for program `name` with `opts` following the GNU Standards format.
 fn help(name: &str, opts: &Options) -> Result<i32> {
-    let brief = format!("Usage: {} [options] [program-file]", name);
+    let brief = format!("Usage: {} [options] [program-file [program-args]]", name);
     println!("{}", opts.usage(&brief));
     println!("Report bugs to: https://github.com/jmmv/endbasic/issues");
     println!("EndBASIC home page: https://github.com/jmmv/endbasic");
@@ -104,46 +93,98 @@ fn get_programs_dir(flag: Option<String>) -> Result<PathBuf> {
     Ok(dir.unwrap())
 }
 
-/// Creates a new store backed by `dir` and overlays the built-in demos.
-fn new_store_with_demos(dir: &Path) -> Rc<RefCell<dyn Store>> {
-    if dir == Path::new(":memory:") {
-        Rc::from(RefCell::from(endbasic::demos::DemoStoreOverlay::new(
-            endbasic_std::store::InMemoryStore::default(),
-        )))
-    } else {
-        Rc::from(RefCell::from(endbasic::demos::DemoStoreOverlay::new(FileStore::new(dir))))
+/// Interruption source backed by a flag that the process' `SIGINT` handler raises.
+struct SignalInterrupt(Arc<AtomicBool>);
+
+impl InterruptSource for SignalInterrupt {
+    fn take_interrupt(&self) -> bool {
+        self.0.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// Installs a Ctrl-C handler that interrupts any program running in `machine`.
+///
+/// This only takes effect while the terminal is not in raw mode, which is the case while programs
+/// run: when the console reads keys, Ctrl-C is delivered as a key press instead.
+fn install_interrupt_handler(machine: &mut Machine) -> Result<()> {
+    let flag = Arc::from(AtomicBool::new(false));
+    {
+        let flag = flag.clone();
+        ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))?;
     }
+    machine.set_interrupt_source(Rc::from(SignalInterrupt(flag)));
+    Ok(())
+}
+
+/// Creates a new set of drives for the interpreter: `LOCAL`, backed by `dir`, `DEMO`, which holds
+/// the read-only built-in demos, and `MEMORY`, a scratch drive that is lost on exit.
+fn new_drives(dir: &Path) -> Rc<RefCell<DriveStore>> {
+    let local: Box<dyn Store> = if dir == Path::new(":memory:") {
+        Box::from(InMemoryStore::default())
+    } else {
+        Box::from(FileStore::new(dir))
+    };
+    let mut drives = DriveStore::new("LOCAL", local);
+    drives
+        .mount("DEMO", Box::from(endbasic::demos::DemoStore::default()))
+        .expect("Drive names must be unique");
+    drives
+        .mount("MEMORY", Box::from(InMemoryStore::default()))
+        .expect("Drive names must be unique");
+    Rc::from(RefCell::from(drives))
 }
 
 /// Enters the interactive interpreter.
 ///
 /// `dir` specifies the directory that the interpreter will use for any commands that manipulate
 /// files.  The special name `:memory:` makes the interpreter use an in-memory only store.
-fn run_repl_loop(dir: &Path) -> io::Result<i32> {
+fn run_repl_loop(dir: &Path) -> Result<i32> {
     let console = Rc::from(RefCell::from(TerminalConsole::from_stdio()?));
-    let store = new_store_with_demos(dir);
-    let mut machine = endbasic_std::interactive_machine(console.clone(), store.clone());
+    let drives = new_drives(dir);
+    let store: Rc<RefCell<dyn Store>> = drives.clone();
+    let history = Rc::from(RefCell::from(History::default()));
+    let mut machine =
+        endbasic_std::interactive_machine(console.clone(), store.clone(), history.clone(), vec![]);
+    machine.add_command(DriveCommand::new(console.clone(), drives))?;
+    endbasic_std::shell::add_all(&mut machine)?;
+    install_interrupt_handler(&mut machine)?;
     endbasic::print_welcome(console.clone())?;
-    endbasic::try_load_autoexec(&mut machine, console.clone(), store)?;
-    block_on(endbasic::run_repl_loop(&mut machine, console))
+    endbasic::try_load_autoexec(&mut machine, console.clone(), store.clone())?;
+    Ok(block_on(endbasic::run_repl_loop(&mut machine, console, store, history))?)
 }
 
-/// Executes the `path` program in a fresh machine.
-fn run_script<P: AsRef<Path>>(path: P) -> endbasic_core::exec::Result<i32> {
+/// Executes the `path` program in a fresh machine, passing it the given `args`.
+fn run_script<P: AsRef<Path>>(path: P, args: Vec<String>) -> Result<i32> {
     let console = Rc::from(RefCell::from(TerminalConsole::from_stdio()?));
-    let mut machine = endbasic_std::scripting_machine(console);
+    let mut machine = endbasic_std::scripting_machine(console, args);
+    endbasic_std::shell::add_all(&mut machine)?;
+    install_interrupt_handler(&mut machine)?;
     let mut input = File::open(path)?;
-    Ok(block_on(machine.exec(&mut input))?.as_exit_code())
+    match block_on(machine.exec(&mut input)) {
+        Ok(stop_reason) => Ok(stop_reason.as_exit_code()),
+        Err(e) => Err(anyhow!(endbasic_std::format_error(&e.in_program()))),
+    }
 }
 
 /// Executes the `path` program in a fresh machine allowing any interactive-only calls.
 ///
 /// `dir` has the same meaning as the parameter passed to `run_repl_loop`.
-fn run_interactive<P: AsRef<Path>>(path: P, dir: &Path) -> endbasic_core::exec::Result<i32> {
+fn run_interactive<P: AsRef<Path>>(path: P, args: Vec<String>, dir: &Path) -> Result<i32> {
     let console = Rc::from(RefCell::from(TerminalConsole::from_stdio()?));
-    let mut machine = endbasic_std::interactive_machine(console, new_store_with_demos(dir));
+    let drives = new_drives(dir);
+    let history = Rc::from(RefCell::from(History::default()));
+    let mut machine =
+        endbasic_std::interactive_machine(console.clone(), drives.clone(), history, args);
+    machine.add_command(DriveCommand::new(console, drives))?;
+    endbasic_std::shell::add_all(&mut machine)?;
+    install_interrupt_handler(&mut machine)?;
     let mut input = File::open(path)?;
-    Ok(block_on(machine.exec(&mut input))?.as_exit_code())
+    let result = block_on(machine.exec(&mut input));
+    let finish_result = machine.finish_program();
+    match result.and_then(|stop_reason| finish_result.map(|()| stop_reason)) {
+        Ok(stop_reason) => Ok(stop_reason.as_exit_code()),
+        Err(e) => Err(anyhow!(endbasic_std::format_error(&e.in_program()))),
+    }
 }
 
 /// Version of `main` that returns errors to the caller for reporting.
@@ -151,6 +192,9 @@ fn safe_main(name: &str, args: env::Args) -> Result<i32> {
     let args: Vec<String> = args.collect();
 
     let mut opts = Options::new();
+    // Everything after the program file belongs to the program, including anything that looks
+    // like one of our own flags.
+    opts.parsing_style(getopts::ParsingStyle::StopAtFirstFree);
     opts.optflag("h", "help", "show command-line usage information and exit");
     opts.optflag("i", "interactive", "force interactive mode when running a script");
     opts.optopt("", "programs-dir", "directory where user programs are stored", "PATH");
@@ -170,15 +214,15 @@ fn safe_main(name: &str, args: env::Args) -> Result<i32> {
             let programs_dir = get_programs_dir(matches.opt_str("programs-dir"))?;
             Ok(run_repl_loop(&programs_dir)?)
         }
-        [file] => {
+        [file, args @ ..] => {
+            let args = args.to_vec();
             if matches.opt_present("interactive") {
                 let programs_dir = get_programs_dir(matches.opt_str("programs-dir"))?;
-                Ok(run_interactive(file, &programs_dir)?)
+                Ok(run_interactive(file, args, &programs_dir)?)
             } else {
-                Ok(run_script(file)?)
+                Ok(run_script(file, args)?)
             }
         }
-        [_, ..] => Err(UsageError::new("Too many arguments").into()),
     }
 }
 
@@ -187,11 +231,7 @@ fn main() {
     let exit_code = match safe_main(&name, args) {
         Ok(code) => code,
         Err(e) => {
-            if let Some(e) = e.downcast_ref::<UsageError>() {
-                eprintln!("Usage error: {}", e);
-                eprintln!("Type {} --help for more information", name);
-                2
-            } else if let Some(e) = e.downcast_ref::<getopts::Fail>() {
+            if let Some(e) = e.downcast_ref::<getopts::Fail>() {
                 eprintln!("Usage error: {}", e);
                 eprintln!("Type {} --help for more information", name);
                 2
@@ -183,6 +183,18 @@ fn test_cli_autoexec_is_ignored() {
     );
 }
 
+#[test]
+fn test_cli_describe_frontend_commands() {
+    check(
+        bin_path("endbasic"),
+        &["-i", &src_str("cli/tests/cli/describe.bas")],
+        0,
+        Behavior::Null,
+        Behavior::File(src_path("cli/tests/cli/describe.out")),
+        Behavior::Null,
+    );
+}
+
 #[test]
 fn test_cli_help() {
     fn check_with_args(args: &[&str]) {
@@ -198,7 +210,7 @@ fn test_cli_help() {
     check_with_args(&["-h"]);
     check_with_args(&["--help"]);
     check_with_args(&["--version", "--help"]);
-    check_with_args(&["the", "--help", "flag always wins"]);
+    check_with_args(&["--help", "the", "flag always wins"]);
 }
 
 #[test]
@@ -253,29 +265,46 @@ fn test_cli_program_name_uses_arg0() {
     fs::copy(&original, &custom).unwrap();
     check(
         &custom,
-        &["one", "two", "three"],
+        &["-Z"],
         2,
         Behavior::Null,
         Behavior::Null,
         Behavior::Literal(
-            "Usage error: Too many arguments\nType custom-name --help for more information\n"
+            "Usage error: Unrecognized option: 'Z'\nType custom-name --help for more information\n"
                 .to_owned(),
         ),
     );
 }
 
 #[test]
-fn test_cli_too_many_args() {
+fn test_cli_program_args() {
     check(
-        &bin_path("endbasic"),
-        &["foo", "bar"],
-        2,
+        bin_path("endbasic"),
+        &[&src_str("cli/tests/cli/args.bas")],
+        0,
+        Behavior::Null,
+        Behavior::Literal("Got 0 arguments: \n".to_owned()),
+        Behavior::Null,
+    );
+
+    check(
+        bin_path("endbasic"),
+        &[&src_str("cli/tests/cli/args.bas"), "foo", "bar baz", "-i"],
+        0,
         Behavior::Null,
+        Behavior::File(src_path("cli/tests/cli/args.out")),
+        Behavior::Null,
+    );
+
+    check(
+        bin_path("endbasic"),
+        &[&src_str("cli/tests/cli/args.bas"), "-h", "--version"],
+        0,
         Behavior::Null,
         Behavior::Literal(
-            "Usage error: Too many arguments\nType endbasic --help for more information\n"
-                .to_owned(),
+            "Got 2 arguments: -h --version\nArgument 1 is -h\nArgument 2 is --version\n".to_owned(),
         ),
+        Behavior::Null,
     );
 }
 
@@ -307,7 +336,7 @@ fn test_cli_version() {
         );
     }
     check_with_args(&["--version"]);
-    check_with_args(&["the", "--version", "flag wins over arguments"]);
+    check_with_args(&["--version", "the", "flag wins over arguments"]);
 }
 
 #[test]
@@ -358,6 +387,30 @@ fn test_lang_control_flow() {
     );
 }
 
+#[test]
+fn test_lang_end() {
+    check(
+        bin_path("endbasic"),
+        &[&src_str("cli/tests/lang/end.bas")],
+        3,
+        Behavior::Null,
+        Behavior::File(src_path("cli/tests/lang/end.out")),
+        Behavior::Null,
+    );
+}
+
+#[test]
+fn test_lang_end_no_code() {
+    check(
+        bin_path("endbasic"),
+        &[&src_str("cli/tests/lang/end-no-code.bas")],
+        0,
+        Behavior::Null,
+        Behavior::File(src_path("cli/tests/lang/end-no-code.out")),
+        Behavior::Null,
+    );
+}
+
 #[test]
 fn test_lang_exec_error() {
     check(
@@ -504,6 +557,18 @@ fn test_repl_editor() {
     );
 }
 
+#[test]
+fn test_repl_end() {
+    check(
+        bin_path("endbasic"),
+        &[],
+        0,
+        Behavior::File(src_path("cli/tests/repl/end.in")),
+        Behavior::File(src_path("cli/tests/repl/end.out")),
+        Behavior::Null,
+    );
+}
+
 #[test]
 fn test_repl_exit_nonzero() {
     check(
@@ -558,6 +623,18 @@ fn test_repl_help() {
     );
 }
 
+#[test]
+fn test_repl_history() {
+    check(
+        bin_path("endbasic"),
+        &[],
+        0,
+        Behavior::File(src_path("cli/tests/repl/history.in")),
+        Behavior::File(src_path("cli/tests/repl/history.out")),
+        Behavior::Null,
+    );
+}
+
 #[test]
 fn test_repl_interactive() {
     check(
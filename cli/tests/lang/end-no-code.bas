@@ -0,0 +1,24 @@
+' EndBASIC
+' Copyright 2020 Julio Merino
+'
+' Licensed under the Apache License, Version 2.0 (the "License"); you may not
+' use this file except in compliance with the License.  You may obtain a copy
+' of the License at:
+'
+'     http://www.apache.org/licenses/LICENSE-2.0
+'
+' Unless required by applicable law or agreed to in writing, software
+' distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+' WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+' License for the specific language governing permissions and limitations
+' under the License.
+
+' Terminates the program early without an explicit exit code.
+
+FOR i = 1 TO 10
+    PRINT i
+    IF i = 2 THEN
+        END
+    END IF
+NEXT
+PRINT "Should not be executed"
@@ -18,6 +18,16 @@
 use crate::parser::{Error, Result};
 use std::fmt;
 
+/// Position of an element in the source code.  Both numbers start at 1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LineCol {
+    /// The line number.
+    pub line: usize,
+
+    /// The column number within the line.
+    pub col: usize,
+}
+
 /// Represents an expression and provides mechanisms to evaluate it.
 #[derive(Clone, Debug, PartialEq)]
 pub enum Expr {
@@ -69,6 +79,9 @@ pub enum Expr {
 
     /// A function call.
     Call(VarRef, Vec<Expr>),
+
+    /// A reference to a file channel, as in `#1`.  Only valid as an argument to a builtin call.
+    Channel(Box<Expr>),
 }
 
 /// Collection of types for a variable.
@@ -153,6 +166,8 @@ impl VarRef {
     pub fn accepts(&self, value: &Value) -> bool {
         match (self.ref_type, value) {
             (VarType::Auto, _) => true,
+            (VarType::Void, _) => false,
+            (_, Value::Empty) => true,
             (VarType::Boolean, Value::Boolean(_)) => true,
             (VarType::Double, Value::Double(_)) => true,
             (VarType::Integer, Value::Integer(_)) => true,
@@ -182,6 +197,11 @@ pub enum Value {
 
     /// A string value.
     Text(String), // Should be `String` but would get confusing with the built-in Rust type.
+
+    /// The absence of a value, such as the result of an `INPUT` that received no answer.
+    ///
+    /// Empty values can be stored in variables of any type but cannot be operated on.
+    Empty,
 }
 
 impl From<bool> for Value {
@@ -219,6 +239,9 @@ pub enum ArgSep {
 
     /// Long separator (`,`).
     Long,
+
+    /// Whitespace between two bare words, as in `HELP LANG OPERATORS`.
+    Space,
 }
 
 /// Represents a statement in the program along all data to execute it.
@@ -241,32 +264,46 @@ pub enum Statement {
     /// calls of the form `PRINT a, , b`.
     BuiltinCall(String, Vec<(Option<Expr>, ArgSep)>),
 
+    /// Represents an `END` statement.
+    ///
+    /// The first and only parameter is the optional expression to compute the exit code with.
+    End(Option<Expr>),
+
     /// Represents an `IF` statement.
     ///
     /// The first and only parameter is a sequence containing all the branches of the statement.
     /// Each element is a pair of the conditional guard for the branch and the collection of
-    /// statements in that branch.  The final `ELSE` branch, if present, is also included here
-    /// and its guard clause is always a true expression.
-    If(Vec<(Expr, Vec<Statement>)>),
+    /// statements in that branch, each with its position in the source code.  The final `ELSE`
+    /// branch, if present, is also included here and its guard clause is always a true expression.
+    If(Vec<(Expr, Vec<(Statement, LineCol)>)>),
 
     /// Represents a `FOR` statement.
     ///
     /// The first parameter is the loop's iterator name, which is expressed a variable reference
-    /// that must be either automatic or an integer.  The second parameter is the expression to
-    /// compute the iterator's initial value, which must evaluate to an integer.  The third
-    /// parameter is the condition to test after each body execution, which if false terminates the
-    /// loop.  The fourth parameter is the expression to compute the iterator's next value.  The
-    /// fifth parameter is the collection of statements within the loop.
+    /// that must be automatic, an integer or a double.  The second parameter is the expression to
+    /// compute the iterator's initial value.  The third parameter is the expression to compute the
+    /// iterator's final value.  The fourth parameter is the step by which to advance the iterator
+    /// after each body execution.  The fifth parameter is the collection of statements within the
+    /// loop, each with its position in the source code.
+    ///
+    /// The step is stored as a value instead of as an expression because we force it to be a
+    /// non-zero integer or double literal at parse time.  This lets us know the direction of the
+    /// loop ahead of time.  As a consequence, the body of a loop whose step goes in the opposite
+    /// direction of its range never runs, and a zero step is rejected at parse time instead of
+    /// causing an infinite loop.
+    For(VarRef, Expr, Expr, Value, Vec<(Statement, LineCol)>),
+
+    /// Represents an assignment to a portion of a string of the form `MID$(s$, start%) = expr$`.
     ///
-    /// Note that we do not store the original end and step values, and instead use expressions to
-    /// represent the loop condition and the computation of the next iterator value.  We do this
-    /// for run-time efficiency.  The reason this is possible is because we force the step to be an
-    /// integer literal at parse time and do not allow it to be an expression.
-    For(VarRef, Expr, Expr, Expr, Vec<Statement>),
+    /// The first parameter is the reference to the string variable to modify.  The second
+    /// parameter is the expression to compute the starting position of the portion to replace.
+    /// The third parameter is the optional expression to compute the maximum number of characters
+    /// to replace.  The fourth parameter is the expression to compute the replacement string.
+    MidAssignment(VarRef, Expr, Option<Expr>, Expr),
 
     /// Represents a `WHILE` statement.
     ///
     /// The first parameter is the loop's condition.  The second parameter is the collection of
-    /// statements within the loop.
-    While(Expr, Vec<Statement>),
+    /// statements within the loop, each with its position in the source code.
+    While(Expr, Vec<(Statement, LineCol)>),
 }
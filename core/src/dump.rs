@@ -0,0 +1,373 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Human-readable dumps of the internal representations of programs.
+//!
+//! These are meant to help in debugging the interpreter and in understanding how it sees a
+//! program, so their output should remain stable across releases unless the underlying
+//! representations change.
+
+use crate::ast::{ArgSep, Expr, LineCol, Statement, Value};
+use crate::exec;
+use crate::lexer::{Lexer, Token};
+use crate::parser::Parser;
+use std::io;
+
+/// Number of spaces to indent each nested level of the AST by.
+const INDENT: usize = 2;
+
+/// Returns a description of `token` made of its kind and, for literals and symbols, its value.
+fn describe_token(token: &Token) -> String {
+    match token {
+        Token::Eof => "EOF".to_owned(),
+        Token::Eol => "EOL".to_owned(),
+        Token::Bad(msg) => format!("BAD {}", msg),
+
+        Token::Boolean(b) => format!("BOOLEAN {}", if *b { "TRUE" } else { "FALSE" }),
+        Token::Double(d) => format!("DOUBLE {:?}", d),
+        Token::Integer(i) => format!("INTEGER {}", i),
+        Token::Text(s) => format!("TEXT {:?}", s),
+        Token::Symbol(vref) => format!("SYMBOL {}", vref),
+
+        Token::Comma => ",".to_owned(),
+        Token::Semicolon => ";".to_owned(),
+        Token::Hash => "#".to_owned(),
+        Token::QuestionMark => "?".to_owned(),
+        Token::LeftParen => "(".to_owned(),
+        Token::RightParen => ")".to_owned(),
+
+        Token::Plus => "+".to_owned(),
+        Token::Minus => "-".to_owned(),
+        Token::Multiply => "*".to_owned(),
+        Token::Divide => "/".to_owned(),
+        Token::Modulo => "MOD".to_owned(),
+
+        Token::Equal => "=".to_owned(),
+        Token::NotEqual => "<>".to_owned(),
+        Token::Less => "<".to_owned(),
+        Token::LessEqual => "<=".to_owned(),
+        Token::Greater => ">".to_owned(),
+        Token::GreaterEqual => ">=".to_owned(),
+
+        Token::And => "AND".to_owned(),
+        Token::Not => "NOT".to_owned(),
+        Token::Or => "OR".to_owned(),
+        Token::Xor => "XOR".to_owned(),
+
+        Token::Else => "ELSE".to_owned(),
+        Token::Elseif => "ELSEIF".to_owned(),
+        Token::End => "END".to_owned(),
+        Token::For => "FOR".to_owned(),
+        Token::If => "IF".to_owned(),
+        Token::Next => "NEXT".to_owned(),
+        Token::Step => "STEP".to_owned(),
+        Token::Then => "THEN".to_owned(),
+        Token::To => "TO".to_owned(),
+        Token::Wend => "WEND".to_owned(),
+        Token::While => "WHILE".to_owned(),
+    }
+}
+
+/// Splits the program in `input` into tokens and returns one line per token with its position
+/// and its description.
+///
+/// Malformed tokens do not stop the scan and show up as `BAD` entries with the reason for the
+/// problem.  The final `EOF` token is not included.
+pub fn dump_tokens(input: &mut dyn io::Read) -> io::Result<Vec<String>> {
+    let mut lexer = Lexer::from(input);
+    let mut lines = vec![];
+    loop {
+        let token = lexer.read()?;
+        if token == Token::Eof {
+            break;
+        }
+        let pos = lexer.last_pos();
+        lines.push(format!("{}:{} {}", pos.line, pos.col, describe_token(&token)));
+    }
+    Ok(lines)
+}
+
+/// Formats `expr` as a fully-parenthesized prefix expression so that its structure is explicit.
+fn describe_expr(expr: &Expr) -> String {
+    let binary = |op: &str, lhs: &Expr, rhs: &Expr| {
+        format!("({} {} {})", op, describe_expr(lhs), describe_expr(rhs))
+    };
+    match expr {
+        Expr::Boolean(b) => (if *b { "TRUE" } else { "FALSE" }).to_owned(),
+        Expr::Double(d) => format!("{:?}", d),
+        Expr::Integer(i) => format!("{}", i),
+        Expr::Symbol(vref) => format!("{}", vref),
+        Expr::Text(s) => format!("{:?}", s),
+
+        Expr::Add(lhs, rhs) => binary("+", lhs, rhs),
+        Expr::Subtract(lhs, rhs) => binary("-", lhs, rhs),
+        Expr::Multiply(lhs, rhs) => binary("*", lhs, rhs),
+        Expr::Divide(lhs, rhs) => binary("/", lhs, rhs),
+        Expr::Modulo(lhs, rhs) => binary("MOD", lhs, rhs),
+        Expr::Negate(e) => format!("(NEG {})", describe_expr(e)),
+
+        Expr::Equal(lhs, rhs) => binary("=", lhs, rhs),
+        Expr::NotEqual(lhs, rhs) => binary("<>", lhs, rhs),
+        Expr::Less(lhs, rhs) => binary("<", lhs, rhs),
+        Expr::LessEqual(lhs, rhs) => binary("<=", lhs, rhs),
+        Expr::Greater(lhs, rhs) => binary(">", lhs, rhs),
+        Expr::GreaterEqual(lhs, rhs) => binary(">=", lhs, rhs),
+
+        Expr::And(lhs, rhs) => binary("AND", lhs, rhs),
+        Expr::Not(e) => format!("(NOT {})", describe_expr(e)),
+        Expr::Or(lhs, rhs) => binary("OR", lhs, rhs),
+        Expr::Xor(lhs, rhs) => binary("XOR", lhs, rhs),
+
+        Expr::Call(fref, args) => {
+            let mut out = format!("(CALL {}", fref);
+            for arg in args {
+                out.push(' ');
+                out.push_str(&describe_expr(arg));
+            }
+            out.push(')');
+            out
+        }
+
+        Expr::Channel(e) => format!("(# {})", describe_expr(e)),
+    }
+}
+
+/// Formats the literal `value`, which must have a type that can appear in the AST.
+fn describe_value(value: &Value) -> String {
+    match value {
+        Value::Boolean(b) => describe_expr(&Expr::Boolean(*b)),
+        Value::Double(d) => describe_expr(&Expr::Double(*d)),
+        Value::Integer(i) => describe_expr(&Expr::Integer(*i)),
+        Value::Text(s) => describe_expr(&Expr::Text(s.clone())),
+        Value::Empty => "(empty)".to_owned(),
+    }
+}
+
+/// Formats the optional `expr`, using a placeholder when it is missing.
+fn describe_opt_expr(expr: &Option<Expr>) -> String {
+    match expr {
+        Some(expr) => describe_expr(expr),
+        None => "(none)".to_owned(),
+    }
+}
+
+/// Appends to `lines` the description of the statements in `stmts`, indented to `level`.
+fn dump_block(lines: &mut Vec<String>, stmts: &[(Statement, LineCol)], level: usize) {
+    for (stmt, pos) in stmts {
+        dump_statement(lines, stmt, *pos, level);
+    }
+}
+
+/// Appends to `lines` the description of `stmt`, found at `pos`, indented to `level`.  Nested
+/// elements of the statement are indented one level further.
+fn dump_statement(lines: &mut Vec<String>, stmt: &Statement, pos: LineCol, level: usize) {
+    let indent = " ".repeat(level * INDENT);
+    let nested = " ".repeat((level + 1) * INDENT);
+    let header = match stmt {
+        Statement::Assignment(vref, expr) => format!("ASSIGNMENT {} {}", vref, describe_expr(expr)),
+        Statement::BuiltinCall(name, _) => format!("BUILTIN {}", name),
+        Statement::End(code) => match code {
+            Some(code) => format!("END {}", describe_expr(code)),
+            None => "END".to_owned(),
+        },
+        Statement::If(_) => "IF".to_owned(),
+        Statement::For(iterator, start, end, step, _) => format!(
+            "FOR {} {} {} STEP {}",
+            iterator,
+            describe_expr(start),
+            describe_expr(end),
+            describe_value(step)
+        ),
+        Statement::MidAssignment(vref, start, length, expr) => format!(
+            "MIDASSIGNMENT {} {} {} {}",
+            vref,
+            describe_expr(start),
+            describe_opt_expr(length),
+            describe_expr(expr)
+        ),
+        Statement::While(cond, _) => format!("WHILE {}", describe_expr(cond)),
+    };
+    lines.push(format!("{}{}:{} {}", indent, pos.line, pos.col, header));
+
+    match stmt {
+        Statement::BuiltinCall(_, args) => {
+            for (expr, sep) in args {
+                let sep = match sep {
+                    ArgSep::End => "END",
+                    ArgSep::Short => "SHORT",
+                    ArgSep::Long => "LONG",
+                    ArgSep::Space => "SPACE",
+                };
+                lines.push(format!("{}ARG {} {}", nested, describe_opt_expr(expr), sep));
+            }
+        }
+        Statement::If(branches) => {
+            for (guard, body) in branches {
+                lines.push(format!("{}BRANCH {}", nested, describe_expr(guard)));
+                dump_block(lines, body, level + 2);
+            }
+        }
+        Statement::For(_, _, _, _, body) | Statement::While(_, body) => {
+            dump_block(lines, body, level + 1);
+        }
+        Statement::Assignment(_, _) | Statement::End(_) | Statement::MidAssignment(_, _, _, _) => {}
+    }
+}
+
+/// Parses the program in `input` and returns one line per node of its abstract syntax tree.
+///
+/// Statements show the position where they start and their nested statements are indented below
+/// them.  Expressions are shown in prefix form with all of their parenthesis so that the
+/// precedence of their operators is explicit.
+///
+/// Fails with the first syntax error found in the program, if any.
+pub fn dump_ast(input: &mut dyn io::Read) -> exec::Result<Vec<String>> {
+    let (stmts, errors) = Parser::from(input).parse_all();
+    if let Some((pos, e)) = errors.into_iter().next() {
+        return Err(exec::Error::from(e).at(pos));
+    }
+    let mut lines = vec![];
+    dump_block(&mut lines, &stmts, 0);
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Dumps the tokens of `input` and checks that they match `expected`.
+    fn do_test(input: &str, expected: &[&str]) {
+        assert_eq!(expected, dump_tokens(&mut input.as_bytes()).unwrap().as_slice());
+    }
+
+    /// Dumps the AST of `input` and checks that it matches `expected`.
+    fn do_ast_test(input: &str, expected: &[&str]) {
+        assert_eq!(expected, dump_ast(&mut input.as_bytes()).unwrap().as_slice());
+    }
+
+    #[test]
+    fn test_dump_tokens_empty() {
+        do_test("", &[]);
+        do_test("  ' Only a comment", &[]);
+    }
+
+    #[test]
+    fn test_dump_tokens_expression() {
+        do_test(
+            "a% = (b + 2.5) * 3 <= \"x\"",
+            &[
+                "1:1 SYMBOL a%",
+                "1:4 =",
+                "1:6 (",
+                "1:7 SYMBOL b",
+                "1:9 +",
+                "1:11 DOUBLE 2.5",
+                "1:14 )",
+                "1:16 *",
+                "1:18 INTEGER 3",
+                "1:20 <=",
+                "1:23 TEXT \"x\"",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dump_tokens_statements() {
+        do_test(
+            "IF NOT TRUE THEN: PRINT 1 MOD 2; x$\nEND IF",
+            &[
+                "1:1 IF",
+                "1:4 NOT",
+                "1:8 BOOLEAN TRUE",
+                "1:13 THEN",
+                "1:17 EOL",
+                "1:19 SYMBOL PRINT",
+                "1:25 INTEGER 1",
+                "1:27 MOD",
+                "1:31 INTEGER 2",
+                "1:32 ;",
+                "1:34 SYMBOL x$",
+                "1:36 EOL",
+                "2:1 END",
+                "2:5 IF",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dump_tokens_bad() {
+        do_test(
+            "a = 1 @ 2",
+            &[
+                "1:1 SYMBOL a",
+                "1:3 =",
+                "1:5 INTEGER 1",
+                "1:7 BAD Unknown character: @",
+                "1:9 INTEGER 2",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dump_ast_empty() {
+        do_ast_test("", &[]);
+        do_ast_test("' Only a comment\n\n", &[]);
+    }
+
+    #[test]
+    fn test_dump_ast_if_and_for() {
+        do_ast_test(
+            "a = 2 + 3 * -b\nIF a > 1 AND NOT c? THEN\n    FOR i% = 1 TO a STEP 2\n        \
+             PRINT i%; LEFT$(\"x\", 1), , 2.5\n    NEXT\nELSEIF a = 0 THEN\n    END\nELSE\n    \
+             END a MOD 3\nEND IF",
+            &[
+                "1:1 ASSIGNMENT a (+ 2 (* 3 (NEG b)))",
+                "2:1 IF",
+                "  BRANCH (AND (> a 1) (NOT c?))",
+                "    3:5 FOR i% 1 a STEP 2",
+                "      4:9 BUILTIN PRINT",
+                "        ARG i% SHORT",
+                "        ARG (CALL LEFT$ \"x\" 1) LONG",
+                "        ARG (none) LONG",
+                "        ARG 2.5 END",
+                "  BRANCH (= a 0)",
+                "    7:5 END",
+                "  BRANCH TRUE",
+                "    9:5 END (MOD a 3)",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dump_ast_other_statements() {
+        do_ast_test(
+            "WHILE x < 3: x = x + 1: WEND\nMID$(s$, 2) = \"ab\"\nCLOSE #1",
+            &[
+                "1:1 WHILE (< x 3)",
+                "  1:14 ASSIGNMENT x (+ x 1)",
+                "2:1 MIDASSIGNMENT s$ 2 (none) \"ab\"",
+                "3:1 BUILTIN CLOSE",
+                "  ARG (# 1) END",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dump_ast_syntax_error() {
+        let err = dump_ast(&mut "a = 1\nb = )".as_bytes()).unwrap_err();
+        assert_eq!(Some(LineCol { line: 2, col: 5 }), err.position());
+        assert_eq!("Unbalanced parenthesis", format!("{}", err));
+    }
+}
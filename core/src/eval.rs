@@ -16,8 +16,10 @@
 //! Evaluator for EndBASIC expressions.
 
 use crate::ast::{Expr, Value, VarRef, VarType};
+use crate::parser::Parser;
+use std::cell::Cell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::mem;
 use std::rc::Rc;
 use std::str::Lines;
 
@@ -51,32 +53,82 @@ impl From<Error> for FunctionError {
 /// Result for function evaluation return values.
 pub type FunctionResult = std::result::Result<Value, FunctionError>;
 
+/// Machine-readable categories of errors, for callers that need to react to specific failures
+/// without inspecting messages.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// Execution was interrupted by the user.
+    Break,
+
+    /// Integer division or modulo by zero.
+    DivisionByZero,
+
+    /// Misconfiguration of the interpreter, such as invalid builtin callables.
+    Internal,
+
+    /// Failure while accessing a console, file or other external resource.
+    IoError,
+
+    /// Integer arithmetic that does not fit in the result type.
+    Overflow,
+
+    /// Malformed statements or invalid arguments to a builtin.
+    SyntaxError,
+
+    /// Operation applied to values of types that it does not support.
+    TypeError,
+
+    /// Reference to a variable, function or command that does not exist.
+    UndefinedSymbol,
+
+    /// Builtin command called with invalid arguments.
+    UsageError,
+
+    /// Value of the right type but outside of the accepted range or format.
+    ValueError,
+}
+
 /// Evaluation errors.
 #[derive(Debug, thiserror::Error)]
 #[error("{message}")]
 pub struct Error {
+    kind: ErrorKind,
     message: String,
 }
 
 impl Error {
-    /// Constructs a new evaluation error from a textual `message`.
-    fn new<S: Into<String>>(message: S) -> Self {
-        Self { message: message.into() }
+    /// Constructs a new evaluation error of the given `kind` from a textual `message`.
+    pub(crate) fn new<S: Into<String>>(kind: ErrorKind, message: S) -> Self {
+        Self { kind, message: message.into() }
+    }
+
+    /// Returns the category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
     }
 
     /// Annotates a function evaluation error with the function's metadata.
     fn from_function_error(md: &CallableMetadata, e: FunctionError) -> Self {
-        let message = match e {
+        let (kind, message) = match e {
             FunctionError::ArgumentError(e) => {
-                format!("Syntax error in call to {}: {}", md.name(), e)
+                (ErrorKind::ValueError, format!("Syntax error in call to {}: {}", md.name(), e))
+            }
+            FunctionError::EvalError(e) => {
+                (e.kind, format!("Error in call to {}: {}", md.name(), e))
             }
-            FunctionError::EvalError(e) => format!("Error in call to {}: {}", md.name(), e),
-            FunctionError::InternalError(e) => format!("Error in call to {}: {}", md.name(), e),
-            FunctionError::SyntaxError => {
-                format!("Syntax error in call to {}: expected {}", md.name(), md.syntax())
+            FunctionError::InternalError(e) => {
+                (ErrorKind::Internal, format!("Error in call to {}: {}", md.name(), e))
             }
+            FunctionError::SyntaxError if md.syntax().is_empty() => (
+                ErrorKind::SyntaxError,
+                format!("Syntax error in call to {}: expected no arguments", md.name()),
+            ),
+            FunctionError::SyntaxError => (
+                ErrorKind::SyntaxError,
+                format!("Syntax error in call to {}: expected {}", md.name(), md.syntax()),
+            ),
         };
-        Self { message }
+        Self { kind, message }
     }
 }
 
@@ -89,17 +141,19 @@ impl Value {
         fn parse_f64(s: &str) -> Result<Value> {
             match s.parse::<f64>() {
                 Ok(d) => Ok(Value::Double(d)),
-                Err(_) => Err(Error::new(format!(
-                    "Invalid double-precision floating point literal {}",
-                    s
-                ))),
+                Err(_) => Err(Error::new(
+                    ErrorKind::ValueError,
+                    format!("Invalid double-precision floating point literal {}", s),
+                )),
             }
         }
 
         fn parse_i32(s: &str) -> Result<Value> {
             match s.parse::<i32>() {
                 Ok(i) => Ok(Value::Integer(i)),
-                Err(_) => Err(Error::new(format!("Invalid integer literal {}", s))),
+                Err(_) => {
+                    Err(Error::new(ErrorKind::ValueError, format!("Invalid integer literal {}", s)))
+                }
             }
         }
 
@@ -113,7 +167,7 @@ impl Value {
                 } else if raw == "FALSE" || raw == "NO" || raw == "N" {
                     Ok(Value::Boolean(false))
                 } else {
-                    Err(Error::new(format!("Invalid boolean literal {}", s)))
+                    Err(Error::new(ErrorKind::ValueError, format!("Invalid boolean literal {}", s)))
                 }
             }
             VarType::Double => parse_f64(&s),
@@ -127,7 +181,10 @@ impl Value {
     pub fn and(&self, other: &Self) -> Result<Self> {
         match (self, other) {
             (Value::Boolean(lhs), Value::Boolean(rhs)) => Ok(Value::Boolean(*lhs && *rhs)),
-            (_, _) => Err(Error::new(format!("Cannot AND {:?} and {:?}", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot AND {:?} and {:?}", self, other),
+            )),
         }
     }
 
@@ -135,7 +192,10 @@ impl Value {
     pub fn or(&self, other: &Self) -> Result<Self> {
         match (self, other) {
             (Value::Boolean(lhs), Value::Boolean(rhs)) => Ok(Value::Boolean(*lhs || *rhs)),
-            (_, _) => Err(Error::new(format!("Cannot OR {:?} and {:?}", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot OR {:?} and {:?}", self, other),
+            )),
         }
     }
 
@@ -143,7 +203,10 @@ impl Value {
     pub fn xor(&self, other: &Self) -> Result<Self> {
         match (self, other) {
             (Value::Boolean(lhs), Value::Boolean(rhs)) => Ok(Value::Boolean(*lhs ^ *rhs)),
-            (_, _) => Err(Error::new(format!("Cannot XOR {:?} and {:?}", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot XOR {:?} and {:?}", self, other),
+            )),
         }
     }
 
@@ -151,69 +214,117 @@ impl Value {
     pub fn not(&self) -> Result<Self> {
         match self {
             Value::Boolean(b) => Ok(Value::Boolean(!b)),
-            _ => Err(Error::new(format!("Cannot apply NOT to {:?}", self))),
+            _ => Err(Error::new(ErrorKind::TypeError, format!("Cannot apply NOT to {:?}", self))),
         }
     }
 
     /// Performs an equality check.
+    ///
+    /// Integers and doubles can be compared with each other by promoting the integer to a double.
+    /// A NaN is never equal to anything, including itself.
     pub fn eq(&self, other: &Self) -> Result<Self> {
         match (self, other) {
             (Value::Boolean(lhs), Value::Boolean(rhs)) => Ok(Value::Boolean(lhs == rhs)),
             (Value::Double(lhs), Value::Double(rhs)) => Ok(Value::Boolean(lhs == rhs)),
+            (Value::Double(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(*lhs == *rhs as f64)),
+            (Value::Integer(lhs), Value::Double(rhs)) => Ok(Value::Boolean(*lhs as f64 == *rhs)),
             (Value::Integer(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(lhs == rhs)),
             (Value::Text(lhs), Value::Text(rhs)) => Ok(Value::Boolean(lhs == rhs)),
-            (_, _) => Err(Error::new(format!("Cannot compare {:?} and {:?} with =", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot compare {:?} and {:?} with =", self, other),
+            )),
         }
     }
 
     /// Performs an inequality check.
+    ///
+    /// Integers and doubles can be compared with each other by promoting the integer to a double.
+    /// A NaN is always different from anything, including itself.
     pub fn ne(&self, other: &Self) -> Result<Self> {
         match (self, other) {
             (Value::Boolean(lhs), Value::Boolean(rhs)) => Ok(Value::Boolean(lhs != rhs)),
             (Value::Double(lhs), Value::Double(rhs)) => Ok(Value::Boolean(lhs != rhs)),
+            (Value::Double(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(*lhs != *rhs as f64)),
+            (Value::Integer(lhs), Value::Double(rhs)) => Ok(Value::Boolean(*lhs as f64 != *rhs)),
             (Value::Integer(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(lhs != rhs)),
             (Value::Text(lhs), Value::Text(rhs)) => Ok(Value::Boolean(lhs != rhs)),
-            (_, _) => Err(Error::new(format!("Cannot compare {:?} and {:?} with <>", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot compare {:?} and {:?} with <>", self, other),
+            )),
         }
     }
 
     /// Performs a less-than check.
+    ///
+    /// Integers and doubles can be compared with each other by promoting the integer to a double.
+    /// A NaN is never ordered with respect to anything, so the result is always false.
     pub fn lt(&self, other: &Self) -> Result<Self> {
         match (self, other) {
             (Value::Double(lhs), Value::Double(rhs)) => Ok(Value::Boolean(lhs < rhs)),
+            (Value::Double(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(*lhs < *rhs as f64)),
+            (Value::Integer(lhs), Value::Double(rhs)) => Ok(Value::Boolean((*lhs as f64) < *rhs)),
             (Value::Integer(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(lhs < rhs)),
             (Value::Text(lhs), Value::Text(rhs)) => Ok(Value::Boolean(lhs < rhs)),
-            (_, _) => Err(Error::new(format!("Cannot compare {:?} and {:?} with <", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot compare {:?} and {:?} with <", self, other),
+            )),
         }
     }
 
     /// Performs a less-than or equal-to check.
+    ///
+    /// Integers and doubles can be compared with each other by promoting the integer to a double.
+    /// A NaN is never ordered with respect to anything, so the result is always false.
     pub fn le(&self, other: &Self) -> Result<Self> {
         match (self, other) {
             (Value::Double(lhs), Value::Double(rhs)) => Ok(Value::Boolean(lhs <= rhs)),
+            (Value::Double(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(*lhs <= *rhs as f64)),
+            (Value::Integer(lhs), Value::Double(rhs)) => Ok(Value::Boolean((*lhs as f64) <= *rhs)),
             (Value::Integer(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(lhs <= rhs)),
             (Value::Text(lhs), Value::Text(rhs)) => Ok(Value::Boolean(lhs <= rhs)),
-            (_, _) => Err(Error::new(format!("Cannot compare {:?} and {:?} with <=", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot compare {:?} and {:?} with <=", self, other),
+            )),
         }
     }
 
     /// Performs a greater-than check.
+    ///
+    /// Integers and doubles can be compared with each other by promoting the integer to a double.
+    /// A NaN is never ordered with respect to anything, so the result is always false.
     pub fn gt(&self, other: &Self) -> Result<Self> {
         match (self, other) {
             (Value::Double(lhs), Value::Double(rhs)) => Ok(Value::Boolean(lhs > rhs)),
+            (Value::Double(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(*lhs > *rhs as f64)),
+            (Value::Integer(lhs), Value::Double(rhs)) => Ok(Value::Boolean((*lhs as f64) > *rhs)),
             (Value::Integer(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(lhs > rhs)),
             (Value::Text(lhs), Value::Text(rhs)) => Ok(Value::Boolean(lhs > rhs)),
-            (_, _) => Err(Error::new(format!("Cannot compare {:?} and {:?} with >", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot compare {:?} and {:?} with >", self, other),
+            )),
         }
     }
 
     /// Performs a greater-than or equal to check.
+    ///
+    /// Integers and doubles can be compared with each other by promoting the integer to a double.
+    /// A NaN is never ordered with respect to anything, so the result is always false.
     pub fn ge(&self, other: &Self) -> Result<Self> {
         match (self, other) {
             (Value::Double(lhs), Value::Double(rhs)) => Ok(Value::Boolean(lhs >= rhs)),
+            (Value::Double(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(*lhs >= *rhs as f64)),
+            (Value::Integer(lhs), Value::Double(rhs)) => Ok(Value::Boolean((*lhs as f64) >= *rhs)),
             (Value::Integer(lhs), Value::Integer(rhs)) => Ok(Value::Boolean(lhs >= rhs)),
             (Value::Text(lhs), Value::Text(rhs)) => Ok(Value::Boolean(lhs >= rhs)),
-            (_, _) => Err(Error::new(format!("Cannot compare {:?} and {:?} with >=", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot compare {:?} and {:?} with >=", self, other),
+            )),
         }
     }
 
@@ -223,10 +334,16 @@ impl Value {
             (Value::Double(lhs), Value::Double(rhs)) => Ok(Value::Double(lhs + rhs)),
             (Value::Integer(lhs), Value::Integer(rhs)) => match lhs.checked_add(*rhs) {
                 Some(i) => Ok(Value::Integer(i)),
-                None => Err(Error::new(format!("Overflow adding {} and {}", lhs, rhs))),
+                None => Err(Error::new(
+                    ErrorKind::Overflow,
+                    format!("Overflow adding {} and {}", lhs, rhs),
+                )),
             },
             (Value::Text(lhs), Value::Text(rhs)) => Ok(Value::Text(lhs.to_owned() + rhs)),
-            (_, _) => Err(Error::new(format!("Cannot add {:?} and {:?}", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot add {:?} and {:?}", self, other),
+            )),
         }
     }
 
@@ -236,9 +353,15 @@ impl Value {
             (Value::Double(lhs), Value::Double(rhs)) => Ok(Value::Double(lhs - rhs)),
             (Value::Integer(lhs), Value::Integer(rhs)) => match lhs.checked_sub(*rhs) {
                 Some(i) => Ok(Value::Integer(i)),
-                None => Err(Error::new(format!("Overflow subtracting {} from {}", rhs, lhs))),
+                None => Err(Error::new(
+                    ErrorKind::Overflow,
+                    format!("Overflow subtracting {} from {}", rhs, lhs),
+                )),
             },
-            (_, _) => Err(Error::new(format!("Cannot subtract {:?} from {:?}", other, self))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot subtract {:?} from {:?}", other, self),
+            )),
         }
     }
 
@@ -248,9 +371,15 @@ impl Value {
             (Value::Double(lhs), Value::Double(rhs)) => Ok(Value::Double(lhs * rhs)),
             (Value::Integer(lhs), Value::Integer(rhs)) => match lhs.checked_mul(*rhs) {
                 Some(i) => Ok(Value::Integer(i)),
-                None => Err(Error::new(format!("Overflow multiplying {} by {}", lhs, rhs))),
+                None => Err(Error::new(
+                    ErrorKind::Overflow,
+                    format!("Overflow multiplying {} by {}", lhs, rhs),
+                )),
             },
-            (_, _) => Err(Error::new(format!("Cannot multiply {:?} by {:?}", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot multiply {:?} by {:?}", self, other),
+            )),
         }
     }
 
@@ -260,14 +389,20 @@ impl Value {
             (Value::Double(lhs), Value::Double(rhs)) => Ok(Value::Double(lhs / rhs)),
             (Value::Integer(lhs), Value::Integer(rhs)) => {
                 if rhs == &0 {
-                    return Err(Error::new("Division by zero"));
+                    return Err(Error::new(ErrorKind::DivisionByZero, "Division by zero"));
                 }
                 match lhs.checked_div(*rhs) {
                     Some(i) => Ok(Value::Integer(i)),
-                    None => Err(Error::new(format!("Overflow dividing {} by {}", lhs, rhs))),
+                    None => Err(Error::new(
+                        ErrorKind::Overflow,
+                        format!("Overflow dividing {} by {}", lhs, rhs),
+                    )),
                 }
             }
-            (_, _) => Err(Error::new(format!("Cannot divide {:?} by {:?}", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot divide {:?} by {:?}", self, other),
+            )),
         }
     }
 
@@ -277,14 +412,20 @@ impl Value {
             (Value::Double(lhs), Value::Double(rhs)) => Ok(Value::Double(lhs % rhs)),
             (Value::Integer(lhs), Value::Integer(rhs)) => {
                 if rhs == &0 {
-                    return Err(Error::new("Modulo by zero"));
+                    return Err(Error::new(ErrorKind::DivisionByZero, "Modulo by zero"));
                 }
                 match lhs.checked_rem(*rhs) {
                     Some(i) => Ok(Value::Integer(i)),
-                    None => Err(Error::new(format!("Overflow modulo {} by {}", lhs, rhs))),
+                    None => Err(Error::new(
+                        ErrorKind::Overflow,
+                        format!("Overflow modulo {} by {}", lhs, rhs),
+                    )),
                 }
             }
-            (_, _) => Err(Error::new(format!("Cannot modulo {:?} by {:?}", self, other))),
+            (_, _) => Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Cannot modulo {:?} by {:?}", self, other),
+            )),
         }
     }
 
@@ -294,9 +435,9 @@ impl Value {
             Value::Double(d) => Ok(Value::Double(-d)),
             Value::Integer(i) => match i.checked_neg() {
                 Some(i) => Ok(Value::Integer(i)),
-                None => Err(Error::new(format!("Overflow negating {}", i))),
+                None => Err(Error::new(ErrorKind::Overflow, format!("Overflow negating {}", i))),
             },
-            _ => Err(Error::new(format!("Cannot negate {:?}", self))),
+            _ => Err(Error::new(ErrorKind::TypeError, format!("Cannot negate {:?}", self))),
         }
     }
 }
@@ -309,26 +450,80 @@ impl ToString for Value {
             Value::Double(d) => format!("{}", d),
             Value::Integer(i) => format!("{}", i),
             Value::Text(s2) => s2.clone(),
+            Value::Empty => "".to_owned(),
         }
     }
 }
 
 /// Storage for all variables that exist at runtime.
+///
+/// Variable names are case-insensitive: `Count` and `COUNT` refer to the same variable.
 #[derive(Debug, Default)]
 pub struct Vars {
-    /// Map of variable names (without type annotations) to their values.
+    /// Map of variable names (without type annotations and in uppercase) to their values.
     vars: HashMap<String, Value>,
+
+    /// Whether integer arithmetic wraps around on overflow instead of failing.
+    overflow_wraps: bool,
+
+    /// Whether booleans are displayed as the numbers -1 and 0 instead of as TRUE and FALSE.
+    numeric_booleans: bool,
+
+    /// Number of `EVAL` calls currently being evaluated, to stop runaway recursion.
+    eval_depth: Cell<usize>,
+
+    /// Types of the variables that hold `Value::Empty`, keyed like `vars`, for those variables
+    /// whose type is known because they were typed before or assigned via a typed reference.
+    empty_types: HashMap<String, VarType>,
+}
+
+/// Returns the type of `value`, or `VarType::Auto` if the value is empty and thus has no type.
+fn value_type(value: &Value) -> VarType {
+    match value {
+        Value::Boolean(_) => VarType::Boolean,
+        Value::Double(_) => VarType::Double,
+        Value::Integer(_) => VarType::Integer,
+        Value::Text(_) => VarType::Text,
+        Value::Empty => VarType::Auto,
+    }
 }
 
 impl Vars {
-    /// Returns the mapping of all variables.
+    /// Returns the mapping of all variables.  Keys are always in uppercase.
     pub fn as_hashmap(&self) -> &HashMap<String, Value> {
         &self.vars
     }
 
-    /// Clears all variables.
+    /// Clears all variables and restores the default overflow and display behaviors.
     pub fn clear(&mut self) {
-        self.vars.clear()
+        self.vars.clear();
+        self.empty_types.clear();
+        self.overflow_wraps = false;
+        self.numeric_booleans = false;
+    }
+
+    /// Returns true if booleans should be displayed as the numbers -1 and 0.
+    ///
+    /// This only affects how booleans are shown to the user: they remain booleans internally.
+    pub fn numeric_booleans(&self) -> bool {
+        self.numeric_booleans
+    }
+
+    /// Sets whether booleans are displayed as the numbers -1 and 0 (`numeric` is true) or as
+    /// `TRUE` and `FALSE` (`numeric` is false, the default).
+    pub fn set_numeric_booleans(&mut self, numeric: bool) {
+        self.numeric_booleans = numeric;
+    }
+
+    /// Returns true if integer arithmetic in expressions wraps around on overflow.
+    pub fn overflow_wraps(&self) -> bool {
+        self.overflow_wraps
+    }
+
+    /// Sets whether integer arithmetic in expressions wraps around on overflow (`wraps` is true) or
+    /// fails with an error (`wraps` is false, the default).
+    pub fn set_overflow_wraps(&mut self, wraps: bool) {
+        self.overflow_wraps = wraps;
     }
 
     /// Obtains the value of a variable.
@@ -336,16 +531,34 @@ impl Vars {
     /// Returns an error if the variable is not defined, or if the type annotation in the variable
     /// reference does not match the type of the value that the variable contains.
     pub fn get(&self, vref: &VarRef) -> Result<&Value> {
-        let value = match self.vars.get(&vref.name().to_ascii_uppercase()) {
+        let name = vref.name().to_ascii_uppercase();
+        let value = match self.vars.get(&name) {
             Some(v) => v,
-            None => return Err(Error::new(format!("Undefined variable {}", vref.name()))),
+            None => {
+                return Err(Error::new(
+                    ErrorKind::UndefinedSymbol,
+                    format!("Undefined variable {}", vref.name()),
+                ))
+            }
         };
-        if !vref.accepts(&value) {
-            return Err(Error::new(format!("Incompatible types in {} reference", vref)));
+        if !vref.accepts(&value) || !types_match(vref.ref_type(), self.type_of(&name, value)) {
+            return Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Incompatible types in {} reference", vref),
+            ));
         }
         Ok(value)
     }
 
+    /// Returns the type of the variable `name` that holds `value`, which is `VarType::Auto` if
+    /// the variable is empty and was never given a type.
+    fn type_of(&self, name: &str, value: &Value) -> VarType {
+        match value {
+            Value::Empty => self.empty_types.get(name).copied().unwrap_or(VarType::Auto),
+            value => value_type(value),
+        }
+    }
+
     /// Returns true if this contains no variables.
     pub fn is_empty(&self) -> bool {
         self.vars.is_empty()
@@ -358,23 +571,51 @@ impl Vars {
     ///
     /// If the variable is already defined, then the type of the new value must be compatible with
     /// the existing variable.  In other words: a variable cannot change types while it's alive.
+    /// `Value::Empty` has no type of its own and can be stored in any variable, but the variable
+    /// keeps the type it had before, or the type of `vref` if it had none, so that later
+    /// assignments must still match that type.
     pub fn set(&mut self, vref: &VarRef, value: Value) -> Result<()> {
         let name = vref.name().to_ascii_uppercase();
         if !vref.accepts(&value) {
-            return Err(Error::new(format!("Incompatible types in {} assignment", vref)));
+            return Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Incompatible types in {} assignment", vref),
+            ));
         }
-        if let Some(old_value) = self.vars.get_mut(&name) {
-            if mem::discriminant(&value) != mem::discriminant(old_value) {
-                return Err(Error::new(format!("Incompatible types in {} assignment", vref)));
-            }
-            *old_value = value;
+
+        let old_type = match self.vars.get(&name) {
+            Some(old_value) => self.type_of(&name, old_value),
+            None => VarType::Auto,
+        };
+        let new_type = match value {
+            Value::Empty => vref.ref_type(),
+            ref value => value_type(value),
+        };
+        if !types_match(old_type, new_type) {
+            return Err(Error::new(
+                ErrorKind::TypeError,
+                format!("Incompatible types in {} assignment", vref),
+            ));
+        }
+
+        if value == Value::Empty {
+            match if old_type == VarType::Auto { new_type } else { old_type } {
+                VarType::Auto => self.empty_types.remove(&name),
+                vtype => self.empty_types.insert(name.clone(), vtype),
+            };
         } else {
-            self.vars.insert(name, value);
+            self.empty_types.remove(&name);
         }
+        self.vars.insert(name, value);
         Ok(())
     }
 }
 
+/// Returns true if the types `a` and `b` are compatible, treating `VarType::Auto` as unknown.
+fn types_match(a: VarType, b: VarType) -> bool {
+    a == VarType::Auto || b == VarType::Auto || a == b
+}
+
 /// Builder pattern for a callable's metadata.
 pub struct CallableMetadataBuilder {
     name: &'static str,
@@ -423,14 +664,57 @@ impl CallableMetadataBuilder {
     }
 
     /// Generates the final `CallableMetadata` object, ensuring all values are present.
+    ///
+    /// Panics if the metadata is incomplete.  See `build_checked` for details.
     pub fn build(self) -> CallableMetadata {
-        CallableMetadata {
+        match self.build_checked() {
+            Ok(metadata) => metadata,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Generates the final `CallableMetadata` object, ensuring all values are present.
+    ///
+    /// The name, category and description must not be empty, and the syntax must have been set
+    /// even if it is empty.  Returns an error describing the first missing value otherwise.
+    pub fn build_checked(self) -> Result<CallableMetadata> {
+        if self.name.is_empty() {
+            return Err(Error::new(ErrorKind::Internal, "Callable name cannot be empty"));
+        }
+        let syntax = match self.syntax {
+            Some(syntax) => syntax,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::Internal,
+                    format!("Callable {} must specify a syntax", self.name),
+                ))
+            }
+        };
+        let category = match self.category {
+            Some(category) if !category.is_empty() => category,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Internal,
+                    format!("Callable {} must specify a category", self.name),
+                ))
+            }
+        };
+        let description = match self.description {
+            Some(description) if !description.is_empty() => description,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Internal,
+                    format!("Callable {} must specify a description", self.name),
+                ))
+            }
+        };
+        Ok(CallableMetadata {
             name: self.name,
             return_type: self.return_type,
-            syntax: self.syntax.expect("All callables must specify a syntax"),
-            category: self.category.expect("All callables must specify a category"),
-            description: self.description.expect("All callables must specify a description"),
-        }
+            syntax,
+            category,
+            description,
+        })
     }
 
     /// Generates the final `CallableMetadata` object, ensuring the minimal set of values are
@@ -506,6 +790,28 @@ pub trait Function {
     fn exec(&self, args: Vec<Value>) -> FunctionResult;
 }
 
+/// Computes an arithmetic operation on `lhs` and `rhs` with the `checked` implementation, or with
+/// the `wrapping` one if `vars` request wrapping semantics and both operands are integers.
+///
+/// `wrapping` returns `None` for operations that must still fail in wrapping mode, such as a
+/// division by zero, in which case the `checked` implementation reports the error.
+fn arith(
+    vars: &Vars,
+    lhs: Value,
+    rhs: Value,
+    checked: fn(&Value, &Value) -> Result<Value>,
+    wrapping: fn(i32, i32) -> Option<i32>,
+) -> Result<Value> {
+    if vars.overflow_wraps() {
+        if let (Value::Integer(l), Value::Integer(r)) = (&lhs, &rhs) {
+            if let Some(i) = wrapping(*l, *r) {
+                return Ok(Value::Integer(i));
+            }
+        }
+    }
+    checked(&lhs, &rhs)
+}
+
 impl Expr {
     /// Evaluates the expression to a value.
     ///
@@ -518,7 +824,19 @@ impl Expr {
             Expr::Integer(i) => Ok(Value::Integer(*i)),
             Expr::Text(s) => Ok(Value::Text(s.clone())),
 
-            Expr::Symbol(vref) => Ok(vars.get(vref)?.clone()),
+            Expr::Symbol(vref) => match vars.get(vref) {
+                Ok(value) => Ok(value.clone()),
+                Err(e) => {
+                    // A bare name that is not a variable may be a call to a function that takes
+                    // no arguments, such as `COMMAND$`.
+                    let name = vref.name().to_ascii_uppercase();
+                    if !vars.as_hashmap().contains_key(&name) && fs.contains_key(name.as_str()) {
+                        eval_call(vref, &name, &[], vars, fs)
+                    } else {
+                        Err(e)
+                    }
+                }
+            },
 
             Expr::And(lhs, rhs) => Value::and(&lhs.eval(vars, fs)?, &rhs.eval(vars, fs)?),
             Expr::Or(lhs, rhs) => Value::or(&lhs.eval(vars, fs)?, &rhs.eval(vars, fs)?),
@@ -532,48 +850,282 @@ impl Expr {
             Expr::Greater(lhs, rhs) => Value::gt(&lhs.eval(vars, fs)?, &rhs.eval(vars, fs)?),
             Expr::GreaterEqual(lhs, rhs) => Value::ge(&lhs.eval(vars, fs)?, &rhs.eval(vars, fs)?),
 
-            Expr::Add(lhs, rhs) => Value::add(&lhs.eval(vars, fs)?, &rhs.eval(vars, fs)?),
-            Expr::Subtract(lhs, rhs) => Value::sub(&lhs.eval(vars, fs)?, &rhs.eval(vars, fs)?),
-            Expr::Multiply(lhs, rhs) => Value::mul(&lhs.eval(vars, fs)?, &rhs.eval(vars, fs)?),
-            Expr::Divide(lhs, rhs) => Value::div(&lhs.eval(vars, fs)?, &rhs.eval(vars, fs)?),
-            Expr::Modulo(lhs, rhs) => Value::modulo(&lhs.eval(vars, fs)?, &rhs.eval(vars, fs)?),
-            Expr::Negate(e) => Value::neg(&e.eval(vars, fs)?),
-
-            Expr::Call(fref, args) => match fs.get(fref.name().to_ascii_uppercase().as_str()) {
-                Some(f) => {
-                    let metadata = f.metadata();
-                    if fref.ref_type() != VarType::Auto && fref.ref_type() != metadata.return_type()
-                    {
-                        return Err(Error::new("Incompatible type annotation for function call"));
+            Expr::Add(lhs, rhs) => {
+                arith(vars, lhs.eval(vars, fs)?, rhs.eval(vars, fs)?, Value::add, |l, r| {
+                    Some(l.wrapping_add(r))
+                })
+            }
+            Expr::Subtract(lhs, rhs) => {
+                arith(vars, lhs.eval(vars, fs)?, rhs.eval(vars, fs)?, Value::sub, |l, r| {
+                    Some(l.wrapping_sub(r))
+                })
+            }
+            Expr::Multiply(lhs, rhs) => {
+                arith(vars, lhs.eval(vars, fs)?, rhs.eval(vars, fs)?, Value::mul, |l, r| {
+                    Some(l.wrapping_mul(r))
+                })
+            }
+            Expr::Divide(lhs, rhs) => {
+                arith(vars, lhs.eval(vars, fs)?, rhs.eval(vars, fs)?, Value::div, |l, r| {
+                    if r == 0 {
+                        None
+                    } else {
+                        Some(l.wrapping_div(r))
                     }
-
-                    let mut values = Vec::with_capacity(args.len());
-                    for a in args {
-                        values.push(a.eval(vars, fs)?);
+                })
+            }
+            Expr::Modulo(lhs, rhs) => {
+                arith(vars, lhs.eval(vars, fs)?, rhs.eval(vars, fs)?, Value::modulo, |l, r| {
+                    if r == 0 {
+                        None
+                    } else {
+                        Some(l.wrapping_rem(r))
                     }
-                    let result = f.exec(values);
-                    match result {
-                        Ok(value) => {
-                            debug_assert!(metadata.return_type() != VarType::Auto);
-                            let fref = VarRef::new(fref.name(), metadata.return_type());
-                            // Given that we only support built-in functions at the moment, this
-                            // could well be an assertion.  Doing so could turn into a time bomb
-                            // when/if we add user-defined functions, so handle the problem as an
-                            // error.
-                            if !fref.accepts(&value) {
-                                return Err(Error::new(format!(
-                                    "Value returned by {} is incompatible with its type definition",
-                                    fref.name(),
-                                )));
-                            }
-                            Ok(value)
-                        }
-                        Err(e) => Err(Error::from_function_error(&metadata, e)),
+                })
+            }
+            Expr::Negate(e) => match e.eval(vars, fs)? {
+                Value::Integer(i) if vars.overflow_wraps() => Ok(Value::Integer(i.wrapping_neg())),
+                value => Value::neg(&value),
+            },
+
+            Expr::Call(fref, args) => match fref.name().to_ascii_uppercase().as_str() {
+                "CHOOSE" => eval_choose(fref, args, vars, fs),
+                "EVAL" => eval_eval(fref, args, vars, fs),
+                "IIF" => eval_iif(fref, args, vars, fs),
+                "MAX" => eval_min_max(fref, "MAX", Ordering::Greater, args, vars, fs),
+                "MIN" => eval_min_max(fref, "MIN", Ordering::Less, args, vars, fs),
+                name => eval_call(fref, name, args, vars, fs),
+            },
+
+            Expr::Channel(_) => Err(Error::new(
+                ErrorKind::SyntaxError,
+                "Channel references are only valid as command arguments",
+            )),
+        }
+    }
+}
+
+/// Checks that the `value` computed by the special form `fref` matches its type annotation.
+fn check_special_form_type(fref: &VarRef, value: Value) -> Result<Value> {
+    if fref.ref_type() != VarType::Auto && !fref.accepts(&value) {
+        return Err(Error::new(
+            ErrorKind::TypeError,
+            "Incompatible type annotation for function call",
+        ));
+    }
+    Ok(value)
+}
+
+/// Evaluates the `CHOOSE(index%, v1, ..., vN)` special form referenced by `fref`.
+///
+/// This is not a regular function because functions must return values of a fixed type, whereas
+/// `CHOOSE` returns whichever type the selected value has.  As a consequence, only the selected
+/// value is evaluated.
+fn eval_choose(
+    fref: &VarRef,
+    args: &[Expr],
+    vars: &Vars,
+    fs: &HashMap<&'static str, Rc<dyn Function>>,
+) -> Result<Value> {
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::SyntaxError,
+            "CHOOSE requires an index and at least one value",
+        ));
+    }
+    let index = match args[0].eval(vars, fs)? {
+        Value::Integer(i) => i,
+        _ => return Err(Error::new(ErrorKind::TypeError, "CHOOSE requires an integer index")),
+    };
+    let n = args.len() - 1;
+    if index < 1 || index as usize > n {
+        return Err(Error::new(
+            ErrorKind::ValueError,
+            format!("CHOOSE index {} out of range [1, {}]", index, n),
+        ));
+    }
+    check_special_form_type(fref, args[index as usize].eval(vars, fs)?)
+}
+
+/// Maximum number of nested `EVAL` calls, which keeps self-referential expressions from
+/// overflowing the stack.
+const MAX_EVAL_DEPTH: usize = 100;
+
+/// Evaluates the `EVAL(expr$)` special form referenced by `fref`.
+///
+/// This is not a regular function because functions only receive the values of their arguments,
+/// whereas `EVAL` must parse its argument as an expression and evaluate it against the current
+/// variables, and because the type of the result depends on that expression.
+fn eval_eval(
+    fref: &VarRef,
+    args: &[Expr],
+    vars: &Vars,
+    fs: &HashMap<&'static str, Rc<dyn Function>>,
+) -> Result<Value> {
+    let text = match args {
+        [arg] => match arg.eval(vars, fs)? {
+            Value::Text(text) => text,
+            _ => return Err(Error::new(ErrorKind::TypeError, "EVAL requires a string")),
+        },
+        _ => return Err(Error::new(ErrorKind::SyntaxError, "EVAL requires a string")),
+    };
+    let expr = match Parser::from(&mut text.as_bytes()).parse_single_expr() {
+        Ok(expr) => expr,
+        Err(e) => {
+            return Err(Error::new(ErrorKind::SyntaxError, format!("Invalid expression: {}", e)))
+        }
+    };
+
+    let depth = vars.eval_depth.get();
+    if depth >= MAX_EVAL_DEPTH {
+        return Err(Error::new(ErrorKind::ValueError, "EVAL nested too deeply"));
+    }
+    vars.eval_depth.set(depth + 1);
+    let result = expr.eval(vars, fs);
+    vars.eval_depth.set(depth);
+    check_special_form_type(fref, result?)
+}
+
+/// Evaluates the `IIF(cond?, if_true, if_false)` special form referenced by `fref`.
+///
+/// This is not a regular function because functions receive their arguments already evaluated,
+/// whereas `IIF` must only evaluate the branch selected by the condition so that the other one can
+/// contain code that would fail, such as a division by zero the condition guards against.
+fn eval_iif(
+    fref: &VarRef,
+    args: &[Expr],
+    vars: &Vars,
+    fs: &HashMap<&'static str, Rc<dyn Function>>,
+) -> Result<Value> {
+    let (cond, if_true, if_false) = match args {
+        [cond, if_true, if_false] => (cond, if_true, if_false),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::SyntaxError,
+                "IIF requires a condition and two values",
+            ))
+        }
+    };
+    let value = match cond.eval(vars, fs)? {
+        Value::Boolean(true) => if_true.eval(vars, fs)?,
+        Value::Boolean(false) => if_false.eval(vars, fs)?,
+        _ => return Err(Error::new(ErrorKind::TypeError, "IIF requires a boolean condition")),
+    };
+    check_special_form_type(fref, value)
+}
+
+/// Evaluates the `MIN(a, b, ...)` or `MAX(a, b, ...)` special form referenced by `fref`, whose
+/// `name` is used in error messages.  `wanted` is the ordering that a value must have with respect
+/// to the current result for it to replace that result.
+///
+/// These are not regular functions because functions must return values of a fixed type, whereas
+/// these return an integer when all values are integers and a double otherwise.  Integers are
+/// converted to doubles before comparing them to doubles.
+fn eval_min_max(
+    fref: &VarRef,
+    name: &str,
+    wanted: Ordering,
+    args: &[Expr],
+    vars: &Vars,
+    fs: &HashMap<&'static str, Rc<dyn Function>>,
+) -> Result<Value> {
+    if args.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::SyntaxError,
+            format!("{} requires at least two values", name),
+        ));
+    }
+
+    let mut values = Vec::with_capacity(args.len());
+    let mut all_integers = true;
+    for arg in args {
+        match arg.eval(vars, fs)? {
+            value @ Value::Integer(_) => values.push(value),
+            value @ Value::Double(_) => {
+                all_integers = false;
+                values.push(value);
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::TypeError,
+                    format!("{} requires numeric values", name),
+                ))
+            }
+        }
+    }
+
+    let result =
+        if all_integers {
+            let mut values = values.into_iter().map(|v| match v {
+                Value::Integer(i) => i,
+                _ => unreachable!("All values must be integers"),
+            });
+            let first = values.next().expect("Must have at least two values");
+            Value::Integer(values.fold(first, |acc, i| if i.cmp(&acc) == wanted { i } else { acc }))
+        } else {
+            let mut values = values.into_iter().map(|v| match v {
+                Value::Double(d) => d,
+                Value::Integer(i) => i as f64,
+                _ => unreachable!("All values must be numeric"),
+            });
+            let first = values.next().expect("Must have at least two values");
+            Value::Double(values.fold(first, |acc, d| {
+                if d.partial_cmp(&acc) == Some(wanted) {
+                    d
+                } else {
+                    acc
+                }
+            }))
+        };
+    check_special_form_type(fref, result)
+}
+
+/// Evaluates a call to the function `name` referenced by `fref` with the given `args`.
+fn eval_call(
+    fref: &VarRef,
+    name: &str,
+    args: &[Expr],
+    vars: &Vars,
+    fs: &HashMap<&'static str, Rc<dyn Function>>,
+) -> Result<Value> {
+    match fs.get(name) {
+        Some(f) => {
+            let metadata = f.metadata();
+            if fref.ref_type() != VarType::Auto && fref.ref_type() != metadata.return_type() {
+                return Err(Error::new(
+                    ErrorKind::TypeError,
+                    "Incompatible type annotation for function call",
+                ));
+            }
+
+            let mut values = Vec::with_capacity(args.len());
+            for a in args {
+                values.push(a.eval(vars, fs)?);
+            }
+            let result = f.exec(values);
+            match result {
+                Ok(value) => {
+                    debug_assert!(metadata.return_type() != VarType::Auto);
+                    let fref = VarRef::new(fref.name(), metadata.return_type());
+                    // Given that we only support built-in functions at the moment, this
+                    // could well be an assertion.  Doing so could turn into a time bomb
+                    // when/if we add user-defined functions, so handle the problem as an
+                    // error.
+                    if !fref.accepts(&value) {
+                        return Err(Error::new(
+                            ErrorKind::Internal,
+                            format!(
+                                "Value returned by {} is incompatible with its type definition",
+                                fref.name(),
+                            ),
+                        ));
                     }
+                    Ok(value)
                 }
-                None => Err(Error::new(format!("Unknown function {}", fref))),
-            },
+                Err(e) => Err(Error::from_function_error(&metadata, e)),
+            }
         }
+        None => Err(Error::new(ErrorKind::UndefinedSymbol, format!("Unknown function {}", fref))),
     }
 }
 
@@ -661,7 +1213,7 @@ pub(crate) mod testutils {
                     if s == "argument" {
                         Err(FunctionError::ArgumentError("Bad argument".to_owned()))
                     } else if s == "eval" {
-                        Err(Error::new("Some eval error").into())
+                        Err(Error::new(ErrorKind::ValueError, "Some eval error").into())
                     } else if s == "internal" {
                         Err(FunctionError::InternalError("Some internal error".to_owned()))
                     } else if s == "syntax" {
@@ -880,16 +1432,18 @@ mod tests {
 
         assert_eq!(Boolean(true), Double(2.5).eq(&Double(2.5)).unwrap());
         assert_eq!(Boolean(false), Double(3.5).eq(&Double(3.6)).unwrap());
-        assert_eq!(
-            "Cannot compare Double(4.0) and Integer(1) with =",
-            format!("{}", Double(4.0).eq(&Integer(1)).unwrap_err())
-        );
+        assert_eq!(Boolean(true), Double(4.0).eq(&Integer(4)).unwrap());
+        assert_eq!(Boolean(false), Double(2.5).eq(&Integer(2)).unwrap());
+        assert_eq!(Boolean(false), Double(f64::NAN).eq(&Double(f64::NAN)).unwrap());
+        assert_eq!(Boolean(false), Double(f64::NAN).eq(&Integer(0)).unwrap());
 
         assert_eq!(Boolean(true), Integer(2).eq(&Integer(2)).unwrap());
         assert_eq!(Boolean(false), Integer(3).eq(&Integer(4)).unwrap());
+        assert_eq!(Boolean(true), Integer(1).eq(&Double(1.0)).unwrap());
+        assert_eq!(Boolean(false), Integer(2).eq(&Double(2.5)).unwrap());
         assert_eq!(
-            "Cannot compare Integer(4) and Double(1.0) with =",
-            format!("{}", Integer(4).eq(&Double(1.0)).unwrap_err())
+            "Cannot compare Integer(1) and Text(\"1\") with =",
+            format!("{}", Integer(1).eq(&Text("1".to_owned())).unwrap_err())
         );
 
         assert_eq!(Boolean(true), Text("a".to_owned()).eq(&Text("a".to_owned())).unwrap());
@@ -898,6 +1452,10 @@ mod tests {
             "Cannot compare Text(\"\") and Boolean(false) with =",
             format!("{}", Text("".to_owned()).eq(&Boolean(false)).unwrap_err())
         );
+        assert_eq!(
+            "Cannot compare Text(\"1\") and Integer(1) with =",
+            format!("{}", Text("1".to_owned()).eq(&Integer(1)).unwrap_err())
+        );
     }
 
     #[test]
@@ -913,16 +1471,18 @@ mod tests {
 
         assert_eq!(Boolean(false), Double(2.5).ne(&Double(2.5)).unwrap());
         assert_eq!(Boolean(true), Double(3.5).ne(&Double(3.6)).unwrap());
-        assert_eq!(
-            "Cannot compare Double(4.0) and Integer(1) with <>",
-            format!("{}", Double(4.0).ne(&Integer(1)).unwrap_err())
-        );
+        assert_eq!(Boolean(false), Double(4.0).ne(&Integer(4)).unwrap());
+        assert_eq!(Boolean(true), Double(2.5).ne(&Integer(2)).unwrap());
+        assert_eq!(Boolean(true), Double(f64::NAN).ne(&Double(f64::NAN)).unwrap());
+        assert_eq!(Boolean(true), Double(f64::NAN).ne(&Integer(0)).unwrap());
 
         assert_eq!(Boolean(false), Integer(2).ne(&Integer(2)).unwrap());
         assert_eq!(Boolean(true), Integer(3).ne(&Integer(4)).unwrap());
+        assert_eq!(Boolean(false), Integer(1).ne(&Double(1.0)).unwrap());
+        assert_eq!(Boolean(true), Integer(2).ne(&Double(2.5)).unwrap());
         assert_eq!(
-            "Cannot compare Integer(4) and Double(1.0) with <>",
-            format!("{}", Integer(4).ne(&Double(1.0)).unwrap_err())
+            "Cannot compare Integer(1) and Text(\"1\") with <>",
+            format!("{}", Integer(1).ne(&Text("1".to_owned())).unwrap_err())
         );
 
         assert_eq!(Boolean(false), Text("a".to_owned()).ne(&Text("a".to_owned())).unwrap());
@@ -931,6 +1491,10 @@ mod tests {
             "Cannot compare Text(\"\") and Boolean(false) with <>",
             format!("{}", Text("".to_owned()).ne(&Boolean(false)).unwrap_err())
         );
+        assert_eq!(
+            "Cannot compare Text(\"1\") and Integer(1) with <>",
+            format!("{}", Text("1".to_owned()).ne(&Integer(1)).unwrap_err())
+        );
     }
 
     #[test]
@@ -944,17 +1508,15 @@ mod tests {
 
         assert_eq!(Boolean(false), Double(2.5).lt(&Double(2.5)).unwrap());
         assert_eq!(Boolean(true), Double(3.5).lt(&Double(3.6)).unwrap());
-        assert_eq!(
-            "Cannot compare Double(4.0) and Integer(1) with <",
-            format!("{}", Double(4.0).lt(&Integer(1)).unwrap_err())
-        );
+        assert_eq!(Boolean(false), Double(4.0).lt(&Integer(4)).unwrap());
+        assert_eq!(Boolean(true), Double(3.5).lt(&Integer(4)).unwrap());
+        assert_eq!(Boolean(false), Double(f64::NAN).lt(&Integer(0)).unwrap());
 
         assert_eq!(Boolean(false), Integer(2).lt(&Integer(2)).unwrap());
         assert_eq!(Boolean(true), Integer(3).lt(&Integer(4)).unwrap());
-        assert_eq!(
-            "Cannot compare Integer(4) and Double(1.0) with <",
-            format!("{}", Integer(4).lt(&Double(1.0)).unwrap_err())
-        );
+        assert_eq!(Boolean(false), Integer(4).lt(&Double(4.0)).unwrap());
+        assert_eq!(Boolean(true), Integer(1).lt(&Double(1.5)).unwrap());
+        assert_eq!(Boolean(false), Integer(0).lt(&Double(f64::NAN)).unwrap());
 
         assert_eq!(Boolean(false), Text("a".to_owned()).lt(&Text("a".to_owned())).unwrap());
         assert_eq!(Boolean(true), Text("a".to_owned()).lt(&Text("c".to_owned())).unwrap());
@@ -976,18 +1538,16 @@ mod tests {
         assert_eq!(Boolean(false), Double(2.1).le(&Double(2.0)).unwrap());
         assert_eq!(Boolean(true), Double(2.1).le(&Double(2.1)).unwrap());
         assert_eq!(Boolean(true), Double(2.1).le(&Double(2.2)).unwrap());
-        assert_eq!(
-            "Cannot compare Double(4.0) and Integer(1) with <=",
-            format!("{}", Double(4.0).le(&Integer(1)).unwrap_err())
-        );
+        assert_eq!(Boolean(false), Double(4.5).le(&Integer(4)).unwrap());
+        assert_eq!(Boolean(true), Double(4.0).le(&Integer(4)).unwrap());
+        assert_eq!(Boolean(false), Double(f64::NAN).le(&Integer(0)).unwrap());
 
         assert_eq!(Boolean(false), Integer(2).le(&Integer(1)).unwrap());
         assert_eq!(Boolean(true), Integer(2).le(&Integer(2)).unwrap());
         assert_eq!(Boolean(true), Integer(2).le(&Integer(3)).unwrap());
-        assert_eq!(
-            "Cannot compare Integer(4) and Double(1.0) with <=",
-            format!("{}", Integer(4).le(&Double(1.0)).unwrap_err())
-        );
+        assert_eq!(Boolean(false), Integer(2).le(&Double(1.5)).unwrap());
+        assert_eq!(Boolean(true), Integer(2).le(&Double(2.0)).unwrap());
+        assert_eq!(Boolean(false), Integer(0).le(&Double(f64::NAN)).unwrap());
 
         assert_eq!(Boolean(false), Text("b".to_owned()).le(&Text("a".to_owned())).unwrap());
         assert_eq!(Boolean(true), Text("a".to_owned()).le(&Text("a".to_owned())).unwrap());
@@ -1009,17 +1569,15 @@ mod tests {
 
         assert_eq!(Boolean(false), Double(2.1).gt(&Double(2.1)).unwrap());
         assert_eq!(Boolean(true), Double(4.1).gt(&Double(4.0)).unwrap());
-        assert_eq!(
-            "Cannot compare Double(4.0) and Integer(1) with >",
-            format!("{}", Double(4.0).gt(&Integer(1)).unwrap_err())
-        );
+        assert_eq!(Boolean(false), Double(4.0).gt(&Integer(4)).unwrap());
+        assert_eq!(Boolean(true), Double(4.5).gt(&Integer(4)).unwrap());
+        assert_eq!(Boolean(false), Double(f64::NAN).gt(&Integer(0)).unwrap());
 
         assert_eq!(Boolean(false), Integer(2).gt(&Integer(2)).unwrap());
         assert_eq!(Boolean(true), Integer(4).gt(&Integer(3)).unwrap());
-        assert_eq!(
-            "Cannot compare Integer(4) and Double(1.0) with >",
-            format!("{}", Integer(4).gt(&Double(1.0)).unwrap_err())
-        );
+        assert_eq!(Boolean(false), Integer(1).gt(&Double(1.5)).unwrap());
+        assert_eq!(Boolean(true), Integer(2).gt(&Double(1.5)).unwrap());
+        assert_eq!(Boolean(false), Integer(0).gt(&Double(f64::NAN)).unwrap());
 
         assert_eq!(Boolean(false), Text("a".to_owned()).gt(&Text("a".to_owned())).unwrap());
         assert_eq!(Boolean(true), Text("c".to_owned()).gt(&Text("a".to_owned())).unwrap());
@@ -1041,18 +1599,16 @@ mod tests {
         assert_eq!(Boolean(false), Double(2.0).ge(&Double(2.1)).unwrap());
         assert_eq!(Boolean(true), Double(2.1).ge(&Double(2.1)).unwrap());
         assert_eq!(Boolean(true), Double(2.2).ge(&Double(2.1)).unwrap());
-        assert_eq!(
-            "Cannot compare Double(4.0) and Integer(1) with >=",
-            format!("{}", Double(4.0).ge(&Integer(1)).unwrap_err())
-        );
+        assert_eq!(Boolean(false), Double(3.5).ge(&Integer(4)).unwrap());
+        assert_eq!(Boolean(true), Double(4.0).ge(&Integer(4)).unwrap());
+        assert_eq!(Boolean(false), Double(f64::NAN).ge(&Integer(0)).unwrap());
 
         assert_eq!(Boolean(false), Integer(1).ge(&Integer(2)).unwrap());
         assert_eq!(Boolean(true), Integer(2).ge(&Integer(2)).unwrap());
         assert_eq!(Boolean(true), Integer(4).ge(&Integer(3)).unwrap());
-        assert_eq!(
-            "Cannot compare Integer(4) and Double(1.0) with >=",
-            format!("{}", Integer(4).ge(&Double(1.0)).unwrap_err())
-        );
+        assert_eq!(Boolean(false), Integer(1).ge(&Double(1.5)).unwrap());
+        assert_eq!(Boolean(true), Integer(2).ge(&Double(2.0)).unwrap());
+        assert_eq!(Boolean(false), Integer(0).ge(&Double(f64::NAN)).unwrap());
 
         assert_eq!(Boolean(false), Text("".to_owned()).ge(&Text("b".to_owned())).unwrap());
         assert_eq!(Boolean(true), Text("a".to_owned()).ge(&Text("a".to_owned())).unwrap());
@@ -1094,6 +1650,15 @@ mod tests {
             "Cannot add Text(\"\") and Boolean(false)",
             format!("{}", Text("".to_owned()).add(&Boolean(false)).unwrap_err())
         );
+
+        assert_eq!(
+            "Cannot add Empty and Integer(1)",
+            format!("{}", Empty.add(&Integer(1)).unwrap_err())
+        );
+        assert_eq!(
+            "Cannot add Text(\"a\") and Empty",
+            format!("{}", Text("a".to_owned()).add(&Empty).unwrap_err())
+        );
     }
 
     #[test]
@@ -1310,13 +1875,79 @@ mod tests {
         assert!(!VarRef::new("a", VarType::Text).accepts(&double_val));
         assert!(!VarRef::new("a", VarType::Text).accepts(&int_val));
         assert!(VarRef::new("a", VarType::Text).accepts(&text_val));
+
+        assert!(VarRef::new("a", VarType::Auto).accepts(&Value::Empty));
+        assert!(VarRef::new("a", VarType::Boolean).accepts(&Value::Empty));
+        assert!(VarRef::new("a", VarType::Double).accepts(&Value::Empty));
+        assert!(VarRef::new("a", VarType::Integer).accepts(&Value::Empty));
+        assert!(VarRef::new("a", VarType::Text).accepts(&Value::Empty));
+        assert!(!VarRef::new("a", VarType::Void).accepts(&Value::Empty));
+    }
+
+    #[test]
+    fn test_callable_metadata_build_checked_ok() {
+        let md = CallableMetadataBuilder::new("FOO", VarType::Void)
+            .with_syntax("")
+            .with_category("Testing")
+            .with_description("Does something.\nIn detail.")
+            .build_checked()
+            .unwrap();
+        assert_eq!("FOO", md.name());
+        assert_eq!("", md.syntax());
+        assert_eq!("Testing", md.category());
+        assert_eq!(vec!["Does something.", "In detail."], md.description().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_callable_metadata_build_checked_errors() {
+        fn check(exp_error: &str, builder: CallableMetadataBuilder) {
+            assert_eq!(exp_error, format!("{}", builder.build_checked().err().unwrap()));
+        }
+
+        let full = || {
+            CallableMetadataBuilder::new("FOO", VarType::Void)
+                .with_syntax("")
+                .with_category("Testing")
+                .with_description("Does something.")
+        };
+        check("Callable name cannot be empty", CallableMetadataBuilder::new("", VarType::Void));
+        check(
+            "Callable FOO must specify a syntax",
+            CallableMetadataBuilder::new("FOO", VarType::Void)
+                .with_category("Testing")
+                .with_description("Does something."),
+        );
+        check(
+            "Callable FOO must specify a category",
+            CallableMetadataBuilder::new("FOO", VarType::Void)
+                .with_syntax("")
+                .with_description("Does something."),
+        );
+        check("Callable FOO must specify a category", full().with_category(""));
+        check(
+            "Callable FOO must specify a description",
+            CallableMetadataBuilder::new("FOO", VarType::Void)
+                .with_syntax("")
+                .with_category("Testing"),
+        );
+        check("Callable FOO must specify a description", full().with_description(""));
+    }
+
+    #[test]
+    #[should_panic(expected = "Callable FOO must specify a description")]
+    fn test_callable_metadata_build_panics() {
+        CallableMetadataBuilder::new("FOO", VarType::Void)
+            .with_syntax("")
+            .with_category("Testing")
+            .with_description("")
+            .build();
     }
 
     #[test]
     fn test_vars_clear() {
         let mut raw_vars = HashMap::new();
         raw_vars.insert("FOO".to_owned(), Value::Boolean(true));
-        let mut vars = Vars { vars: raw_vars };
+        let mut vars = Vars { vars: raw_vars, ..Default::default() };
         assert!(!vars.is_empty());
         vars.clear();
         assert!(vars.is_empty());
@@ -1329,7 +1960,7 @@ mod tests {
         raw_vars.insert("A_DOUBLE".to_owned(), Value::Double(3.0));
         raw_vars.insert("AN_INTEGER".to_owned(), Value::Integer(3));
         raw_vars.insert("A_STRING".to_owned(), Value::Text("some text".to_owned()));
-        let vars = Vars { vars: raw_vars };
+        let vars = Vars { vars: raw_vars, ..Default::default() };
 
         assert_eq!(
             Value::Boolean(true),
@@ -1356,7 +1987,7 @@ mod tests {
         raw_vars.insert("A_DOUBLE".to_owned(), Value::Double(3.0));
         raw_vars.insert("AN_INTEGER".to_owned(), Value::Integer(3));
         raw_vars.insert("A_STRING".to_owned(), Value::Text("some text".to_owned()));
-        let vars = Vars { vars: raw_vars };
+        let vars = Vars { vars: raw_vars, ..Default::default() };
 
         assert_eq!(
             Value::Boolean(true),
@@ -1377,7 +2008,7 @@ mod tests {
     fn test_vars_get_undefined_error() {
         let mut raw_vars = HashMap::new();
         raw_vars.insert("a_string".to_owned(), Value::Text("some text".to_owned()));
-        let vars = Vars { vars: raw_vars };
+        let vars = Vars { vars: raw_vars, ..Default::default() };
 
         assert_eq!(
             "Undefined variable a_str",
@@ -1392,7 +2023,7 @@ mod tests {
         raw_vars.insert("A_DOUBLE".to_owned(), Value::Double(3.0));
         raw_vars.insert("AN_INTEGER".to_owned(), Value::Integer(3));
         raw_vars.insert("A_STRING".to_owned(), Value::Text("some text".to_owned()));
-        let vars = Vars { vars: raw_vars };
+        let vars = Vars { vars: raw_vars, ..Default::default() };
 
         assert_eq!(
             "Incompatible types in a_boolean$ reference",
@@ -1467,6 +2098,81 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_vars_set_empty() {
+        let mut vars = Vars::default();
+
+        for (name, vtype) in &[
+            ("a", VarType::Auto),
+            ("b", VarType::Boolean),
+            ("d", VarType::Double),
+            ("i", VarType::Integer),
+            ("t", VarType::Text),
+        ] {
+            vars.set(&VarRef::new(*name, *vtype), Value::Empty).unwrap();
+            assert_eq!(Value::Empty, *vars.get(&VarRef::new(*name, *vtype)).unwrap());
+            assert_eq!(Value::Empty, *vars.get(&VarRef::new(*name, VarType::Auto)).unwrap());
+        }
+
+        // A variable that was never typed takes the type of the first non-empty value.
+        vars.set(&VarRef::new("a", VarType::Auto), Value::Text("x".to_owned())).unwrap();
+        assert_eq!(
+            Value::Text("x".to_owned()),
+            *vars.get(&VarRef::new("a", VarType::Auto)).unwrap()
+        );
+
+        vars.set(&VarRef::new("i", VarType::Auto), Value::Integer(3)).unwrap();
+        vars.set(&VarRef::new("i", VarType::Auto), Value::Empty).unwrap();
+        vars.set(&VarRef::new("i", VarType::Auto), Value::Integer(4)).unwrap();
+        assert_eq!(Value::Integer(4), *vars.get(&VarRef::new("i", VarType::Auto)).unwrap());
+    }
+
+    #[test]
+    fn test_vars_set_empty_keeps_type() {
+        let mut vars = Vars::default();
+
+        vars.set(&VarRef::new("a", VarType::Integer), Value::Empty).unwrap();
+        assert_eq!(
+            "Incompatible types in a$ assignment",
+            format!(
+                "{}",
+                vars.set(&VarRef::new("a", VarType::Text), Value::Text("x".to_owned()))
+                    .unwrap_err()
+            )
+        );
+        assert_eq!(
+            "Incompatible types in a assignment",
+            format!(
+                "{}",
+                vars.set(&VarRef::new("a", VarType::Auto), Value::Text("x".to_owned()))
+                    .unwrap_err()
+            )
+        );
+        assert_eq!(
+            "Incompatible types in a$ reference",
+            format!("{}", vars.get(&VarRef::new("a", VarType::Text)).unwrap_err())
+        );
+        assert_eq!(
+            "Incompatible types in a$ assignment",
+            format!("{}", vars.set(&VarRef::new("a", VarType::Text), Value::Empty).unwrap_err())
+        );
+
+        vars.set(&VarRef::new("b", VarType::Auto), Value::Boolean(true)).unwrap();
+        vars.set(&VarRef::new("b", VarType::Auto), Value::Empty).unwrap();
+        vars.set(&VarRef::new("b", VarType::Auto), Value::Empty).unwrap();
+        assert_eq!(
+            "Incompatible types in b assignment",
+            format!(
+                "{}",
+                vars.set(&VarRef::new("b", VarType::Auto), Value::Integer(1)).unwrap_err()
+            )
+        );
+        vars.set(&VarRef::new("b", VarType::Boolean), Value::Boolean(false)).unwrap();
+
+        vars.clear();
+        vars.set(&VarRef::new("a", VarType::Text), Value::Text("x".to_owned())).unwrap();
+    }
+
     #[test]
     fn test_vars_set_mismatched_type_with_existing_value() {
         let bool_ref = VarRef::new("a_boolean", VarType::Auto);
@@ -1537,6 +2243,11 @@ mod tests {
         let mut vars = Vars::default();
         vars.set(&VarRef::new("SomeName", VarType::Auto), Value::Integer(6)).unwrap();
         assert_eq!(Value::Integer(6), *vars.get(&VarRef::new("somename", VarType::Auto)).unwrap());
+
+        vars.set(&VarRef::new("SOMENAME", VarType::Integer), Value::Integer(7)).unwrap();
+        assert_eq!(Value::Integer(7), *vars.get(&VarRef::new("someName", VarType::Auto)).unwrap());
+        assert_eq!(1, vars.as_hashmap().len());
+        assert_eq!(Value::Integer(7), *vars.as_hashmap().get("SOMENAME").unwrap());
     }
 
     #[test]
@@ -1594,6 +2305,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_expr_channel_error() {
+        let vars = Vars::default();
+        let fs = HashMap::default();
+        assert_eq!(
+            "Channel references are only valid as command arguments",
+            format!("{}", Expr::Channel(Box::from(Expr::Integer(1))).eval(&vars, &fs).unwrap_err())
+        );
+    }
+
     #[test]
     fn test_expr_logical_ops() {
         let a_bool = Box::from(Expr::Boolean(false));
@@ -1832,6 +2553,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_expr_function_call_without_parens() {
+        let mut vars = Vars::default();
+
+        let mut fs: HashMap<&'static str, Rc<dyn Function>> = HashMap::default();
+        let sum = SumFunction::new();
+        fs.insert(sum.metadata().name(), sum);
+
+        assert_eq!(
+            Value::Integer(0),
+            Expr::Symbol(VarRef::new("sum", VarType::Auto)).eval(&vars, &fs).unwrap()
+        );
+        assert_eq!(
+            Value::Integer(0),
+            Expr::Symbol(VarRef::new("SUM", VarType::Integer)).eval(&vars, &fs).unwrap()
+        );
+        assert_eq!(
+            "Incompatible type annotation for function call",
+            format!(
+                "{}",
+                Expr::Symbol(VarRef::new("SUM", VarType::Text)).eval(&vars, &fs).unwrap_err()
+            )
+        );
+
+        vars.set(&VarRef::new("sum", VarType::Integer), Value::Integer(8)).unwrap();
+        assert_eq!(
+            Value::Integer(8),
+            Expr::Symbol(VarRef::new("SUM", VarType::Auto)).eval(&vars, &fs).unwrap()
+        );
+        assert_eq!(
+            "Incompatible types in SUM$ reference",
+            format!(
+                "{}",
+                Expr::Symbol(VarRef::new("SUM", VarType::Text)).eval(&vars, &fs).unwrap_err()
+            )
+        );
+    }
+
     #[test]
     fn test_expr_function_call_type_check() {
         let vars = Vars::default();
@@ -1864,6 +2623,359 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_expr_overflow_modes() {
+        let int = |i| Box::from(Expr::Integer(i));
+        let exprs = [
+            Expr::Add(int(i32::MAX), int(1)),
+            Expr::Subtract(int(i32::MIN), int(1)),
+            Expr::Multiply(int(i32::MAX), int(2)),
+            Expr::Divide(int(i32::MIN), int(-1)),
+            Expr::Modulo(int(i32::MIN), int(-1)),
+            Expr::Negate(int(i32::MIN)),
+        ];
+        let wrapped = [i32::MIN, i32::MAX, i32::MAX.wrapping_mul(2), i32::MIN, 0, i32::MIN]
+            .map(Value::Integer);
+        let errors = [
+            format!("Overflow adding {} and 1", i32::MAX),
+            format!("Overflow subtracting 1 from {}", i32::MIN),
+            format!("Overflow multiplying {} by 2", i32::MAX),
+            format!("Overflow dividing {} by -1", i32::MIN),
+            format!("Overflow modulo {} by -1", i32::MIN),
+            format!("Overflow negating {}", i32::MIN),
+        ];
+        let fs = HashMap::default();
+
+        let mut vars = Vars::default();
+        assert!(!vars.overflow_wraps());
+        for (expr, error) in exprs.iter().zip(errors.iter()) {
+            assert_eq!(*error, format!("{}", expr.eval(&vars, &fs).unwrap_err()));
+        }
+
+        vars.set_overflow_wraps(true);
+        for (expr, value) in exprs.iter().zip(wrapped.iter()) {
+            assert_eq!(*value, expr.eval(&vars, &fs).unwrap());
+        }
+        assert_eq!(
+            Value::Integer(5),
+            Expr::Add(int(2), int(3)).eval(&vars, &fs).unwrap(),
+            "Non-overflowing operations must be unaffected"
+        );
+        assert_eq!(
+            "Division by zero",
+            format!("{}", Expr::Divide(int(1), int(0)).eval(&vars, &fs).unwrap_err())
+        );
+        assert_eq!(
+            "Modulo by zero",
+            format!("{}", Expr::Modulo(int(1), int(0)).eval(&vars, &fs).unwrap_err())
+        );
+
+        vars.clear();
+        assert!(!vars.overflow_wraps());
+    }
+
+    #[test]
+    fn test_numeric_booleans_option() {
+        let fs = HashMap::default();
+
+        let mut vars = Vars::default();
+        assert!(!vars.numeric_booleans());
+        vars.set_numeric_booleans(true);
+        assert!(vars.numeric_booleans());
+        assert_eq!(
+            Value::Boolean(true),
+            Expr::Equal(Box::from(Expr::Integer(1)), Box::from(Expr::Integer(1)))
+                .eval(&vars, &fs)
+                .unwrap(),
+            "Comparisons must still yield booleans"
+        );
+
+        vars.clear();
+        assert!(!vars.numeric_booleans());
+    }
+
+    /// Builds a call to `name` with `vtype` annotation and the given `args`.
+    fn call(name: &str, vtype: VarType, args: Vec<Expr>) -> Expr {
+        Expr::Call(VarRef::new(name, vtype), args)
+    }
+
+    /// Builds an expression that fails to evaluate if it is ever reached.
+    fn div_by_zero() -> Expr {
+        Expr::Divide(Box::from(Expr::Integer(1)), Box::from(Expr::Integer(0)))
+    }
+
+    #[test]
+    fn test_expr_iif() {
+        let mut vars = Vars::default();
+        vars.set(&VarRef::new("x", VarType::Integer), Value::Integer(0)).unwrap();
+        let fs = HashMap::default();
+
+        let x = Expr::Symbol(VarRef::new("x", VarType::Integer));
+        let cond = Expr::Equal(Box::from(x.clone()), Box::from(Expr::Integer(0)));
+        let guarded = Expr::Divide(Box::from(Expr::Integer(10)), Box::from(x));
+        assert_eq!(
+            Value::Integer(-1),
+            call("iif", VarType::Auto, vec![cond, Expr::Integer(-1), guarded])
+                .eval(&vars, &fs)
+                .unwrap()
+        );
+
+        assert_eq!(
+            Value::Text("a".to_owned()),
+            call(
+                "IIF",
+                VarType::Text,
+                vec![Expr::Boolean(true), Expr::Text("a".to_owned()), div_by_zero()]
+            )
+            .eval(&vars, &fs)
+            .unwrap()
+        );
+        assert_eq!(
+            Value::Boolean(false),
+            call(
+                "IIF",
+                VarType::Auto,
+                vec![
+                    Expr::Boolean(false),
+                    Expr::Symbol(VarRef::new("undefined", VarType::Auto)),
+                    Expr::Boolean(false)
+                ]
+            )
+            .eval(&vars, &fs)
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_expr_iif_errors() {
+        let vars = Vars::default();
+        let fs = HashMap::default();
+
+        for (expected, expr) in &[
+            (
+                "IIF requires a condition and two values",
+                call("IIF", VarType::Auto, vec![Expr::Boolean(true), Expr::Integer(1)]),
+            ),
+            (
+                "IIF requires a boolean condition",
+                call("IIF", VarType::Auto, vec![Expr::Integer(1), div_by_zero(), div_by_zero()]),
+            ),
+            (
+                "Division by zero",
+                call(
+                    "IIF",
+                    VarType::Auto,
+                    vec![Expr::Boolean(false), Expr::Integer(1), div_by_zero()],
+                ),
+            ),
+            (
+                "Incompatible type annotation for function call",
+                call(
+                    "IIF",
+                    VarType::Text,
+                    vec![Expr::Boolean(true), Expr::Integer(1), Expr::Text("a".to_owned())],
+                ),
+            ),
+        ] {
+            assert_eq!(*expected, format!("{}", expr.eval(&vars, &fs).unwrap_err()));
+        }
+    }
+
+    #[test]
+    fn test_expr_choose() {
+        let vars = Vars::default();
+        let fs = HashMap::default();
+
+        let values = || {
+            vec![Expr::Text("a".to_owned()), Expr::Integer(2), Expr::Boolean(true), div_by_zero()]
+        };
+        for (index, expected) in
+            &[(1, Value::Text("a".to_owned())), (2, Value::Integer(2)), (3, Value::Boolean(true))]
+        {
+            let mut args = vec![Expr::Integer(*index)];
+            args.extend(values());
+            assert_eq!(*expected, call("choose", VarType::Auto, args).eval(&vars, &fs).unwrap());
+        }
+
+        assert_eq!(
+            Value::Integer(2),
+            call("CHOOSE", VarType::Integer, vec![Expr::Integer(1), Expr::Integer(2)])
+                .eval(&vars, &fs)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_expr_choose_errors() {
+        let vars = Vars::default();
+        let fs = HashMap::default();
+
+        for (expected, expr) in &[
+            (
+                "CHOOSE requires an index and at least one value",
+                call("CHOOSE", VarType::Auto, vec![Expr::Integer(1)]),
+            ),
+            (
+                "CHOOSE requires an integer index",
+                call("CHOOSE", VarType::Auto, vec![Expr::Double(1.0), Expr::Integer(1)]),
+            ),
+            (
+                "CHOOSE index 0 out of range [1, 2]",
+                call(
+                    "CHOOSE",
+                    VarType::Auto,
+                    vec![Expr::Integer(0), Expr::Integer(1), Expr::Integer(2)],
+                ),
+            ),
+            (
+                "CHOOSE index 3 out of range [1, 2]",
+                call(
+                    "CHOOSE",
+                    VarType::Auto,
+                    vec![Expr::Integer(3), Expr::Integer(1), Expr::Integer(2)],
+                ),
+            ),
+            (
+                "Division by zero",
+                call("CHOOSE", VarType::Auto, vec![Expr::Integer(1), div_by_zero()]),
+            ),
+            (
+                "Incompatible type annotation for function call",
+                call("CHOOSE", VarType::Double, vec![Expr::Integer(1), Expr::Integer(1)]),
+            ),
+        ] {
+            assert_eq!(*expected, format!("{}", expr.eval(&vars, &fs).unwrap_err()));
+        }
+    }
+
+    #[test]
+    fn test_expr_min_max() {
+        let vars = Vars::default();
+        let fs = HashMap::default();
+
+        for (name, args, expected) in &[
+            ("MIN", vec![Expr::Integer(3), Expr::Integer(-2)], Value::Integer(-2)),
+            ("max", vec![Expr::Integer(3), Expr::Integer(-2)], Value::Integer(3)),
+            (
+                "MIN",
+                vec![Expr::Integer(5), Expr::Integer(1), Expr::Integer(8), Expr::Integer(1)],
+                Value::Integer(1),
+            ),
+            (
+                "MAX",
+                vec![Expr::Integer(5), Expr::Integer(1), Expr::Integer(8), Expr::Integer(2)],
+                Value::Integer(8),
+            ),
+            ("MIN", vec![Expr::Double(2.5), Expr::Double(-0.5)], Value::Double(-0.5)),
+            ("MIN", vec![Expr::Integer(2), Expr::Double(2.5)], Value::Double(2.0)),
+            (
+                "MAX",
+                vec![Expr::Double(2.5), Expr::Integer(7), Expr::Integer(3)],
+                Value::Double(7.0),
+            ),
+        ] {
+            let value = call(name, VarType::Auto, args.clone()).eval(&vars, &fs).unwrap();
+            assert_eq!(*expected, value);
+        }
+
+        assert_eq!(
+            Value::Double(1.0),
+            call("MIN", VarType::Double, vec![Expr::Double(1.0), Expr::Integer(2)])
+                .eval(&vars, &fs)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_expr_min_max_errors() {
+        let vars = Vars::default();
+        let fs = HashMap::default();
+
+        for (expected, expr) in &[
+            ("MIN requires at least two values", call("MIN", VarType::Auto, vec![])),
+            (
+                "MAX requires at least two values",
+                call("MAX", VarType::Auto, vec![Expr::Integer(1)]),
+            ),
+            (
+                "MIN requires numeric values",
+                call("MIN", VarType::Auto, vec![Expr::Integer(1), Expr::Text("a".to_owned())]),
+            ),
+            (
+                "MAX requires numeric values",
+                call("MAX", VarType::Auto, vec![Expr::Boolean(true), Expr::Double(1.0)]),
+            ),
+            ("Division by zero", call("MAX", VarType::Auto, vec![Expr::Integer(1), div_by_zero()])),
+            (
+                "Incompatible type annotation for function call",
+                call("MAX", VarType::Integer, vec![Expr::Integer(1), Expr::Double(1.0)]),
+            ),
+        ] {
+            assert_eq!(*expected, format!("{}", expr.eval(&vars, &fs).unwrap_err()));
+        }
+    }
+
+    #[test]
+    fn test_expr_eval() {
+        let mut vars = Vars::default();
+        vars.set(&VarRef::new("a", VarType::Integer), Value::Integer(5)).unwrap();
+        vars.set(&VarRef::new("d", VarType::Double), Value::Double(5.0)).unwrap();
+        let fs = HashMap::default();
+
+        let eval = |text: &str, vtype| call("EVAL", vtype, vec![Expr::Text(text.to_owned())]);
+        assert_eq!(Value::Integer(7), eval("1 + 2 * 3", VarType::Auto).eval(&vars, &fs).unwrap());
+        assert_eq!(Value::Double(2.5), eval("d / 2.0", VarType::Double).eval(&vars, &fs).unwrap());
+        assert_eq!(
+            Value::Integer(6),
+            eval("MAX(a + 1, 3)", VarType::Auto).eval(&vars, &fs).unwrap()
+        );
+        assert_eq!(
+            Value::Boolean(true),
+            eval("EVAL(\"a\") = 5", VarType::Boolean).eval(&vars, &fs).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_expr_eval_errors() {
+        let vars = Vars::default();
+        let fs = HashMap::default();
+
+        let eval = |text: &str| call("EVAL", VarType::Auto, vec![Expr::Text(text.to_owned())]);
+        for (expected, expr) in &[
+            ("EVAL requires a string", call("EVAL", VarType::Auto, vec![])),
+            ("EVAL requires a string", call("EVAL", VarType::Auto, vec![Expr::Integer(1)])),
+            (
+                "EVAL requires a string",
+                call("EVAL", VarType::Auto, vec![Expr::Text("1".to_owned()), Expr::Integer(1)]),
+            ),
+            ("Invalid expression: Missing expression", eval("")),
+            ("Invalid expression: Not enough values to apply operator", eval("1 +")),
+            ("Invalid expression: Expected a single expression", eval("1: PRINT 2")),
+            ("Undefined variable a", eval("a + 1")),
+            ("Division by zero", eval("1 / 0")),
+            (
+                "Incompatible type annotation for function call",
+                call("EVAL", VarType::Text, vec![Expr::Text("1".to_owned())]),
+            ),
+        ] {
+            assert_eq!(*expected, format!("{}", expr.eval(&vars, &fs).unwrap_err()));
+        }
+    }
+
+    #[test]
+    fn test_expr_eval_self_reference() {
+        let mut vars = Vars::default();
+        vars.set(&VarRef::new("a", VarType::Text), Value::Text("EVAL(a$)".to_owned())).unwrap();
+        let fs = HashMap::default();
+
+        let expr = call("EVAL", VarType::Auto, vec![Expr::Symbol(VarRef::new("a", VarType::Text))]);
+        assert_eq!("EVAL nested too deeply", format!("{}", expr.eval(&vars, &fs).unwrap_err()));
+
+        // The depth must be restored after the failure so that later calls work.
+        let expr = call("EVAL", VarType::Auto, vec![Expr::Text("EVAL(\"1\")".to_owned())]);
+        assert_eq!(Value::Integer(1), expr.eval(&vars, &fs).unwrap());
+    }
+
     #[test]
     fn test_expr_function_error_check() {
         let vars = Vars::default();
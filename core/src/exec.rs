@@ -15,11 +15,14 @@
 
 //! Execution engine for EndBASIC programs.
 
-use crate::ast::{ArgSep, Expr, Statement, Value, VarRef, VarType};
+use crate::ast::{ArgSep, Expr, LineCol, Statement, Value, VarRef, VarType};
+pub use crate::eval::ErrorKind;
 use crate::eval::{self, CallableMetadata, Function, Vars};
+use crate::fold;
 use crate::parser::{self, Parser};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::future::Future;
 use std::io;
 use std::pin::Pin;
@@ -28,6 +31,10 @@ use std::rc::Rc;
 /// Execution errors.
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    /// Attempt to register a builtin callable with a name that is already in use.
+    #[error("A command or function named {0} is already registered")]
+    DuplicateCallable(&'static str),
+
     /// Evaluation error during execution.
     #[error("{0}")]
     EvalError(#[from] eval::Error),
@@ -36,6 +43,22 @@ pub enum Error {
     #[error("{0}")]
     IoError(#[from] io::Error),
 
+    /// Execution was interrupted by the user.
+    #[error("Break")]
+    Break,
+
+    /// Error raised by the statement at the given position in the source code.
+    ///
+    /// The message of the wrapped error is returned as is; use `position` to query the location.
+    #[error("{1}")]
+    Located(LineCol, Box<Error>),
+
+    /// Error raised while executing a stored program, as opposed to a line typed interactively.
+    ///
+    /// The message of the wrapped error is returned as is; use `is_in_program` to query this.
+    #[error("{0}")]
+    InProgram(Box<Error>),
+
     /// Parsing error during execution.
     #[error("{0}")]
     ParseError(#[from] parser::Error),
@@ -49,6 +72,65 @@ pub enum Error {
     UsageError(String),
 }
 
+impl Error {
+    /// Annotates this error with the position `pos` of the statement that raised it, unless the
+    /// error already carries a more specific position.
+    pub(crate) fn at(self, pos: LineCol) -> Self {
+        match self {
+            Error::Located(..) | Error::InProgram(..) => self,
+            e => Error::Located(pos, Box::from(e)),
+        }
+    }
+
+    /// Marks this error as raised while executing a stored program, which tells front-ends that
+    /// its position refers to a line of that program and not to the line typed by the user.
+    pub fn in_program(self) -> Self {
+        match self {
+            Error::InProgram(..) => self,
+            e => Error::InProgram(Box::from(e)),
+        }
+    }
+
+    /// Returns true if this error was raised while executing a stored program.
+    pub fn is_in_program(&self) -> bool {
+        matches!(self, Error::InProgram(..))
+    }
+
+    /// Returns true if this error represents an interruption requested by the user.
+    pub fn is_break(&self) -> bool {
+        match self {
+            Error::Break => true,
+            Error::Located(_, e) => e.is_break(),
+            Error::InProgram(e) => e.is_break(),
+            _ => false,
+        }
+    }
+
+    /// Returns the category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::DuplicateCallable(_) => ErrorKind::Internal,
+            Error::EvalError(e) => e.kind(),
+            Error::IoError(_) => ErrorKind::IoError,
+            Error::Break => ErrorKind::Break,
+            Error::Located(_, e) => e.kind(),
+            Error::InProgram(e) => e.kind(),
+            Error::ParseError(_) => ErrorKind::SyntaxError,
+            Error::SyntaxError(_) => ErrorKind::SyntaxError,
+            Error::UsageError(_) => ErrorKind::UsageError,
+        }
+    }
+
+    /// Returns the position of the statement that raised this error, if known.
+    pub fn position(&self) -> Option<LineCol> {
+        match self {
+            Error::Located(pos, _) => Some(*pos),
+            Error::InProgram(e) => e.position(),
+            _ => None,
+        }
+    }
+}
+
 /// Result for execution return values.
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -62,6 +144,42 @@ pub fn new_usage_error<T, S: Into<String>>(message: S) -> Result<T> {
     Err(Error::UsageError(message.into()))
 }
 
+/// Parses the whole program in `input` without executing it and returns all syntax errors found
+/// in it, instead of stopping at the first one like `Machine::exec` does.
+///
+/// Each returned error carries the position where it was detected.
+pub fn check_syntax(input: &mut dyn io::Read) -> Vec<Error> {
+    let (_stmts, errors) = Parser::from(input).parse_all();
+    errors.into_iter().map(|(pos, e)| Error::from(e).at(pos)).collect()
+}
+
+/// Converts the `value` of a `FOR` loop's start, end or step to a double.
+fn for_bound_as_f64(value: Value) -> Result<f64> {
+    match value {
+        Value::Double(d) => Ok(d),
+        Value::Integer(i) => Ok(i as f64),
+        _ => new_syntax_error("FOR supports numeric iteration only"),
+    }
+}
+
+/// Computes the number of iterations of a `FOR` loop over doubles that goes from `start` to `end`
+/// in increments of `step`.
+///
+/// Adding `step` to the iterator over and over again accumulates floating point errors, which
+/// can cause the loop to miss its last iteration: e.g. `FOR x = 0 TO 1 STEP 0.1` could stop at
+/// `0.9`.  To avoid this, we compute the number of iterations up front by dividing the range by
+/// the step and rounding the quotient to 9 decimal places before truncating it.  The iterator is
+/// then computed on each iteration as `start + i * step` where `i` is the iteration number.
+fn for_iterations(start: f64, end: f64, step: f64) -> u64 {
+    debug_assert!(step != 0.0);
+    let steps = ((end - start) / step * 1e9).round() / 1e9;
+    if steps >= 0.0 {
+        steps.floor() as u64 + 1
+    } else {
+        0
+    }
+}
+
 /// A trait to define a command that is executed by a `Machine`.
 ///
 /// The commands themselves are immutable but they can reference mutable state.  Given that
@@ -91,6 +209,69 @@ pub trait Command {
     async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()>;
 }
 
+/// A source of requests to interrupt the execution of a program, such as the user pressing Ctrl-C.
+pub trait InterruptSource {
+    /// Returns true if an interruption was requested since the last call.
+    fn take_interrupt(&self) -> bool;
+}
+
+/// A flag that can be raised from outside of the machine to interrupt execution.
+impl InterruptSource for Cell<bool> {
+    fn take_interrupt(&self) -> bool {
+        self.replace(false)
+    }
+}
+
+/// Hook to reset state that lives outside of the machine, such as files opened by a program.
+pub trait Clearable {
+    /// Resets the state, releasing any resources that the program left behind.
+    ///
+    /// This is called when the machine is cleared and when a stored program finishes running.
+    fn reset_state(&self) -> Result<()>;
+}
+
+/// Hook to observe the commands and functions registered in a machine.
+pub trait RegistrationListener {
+    /// Called when the command or function described by `metadata` is registered.
+    fn on_register(&self, metadata: &CallableMetadata);
+}
+
+/// Hook to hand control over to the user when a program reaches a breakpoint.
+#[async_trait(?Send)]
+pub trait Debugger {
+    /// Called when the statement at `pos` is about to run and its line has a breakpoint, or when
+    /// it is the next statement to run after a `step` request.
+    ///
+    /// The implementation can inspect and modify the `machine`, and even run other code in it, for
+    /// as long as it wants.  Breakpoints are disabled while this runs.  Execution of the program
+    /// resumes once this returns `None`, whereas returning a stop reason terminates the program as
+    /// if it had run into an `END` or `EXIT` itself.
+    async fn on_breakpoint(
+        &self,
+        machine: &mut Machine,
+        pos: LineCol,
+    ) -> Result<Option<StopReason>>;
+
+    /// Called right after the statement at `pos` requested the program to stop via
+    /// `Machine::request_stop`.
+    ///
+    /// This behaves like `on_breakpoint` and, by default, delegates to it.
+    async fn on_stop(&self, machine: &mut Machine, pos: LineCol) -> Result<Option<StopReason>> {
+        self.on_breakpoint(machine, pos).await
+    }
+
+    /// Called when a program finishes while a `step` request is still pending, which means that
+    /// there were no more statements to step into.
+    fn on_step_past_end(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after the watched variable `name` has been assigned the new `value`.
+    fn on_watch(&self, _name: &str, _value: &Value) -> Result<()> {
+        Ok(())
+    }
+}
+
 /// Describes how the machine stopped execution while it was running a script via `exec()`.
 #[derive(Clone, Debug, PartialEq)]
 #[must_use]
@@ -98,6 +279,12 @@ pub enum StopReason {
     /// Execution terminates because the machine reached the end of the input.
     Eof,
 
+    /// Execution terminated because the program reached an `END` statement.
+    ///
+    /// Unlike `Exited`, this only requests the termination of the running program: an
+    /// interactive interpreter should keep running after it.
+    End(u8),
+
     /// Execution terminated because the machine was asked to terminate with `exit()`.
     Exited(u8),
 }
@@ -107,6 +294,7 @@ impl StopReason {
     pub fn as_exit_code(&self) -> i32 {
         match self {
             StopReason::Eof => 0,
+            StopReason::End(i) => *i as i32,
             StopReason::Exited(i) => *i as i32,
         }
     }
@@ -117,34 +305,206 @@ impl StopReason {
 pub struct Machine {
     commands: HashMap<&'static str, Rc<dyn Command>>,
     functions: HashMap<&'static str, Rc<dyn Function>>,
+    aliases: HashMap<&'static str, &'static str>,
+    clearables: Vec<Rc<dyn Clearable>>,
+    listeners: Vec<Rc<dyn RegistrationListener>>,
     vars: Vars,
     stop_reason: Option<StopReason>,
+    interrupt: Option<Rc<dyn InterruptSource>>,
+    breakpoints: BTreeSet<usize>,
+    debugger: Option<Rc<dyn Debugger>>,
+    breakpoints_armed: bool,
+    step: bool,
+    stop_requested: bool,
+    watches: BTreeSet<String>,
+    profiling: bool,
+    profile: BTreeMap<usize, usize>,
 }
 
 impl Machine {
-    /// Registers the given builtin command, which must not yet be registered.
-    pub fn add_command(&mut self, command: Rc<dyn Command>) {
-        let metadata = command.metadata();
-        assert!(
-            self.commands.get(&metadata.name()).is_none(),
-            "Command with the same name already registered"
-        );
-        self.commands.insert(metadata.name(), command);
+    /// Registers the given builtin command.
+    ///
+    /// Fails if a command or a function with the same name is already registered, as both share
+    /// the same namespace.
+    pub fn add_command(&mut self, command: Rc<dyn Command>) -> Result<()> {
+        let name = command.metadata().name();
+        if self.is_name_in_use(name) {
+            return Err(Error::DuplicateCallable(name));
+        }
+        for listener in &self.listeners {
+            listener.on_register(command.metadata());
+        }
+        self.commands.insert(name, command);
+        Ok(())
     }
 
-    /// Registers the given builtin function, which must not yet be registered.
-    pub fn add_function(&mut self, function: Rc<dyn Function>) {
-        let metadata = function.metadata();
-        assert!(
-            self.functions.get(&metadata.name()).is_none(),
-            "Function with the same name already registered"
-        );
-        self.functions.insert(metadata.name(), function);
+    /// Registers the given builtin function.
+    ///
+    /// Fails if a command or a function with the same name is already registered, as both share
+    /// the same namespace.
+    pub fn add_function(&mut self, function: Rc<dyn Function>) -> Result<()> {
+        let name = function.metadata().name();
+        if self.is_name_in_use(name) {
+            return Err(Error::DuplicateCallable(name));
+        }
+        for listener in &self.listeners {
+            listener.on_register(function.metadata());
+        }
+        self.functions.insert(name, function);
+        Ok(())
+    }
+
+    /// Registers `alias` as an alternate name for the already-registered command `name`.
+    ///
+    /// Fails if a command, a function or another alias with the same name as `alias` is already
+    /// registered, as they all share the same namespace.
+    ///
+    /// Panics if `name` is not a registered command.
+    pub fn add_alias(&mut self, alias: &'static str, name: &'static str) -> Result<()> {
+        assert!(self.commands.contains_key(name), "Aliases must refer to existing commands");
+        if self.is_name_in_use(alias) {
+            return Err(Error::DuplicateCallable(alias));
+        }
+        self.aliases.insert(alias, name);
+        Ok(())
+    }
+
+    /// Registers the given clearable to reset its state whenever the machine is cleared or a
+    /// program finishes.
+    pub fn add_clearable(&mut self, clearable: Rc<dyn Clearable>) {
+        self.clearables.push(clearable);
+    }
+
+    /// Registers the given listener to be notified of all commands and functions registered from
+    /// now on.  The listener is immediately notified of those that are already registered.
+    pub fn add_registration_listener(&mut self, listener: Rc<dyn RegistrationListener>) {
+        for command in self.commands.values() {
+            listener.on_register(command.metadata());
+        }
+        for function in self.functions.values() {
+            listener.on_register(function.metadata());
+        }
+        self.listeners.push(listener);
+    }
+
+    /// Returns true if `name` is already registered as a command, a function or an alias.
+    fn is_name_in_use(&self, name: &str) -> bool {
+        self.commands.contains_key(name)
+            || self.functions.contains_key(name)
+            || self.aliases.contains_key(name)
+    }
+
+    /// Sets the `source` of interruption requests to check for while executing programs.
+    ///
+    /// The machine checks for interruptions before running every statement and on every loop
+    /// iteration, and aborts execution with `Error::Break` when one is requested.
+    pub fn set_interrupt_source(&mut self, source: Rc<dyn InterruptSource>) {
+        self.interrupt = Some(source);
+    }
+
+    /// Sets the `debugger` to invoke when a program run via `exec_with_breakpoints` reaches a
+    /// breakpoint.
+    pub fn set_debugger(&mut self, debugger: Rc<dyn Debugger>) {
+        self.debugger = Some(debugger);
+    }
+
+    /// Sets a breakpoint on the given source `line`.
+    pub fn add_breakpoint(&mut self, line: usize) {
+        self.breakpoints.insert(line);
+    }
+
+    /// Clears the breakpoint on the given source `line`.  Returns false if there was none.
+    pub fn remove_breakpoint(&mut self, line: usize) -> bool {
+        self.breakpoints.remove(&line)
+    }
+
+    /// Obtains immutable access to the lines that have breakpoints set, in ascending order.
+    pub fn get_breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    /// Starts watching the variable `name` so that the debugger is notified of every assignment
+    /// that the program makes to it.
+    pub fn add_watch(&mut self, name: &str) {
+        self.watches.insert(name.to_ascii_uppercase());
+    }
+
+    /// Stops watching the variable `name`.  Returns false if it was not being watched.
+    pub fn remove_watch(&mut self, name: &str) -> bool {
+        self.watches.remove(&name.to_ascii_uppercase())
+    }
+
+    /// Obtains immutable access to the names of the watched variables, in ascending order.
+    pub fn get_watches(&self) -> &BTreeSet<String> {
+        &self.watches
+    }
+
+    /// Starts counting how many statements execute on each line of the programs run via
+    /// `exec_with_breakpoints`, discarding any previous counts.
+    pub fn start_profiling(&mut self) {
+        self.profiling = true;
+        self.profile.clear();
+    }
+
+    /// Stops counting line executions, keeping the counts collected so far.
+    pub fn stop_profiling(&mut self) {
+        self.profiling = false;
+    }
+
+    /// Obtains immutable access to the execution counts collected while profiling, keyed by line.
+    pub fn get_profile(&self) -> &BTreeMap<usize, usize> {
+        &self.profile
+    }
+
+    /// Requests the debugger to be invoked again before the next statement of the program being
+    /// debugged runs, regardless of whether that statement has a breakpoint or not.
+    ///
+    /// This is meant to be called while the debugger has control over a stopped program.
+    pub fn step(&mut self) {
+        self.step = true;
+    }
+
+    /// Requests the debugger to be invoked right after the current statement finishes, as if the
+    /// program had reached a breakpoint.
+    ///
+    /// If the program is not being debugged, because it was not run via `exec_with_breakpoints` or
+    /// because there is no debugger, the program terminates as if it had run into an `END`.
+    pub fn request_stop(&mut self) {
+        self.stop_requested = true;
+    }
+
+    /// Fails with `Error::Break` if an interruption has been requested.
+    fn check_interrupt(&self) -> Result<()> {
+        match &self.interrupt {
+            Some(source) if source.take_interrupt() => Err(Error::Break),
+            _ => Ok(()),
+        }
     }
 
-    /// Resets the state of the machine by clearing all variable.
-    pub fn clear(&mut self) {
-        self.vars.clear()
+    /// Resets the state of the machine by clearing all variables and resetting all clearables.
+    ///
+    /// All clearables are reset even if some of them fail, in which case the first error is
+    /// returned.
+    pub fn clear(&mut self) -> Result<()> {
+        self.vars.clear();
+        self.finish_program()
+    }
+
+    /// Tells the machine that a program finished running, either normally or due to an error, so
+    /// that all clearables release the resources the program left behind.  Variables are kept.
+    ///
+    /// All clearables are reset even if some of them fail, in which case the first error is
+    /// returned.
+    pub fn finish_program(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for clearable in &self.clearables {
+            if let Err(e) = clearable.reset_state() {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
     }
 
     /// Tells the machine to stop execution at the next statement boundary.
@@ -155,6 +515,11 @@ impl Machine {
         self.stop_reason = Some(StopReason::Exited(code));
     }
 
+    /// Obtains immutable access to the command aliases, mapping each alias to its command's name.
+    pub fn get_aliases(&self) -> &HashMap<&'static str, &'static str> {
+        &self.aliases
+    }
+
     /// Obtains immutable access to the builtin commands provided by this machine.
     pub fn get_commands(&self) -> &HashMap<&'static str, Rc<dyn Command>> {
         &self.commands
@@ -175,6 +540,28 @@ impl Machine {
         &mut self.vars
     }
 
+    /// Sets the variable `vref` to `value` and notifies the debugger if the variable is watched.
+    ///
+    /// Commands that assign values to variables must use this instead of `get_mut_vars` so that
+    /// their assignments are visible to `WATCH`.
+    pub fn set_var(&mut self, vref: &VarRef, value: Value) -> Result<()> {
+        if self.watches.is_empty() {
+            self.vars.set(vref, value)?;
+            return Ok(());
+        }
+
+        let name = vref.name().to_ascii_uppercase();
+        if !self.watches.contains(&name) {
+            self.vars.set(vref, value)?;
+            return Ok(());
+        }
+        self.vars.set(vref, value.clone())?;
+        match self.debugger.as_ref() {
+            Some(debugger) => debugger.on_watch(&name, &value),
+            None => Ok(()),
+        }
+    }
+
     /// Retrieves the variable `name` as a boolean.  Fails if it is some other type or if it's not
     /// defined.
     pub fn get_var_as_bool(&self, name: &str) -> Result<bool> {
@@ -205,17 +592,17 @@ impl Machine {
     /// Assigns the value of `expr` to the variable `vref`.
     fn assign(&mut self, vref: &VarRef, expr: &Expr) -> Result<()> {
         let value = expr.eval(&self.vars, &self.functions)?;
-        self.vars.set(&vref, value)?;
+        self.set_var(&vref, value)?;
         Ok(())
     }
 
     /// Executes an `IF` statement.
-    async fn do_if(&mut self, branches: &[(Expr, Vec<Statement>)]) -> Result<()> {
+    async fn do_if(&mut self, branches: &[(Expr, Vec<(Statement, LineCol)>)]) -> Result<()> {
         for (expr, stmts) in branches {
             match expr.eval(&self.vars, &self.functions)? {
                 Value::Boolean(true) => {
-                    for s in stmts {
-                        self.exec_one(s).await?;
+                    for (s, pos) in stmts {
+                        self.exec_one(s, *pos).await?;
                     }
                     break;
                 }
@@ -227,48 +614,112 @@ impl Machine {
     }
 
     /// Executes a `FOR` loop.
+    ///
+    /// The start and end values are evaluated only once, before the loop begins.  If these and the
+    /// `step` are all integers, the iterator is advanced by adding the step to it after each
+    /// iteration, which means that the body can modify the iterator to alter the loop.  Otherwise,
+    /// the loop operates on doubles and the iterator is recomputed on each iteration as described
+    /// in `for_iterations`.
     async fn do_for(
         &mut self,
         iterator: &VarRef,
         start: &Expr,
         end: &Expr,
-        next: &Expr,
-        body: &[Statement],
+        step: &Value,
+        body: &[(Statement, LineCol)],
     ) -> Result<()> {
         debug_assert!(
-            iterator.ref_type() == VarType::Auto || iterator.ref_type() == VarType::Integer
+            iterator.ref_type() == VarType::Auto
+                || iterator.ref_type() == VarType::Double
+                || iterator.ref_type() == VarType::Integer
         );
-        let start_value = start.eval(&self.vars, &self.functions)?;
-        match start_value {
-            Value::Integer(_) => self.vars.set(iterator, start_value)?,
-            _ => return new_syntax_error("FOR supports integer iteration only"),
+        let start = start.eval(&self.vars, &self.functions)?;
+        let end = end.eval(&self.vars, &self.functions)?;
+        match (start, end, step) {
+            (Value::Integer(start), Value::Integer(end), Value::Integer(step)) => {
+                self.do_for_integer(iterator, start, end, *step, body).await
+            }
+            (start, end, step) => {
+                let start = for_bound_as_f64(start)?;
+                let end = for_bound_as_f64(end)?;
+                let step = for_bound_as_f64(step.clone())?;
+                self.do_for_double(iterator, start, end, step, body).await
+            }
         }
+    }
 
+    /// Executes a `FOR` loop over integers.
+    async fn do_for_integer(
+        &mut self,
+        iterator: &VarRef,
+        start: i32,
+        end: i32,
+        step: i32,
+        body: &[(Statement, LineCol)],
+    ) -> Result<()> {
+        debug_assert!(step != 0);
+        self.set_var(iterator, Value::Integer(start))?;
         loop {
-            match end.eval(&self.vars, &self.functions)? {
-                Value::Boolean(false) => {
-                    break;
-                }
-                Value::Boolean(true) => (),
-                _ => panic!("Built-in condition should have evaluated to a boolean"),
+            let done = match self.vars.get(iterator)? {
+                Value::Integer(i) => (step > 0 && *i > end) || (step < 0 && *i < end),
+                _ => unreachable!("Variables cannot change types"),
+            };
+            if done {
+                break;
             }
 
-            for s in body {
-                self.exec_one(s).await?;
+            self.check_interrupt()?;
+            for (s, pos) in body {
+                self.exec_one(s, *pos).await?;
             }
+            if self.stop_reason.is_some() {
+                break;
+            }
+
+            let next = self.vars.get(iterator)?.add(&Value::Integer(step))?;
+            self.set_var(iterator, next)?;
+        }
+        Ok(())
+    }
+
+    /// Executes a `FOR` loop over doubles.
+    async fn do_for_double(
+        &mut self,
+        iterator: &VarRef,
+        start: f64,
+        end: f64,
+        step: f64,
+        body: &[(Statement, LineCol)],
+    ) -> Result<()> {
+        let iterations = for_iterations(start, end, step);
+        for i in 0..iterations {
+            self.set_var(iterator, Value::Double(start + (i as f64) * step))?;
 
-            self.assign(iterator, next)?;
+            self.check_interrupt()?;
+            for (s, pos) in body {
+                self.exec_one(s, *pos).await?;
+            }
+            if self.stop_reason.is_some() {
+                return Ok(());
+            }
         }
+
+        // Leave the iterator past the end value, just like integer loops do.
+        self.set_var(iterator, Value::Double(start + (iterations as f64) * step))?;
         Ok(())
     }
 
     /// Executes a `WHILE` loop.
-    async fn do_while(&mut self, condition: &Expr, body: &[Statement]) -> Result<()> {
+    async fn do_while(&mut self, condition: &Expr, body: &[(Statement, LineCol)]) -> Result<()> {
         loop {
             match condition.eval(&self.vars, &self.functions)? {
                 Value::Boolean(true) => {
-                    for s in body {
-                        self.exec_one(s).await?;
+                    self.check_interrupt()?;
+                    for (s, pos) in body {
+                        self.exec_one(s, *pos).await?;
+                    }
+                    if self.stop_reason.is_some() {
+                        break;
                     }
                 }
                 Value::Boolean(false) => break,
@@ -278,34 +729,170 @@ impl Machine {
         Ok(())
     }
 
+    /// Evaluates the optional exit `code` of an `END` statement and stops execution.
+    fn end(&mut self, code: &Option<Expr>) -> Result<()> {
+        let code = match code {
+            None => 0,
+            Some(expr) => match expr.eval(&self.vars, &self.functions)? {
+                Value::Integer(n) if n < 0 => {
+                    return new_usage_error("Exit code must be a positive integer")
+                }
+                Value::Integer(n) if n >= 128 => {
+                    return new_usage_error("Exit code cannot be larger than 127")
+                }
+                Value::Integer(n) => n as u8,
+                _ => return new_usage_error("Exit code must be a positive integer"),
+            },
+        };
+        self.stop_reason = Some(StopReason::End(code));
+        Ok(())
+    }
+
+    /// Executes a `MID$` assignment.
+    fn mid_assign(
+        &mut self,
+        target: &VarRef,
+        start: &Expr,
+        length: &Option<Expr>,
+        value: &Expr,
+    ) -> Result<()> {
+        let mut chars: Vec<char> = match self.vars.get(target)? {
+            Value::Text(text) => text.chars().collect(),
+            _ => return new_syntax_error("MID$ assignment requires a string variable"),
+        };
+
+        let start = match start.eval(&self.vars, &self.functions)? {
+            Value::Integer(n) if n < 0 || n as usize >= chars.len() => {
+                return new_syntax_error("MID$ start position out of range")
+            }
+            Value::Integer(n) => n as usize,
+            _ => return new_syntax_error("MID$ start position must be an integer"),
+        };
+
+        let length = match length {
+            None => None,
+            Some(expr) => match expr.eval(&self.vars, &self.functions)? {
+                Value::Integer(n) if n < 0 => {
+                    return new_syntax_error("MID$ length cannot be negative")
+                }
+                Value::Integer(n) => Some(n as usize),
+                _ => return new_syntax_error("MID$ length must be an integer"),
+            },
+        };
+
+        let replacement: Vec<char> = match value.eval(&self.vars, &self.functions)? {
+            Value::Text(text) => text.chars().collect(),
+            _ => return new_syntax_error("MID$ assignment requires a string value"),
+        };
+
+        let mut n = replacement.len().min(chars.len() - start);
+        if let Some(length) = length {
+            n = n.min(length);
+        }
+        chars[start..start + n].copy_from_slice(&replacement[..n]);
+
+        self.set_var(target, Value::Text(chars.into_iter().collect()))?;
+        Ok(())
+    }
+
+    /// Executes a single statement located at `pos`, annotating any error with that position.
+    async fn exec_one<'a>(&'a mut self, stmt: &'a Statement, pos: LineCol) -> Result<()> {
+        if self.profiling && self.breakpoints_armed {
+            *self.profile.entry(pos.line).or_insert(0) += 1;
+        }
+        self.check_breakpoint(pos).await.map_err(|e| e.at(pos))?;
+        self.exec_one_unlocated(stmt).await.map_err(|e| e.at(pos))?;
+        if std::mem::take(&mut self.stop_requested) {
+            self.check_stop(pos).await.map_err(|e| e.at(pos))?;
+        }
+        Ok(())
+    }
+
+    /// Hands control to the debugger after the statement at `pos` requested the program to stop,
+    /// or terminates the program if it is not being debugged.
+    async fn check_stop(&mut self, pos: LineCol) -> Result<()> {
+        let debugger = match self.debugger.as_ref() {
+            Some(debugger) if self.breakpoints_armed => debugger.clone(),
+            _ => {
+                self.stop_reason = Some(StopReason::End(0));
+                return Ok(());
+            }
+        };
+
+        self.step = false;
+        self.breakpoints_armed = false;
+        let result = debugger.on_stop(self, pos).await;
+        self.breakpoints_armed = true;
+        if let Some(reason) = result? {
+            self.stop_reason = Some(reason);
+        }
+        Ok(())
+    }
+
+    /// Hands control to the debugger if breakpoints are armed and the statement at `pos` has one
+    /// or a step was requested.
+    async fn check_breakpoint(&mut self, pos: LineCol) -> Result<()> {
+        if !self.breakpoints_armed
+            || self.stop_reason.is_some()
+            || !(self.step || self.breakpoints.contains(&pos.line))
+        {
+            return Ok(());
+        }
+        self.step = false;
+        let debugger = match self.debugger.as_ref() {
+            Some(debugger) => debugger.clone(),
+            None => return Ok(()),
+        };
+
+        self.breakpoints_armed = false;
+        let result = debugger.on_breakpoint(self, pos).await;
+        self.breakpoints_armed = true;
+        if let Some(reason) = result? {
+            self.stop_reason = Some(reason);
+        }
+        Ok(())
+    }
+
     /// Executes a single statement.
-    async fn exec_one<'a>(&'a mut self, stmt: &'a Statement) -> Result<()> {
+    async fn exec_one_unlocated<'a>(&'a mut self, stmt: &'a Statement) -> Result<()> {
         if self.stop_reason.is_some() {
             return Ok(());
         }
+        self.check_interrupt()?;
 
         match stmt {
             Statement::Assignment(vref, expr) => self.assign(vref, expr)?,
             Statement::BuiltinCall(name, args) => {
-                let cmd = match self.commands.get(name.as_str()) {
+                let name = self.aliases.get(name.as_str()).copied().unwrap_or(name.as_str());
+                let cmd = match self.commands.get(name) {
                     Some(cmd) => cmd.clone(),
-                    None => return new_syntax_error(format!("Unknown builtin {}", name)),
+                    None => {
+                        return Err(eval::Error::new(
+                            ErrorKind::UndefinedSymbol,
+                            format!("Unknown builtin {}", name),
+                        )
+                        .into())
+                    }
                 };
                 cmd.exec(&args, self).await?
             }
+            Statement::End(code) => self.end(code)?,
             Statement::If(branches) => {
                 // Change this to using FutureExt::boxed_local if we ever depend on the futures or
                 // futures_lite crate directly.
                 let f: Pin<Box<dyn Future<Output = Result<()>>>> = Box::pin(self.do_if(branches));
                 f.await?;
             }
-            Statement::For(iterator, start, end, next, body) => {
+            Statement::For(iterator, start, end, step, body) => {
                 // Change this to using FutureExt::boxed_local if we ever depend on the futures or
                 // futures_lite crate directly.
                 let f: Pin<Box<dyn Future<Output = Result<()>>>> =
-                    Box::pin(self.do_for(iterator, start, end, next, body));
+                    Box::pin(self.do_for(iterator, start, end, step, body));
                 f.await?;
             }
+            Statement::MidAssignment(target, start, length, value) => {
+                self.mid_assign(target, start, length, value)?
+            }
             Statement::While(condition, body) => {
                 // Change this to using FutureExt::boxed_local if we ever depend on the futures or
                 // futures_lite crate directly.
@@ -323,15 +910,38 @@ impl Machine {
     /// different programs on the same machine, all sharing state.
     pub async fn exec(&mut self, input: &mut dyn io::Read) -> Result<StopReason> {
         debug_assert!(self.stop_reason.is_none());
+        // Discard any interruption requested while no program was running.
+        let _ = self.check_interrupt();
         let mut parser = Parser::from(input);
         while self.stop_reason.is_none() {
             match parser.parse()? {
-                Some(stmt) => self.exec_one(&stmt).await?,
+                Some((mut stmt, pos)) => {
+                    fold::fold_statement(&mut stmt);
+                    self.exec_one(&stmt, pos).await?
+                }
                 None => break,
             }
         }
         Ok(self.stop_reason.take().unwrap_or(StopReason::Eof))
     }
+
+    /// Executes a program extracted from the `input` readable like `exec` does, but stopping at
+    /// any breakpoints to hand control to the debugger.
+    ///
+    /// Breakpoints and profiling only apply to programs run this way so that any code that the
+    /// debugger runs while the program is stopped does not trigger them.
+    pub async fn exec_with_breakpoints(&mut self, input: &mut dyn io::Read) -> Result<StopReason> {
+        let armed = self.breakpoints_armed;
+        self.breakpoints_armed = true;
+        let result = self.exec(input).await;
+        self.breakpoints_armed = armed;
+        if std::mem::take(&mut self.step) && result.is_ok() {
+            if let Some(debugger) = self.debugger.as_ref() {
+                debugger.on_step_past_end()?;
+            }
+        }
+        result
+    }
 }
 
 #[cfg(test)]
@@ -378,6 +988,33 @@ pub(crate) mod testutils {
         }
     }
 
+    /// Simplified version of `STOP` to test the machine's `request_stop()` method.
+    pub struct StopCommand {
+        metadata: CallableMetadata,
+    }
+
+    impl StopCommand {
+        /// Creates a new command that requests the program to stop once called.
+        pub fn new() -> Rc<Self> {
+            Rc::from(Self {
+                metadata: CallableMetadataBuilder::new("STOP", VarType::Void).test_build(),
+            })
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl Command for StopCommand {
+        fn metadata(&self) -> &CallableMetadata {
+            &self.metadata
+        }
+
+        async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+            assert!(args.is_empty(), "STOP takes no arguments");
+            machine.request_stop();
+            Ok(())
+        }
+    }
+
     /// Simplified version of `INPUT` to feed input values based on some golden `data`.
     ///
     /// Every time this command is invoked, it yields the next value from the `data` iterator and
@@ -420,7 +1057,7 @@ pub(crate) mod testutils {
             let mut data = self.data.borrow_mut();
             let raw_value = data.next().unwrap().to_owned();
             let value = Value::parse_as(vref.ref_type(), raw_value)?;
-            machine.get_mut_vars().set(vref, value)?;
+            machine.set_var(vref, value)?;
             Ok(())
         }
     }
@@ -458,7 +1095,7 @@ pub(crate) mod testutils {
                 }
                 match arg.1 {
                     ArgSep::End => break,
-                    ArgSep::Short => text += " ",
+                    ArgSep::Short | ArgSep::Space => text += " ",
                     ArgSep::Long => return new_usage_error("Cannot use the ',' separator"),
                 }
             }
@@ -473,6 +1110,7 @@ mod tests {
     use super::testutils::*;
     use super::*;
     use crate::eval::testutils::*;
+    use crate::eval::CallableMetadataBuilder;
     use futures_lite::future::block_on;
     use std::cell::RefCell;
     use std::rc::Rc;
@@ -486,11 +1124,73 @@ mod tests {
         );
         assert!(machine.get_var_as_bool("a").is_ok());
         assert!(machine.get_var_as_int("b").is_ok());
-        machine.clear();
+        machine.clear().unwrap();
         assert!(machine.get_var_as_bool("a").is_err());
         assert!(machine.get_var_as_int("b").is_err());
     }
 
+    /// Clearable that counts how many times it was reset and optionally fails.
+    #[derive(Default)]
+    struct CountingClearable {
+        resets: Cell<usize>,
+        fail: bool,
+    }
+
+    impl Clearable for CountingClearable {
+        fn reset_state(&self) -> Result<()> {
+            self.resets.set(self.resets.get() + 1);
+            if self.fail {
+                return new_usage_error("Reset failed");
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_clear_and_finish_program_reset_clearables() {
+        let mut machine = Machine::default();
+        let failing = Rc::from(CountingClearable { fail: true, ..Default::default() });
+        let ok = Rc::from(CountingClearable::default());
+        machine.add_clearable(failing.clone());
+        machine.add_clearable(ok.clone());
+
+        assert_eq!(StopReason::Eof, block_on(machine.exec(&mut b"a = 1".as_ref())).unwrap());
+        assert_eq!("Reset failed", format!("{}", machine.finish_program().unwrap_err()));
+        assert_eq!((1, 1), (failing.resets.get(), ok.resets.get()));
+        assert!(machine.get_var_as_int("a").is_ok());
+
+        assert_eq!("Reset failed", format!("{}", machine.clear().unwrap_err()));
+        assert_eq!((2, 2), (failing.resets.get(), ok.resets.get()));
+        assert!(machine.get_var_as_int("a").is_err());
+    }
+
+    /// Registration listener that records the names of the callables it is notified about.
+    #[derive(Default)]
+    struct RecordingListener {
+        names: RefCell<Vec<&'static str>>,
+    }
+
+    impl RegistrationListener for RecordingListener {
+        fn on_register(&self, metadata: &CallableMetadata) {
+            self.names.borrow_mut().push(metadata.name());
+        }
+    }
+
+    #[test]
+    fn test_registration_listener() {
+        let mut machine = Machine::default();
+        machine.add_command(ExitCommand::new()).unwrap();
+
+        let listener = Rc::from(RecordingListener::default());
+        machine.add_registration_listener(listener.clone());
+        assert_eq!(&["EXIT"], listener.names.borrow().as_slice());
+
+        machine.add_command(StopCommand::new()).unwrap();
+        machine.add_function(SumFunction::new()).unwrap();
+        machine.add_command(StopCommand::new()).unwrap_err();
+        assert_eq!(&["EXIT", "STOP", "SUM"], listener.names.borrow().as_slice());
+    }
+
     #[test]
     fn test_get_var_as_bool() {
         let mut machine = Machine::default();
@@ -546,23 +1246,90 @@ mod tests {
         );
     }
 
-    /// Runs the `input` code on a new test machine.
-    ///
-    /// `golden_in` is the sequence of values to yield by `IN`.
-    /// `expected_out` is updated with the sequence of calls to `OUT`.
-    fn run(
-        input: &str,
-        golden_in: &'static [&'static str],
-        captured_out: Rc<RefCell<Vec<String>>>,
-    ) -> Result<StopReason> {
+    #[test]
+    fn test_add_command_and_function() {
+        let captured_out = Rc::from(RefCell::from(vec![]));
         let mut machine = Machine::default();
-        machine.add_command(ExitCommand::new());
-        machine.add_command(InCommand::new(Box::from(RefCell::from(golden_in.iter()))));
-        machine.add_command(OutCommand::new(captured_out));
-        machine.add_function(SumFunction::new());
-        block_on(machine.exec(&mut input.as_bytes()))
-    }
-
+        machine.add_command(OutCommand::new(captured_out.clone())).unwrap();
+        machine.add_function(SumFunction::new()).unwrap();
+        assert_eq!(
+            StopReason::Eof,
+            block_on(machine.exec(&mut b"OUT SUM(1, 2)".as_ref())).expect("Execution failed")
+        );
+        assert_eq!(&["3"], captured_out.borrow().as_slice());
+    }
+
+    #[test]
+    fn test_add_duplicate_names() {
+        /// A command whose name collides with the name of `SumFunction`.
+        struct SumCommand {
+            metadata: CallableMetadata,
+        }
+
+        #[async_trait(?Send)]
+        impl Command for SumCommand {
+            fn metadata(&self) -> &CallableMetadata {
+                &self.metadata
+            }
+
+            async fn exec(&self, _args: &[(Option<Expr>, ArgSep)], _m: &mut Machine) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut machine = Machine::default();
+        machine.add_command(OutCommand::new(Rc::from(RefCell::from(vec![])))).unwrap();
+        machine.add_function(SumFunction::new()).unwrap();
+
+        assert_eq!(
+            "A command or function named OUT is already registered",
+            format!(
+                "{}",
+                machine
+                    .add_command(OutCommand::new(Rc::from(RefCell::from(vec![]))))
+                    .expect_err("Duplicate command registration succeeded")
+            )
+        );
+        assert_eq!(
+            "A command or function named SUM is already registered",
+            format!(
+                "{}",
+                machine
+                    .add_function(SumFunction::new())
+                    .expect_err("Duplicate function registration succeeded")
+            )
+        );
+        let sum_command = Rc::from(SumCommand {
+            metadata: CallableMetadataBuilder::new("SUM", VarType::Void).test_build(),
+        });
+        assert_eq!(
+            "A command or function named SUM is already registered",
+            format!(
+                "{}",
+                machine
+                    .add_command(sum_command)
+                    .expect_err("Command registration with a function name succeeded")
+            )
+        );
+    }
+
+    /// Runs the `input` code on a new test machine.
+    ///
+    /// `golden_in` is the sequence of values to yield by `IN`.
+    /// `expected_out` is updated with the sequence of calls to `OUT`.
+    fn run(
+        input: &str,
+        golden_in: &'static [&'static str],
+        captured_out: Rc<RefCell<Vec<String>>>,
+    ) -> Result<StopReason> {
+        let mut machine = Machine::default();
+        machine.add_command(ExitCommand::new()).unwrap();
+        machine.add_command(InCommand::new(Box::from(RefCell::from(golden_in.iter())))).unwrap();
+        machine.add_command(OutCommand::new(captured_out)).unwrap();
+        machine.add_function(SumFunction::new()).unwrap();
+        block_on(machine.exec(&mut input.as_bytes()))
+    }
+
     /// Runs the `input` code on a new test machine and verifies its output.
     ///
     /// `golden_in` is the sequence of values to yield by `IN`.
@@ -604,6 +1371,109 @@ mod tests {
         do_error_test(input, &[], &[], expected_err);
     }
 
+    /// Runs the `input` code on a new machine and verifies that it fails at position `line` and
+    /// `col` with `expected_err`.
+    fn do_located_error_test(input: &str, line: usize, col: usize, expected_err: &str) {
+        let captured_out = Rc::from(RefCell::from(vec![]));
+        let err = run(input, &[], captured_out).expect_err("Execution did not fail");
+        assert_eq!(Some(LineCol { line, col }), err.position());
+        assert_eq!(expected_err, format!("{}", err));
+    }
+
+    /// Runs the `input` code on a new machine and verifies that it fails with `expected_kind`.
+    fn do_error_kind_test(input: &str, expected_kind: ErrorKind) {
+        let captured_out = Rc::from(RefCell::from(vec![]));
+        let err = run(input, &[], captured_out).expect_err("Execution did not fail");
+        assert_eq!(expected_kind, err.kind(), "Unexpected kind for error: {}", err);
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        do_error_kind_test("a = 1 / 0", ErrorKind::DivisionByZero);
+        do_error_kind_test("a = 5 MOD 0", ErrorKind::DivisionByZero);
+        do_error_kind_test("a = 2147483647\na = a + 1", ErrorKind::Overflow);
+        do_error_kind_test("a = 1 + \"x\"", ErrorKind::TypeError);
+        do_error_kind_test("a = 1\na = \"x\"", ErrorKind::TypeError);
+        do_error_kind_test("IF 1 THEN\nEND IF", ErrorKind::SyntaxError);
+        do_error_kind_test("OUT 1 +", ErrorKind::SyntaxError);
+        do_error_kind_test("OUT SUM(\"x\")", ErrorKind::TypeError);
+        do_error_kind_test("OUT a", ErrorKind::UndefinedSymbol);
+        do_error_kind_test("OUT FOO(1)", ErrorKind::UndefinedSymbol);
+        do_error_kind_test("FOO", ErrorKind::UndefinedSymbol);
+        do_error_kind_test("OUT CHOOSE(3, 1, 2)", ErrorKind::ValueError);
+        do_error_kind_test("OUT SUM(2147483647, 1)", ErrorKind::Overflow);
+
+        assert_eq!(ErrorKind::Break, Error::Break.kind());
+        assert_eq!(ErrorKind::Internal, Error::DuplicateCallable("FOO").kind());
+        assert_eq!(
+            ErrorKind::IoError,
+            Error::from(io::Error::new(io::ErrorKind::NotFound, "foo")).kind()
+        );
+        assert_eq!(ErrorKind::UsageError, new_usage_error::<(), _>("foo").unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_errors_carry_position() {
+        do_located_error_test("a = 1 / 0", 1, 1, "Division by zero");
+        do_located_error_test("OUT 1\nOUT 2\n  a = 3 / 0\nOUT 4", 3, 3, "Division by zero");
+        do_located_error_test("OUT 1: a = 3 / 0", 1, 8, "Division by zero");
+        do_located_error_test(
+            "IF TRUE THEN\n    OUT 1\n    a = 3 / 0\nEND IF",
+            3,
+            5,
+            "Division by zero",
+        );
+        do_located_error_test(
+            "FOR i = 1 TO 2\n    WHILE TRUE\n        OUT 1 / 0\n    END WHILE\nNEXT",
+            3,
+            9,
+            "Division by zero",
+        );
+        do_located_error_test("\n\nFOO", 3, 1, "Unknown builtin FOO");
+    }
+
+    #[test]
+    fn test_errors_in_program() {
+        let e = Error::SyntaxError("Oops".to_owned()).at(LineCol { line: 3, col: 5 });
+        assert!(!e.is_in_program());
+
+        let e = e.in_program().in_program();
+        assert!(e.is_in_program());
+        assert_eq!(Some(LineCol { line: 3, col: 5 }), e.position());
+        assert_eq!("Oops", format!("{}", e));
+
+        let e = e.at(LineCol { line: 1, col: 1 });
+        assert!(e.is_in_program());
+        assert_eq!(Some(LineCol { line: 3, col: 5 }), e.position());
+
+        assert!(Error::Break.in_program().is_break());
+    }
+
+    #[test]
+    fn test_check_syntax() {
+        assert!(check_syntax(&mut "a = 1\nPRINT a".as_bytes()).is_empty());
+
+        let errors = check_syntax(&mut "a = (1\nb = 2\nWEND\nc = 3 +".as_bytes());
+        assert_eq!(
+            vec![
+                (Some(LineCol { line: 1, col: 6 }), "Unbalanced parenthesis".to_owned()),
+                (Some(LineCol { line: 3, col: 1 }), "WEND without WHILE".to_owned()),
+                (
+                    Some(LineCol { line: 4, col: 7 }),
+                    "Not enough values to apply operator".to_owned()
+                ),
+            ],
+            errors.iter().map(|e| (e.position(), format!("{}", e))).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_have_no_position() {
+        let captured_out = Rc::from(RefCell::from(vec![]));
+        let err = run("OUT 1\nIF", &[], captured_out).expect_err("Execution did not fail");
+        assert_eq!(None, err.position());
+    }
+
     #[test]
     fn test_assignment_ok_types() {
         do_ok_test("a = TRUE\nOUT a; a?", &[], &["TRUE TRUE"]);
@@ -621,6 +1491,14 @@ mod tests {
     #[test]
     fn test_assignment_ok_case_insensitive() {
         do_ok_test("foo = 32\nOUT FOO", &[], &["32"]);
+        do_ok_test("x = 1\nX = x + 1\nOUT x; X", &[], &["2 2"]);
+        do_ok_test("Count% = 5\nout count; COUNT%", &[], &["5 5"]);
+    }
+
+    #[test]
+    fn test_assignment_errors_case_insensitive() {
+        do_simple_error_test("a = 3\nA = TRUE\n", "Incompatible types in A assignment");
+        do_simple_error_test("a$ = \"x\"\nOUT A%\n", "Incompatible types in A% reference");
     }
 
     #[test]
@@ -667,13 +1545,24 @@ mod tests {
         assert_eq!(&["0", "1", "2", "3"], captured_out.borrow().as_slice());
     }
 
+    #[test]
+    fn test_exit_in_infinite_loop() {
+        let captured_out = Rc::from(RefCell::from(vec![]));
+        assert_eq!(
+            StopReason::Exited(3),
+            run("WHILE TRUE\nOUT 1\nEXIT 3\nWEND\nOUT 2", &[], captured_out.clone())
+                .expect("Execution failed")
+        );
+        assert_eq!(&["1"], captured_out.borrow().as_slice());
+    }
+
     #[test]
     fn test_exit_can_resume() {
         let captured_out = Rc::from(RefCell::from(vec![]));
         let mut machine = Machine::default();
-        machine.add_command(ExitCommand::new());
-        machine.add_command(OutCommand::new(captured_out.clone()));
-        machine.add_function(SumFunction::new());
+        machine.add_command(ExitCommand::new()).unwrap();
+        machine.add_command(OutCommand::new(captured_out.clone())).unwrap();
+        machine.add_function(SumFunction::new()).unwrap();
 
         assert_eq!(
             StopReason::Exited(10),
@@ -691,6 +1580,63 @@ mod tests {
         assert_eq!(&["2"], captured_out.borrow().as_slice());
     }
 
+    #[test]
+    fn test_end_no_code() {
+        let captured_out = Rc::from(RefCell::from(vec![]));
+        assert_eq!(
+            StopReason::End(0),
+            run("OUT 1\nEND\nOUT 2", &[], captured_out.clone()).expect("Execution failed")
+        );
+        assert_eq!(&["1"], captured_out.borrow().as_slice());
+    }
+
+    #[test]
+    fn test_end_with_code() {
+        let captured_out = Rc::from(RefCell::from(vec![]));
+        assert_eq!(
+            StopReason::End(3),
+            run("OUT 1\nEND 3\nOUT 2", &[], captured_out.clone()).expect("Execution failed")
+        );
+        assert_eq!(&["1"], captured_out.borrow().as_slice());
+        assert_eq!(3, StopReason::End(3).as_exit_code());
+    }
+
+    #[test]
+    fn test_end_nested() {
+        let captured_out = Rc::from(RefCell::from(vec![]));
+        assert_eq!(
+            StopReason::End(42),
+            run(
+                "FOR a = 0 TO 10\nOUT a\nIF a = 3 THEN\nEND 40 + 2\nEND IF\nNEXT",
+                &[],
+                captured_out.clone()
+            )
+            .expect("Execution failed")
+        );
+        assert_eq!(&["0", "1", "2", "3"], captured_out.borrow().as_slice());
+    }
+
+    #[test]
+    fn test_end_in_infinite_loop() {
+        let captured_out = Rc::from(RefCell::from(vec![]));
+        assert_eq!(
+            StopReason::End(5),
+            run("WHILE TRUE\nOUT 1\nEND 5\nWEND\nOUT 2", &[], captured_out.clone())
+                .expect("Execution failed")
+        );
+        assert_eq!(&["1"], captured_out.borrow().as_slice());
+    }
+
+    #[test]
+    fn test_end_errors() {
+        do_simple_error_test("END -1", "Exit code must be a positive integer");
+        do_simple_error_test("END 128", "Exit code cannot be larger than 127");
+        do_simple_error_test("END \"1\"", "Exit code must be a positive integer");
+        do_simple_error_test("END 1.5", "Exit code must be a positive integer");
+        do_error_kind_test("END -1", ErrorKind::UsageError);
+        do_error_kind_test("END 128", ErrorKind::UsageError);
+    }
+
     #[test]
     fn test_if_ok() {
         let code = r#"
@@ -738,16 +1684,56 @@ mod tests {
     }
 
     #[test]
-    fn test_if_errors() {
-        do_simple_error_test("IF TRUE THEN END IF", "Expecting newline after THEN");
+    fn test_if_compares_integers_and_doubles() {
+        let code = r#"
+            IN n
+            IF n = 3.0 THEN
+                OUT "equal"
+            END IF
+            IF n <> 3.5 THEN
+                OUT "different"
+            END IF
+            IF n < 3.5 THEN
+                OUT "less"
+            END IF
+            IF n >= 3.5 THEN
+                OUT "greater or equal"
+            END IF
+        "#;
+        do_ok_test(code, &["3"], &["equal", "different", "less"]);
+        do_ok_test(code, &["4"], &["different", "greater or equal"]);
+
         do_simple_error_test(
-            "IF TRUE THEN\nELSE IF TRUE THEN\nEND IF",
-            "Expecting newline after ELSE",
+            "IF \"1\" = 1 THEN\nEND IF",
+            "Cannot compare Text(\"1\") and Integer(1) with =",
         );
+    }
+
+    #[test]
+    fn test_iif_only_evaluates_selected_value() {
+        let code = r#"
+            IN n
+            OUT IIF(n <> 0, 100 / n, 0); IIF(n = 0, "zero", "nonzero")
+        "#;
+        do_ok_test(code, &["4"], &["25 nonzero"]);
+        do_ok_test(code, &["0"], &["0 zero"]);
+
+        do_simple_error_test("OUT IIF(1, 2, 3)", "IIF requires a boolean condition");
+        do_simple_error_test("OUT IIF(FALSE, 2, 1 / 0)", "Division by zero");
+    }
+
+    #[test]
+    fn test_if_errors() {
+        do_simple_error_test("IF TRUE THEN END IF", "Expecting newline after THEN");
+        do_simple_error_test("IF TRUE THEN\nELSE OUT 3\nEND IF", "Expecting newline after ELSE");
         do_simple_error_test("IF TRUE\nEND IF\nOUT 3", "No THEN in IF statement");
 
         do_simple_error_test("IF 2\nEND IF", "No THEN in IF statement");
         do_simple_error_test("IF 2 THEN\nEND IF", "IF/ELSEIF require a boolean condition");
+        do_simple_error_test(
+            "IF FALSE THEN\nELSE IF 2 THEN\nEND IF",
+            "IF/ELSEIF require a boolean condition",
+        );
         do_simple_error_test(
             "IF FALSE THEN\nELSEIF 2 THEN\nEND IF",
             "IF/ELSEIF require a boolean condition",
@@ -803,6 +1789,44 @@ mod tests {
         do_ok_test("FOR i = 9 TO 10 STEP -1\nOUT i\nNEXT", &[], &[]);
     }
 
+    #[test]
+    fn test_for_step_sign_mismatch() {
+        do_ok_test("FOR i = 1 TO 10 STEP -1\nOUT i\nNEXT\nOUT \"done\"; i", &[], &["done 1"]);
+        do_ok_test("FOR i = 10 TO 1 STEP 2\nOUT i\nNEXT\nOUT \"done\"; i", &[], &["done 10"]);
+    }
+
+    #[test]
+    fn test_for_doubles() {
+        do_ok_test(
+            "FOR x = 0 TO 1 STEP 0.25\nOUT x\nNEXT\nOUT x",
+            &[],
+            &["0", "0.25", "0.5", "0.75", "1", "1.25"],
+        );
+        do_ok_test("FOR x = 1.5 TO -1\nOUT x\nNEXT", &[], &[]);
+        do_ok_test("FOR x = 1.5 TO -1 STEP -1\nOUT x\nNEXT", &[], &["1.5", "0.5", "-0.5"]);
+        do_ok_test("FOR x# = 0.5 TO 2.5\nOUT x#\nNEXT", &[], &["0.5", "1.5", "2.5"]);
+    }
+
+    #[test]
+    fn test_for_doubles_do_not_accumulate_errors() {
+        do_ok_test(
+            "n = 0\nFOR x = 0 TO 1 STEP 0.1\nlast = x\nn = n + 1\nNEXT\nOUT n; last",
+            &[],
+            &["11 1"],
+        );
+        do_ok_test(
+            "n = 0\nFOR x = 1 TO 0 STEP -0.1\nlast = x\nn = n + 1\nNEXT\nOUT n; last",
+            &[],
+            &["11 0"],
+        );
+        do_ok_test(
+            "n = 0\nFOR x = 0 TO 3 STEP 0.3\nlast = x\nn = n + 1\nNEXT\nOUT n; last",
+            &[],
+            &["11 3"],
+        );
+        do_ok_test("n = 0\nFOR x = 0 TO 0.99 STEP 0.1\nn = n + 1\nNEXT\nOUT n", &[], &["10"]);
+    }
+
     #[test]
     fn test_for_iterator_is_visible_after_next() {
         let code = r#"
@@ -824,27 +1848,102 @@ mod tests {
         do_ok_test(code, &[], &["1", "3", "5"]);
     }
 
+    #[test]
+    fn test_for_iterator_is_case_insensitive() {
+        let code = r#"
+            FOR Iter = 1 TO 2
+                OUT iter
+            NEXT
+            OUT ITER
+        "#;
+        do_ok_test(code, &[], &["1", "2", "3"]);
+    }
+
     #[test]
     fn test_for_errors() {
         do_simple_error_test("FOR\nNEXT", "No iterator name in FOR statement");
-        do_simple_error_test("FOR a = 1 TO 10\nEND IF", "Unexpected token End in statement");
+        do_simple_error_test("FOR a = 1 TO 10\nEND IF", "END IF without IF");
+
+        do_simple_error_test("FOR i = \"a\" TO 3\nNEXT", "FOR supports numeric iteration only");
+        do_simple_error_test("FOR i = 1 TO \"a\"\nNEXT", "FOR supports numeric iteration only");
+        do_simple_error_test("FOR i = 1 TO TRUE\nNEXT", "FOR supports numeric iteration only");
+
+        do_simple_error_test(
+            "FOR i = \"b\" TO 7 STEP -8\nNEXT",
+            "FOR supports numeric iteration only",
+        );
+        do_simple_error_test(
+            "FOR i = 1 TO \"b\" STEP -0.5\nNEXT",
+            "FOR supports numeric iteration only",
+        );
 
-        do_simple_error_test("FOR i = \"a\" TO 3\nNEXT", "FOR supports integer iteration only");
+        do_simple_error_test("FOR i% = 1.0 TO 10.0\nNEXT", "Incompatible types in i% assignment");
         do_simple_error_test(
-            "FOR i = 1 TO \"a\"\nNEXT",
-            "Cannot compare Integer(1) and Text(\"a\") with <=",
+            "i = 3\nFOR i = 1.0 TO 10.0\nNEXT",
+            "Incompatible types in i assignment",
+        );
+        do_simple_error_test(
+            "FOR i = 2147483646 TO 2147483647\nNEXT",
+            "Overflow adding 2147483647 and 1",
         );
 
+        do_simple_error_test("FOR a = 1 TO 10 STEP 0\nNEXT", "Infinite FOR loop; STEP cannot be 0");
         do_simple_error_test(
-            "FOR i = \"b\" TO 7 STEP -8\nNEXT",
-            "FOR supports integer iteration only",
+            "FOR a = 1 TO 10 STEP -0\nNEXT",
+            "Infinite FOR loop; STEP cannot be 0",
+        );
+    }
+
+    #[test]
+    fn test_mid_assignment_ok() {
+        do_ok_test("s$ = \"abcdef\"\nMID$(s$, 1) = \"XY\"\nOUT s$", &[], &["aXYdef"]);
+        do_ok_test("s = \"abcdef\"\nmid(s, 0, 1) = \"XY\"\nOUT s", &[], &["Xbcdef"]);
+        do_ok_test("s$ = \"abcdef\"\nMID$(s$, 2, 10) = \"XY\"\nOUT s$", &[], &["abXYef"]);
+        do_ok_test("s$ = \"abcdef\"\nMID$(s$, 2, 0) = \"XY\"\nOUT s$", &[], &["abcdef"]);
+        do_ok_test("s$ = \"abcdef\"\nMID$(s$, 2) = \"\"\nOUT s$", &[], &["abcdef"]);
+    }
+
+    #[test]
+    fn test_mid_assignment_truncates_at_end() {
+        do_ok_test("s$ = \"abcdef\"\nMID$(s$, 4) = \"XYZW\"\nOUT s$", &[], &["abcdXY"]);
+        do_ok_test("s$ = \"abcdef\"\nMID$(s$, 5, 3) = \"XYZW\"\nOUT s$", &[], &["abcdeX"]);
+    }
+
+    #[test]
+    fn test_mid_assignment_errors() {
+        do_simple_error_test("MID$(s$, 0) = \"a\"", "Undefined variable s");
+        do_simple_error_test(
+            "s = 3\nMID(s, 0) = \"a\"",
+            "MID$ assignment requires a string variable",
+        );
+
+        do_simple_error_test(
+            "s$ = \"abc\"\nMID$(s$, -1) = \"a\"",
+            "MID$ start position out of range",
         );
         do_simple_error_test(
-            "FOR i = 1 TO \"b\" STEP -8\nNEXT",
-            "Cannot compare Integer(1) and Text(\"b\") with >=",
+            "s$ = \"abc\"\nMID$(s$, 3) = \"a\"",
+            "MID$ start position out of range",
+        );
+        do_simple_error_test("s$ = \"\"\nMID$(s$, 0) = \"a\"", "MID$ start position out of range");
+        do_simple_error_test(
+            "s$ = \"abc\"\nMID$(s$, \"0\") = \"a\"",
+            "MID$ start position must be an integer",
+        );
+
+        do_simple_error_test(
+            "s$ = \"abc\"\nMID$(s$, 0, -1) = \"a\"",
+            "MID$ length cannot be negative",
+        );
+        do_simple_error_test(
+            "s$ = \"abc\"\nMID$(s$, 0, 1.0) = \"a\"",
+            "MID$ length must be an integer",
         );
 
-        do_simple_error_test("FOR a = 1.0 TO 10.0\nNEXT", "FOR supports integer iteration only");
+        do_simple_error_test(
+            "s$ = \"abc\"\nMID$(s$, 0) = 5",
+            "MID$ assignment requires a string value",
+        );
     }
 
     #[test]
@@ -910,6 +2009,365 @@ mod tests {
         );
     }
 
+    /// Command that raises the `flag` it holds to simulate an interruption from the user.
+    struct RaiseCommand {
+        metadata: CallableMetadata,
+        flag: Rc<Cell<bool>>,
+    }
+
+    impl RaiseCommand {
+        fn new(flag: Rc<Cell<bool>>) -> Rc<Self> {
+            Rc::from(Self {
+                metadata: CallableMetadataBuilder::new("RAISE", VarType::Void).test_build(),
+                flag,
+            })
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl Command for RaiseCommand {
+        fn metadata(&self) -> &CallableMetadata {
+            &self.metadata
+        }
+
+        async fn exec(&self, _args: &[(Option<Expr>, ArgSep)], _m: &mut Machine) -> Result<()> {
+            self.flag.set(true);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_interrupt_stops_loop() {
+        let flag = Rc::from(Cell::new(false));
+        let mut machine = Machine::default();
+        machine.add_command(RaiseCommand::new(flag.clone())).unwrap();
+        machine.set_interrupt_source(flag.clone());
+
+        let err = block_on(
+            machine.exec(
+                &mut "i = 0\nWHILE TRUE\ni = i + 1\nIF i = 3 THEN\nRAISE\nEND IF\nWEND\ni = 100"
+                    .as_bytes(),
+            ),
+        )
+        .expect_err("Execution did not stop");
+        assert!(err.is_break());
+        assert_eq!("Break", format!("{}", err));
+        assert_eq!(3, machine.get_var_as_int("i").unwrap());
+        assert!(!flag.get());
+
+        // The machine must be usable after an interruption.
+        assert_eq!(
+            StopReason::Eof,
+            block_on(machine.exec(&mut "i = i + 1".as_bytes())).expect("Execution failed")
+        );
+        assert_eq!(4, machine.get_var_as_int("i").unwrap());
+    }
+
+    #[test]
+    fn test_interrupt_stops_for_loop() {
+        let flag = Rc::from(Cell::new(false));
+        let mut machine = Machine::default();
+        machine.add_command(RaiseCommand::new(flag.clone())).unwrap();
+        machine.set_interrupt_source(flag);
+
+        let err = block_on(
+            machine.exec(&mut "FOR i = 1 TO 1000\nIF i = 5 THEN\nRAISE\nEND IF\nNEXT".as_bytes()),
+        )
+        .expect_err("Execution did not stop");
+        assert!(err.is_break());
+        // The interruption is only noticed once NEXT has advanced the iterator.
+        assert_eq!(6, machine.get_var_as_int("i").unwrap());
+    }
+
+    #[test]
+    fn test_interrupt_before_exec_is_ignored() {
+        let flag = Rc::from(Cell::new(true));
+        let mut machine = Machine::default();
+        machine.set_interrupt_source(flag);
+        assert_eq!(
+            StopReason::Eof,
+            block_on(machine.exec(&mut "a = 1".as_bytes())).expect("Execution failed")
+        );
+        assert_eq!(1, machine.get_var_as_int("a").unwrap());
+    }
+
+    /// Debugger that records the positions of the breakpoints it hits and that runs `code` on
+    /// each of them.
+    struct RecordingDebugger {
+        hits: RefCell<Vec<LineCol>>,
+        code: &'static str,
+        past_end: Cell<bool>,
+        watched: RefCell<Vec<String>>,
+    }
+
+    impl RecordingDebugger {
+        /// Creates a new debugger that runs `code` on every breakpoint.
+        fn new(code: &'static str) -> Rc<Self> {
+            Rc::from(Self {
+                hits: RefCell::from(vec![]),
+                code,
+                past_end: Cell::new(false),
+                watched: RefCell::from(vec![]),
+            })
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl Debugger for RecordingDebugger {
+        async fn on_breakpoint(
+            &self,
+            machine: &mut Machine,
+            pos: LineCol,
+        ) -> Result<Option<StopReason>> {
+            self.hits.borrow_mut().push(pos);
+            match machine.exec(&mut self.code.as_bytes()).await? {
+                StopReason::Eof => Ok(None),
+                reason => Ok(Some(reason)),
+            }
+        }
+
+        fn on_step_past_end(&self) -> Result<()> {
+            self.past_end.set(true);
+            Ok(())
+        }
+
+        fn on_watch(&self, name: &str, value: &Value) -> Result<()> {
+            self.watched.borrow_mut().push(format!("{} = {}", name, value.to_string()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let debugger = RecordingDebugger::new("b = a");
+        let mut machine = Machine::default();
+        machine.set_debugger(debugger.clone());
+        machine.add_breakpoint(3);
+        machine.add_breakpoint(10);
+
+        let program = "a = 1\nFOR i = 1 TO 2\n    a = a + 1: c = b\nNEXT\nd = 1";
+        assert_eq!(
+            StopReason::Eof,
+            block_on(machine.exec_with_breakpoints(&mut program.as_bytes()))
+                .expect("Execution failed")
+        );
+        assert_eq!(
+            &[
+                LineCol { line: 3, col: 5 },
+                LineCol { line: 3, col: 16 },
+                LineCol { line: 3, col: 5 },
+                LineCol { line: 3, col: 16 },
+            ],
+            debugger.hits.borrow().as_slice()
+        );
+        assert_eq!(3, machine.get_var_as_int("a").unwrap());
+        assert_eq!(3, machine.get_var_as_int("c").unwrap());
+
+        // Breakpoints are ignored when not running in debugging mode.
+        debugger.hits.borrow_mut().clear();
+        assert_eq!(
+            StopReason::Eof,
+            block_on(machine.exec(&mut program.as_bytes())).expect("Execution failed")
+        );
+        assert!(debugger.hits.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_request_stop_invokes_debugger() {
+        let debugger = RecordingDebugger::new("b = a");
+        let mut machine = Machine::default();
+        machine.set_debugger(debugger.clone());
+        machine.add_command(StopCommand::new()).unwrap();
+
+        let program = "a = 1\nWHILE a < 3\n    a = a + 1: STOP\nEND WHILE\nc = b";
+        assert_eq!(
+            StopReason::Eof,
+            block_on(machine.exec_with_breakpoints(&mut program.as_bytes()))
+                .expect("Execution failed")
+        );
+        assert_eq!(
+            &[LineCol { line: 3, col: 16 }, LineCol { line: 3, col: 16 }],
+            debugger.hits.borrow().as_slice()
+        );
+        assert_eq!(3, machine.get_var_as_int("c").unwrap());
+    }
+
+    #[test]
+    fn test_request_stop_can_stop_program() {
+        let debugger = RecordingDebugger::new("END 5");
+        let mut machine = Machine::default();
+        machine.set_debugger(debugger.clone());
+        machine.add_command(StopCommand::new()).unwrap();
+
+        assert_eq!(
+            StopReason::End(5),
+            block_on(machine.exec_with_breakpoints(&mut "a = 1: STOP: b = 2".as_bytes()))
+                .expect("Execution failed")
+        );
+        assert_eq!(&[LineCol { line: 1, col: 8 }], debugger.hits.borrow().as_slice());
+        assert!(machine.get_var_as_int("b").is_err());
+    }
+
+    #[test]
+    fn test_request_stop_ends_program_without_debugging() {
+        let debugger = RecordingDebugger::new("");
+        let mut machine = Machine::default();
+        machine.set_debugger(debugger.clone());
+        machine.add_command(StopCommand::new()).unwrap();
+
+        assert_eq!(
+            StopReason::End(0),
+            block_on(machine.exec(&mut "a = 1: STOP: b = 2".as_bytes())).expect("Execution failed")
+        );
+        assert!(debugger.hits.borrow().is_empty());
+        assert!(machine.get_var_as_int("b").is_err());
+    }
+
+    #[test]
+    fn test_breakpoints_can_stop_program() {
+        let debugger = RecordingDebugger::new("END 5");
+        let mut machine = Machine::default();
+        machine.set_debugger(debugger.clone());
+        machine.add_breakpoint(2);
+        assert!(machine.remove_breakpoint(2));
+        assert!(!machine.remove_breakpoint(2));
+        machine.add_breakpoint(2);
+        assert_eq!(&[2], machine.get_breakpoints().iter().copied().collect::<Vec<_>>().as_slice());
+
+        assert_eq!(
+            StopReason::End(5),
+            block_on(machine.exec_with_breakpoints(&mut "a = 1\nb = 2\nc = 3".as_bytes()))
+                .expect("Execution failed")
+        );
+        assert_eq!(&[LineCol { line: 2, col: 1 }], debugger.hits.borrow().as_slice());
+        assert_eq!(1, machine.get_var_as_int("a").unwrap());
+        assert!(machine.get_var_as_int("b").is_err());
+    }
+
+    /// Command that requests the machine to stop at the next statement.
+    struct StepCommand {
+        metadata: CallableMetadata,
+    }
+
+    impl StepCommand {
+        fn new() -> Rc<Self> {
+            Rc::from(Self {
+                metadata: CallableMetadataBuilder::new("STEP", VarType::Void).test_build(),
+            })
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl Command for StepCommand {
+        fn metadata(&self) -> &CallableMetadata {
+            &self.metadata
+        }
+
+        async fn exec(&self, _args: &[(Option<Expr>, ArgSep)], m: &mut Machine) -> Result<()> {
+            m.step();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let debugger = RecordingDebugger::new("STEP");
+        let mut machine = Machine::default();
+        machine.add_command(StepCommand::new()).unwrap();
+        machine.set_debugger(debugger.clone());
+        machine.add_breakpoint(2);
+
+        let program = "a = 1\nb = 2\nWHILE a < 3\n    a = a + 1\nEND WHILE";
+        assert_eq!(
+            StopReason::Eof,
+            block_on(machine.exec_with_breakpoints(&mut program.as_bytes()))
+                .expect("Execution failed")
+        );
+        assert_eq!(
+            &[
+                LineCol { line: 2, col: 1 },
+                LineCol { line: 3, col: 1 },
+                LineCol { line: 4, col: 5 },
+                LineCol { line: 4, col: 5 },
+            ],
+            debugger.hits.borrow().as_slice()
+        );
+        assert!(debugger.past_end.get());
+        assert_eq!(3, machine.get_var_as_int("a").unwrap());
+
+        // Pending steps do not leak into later runs.
+        debugger.hits.borrow_mut().clear();
+        debugger.past_end.set(false);
+        machine.remove_breakpoint(2);
+        assert_eq!(
+            StopReason::Eof,
+            block_on(machine.exec_with_breakpoints(&mut program.as_bytes()))
+                .expect("Execution failed")
+        );
+        assert!(debugger.hits.borrow().is_empty());
+        assert!(!debugger.past_end.get());
+    }
+
+    #[test]
+    fn test_watches() {
+        let debugger = RecordingDebugger::new("");
+        let mut machine = Machine::default();
+        machine.set_debugger(debugger.clone());
+        machine.add_watch("a");
+        machine.add_watch("I");
+        machine.add_watch("s");
+        assert!(machine.remove_watch("S"));
+        assert!(!machine.remove_watch("s"));
+        assert_eq!(
+            &["A", "I"],
+            machine.get_watches().iter().map(String::as_str).collect::<Vec<_>>().as_slice()
+        );
+
+        let program = "a = 1\nb = 2\nFOR i = 1 TO 2\n    A = a * 10\nNEXT\ns$ = \"x\"";
+        assert_eq!(
+            StopReason::Eof,
+            block_on(machine.exec(&mut program.as_bytes())).expect("Execution failed")
+        );
+        assert_eq!(
+            &["A = 1", "I = 1", "A = 10", "I = 2", "A = 100", "I = 3"],
+            debugger.watched.borrow().as_slice()
+        );
+    }
+
+    #[test]
+    fn test_profile() {
+        let mut machine = Machine::default();
+        let program = "a = 0\nFOR i = 1 TO 5\n    a = a + 1: b = a\nNEXT";
+        let run = |machine: &mut Machine| {
+            assert_eq!(
+                StopReason::Eof,
+                block_on(machine.exec_with_breakpoints(&mut program.as_bytes()))
+                    .expect("Execution failed")
+            );
+        };
+
+        run(&mut machine);
+        assert!(machine.get_profile().is_empty());
+
+        machine.start_profiling();
+        run(&mut machine);
+        assert_eq!(
+            StopReason::Eof,
+            block_on(machine.exec(&mut "c = 1".as_bytes())).expect("Execution failed")
+        );
+        assert_eq!(
+            &[(1, 1), (2, 1), (3, 10)],
+            machine.get_profile().iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>().as_slice()
+        );
+
+        machine.stop_profiling();
+        run(&mut machine);
+        assert_eq!(10, machine.get_profile()[&3]);
+
+        machine.start_profiling();
+        assert!(machine.get_profile().is_empty());
+    }
+
     #[test]
     fn test_exec_shares_state() {
         let mut machine = Machine::default();
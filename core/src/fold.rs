@@ -0,0 +1,270 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Constant folding of parsed programs.
+//!
+//! Folding replaces sub-expressions that only involve literals with the literal they evaluate to
+//! so that they are not recomputed every time a statement runs.  A sub-expression is folded only
+//! if its evaluation succeeds: anything that fails, such as a division by zero or an overflow, is
+//! left untouched so that the error is raised at run time exactly as if folding had not happened.
+//! Function calls are never folded because functions may not be pure.
+
+use crate::ast::{Expr, LineCol, Statement, Value};
+use crate::eval::Vars;
+use std::collections::HashMap;
+
+/// Returns true if `expr` is a literal value.
+fn is_literal(expr: &Expr) -> bool {
+    matches!(expr, Expr::Boolean(_) | Expr::Double(_) | Expr::Integer(_) | Expr::Text(_))
+}
+
+/// Converts the computed `value` back into a literal expression, if there is a literal for it.
+fn to_literal(value: Value) -> Option<Expr> {
+    match value {
+        Value::Boolean(b) => Some(Expr::Boolean(b)),
+        Value::Double(d) => Some(Expr::Double(d)),
+        Value::Integer(i) => Some(Expr::Integer(i)),
+        Value::Text(s) => Some(Expr::Text(s)),
+        Value::Empty => None,
+    }
+}
+
+/// Folds the constant sub-expressions of `expr` in place.
+pub(crate) fn fold_expr(expr: &mut Expr) {
+    let all_literal = match expr {
+        Expr::Boolean(_) | Expr::Double(_) | Expr::Integer(_) | Expr::Symbol(_) | Expr::Text(_) => {
+            return
+        }
+
+        Expr::Add(lhs, rhs)
+        | Expr::Subtract(lhs, rhs)
+        | Expr::Multiply(lhs, rhs)
+        | Expr::Divide(lhs, rhs)
+        | Expr::Modulo(lhs, rhs)
+        | Expr::Equal(lhs, rhs)
+        | Expr::NotEqual(lhs, rhs)
+        | Expr::Less(lhs, rhs)
+        | Expr::LessEqual(lhs, rhs)
+        | Expr::Greater(lhs, rhs)
+        | Expr::GreaterEqual(lhs, rhs)
+        | Expr::And(lhs, rhs)
+        | Expr::Or(lhs, rhs)
+        | Expr::Xor(lhs, rhs) => {
+            fold_expr(lhs);
+            fold_expr(rhs);
+            is_literal(lhs) && is_literal(rhs)
+        }
+
+        Expr::Negate(e) | Expr::Not(e) => {
+            fold_expr(e);
+            is_literal(e)
+        }
+
+        Expr::Call(_, args) => {
+            args.iter_mut().for_each(fold_expr);
+            false
+        }
+
+        Expr::Channel(e) => {
+            fold_expr(e);
+            false
+        }
+    };
+
+    if all_literal {
+        if let Some(literal) =
+            expr.eval(&Vars::default(), &HashMap::default()).ok().and_then(to_literal)
+        {
+            *expr = literal;
+        }
+    }
+}
+
+/// Folds the constant sub-expressions of all statements in `block` in place.
+fn fold_block(block: &mut [(Statement, LineCol)]) {
+    for (stmt, _pos) in block {
+        fold_statement(stmt);
+    }
+}
+
+/// Folds the constant sub-expressions of `stmt`, and of any statements nested in it, in place.
+pub(crate) fn fold_statement(stmt: &mut Statement) {
+    match stmt {
+        Statement::Assignment(_, expr) => fold_expr(expr),
+
+        Statement::BuiltinCall(_, args) => {
+            for (expr, _sep) in args {
+                if let Some(expr) = expr {
+                    fold_expr(expr);
+                }
+            }
+        }
+
+        Statement::End(code) => {
+            if let Some(code) = code {
+                fold_expr(code);
+            }
+        }
+
+        Statement::If(branches) => {
+            for (guard, body) in branches {
+                fold_expr(guard);
+                fold_block(body);
+            }
+        }
+
+        Statement::For(_, start, end, _step, body) => {
+            fold_expr(start);
+            fold_expr(end);
+            fold_block(body);
+        }
+
+        Statement::MidAssignment(_, start, length, value) => {
+            fold_expr(start);
+            if let Some(length) = length {
+                fold_expr(length);
+            }
+            fold_expr(value);
+        }
+
+        Statement::While(condition, body) => {
+            fold_expr(condition);
+            fold_block(body);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{VarRef, VarType};
+    use crate::eval::testutils::SumFunction;
+    use crate::eval::Function;
+    use crate::parser::Parser;
+    use std::rc::Rc;
+
+    /// Parses `input` as a single assignment and returns the expression being assigned.
+    fn parse_expr(input: &str) -> Expr {
+        let input = format!("x = {}", input);
+        let mut input = input.as_bytes();
+        match Parser::from(&mut input).parse().expect("Parsing failed") {
+            Some((Statement::Assignment(_, expr), _)) => expr,
+            _ => panic!("Unexpected statement"),
+        }
+    }
+
+    /// Folds `input` and checks that it yields `expected`.
+    fn do_fold_test(input: &str, expected: Expr) {
+        let mut expr = parse_expr(input);
+        fold_expr(&mut expr);
+        assert_eq!(expected, expr);
+    }
+
+    /// Checks that folding `input` does not change the result of evaluating it.
+    fn do_same_eval_test(input: &str) {
+        let mut vars = Vars::default();
+        vars.set(&VarRef::new("a", VarType::Integer), Value::Integer(7)).unwrap();
+        let mut fs: HashMap<&'static str, Rc<dyn Function>> = HashMap::default();
+        fs.insert("SUM", SumFunction::new());
+
+        let expr = parse_expr(input);
+        let mut folded = expr.clone();
+        fold_expr(&mut folded);
+        match (expr.eval(&vars, &fs), folded.eval(&vars, &fs)) {
+            (Ok(v1), Ok(v2)) => assert_eq!(v1, v2, "Bad value for {}", input),
+            (Err(e1), Err(e2)) => {
+                assert_eq!(format!("{}", e1), format!("{}", e2), "Bad error for {}", input)
+            }
+            (r1, r2) => panic!("Mismatched results for {}: {:?} vs. {:?}", input, r1, r2),
+        }
+    }
+
+    #[test]
+    fn test_fold_literals() {
+        do_fold_test("2 + 3 * 4", Expr::Integer(14));
+        do_fold_test("(1.5 - 0.5) / 4.0", Expr::Double(0.25));
+        do_fold_test("1.5 / 3", parse_expr("1.5 / 3"));
+        do_fold_test("-(3 MOD 2)", Expr::Integer(-1));
+        do_fold_test("\"a\" + \"b\"", Expr::Text("ab".to_owned()));
+        do_fold_test("NOT (1 < 2) OR TRUE", Expr::Boolean(true));
+    }
+
+    #[test]
+    fn test_fold_partial() {
+        do_fold_test(
+            "a + 2 * 3",
+            Expr::Add(
+                Box::from(Expr::Symbol(VarRef::new("a", VarType::Auto))),
+                Box::from(Expr::Integer(6)),
+            ),
+        );
+        do_fold_test(
+            "SUM(1 + 1, a)",
+            Expr::Call(
+                VarRef::new("SUM", VarType::Auto),
+                vec![Expr::Integer(2), Expr::Symbol(VarRef::new("a", VarType::Auto))],
+            ),
+        );
+    }
+
+    #[test]
+    fn test_fold_keeps_errors() {
+        do_fold_test("1 / 0", parse_expr("1 / 0"));
+        do_fold_test("2147483647 + 1", parse_expr("2147483647 + 1"));
+        do_fold_test("1 + TRUE", parse_expr("1 + TRUE"));
+        do_fold_test(
+            "(1 / 0) + (2 * 3)",
+            Expr::Add(
+                Box::from(Expr::Divide(Box::from(Expr::Integer(1)), Box::from(Expr::Integer(0)))),
+                Box::from(Expr::Integer(6)),
+            ),
+        );
+    }
+
+    #[test]
+    fn test_fold_same_eval() {
+        for input in &[
+            "2 + 3 * 4",
+            "(2 - 3) * a + 1",
+            "1.5 / 3",
+            "7 MOD 0",
+            "1 / 0",
+            "a / 0 + 1 * 2",
+            "2147483647 * a + 2 * 2",
+            "\"x\" + 3",
+            "SUM(2 * 3, a, 4 - 5)",
+            "(a > 3) AND (2 <> 2)",
+        ] {
+            do_same_eval_test(input);
+        }
+    }
+
+    #[test]
+    fn test_fold_statements() {
+        /// Parses the first statement in `input`.
+        fn parse_statement(mut input: &[u8]) -> Statement {
+            Parser::from(&mut input).parse().expect("Parsing failed").unwrap().0
+        }
+
+        let mut stmt = parse_statement(
+            b"WHILE a < 2 * 5\n    PRINT 1 + 1, ; a\n    MID$(s$, 1 + 1, 2 - 1) = \"x\"\nWEND",
+        );
+        fold_statement(&mut stmt);
+        assert_eq!(
+            parse_statement(b"WHILE a < 10\n    PRINT 2, ; a\n    MID$(s$, 2, 1) = \"x\"\nWEND"),
+            stmt
+        );
+    }
+}
@@ -0,0 +1,459 @@
+// EndBASIC
+// Copyright 2020 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Pretty-printer to turn a parsed program back into canonical source code.
+//!
+//! The formatter emits keywords, builtin names and operators in uppercase, puts one statement
+//! per line, indents the bodies of `FOR`, `IF` and `WHILE` blocks by four spaces, and surrounds
+//! binary operators with single spaces.  Parenthesis are only emitted where the precedence of
+//! the operators requires them.  Blank lines between statements are kept, collapsed to one.
+//!
+//! Comments are not part of the AST and are thus lost when formatting a program.
+
+use crate::ast::{ArgSep, Expr, LineCol, Statement, Value};
+use crate::exec;
+use crate::parser::Parser;
+use std::io;
+
+/// Number of spaces to indent each nested block by.
+const INDENT: usize = 4;
+
+/// Returns the priority of the operator at the root of `expr`, matching the parser's precedence
+/// rules.  Higher numbers bind tighter.
+fn priority(expr: &Expr) -> i8 {
+    match expr {
+        Expr::Negate(_) | Expr::Not(_) => 4,
+
+        Expr::Multiply(_, _) | Expr::Divide(_, _) | Expr::Modulo(_, _) => 3,
+
+        Expr::Add(_, _) | Expr::Subtract(_, _) => 2,
+
+        Expr::Equal(_, _)
+        | Expr::NotEqual(_, _)
+        | Expr::Less(_, _)
+        | Expr::LessEqual(_, _)
+        | Expr::Greater(_, _)
+        | Expr::GreaterEqual(_, _) => 1,
+
+        Expr::And(_, _) | Expr::Or(_, _) | Expr::Xor(_, _) => 0,
+
+        Expr::Boolean(_)
+        | Expr::Double(_)
+        | Expr::Integer(_)
+        | Expr::Symbol(_)
+        | Expr::Text(_)
+        | Expr::Call(_, _)
+        | Expr::Channel(_) => 5,
+    }
+}
+
+/// Formats a double so that it is parsed back as a double and not as an integer.
+fn format_double(d: f64) -> String {
+    let s = format!("{}", d);
+    if s.contains('.') || !d.is_finite() {
+        s
+    } else {
+        s + ".0"
+    }
+}
+
+/// Formats a string literal, escaping any characters that the lexer would otherwise interpret.
+fn format_text(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for ch in s.chars() {
+        if ch == '"' || ch == '\\' {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out.push('"');
+    out
+}
+
+/// Formats `expr` as an operand of an operator with priority `prio`, wrapping it in parenthesis
+/// if it binds looser than the operator.  `strict` requests parenthesis for operands of the same
+/// priority too, which is necessary for the right-hand side of left-associative operators.
+fn format_operand(expr: &Expr, prio: i8, strict: bool) -> String {
+    let inner = priority(expr);
+    if inner < prio || (strict && inner == prio) {
+        format!("({})", format_expr(expr))
+    } else {
+        format_expr(expr)
+    }
+}
+
+/// Formats the binary operation `op` between `lhs` and `rhs`, which has priority `prio`.
+fn format_binary(lhs: &Expr, op: &str, rhs: &Expr, prio: i8) -> String {
+    format!("{} {} {}", format_operand(lhs, prio, false), op, format_operand(rhs, prio, true))
+}
+
+/// Formats a single expression.
+pub fn format_expr(expr: &Expr) -> String {
+    let prio = priority(expr);
+    match expr {
+        Expr::Boolean(true) => "TRUE".to_owned(),
+        Expr::Boolean(false) => "FALSE".to_owned(),
+        Expr::Double(d) => format_double(*d),
+        Expr::Integer(i) => format!("{}", i),
+        Expr::Symbol(vref) => format!("{}", vref),
+        Expr::Text(s) => format_text(s),
+
+        Expr::Add(lhs, rhs) => format_binary(lhs, "+", rhs, prio),
+        Expr::Subtract(lhs, rhs) => format_binary(lhs, "-", rhs, prio),
+        Expr::Multiply(lhs, rhs) => format_binary(lhs, "*", rhs, prio),
+        Expr::Divide(lhs, rhs) => format_binary(lhs, "/", rhs, prio),
+        Expr::Modulo(lhs, rhs) => format_binary(lhs, "MOD", rhs, prio),
+        Expr::Negate(e) => format!("-{}", format_operand(e, prio, false)),
+
+        Expr::Equal(lhs, rhs) => format_binary(lhs, "=", rhs, prio),
+        Expr::NotEqual(lhs, rhs) => format_binary(lhs, "<>", rhs, prio),
+        Expr::Less(lhs, rhs) => format_binary(lhs, "<", rhs, prio),
+        Expr::LessEqual(lhs, rhs) => format_binary(lhs, "<=", rhs, prio),
+        Expr::Greater(lhs, rhs) => format_binary(lhs, ">", rhs, prio),
+        Expr::GreaterEqual(lhs, rhs) => format_binary(lhs, ">=", rhs, prio),
+
+        Expr::And(lhs, rhs) => format_binary(lhs, "AND", rhs, prio),
+        Expr::Not(e) => format!("NOT {}", format_operand(e, prio, false)),
+        Expr::Or(lhs, rhs) => format_binary(lhs, "OR", rhs, prio),
+        Expr::Xor(lhs, rhs) => format_binary(lhs, "XOR", rhs, prio),
+
+        Expr::Call(vref, args) => {
+            let args: Vec<String> = args.iter().map(format_expr).collect();
+            format!(
+                "{}{}({})",
+                vref.name().to_ascii_uppercase(),
+                vref.ref_type().annotation(),
+                args.join(", ")
+            )
+        }
+
+        // Channels only appear as whole arguments so their expressions need no parenthesis.
+        Expr::Channel(e) => format!("#{}", format_expr(e)),
+    }
+}
+
+/// Formats the arguments to a builtin call, including the leading space if there are any.
+fn format_args(args: &[(Option<Expr>, ArgSep)]) -> String {
+    let mut out = String::new();
+    let mut prev_sep = &ArgSep::End;
+    for (i, (expr, sep)) in args.iter().enumerate() {
+        if let Some(expr) = expr {
+            out.push(' ');
+            if *sep == ArgSep::Space || *prev_sep == ArgSep::Space {
+                // Words separated by spaces act as keywords, as in `HELP LANG OPERATORS`.
+                out += &format_expr(expr).to_ascii_uppercase();
+            } else {
+                out += &format_expr(expr);
+            }
+        } else if i == 0 || *sep != ArgSep::End {
+            out.push(' ');
+        }
+        match sep {
+            ArgSep::End | ArgSep::Space => (),
+            ArgSep::Short => out.push(';'),
+            ArgSep::Long => out.push(','),
+        }
+        prev_sep = sep;
+    }
+    out
+}
+
+/// Formats a call to the builtin `name` with `args`, undoing the rewrites that the parser applies
+/// to statements with a syntax of their own.
+fn format_builtin_call(name: &str, args: &[(Option<Expr>, ArgSep)]) -> String {
+    match (name, args) {
+        (
+            "OPEN",
+            [(Some(file), ArgSep::Long), (Some(Expr::Text(mode)), ArgSep::Long), (Some(channel), ArgSep::End)],
+        ) => format!("OPEN {} FOR {} AS #{}", format_expr(file), mode, format_expr(channel)),
+
+        (name, args) => format!("{}{}", name, format_args(args)),
+    }
+}
+
+/// Formats the `FOR` loop step `step` if it is not the default.
+fn format_step(step: &Value) -> String {
+    match step {
+        Value::Integer(1) => "".to_owned(),
+        Value::Integer(i) => format!(" STEP {}", i),
+        Value::Double(d) => format!(" STEP {}", format_double(*d)),
+        _ => unreachable!("Steps are validated at parse time"),
+    }
+}
+
+/// Returns the last line occupied by `stmt`, which starts at `pos`, assuming that it is laid out
+/// the way this module formats it.
+fn last_line(stmt: &Statement, pos: LineCol) -> usize {
+    let last_of = |body: &[(Statement, LineCol)]| {
+        body.last().map(|(stmt, pos)| last_line(stmt, *pos)).unwrap_or(pos.line)
+    };
+    match stmt {
+        Statement::If(branches) => {
+            let body_end = branches.iter().map(|(_, body)| last_of(body)).max().unwrap_or(0);
+            body_end.max(pos.line + branches.len() - 1) + 1
+        }
+        Statement::For(_, _, _, _, body) | Statement::While(_, body) => last_of(body) + 1,
+        _ => pos.line,
+    }
+}
+
+/// Appends the formatted `body` to `out` at the given `depth` of indentation.
+fn format_block(out: &mut String, body: &[(Statement, LineCol)], depth: usize) {
+    let mut prev_end: Option<usize> = None;
+    for (stmt, pos) in body {
+        if let Some(prev_end) = prev_end {
+            if pos.line > prev_end + 1 {
+                out.push('\n');
+            }
+        }
+        format_statement(out, stmt, depth);
+        prev_end = Some(last_line(stmt, *pos));
+    }
+}
+
+/// Appends `line` to `out` at the given `depth` of indentation.
+fn push_line(out: &mut String, depth: usize, line: &str) {
+    out.push_str(&" ".repeat(depth * INDENT));
+    out.push_str(line);
+    out.push('\n');
+}
+
+/// Appends the formatted `stmt` to `out` at the given `depth` of indentation.
+fn format_statement(out: &mut String, stmt: &Statement, depth: usize) {
+    match stmt {
+        Statement::Assignment(vref, expr) => {
+            push_line(out, depth, &format!("{} = {}", vref, format_expr(expr)))
+        }
+
+        Statement::BuiltinCall(name, args) => {
+            push_line(out, depth, &format_builtin_call(name, args))
+        }
+
+        Statement::End(None) => push_line(out, depth, "END"),
+        Statement::End(Some(code)) => push_line(out, depth, &format!("END {}", format_expr(code))),
+
+        Statement::If(branches) => {
+            for (i, (guard, body)) in branches.iter().enumerate() {
+                let is_else = i > 0 && i == branches.len() - 1 && *guard == Expr::Boolean(true);
+                let header = if is_else {
+                    "ELSE".to_owned()
+                } else if i == 0 {
+                    format!("IF {} THEN", format_expr(guard))
+                } else {
+                    format!("ELSEIF {} THEN", format_expr(guard))
+                };
+                push_line(out, depth, &header);
+                format_block(out, body, depth + 1);
+            }
+            push_line(out, depth, "END IF");
+        }
+
+        Statement::For(iterator, start, end, step, body) => {
+            push_line(
+                out,
+                depth,
+                &format!(
+                    "FOR {} = {} TO {}{}",
+                    iterator,
+                    format_expr(start),
+                    format_expr(end),
+                    format_step(step)
+                ),
+            );
+            format_block(out, body, depth + 1);
+            push_line(out, depth, "NEXT");
+        }
+
+        Statement::MidAssignment(target, start, length, value) => {
+            let length = match length {
+                Some(length) => format!(", {}", format_expr(length)),
+                None => "".to_owned(),
+            };
+            push_line(
+                out,
+                depth,
+                &format!(
+                    "MID$({}, {}{}) = {}",
+                    target,
+                    format_expr(start),
+                    length,
+                    format_expr(value)
+                ),
+            );
+        }
+
+        Statement::While(condition, body) => {
+            push_line(out, depth, &format!("WHILE {}", format_expr(condition)));
+            format_block(out, body, depth + 1);
+            push_line(out, depth, "END WHILE");
+        }
+    }
+}
+
+/// Formats the already-parsed program `stmts` as canonical source code.
+pub fn format_program(stmts: &[(Statement, LineCol)]) -> String {
+    let mut out = String::new();
+    format_block(&mut out, stmts, 0);
+    out
+}
+
+/// Parses the program in `input` and formats it as canonical source code.
+///
+/// Fails with the first syntax error found in the program, if any, as formatting a partial
+/// program would drop the statements that could not be parsed.
+pub fn format_source(input: &mut dyn io::Read) -> exec::Result<String> {
+    let (stmts, errors) = Parser::from(input).parse_all();
+    if let Some((pos, e)) = errors.into_iter().next() {
+        return Err(exec::Error::from(e).at(pos));
+    }
+    Ok(format_program(&stmts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{VarRef, VarType};
+
+    /// Formats `input` and checks that it matches `expected`, and that formatting the result
+    /// again is stable.
+    fn do_ok_test(input: &str, expected: &str) {
+        let formatted = format_source(&mut input.as_bytes()).expect("Formatting failed");
+        assert_eq!(expected, formatted);
+        let reformatted = format_source(&mut formatted.as_bytes()).expect("Reformatting failed");
+        assert_eq!(formatted, reformatted);
+    }
+
+    #[test]
+    fn test_format_empty() {
+        do_ok_test("", "");
+        do_ok_test("\n\n  \n", "");
+    }
+
+    #[test]
+    fn test_format_simple_statements() {
+        do_ok_test("a=1:b$ = \"x\"", "a = 1\nb$ = \"x\"\n");
+        do_ok_test("print a;b,c", "PRINT a; b, c\n");
+        do_ok_test("print", "PRINT\n");
+        do_ok_test("print ,a ;", "PRINT , a;\n");
+        do_ok_test("print a,,b", "PRINT a, , b\n");
+        do_ok_test("end", "END\n");
+        do_ok_test("end 3+2", "END 3 + 2\n");
+        do_ok_test("mid$( s$,2 ) = \"ab\"", "MID$(s$, 2) = \"ab\"\n");
+        do_ok_test("mid$(s$,2,a+1)=t$", "MID$(s$, 2, a + 1) = t$\n");
+    }
+
+    #[test]
+    fn test_format_literals() {
+        do_ok_test("a = true: b = false", "a = TRUE\nb = FALSE\n");
+        do_ok_test("a = 1.0: b = 2.5", "a = 1.0\nb = 2.5\n");
+        do_ok_test("a = \"say \\\"hi\\\" \\\\\"", "a = \"say \\\"hi\\\" \\\\\"\n");
+    }
+
+    #[test]
+    fn test_format_operators() {
+        do_ok_test("a=1+2*3", "a = 1 + 2 * 3\n");
+        do_ok_test("a=(1+2)*3", "a = (1 + 2) * 3\n");
+        do_ok_test("a=((1))-(2-3)", "a = 1 - (2 - 3)\n");
+        do_ok_test("a=(1-2)-3", "a = 1 - 2 - 3\n");
+        do_ok_test("a=7 mod 2/3", "a = 7 MOD 2 / 3\n");
+        do_ok_test("a=-b*-(c+1)", "a = -b * -(c + 1)\n");
+        do_ok_test("a=not b and c", "a = NOT b AND c\n");
+        do_ok_test("a=not (b and c)", "a = NOT (b AND c)\n");
+        do_ok_test("a=b<>c or d<=e xor f>=g", "a = b <> c OR d <= e XOR f >= g\n");
+        do_ok_test("a=b<c and (d>e)", "a = b < c AND d > e\n");
+        do_ok_test("a=(b=c)=d", "a = b = c = d\n");
+    }
+
+    #[test]
+    fn test_format_calls() {
+        do_ok_test("a = left$( b$ ,1+2 )", "a = LEFT$(b$, 1 + 2)\n");
+        do_ok_test("a = sum(sum(1,2),3)", "a = SUM(SUM(1, 2), 3)\n");
+        do_ok_test("print #1,a", "PRINT #1, a\n");
+        do_ok_test("open \"x\" for output as 2", "OPEN \"x\" FOR OUTPUT AS #2\n");
+        do_ok_test("line input #n%+1,l$", "LINE INPUT #n% + 1, l$\n");
+        do_ok_test("help lang   operators", "HELP LANG OPERATORS\n");
+        do_ok_test("help \"lang\"", "HELP \"lang\"\n");
+        do_ok_test("option  overflow wrap", "OPTION OVERFLOW WRAP\n");
+    }
+
+    #[test]
+    fn test_format_blocks() {
+        do_ok_test(
+            "for i=1 to 10 step 2\nif i mod 3=0 then\nprint i\nelseif i=5 then\nwhile false\n\
+             wend\nelse\nend\nend if\nnext",
+            "FOR i = 1 TO 10 STEP 2\n    IF i MOD 3 = 0 THEN\n        PRINT i\n    \
+             ELSEIF i = 5 THEN\n        WHILE FALSE\n        END WHILE\n    ELSE\n        \
+             END\n    END IF\nNEXT\n",
+        );
+        do_ok_test("for d=1.0 to 0 step -0.5\nnext", "FOR d = 1.0 TO 0 STEP -0.5\nNEXT\n");
+        do_ok_test("if a then\nelse if b then\nend if", "IF a THEN\nELSEIF b THEN\nEND IF\n");
+    }
+
+    #[test]
+    fn test_format_messy_program() {
+        do_ok_test(
+            "  REM Messy program\n\
+             n% =0:total=  0\n\
+             \n\n\n\
+             while n%<10 ' Count.\n\
+             \tn%=n%+1\n\
+             \n\
+             \t\t  if n% mod 2=0 then: total=total+n%\n\
+             end if\n\
+             wend\n\
+             print \"Total:\";total\n",
+            "n% = 0\n\
+             total = 0\n\
+             \n\
+             WHILE n% < 10\n    \
+                 n% = n% + 1\n\
+             \n    \
+                 IF n% MOD 2 = 0 THEN\n        \
+                     total = total + n%\n    \
+                 END IF\n\
+             END WHILE\n\
+             PRINT \"Total:\"; total\n",
+        );
+    }
+
+    #[test]
+    fn test_format_keeps_blank_lines_after_blocks() {
+        do_ok_test(
+            "while a\nif b then\nc = 1\nelse\nend if\nwend\n\nd = 2\ne = 3",
+            "WHILE a\n    IF b THEN\n        c = 1\n    ELSE\n    END IF\nEND WHILE\n\nd = 2\ne = 3\n",
+        );
+    }
+
+    #[test]
+    fn test_format_program() {
+        let stmts = vec![(
+            Statement::Assignment(
+                VarRef::new("x", VarType::Integer),
+                Expr::Negate(Box::from(Expr::Negate(Box::from(Expr::Integer(1))))),
+            ),
+            LineCol { line: 1, col: 1 },
+        )];
+        assert_eq!("x% = --1\n", format_program(&stmts));
+        do_ok_test("x% = --1", "x% = --1\n");
+    }
+
+    #[test]
+    fn test_format_errors() {
+        let err = format_source(&mut "a = 1\nb = (2\nc = 3".as_bytes()).unwrap_err();
+        assert_eq!(Some(LineCol { line: 2, col: 6 }), err.position());
+        assert_eq!("Unbalanced parenthesis", format!("{}", err));
+    }
+}
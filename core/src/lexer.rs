@@ -15,7 +15,7 @@
 
 //! Tokenizer for the EndBASIC language.
 
-use crate::ast::{VarRef, VarType};
+use crate::ast::{LineCol, VarRef, VarType};
 use crate::reader::CharReader;
 use std::io;
 use std::iter::Peekable;
@@ -40,6 +40,8 @@ pub enum Token {
 
     Comma,
     Semicolon,
+    Hash,
+    QuestionMark,
     LeftParen,
     RightParen,
 
@@ -70,6 +72,7 @@ pub enum Token {
     Step,
     Then,
     To,
+    Wend,
     While,
 }
 
@@ -116,12 +119,38 @@ impl CharOps for char {
 pub struct Lexer<'a> {
     /// Peekable iterator over the characters to scan.
     input: Peekable<CharReader<'a>>,
+
+    /// Position of the next character to be returned by `input`.
+    pos: LineCol,
+
+    /// Position of the first character of the last token returned by `read`.
+    token_pos: LineCol,
 }
 
 impl<'a> Lexer<'a> {
     /// Creates a new lexer from the given readable.
     pub fn from(input: &'a mut dyn io::Read) -> Self {
-        Self { input: CharReader::from(input).peekable() }
+        let pos = LineCol { line: 1, col: 1 };
+        Self { input: CharReader::from(input).peekable(), pos, token_pos: pos }
+    }
+
+    /// Returns the position of the first character of the last token returned by `read`.
+    pub fn last_pos(&self) -> LineCol {
+        self.token_pos
+    }
+
+    /// Consumes the next character from the input and updates the current position.
+    fn next_char(&mut self) -> Option<io::Result<char>> {
+        let next = self.input.next();
+        if let Some(Ok(ch)) = next {
+            if ch == '\n' {
+                self.pos.line += 1;
+                self.pos.col = 1;
+            } else {
+                self.pos.col += 1;
+            }
+        }
+        next
     }
 
     /// Handles a `input.read()` call that returned an unexpected character.
@@ -133,9 +162,9 @@ impl<'a> Lexer<'a> {
             match self.input.peek() {
                 Some(Ok(ch)) if ch.is_separator() => break,
                 Some(Ok(_)) => {
-                    self.input.next().unwrap()?;
+                    self.next_char().unwrap()?;
                 }
-                Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
+                Some(Err(_)) => return Err(self.next_char().unwrap().unwrap_err()),
                 None => break,
             }
         }
@@ -147,7 +176,7 @@ impl<'a> Lexer<'a> {
     /// This returns a `Token::Bad` with the provided `msg`, consumes the peeked character, and
     /// then skips characters in the input stream until a field separator is found.
     fn handle_bad_peek<S: Into<String>>(&mut self, msg: S) -> io::Result<Token> {
-        self.input.next();
+        self.next_char();
         self.handle_bad_read(msg)
     }
 
@@ -162,16 +191,16 @@ impl<'a> Lexer<'a> {
                     if found_dot {
                         return self.handle_bad_peek("Too many dots in numeric literal");
                     }
-                    s.push(self.input.next().unwrap()?);
+                    s.push(self.next_char().unwrap()?);
                     found_dot = true;
                 }
-                Some(Ok(ch)) if ch.is_digit(10) => s.push(self.input.next().unwrap()?),
+                Some(Ok(ch)) if ch.is_digit(10) => s.push(self.next_char().unwrap()?),
                 Some(Ok(ch)) if ch.is_separator() => break,
                 Some(Ok(ch)) => {
                     let msg = format!("Unexpected character in numeric literal: {}", ch);
                     return self.handle_bad_peek(msg);
                 }
-                Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
+                Some(Err(_)) => return Err(self.next_char().unwrap().unwrap_err()),
                 None => break,
             }
         }
@@ -197,21 +226,21 @@ impl<'a> Lexer<'a> {
     /// Consumes the operator at the current position, whose first character is `first`.
     fn consume_operator(&mut self, first: char) -> io::Result<Token> {
         match (first, self.input.peek()) {
-            (_, Some(Err(_))) => Err(self.input.next().unwrap().unwrap_err()),
+            (_, Some(Err(_))) => Err(self.next_char().unwrap().unwrap_err()),
 
             ('<', Some(Ok('>'))) => {
-                self.input.next().unwrap()?;
+                self.next_char().unwrap()?;
                 Ok(Token::NotEqual)
             }
 
             ('<', Some(Ok('='))) => {
-                self.input.next().unwrap()?;
+                self.next_char().unwrap()?;
                 Ok(Token::LessEqual)
             }
             ('<', _) => Ok(Token::Less),
 
             ('>', Some(Ok('='))) => {
-                self.input.next().unwrap()?;
+                self.next_char().unwrap()?;
                 Ok(Token::GreaterEqual)
             }
             ('>', _) => Ok(Token::Greater),
@@ -229,33 +258,33 @@ impl<'a> Lexer<'a> {
         let mut vtype = VarType::Auto;
         loop {
             match self.input.peek() {
-                Some(Ok(ch)) if ch.is_word() => s.push(self.input.next().unwrap()?),
+                Some(Ok(ch)) if ch.is_word() => s.push(self.next_char().unwrap()?),
                 Some(Ok(ch)) if ch.is_separator() => break,
                 Some(Ok('?')) => {
                     vtype = VarType::Boolean;
-                    self.input.next().unwrap()?;
+                    self.next_char().unwrap()?;
                     break;
                 }
                 Some(Ok('#')) => {
                     vtype = VarType::Double;
-                    self.input.next().unwrap()?;
+                    self.next_char().unwrap()?;
                     break;
                 }
                 Some(Ok('%')) => {
                     vtype = VarType::Integer;
-                    self.input.next().unwrap()?;
+                    self.next_char().unwrap()?;
                     break;
                 }
                 Some(Ok('$')) => {
                     vtype = VarType::Text;
-                    self.input.next().unwrap()?;
+                    self.next_char().unwrap()?;
                     break;
                 }
                 Some(Ok(ch)) => {
                     let msg = format!("Unexpected character in symbol: {}", ch);
                     return self.handle_bad_peek(msg);
                 }
-                Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
+                Some(Err(_)) => return Err(self.next_char().unwrap().unwrap_err()),
                 None => break,
             }
         }
@@ -276,6 +305,7 @@ impl<'a> Lexer<'a> {
             "THEN" => Ok(Token::Then),
             "TO" => Ok(Token::To),
             "TRUE" => Ok(Token::Boolean(true)),
+            "WEND" => Ok(Token::Wend),
             "WHILE" => Ok(Token::While),
             "XOR" => Ok(Token::Xor),
             _ => Ok(Token::Symbol(VarRef::new(s, vtype))),
@@ -292,19 +322,19 @@ impl<'a> Lexer<'a> {
             match self.input.peek() {
                 Some(Ok(ch)) => {
                     if escaping {
-                        s.push(self.input.next().unwrap()?);
+                        s.push(self.next_char().unwrap()?);
                         escaping = false;
                     } else if *ch == '\\' {
-                        self.input.next().unwrap()?;
+                        self.next_char().unwrap()?;
                         escaping = true;
                     } else if *ch == delim {
-                        self.input.next().unwrap()?;
+                        self.next_char().unwrap()?;
                         break;
                     } else {
-                        s.push(self.input.next().unwrap()?);
+                        s.push(self.next_char().unwrap()?);
                     }
                 }
-                Some(Err(_)) => return Err(self.input.next().unwrap().unwrap_err()),
+                Some(Err(_)) => return Err(self.next_char().unwrap().unwrap_err()),
                 None => {
                     return self.handle_bad_peek(format!("Incomplete string due to EOF: {}", s))
                 }
@@ -317,7 +347,7 @@ impl<'a> Lexer<'a> {
     /// (which may be EOF or end of line).
     fn consume_rest_of_line(&mut self) -> io::Result<Token> {
         loop {
-            match self.input.next() {
+            match self.next_char() {
                 None => return Ok(Token::Eof),
                 Some(Ok('\n')) => return Ok(Token::Eol),
                 Some(Err(e)) => return Err(e),
@@ -326,11 +356,37 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Consumes a line continuation, whose `_` character was already consumed, and returns the
+    /// token that follows it.
+    ///
+    /// A `_` that starts a token and is followed by nothing but spaces until the end of the line
+    /// joins the next line into the current one, acting as a plain separator between the tokens
+    /// around it.  Any other `_` that starts a token is treated as the beginning of a symbol.
+    fn consume_continuation(&mut self) -> io::Result<Token> {
+        let mut skipped_spaces = false;
+        loop {
+            match self.input.peek() {
+                Some(Ok(ch)) if ch.is_space() => {
+                    self.next_char().unwrap()?;
+                    skipped_spaces = true;
+                }
+                Some(Ok('\n')) => {
+                    self.next_char().unwrap()?;
+                    return self.read();
+                }
+                Some(Err(_)) => return Err(self.next_char().unwrap().unwrap_err()),
+                _ if skipped_spaces => return Ok(Token::Symbol(VarRef::new("_", VarType::Auto))),
+                _ => return self.consume_symbol('_'),
+            }
+        }
+    }
+
     /// Skips whitespace until it finds the beginning of the next token, and returns its first
     /// character.
     fn advance_and_read_next(&mut self) -> io::Result<Option<char>> {
         loop {
-            match self.input.next() {
+            self.token_pos = self.pos;
+            match self.next_char() {
                 Some(Ok(ch)) if ch.is_space() => (),
                 Some(Ok(ch)) => return Ok(Some(ch)),
                 Some(Err(e)) => return Err(e),
@@ -357,6 +413,8 @@ impl<'a> Lexer<'a> {
 
             ';' => Ok(Token::Semicolon),
             ',' => Ok(Token::Comma),
+            '#' => Ok(Token::Hash),
+            '?' => Ok(Token::QuestionMark),
 
             '(' => Ok(Token::LeftParen),
             ')' => Ok(Token::RightParen),
@@ -369,6 +427,8 @@ impl<'a> Lexer<'a> {
             '=' => Ok(Token::Equal),
             '<' | '>' => self.consume_operator(ch),
 
+            '_' => self.consume_continuation(),
+
             ch if ch.is_digit(10) => self.consume_number(ch),
             ch if ch.is_word() => self.consume_symbol(ch),
             ch => self.handle_bad_read(format!("Unknown character: {}", ch)),
@@ -377,7 +437,8 @@ impl<'a> Lexer<'a> {
 
     /// Returns a peekable adaptor for this lexer.
     pub fn peekable(self) -> PeekableLexer<'a> {
-        PeekableLexer { lexer: self, peeked: None }
+        let last_pos = self.token_pos;
+        PeekableLexer { lexer: self, peeked: None, peeked2: None, last_pos }
     }
 }
 
@@ -390,9 +451,16 @@ pub struct PeekableLexer<'a> {
     /// The wrapped lexer instance.
     lexer: Lexer<'a>,
 
-    /// If not none, contains the character read by `peek`, which will be consumed by the next call
-    /// to `read` or `consume_peeked`.
-    peeked: Option<Token>,
+    /// If not none, contains the character read by `peek` and its position, which will be consumed
+    /// by the next call to `read` or `consume_peeked`.
+    peeked: Option<(Token, LineCol)>,
+
+    /// If not none, contains the token that follows `peeked` as read by `peek2` and its position.
+    /// This can only be set when `peeked` is set too.
+    peeked2: Option<(Token, LineCol)>,
+
+    /// Position of the last token returned by `read` or `consume_peeked`.
+    last_pos: LineCol,
 }
 
 impl<'a> PeekableLexer<'a> {
@@ -402,7 +470,15 @@ impl<'a> PeekableLexer<'a> {
     /// errors and is thus not going to call this when an error is present.
     pub fn consume_peeked(&mut self) -> Token {
         assert!(self.peeked.is_some());
-        self.peeked.take().unwrap()
+        let (t, pos) = self.peeked.take().unwrap();
+        self.peeked = self.peeked2.take();
+        self.last_pos = pos;
+        t
+    }
+
+    /// Returns the position of the last token returned by `read` or `consume_peeked`.
+    pub fn last_pos(&self) -> LineCol {
+        self.last_pos
     }
 
     /// Peeks the upcoming token.
@@ -411,10 +487,23 @@ impl<'a> PeekableLexer<'a> {
     /// the lexer.
     pub fn peek(&mut self) -> io::Result<&Token> {
         if self.peeked.is_none() {
-            let n = self.read()?;
-            self.peeked.replace(n);
+            let n = self.lexer.read()?;
+            self.peeked.replace((n, self.lexer.last_pos()));
+        }
+        Ok(&self.peeked.as_ref().unwrap().0)
+    }
+
+    /// Peeks the token that follows the upcoming one.
+    ///
+    /// It is OK to call this function several times on the same token before extracting it from
+    /// the lexer.
+    pub fn peek2(&mut self) -> io::Result<&Token> {
+        self.peek()?;
+        if self.peeked2.is_none() {
+            let n = self.lexer.read()?;
+            self.peeked2.replace((n, self.lexer.last_pos()));
         }
-        Ok(self.peeked.as_ref().unwrap())
+        Ok(&self.peeked2.as_ref().unwrap().0)
     }
 
     /// Reads the next token.
@@ -423,8 +512,16 @@ impl<'a> PeekableLexer<'a> {
     /// further tokens can be obtained with subsequent calls.
     pub fn read(&mut self) -> io::Result<Token> {
         match self.peeked.take() {
-            Some(t) => Ok(t),
-            None => self.lexer.read(),
+            Some((t, pos)) => {
+                self.peeked = self.peeked2.take();
+                self.last_pos = pos;
+                Ok(t)
+            }
+            None => {
+                let t = self.lexer.read()?;
+                self.last_pos = self.lexer.last_pos();
+                Ok(t)
+            }
         }
     }
 }
@@ -608,6 +705,10 @@ mod tests {
         do_ok_test("WHILE END WHILE", &[Token::While, Token::End, Token::While]);
 
         do_ok_test("while end while", &[Token::While, Token::End, Token::While]);
+
+        do_ok_test("WHILE WEND", &[Token::While, Token::Wend]);
+
+        do_ok_test("while wend", &[Token::While, Token::Wend]);
     }
 
     /// Syntactic sugar to instantiate a test that verifies the parsing of an operator.
@@ -671,6 +772,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_channels() {
+        do_ok_test(
+            "PRINT #1, a# #",
+            &[
+                new_auto_symbol("PRINT"),
+                Token::Hash,
+                Token::Integer(1),
+                Token::Comma,
+                Token::Symbol(VarRef::new("a", VarType::Double)),
+                Token::Hash,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_question_mark() {
+        do_ok_test(
+            "? a?; ?\"x\"",
+            &[
+                Token::QuestionMark,
+                Token::Symbol(VarRef::new("a", VarType::Boolean)),
+                Token::Semicolon,
+                Token::QuestionMark,
+                Token::Text("x".to_owned()),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        do_ok_test(
+            "a = 1 + _\n    2 _ \r\n* 3\nb",
+            &[
+                new_auto_symbol("a"),
+                Token::Equal,
+                Token::Integer(1),
+                Token::Plus,
+                Token::Integer(2),
+                Token::Multiply,
+                Token::Integer(3),
+                Token::Eol,
+                new_auto_symbol("b"),
+            ],
+        );
+        do_ok_test("PRINT _\n", &[new_auto_symbol("PRINT")]);
+        do_ok_test(
+            "\"a _\nb\" _\n\"c\"",
+            &[Token::Text("a _\nb".to_owned()), Token::Text("c".to_owned())],
+        );
+    }
+
+    #[test]
+    fn test_underscore_symbols() {
+        do_ok_test(
+            "a_ _b _ = _%: _",
+            &[
+                new_auto_symbol("a_"),
+                new_auto_symbol("_b"),
+                new_auto_symbol("_"),
+                Token::Equal,
+                Token::Symbol(VarRef::new("_", VarType::Integer)),
+                Token::Eol,
+                new_auto_symbol("_"),
+            ],
+        );
+    }
+
     #[test]
     fn test_peekable_lexer() {
         let mut input = b"a b 123".as_ref();
@@ -685,6 +854,23 @@ mod tests {
         assert_eq!(Token::Eof, lexer.read().unwrap());
     }
 
+    #[test]
+    fn test_peekable_lexer_peek2() {
+        let mut input = b"a b 123 c".as_ref();
+        let mut lexer = Lexer::from(&mut input).peekable();
+        assert_eq!(&new_auto_symbol("b"), lexer.peek2().unwrap());
+        assert_eq!(&new_auto_symbol("a"), lexer.peek().unwrap());
+        assert_eq!(&new_auto_symbol("b"), lexer.peek2().unwrap());
+        assert_eq!(new_auto_symbol("a"), lexer.read().unwrap());
+        assert_eq!(&new_auto_symbol("b"), lexer.peek().unwrap());
+        assert_eq!(&Token::Integer(123), lexer.peek2().unwrap());
+        assert_eq!(new_auto_symbol("b"), lexer.consume_peeked());
+        assert_eq!(Token::Integer(123), lexer.read().unwrap());
+        assert_eq!(&Token::Eof, lexer.peek2().unwrap());
+        assert_eq!(new_auto_symbol("c"), lexer.read().unwrap());
+        assert_eq!(Token::Eof, lexer.read().unwrap());
+    }
+
     #[test]
     fn test_recoverable_errors() {
         do_ok_test(
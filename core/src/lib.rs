@@ -23,8 +23,12 @@
 
 // TODO(jmmv): Should narrow the exposed interface by 1.0.0.
 pub mod ast;
+pub mod dump;
 pub mod eval;
 pub mod exec;
+mod fold;
+pub mod format;
 mod lexer;
+pub mod lint;
 mod parser;
 mod reader;
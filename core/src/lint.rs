@@ -0,0 +1,189 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Static analysis of programs to detect code that can never run.
+//!
+//! The checks in here are deliberately conservative: they only look at the structure of the
+//! program and at literal values, so they never warn about code that could run depending on the
+//! values of variables or on the results of function calls.
+
+use crate::ast::{Expr, LineCol, Statement, Value};
+use crate::exec;
+use crate::parser::Parser;
+use std::io;
+
+/// A problem found in a program that does not prevent it from running.
+#[derive(Debug, PartialEq)]
+pub struct Warning {
+    /// Position of the statement the warning is about.
+    pub pos: LineCol,
+
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl Warning {
+    /// Creates a new warning for the statement at `pos`.
+    fn new<S: Into<String>>(pos: LineCol, message: S) -> Self {
+        Self { pos, message: message.into() }
+    }
+}
+
+/// Returns the value of `expr` if it is a numeric literal, possibly negated.
+fn literal_number(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::Double(d) => Some(*d),
+        Expr::Integer(i) => Some(*i as f64),
+        Expr::Negate(e) => literal_number(e).map(|n| -n),
+        _ => None,
+    }
+}
+
+/// Returns true if a `FOR` loop from `start` to `end` with `step` is known to never run its body.
+fn for_never_runs(start: &Expr, end: &Expr, step: &Value) -> bool {
+    let step = match step {
+        Value::Double(d) => *d,
+        Value::Integer(i) => *i as f64,
+        _ => return false,
+    };
+    match (literal_number(start), literal_number(end)) {
+        (Some(start), Some(end)) => (step > 0.0 && start > end) || (step < 0.0 && start < end),
+        _ => false,
+    }
+}
+
+/// Analyzes the statements in `block` and appends any problems found to `warnings`.
+fn lint_block(block: &[(Statement, LineCol)], warnings: &mut Vec<Warning>) {
+    let mut after_end = false;
+    for (stmt, pos) in block {
+        if after_end {
+            warnings.push(Warning::new(*pos, "Unreachable statement after END"));
+            break;
+        }
+        after_end = matches!(stmt, Statement::End(_));
+
+        match stmt {
+            Statement::If(branches) => {
+                for (_, body) in branches {
+                    lint_block(body, warnings);
+                }
+            }
+
+            Statement::For(_, start, end, step, body) => {
+                if for_never_runs(start, end, step) {
+                    warnings.push(Warning::new(*pos, "FOR loop body never runs"));
+                }
+                lint_block(body, warnings);
+            }
+
+            Statement::While(condition, body) => {
+                if *condition == Expr::Boolean(false) {
+                    warnings.push(Warning::new(*pos, "WHILE loop body never runs"));
+                }
+                lint_block(body, warnings);
+            }
+
+            Statement::Assignment(_, _)
+            | Statement::BuiltinCall(_, _)
+            | Statement::End(_)
+            | Statement::MidAssignment(_, _, _, _) => (),
+        }
+    }
+}
+
+/// Analyzes the already-parsed program `stmts` and returns all problems found, sorted by their
+/// position in the source code.
+pub fn lint(stmts: &[(Statement, LineCol)]) -> Vec<Warning> {
+    let mut warnings = vec![];
+    lint_block(stmts, &mut warnings);
+    warnings.sort_by_key(|w| (w.pos.line, w.pos.col));
+    warnings
+}
+
+/// Parses the program in `input` and analyzes it like `lint` does.
+///
+/// Fails with the first syntax error found in the program, if any.  Use `exec::check_syntax` to
+/// obtain all of them.
+pub fn lint_source(input: &mut dyn io::Read) -> exec::Result<Vec<Warning>> {
+    let (stmts, errors) = Parser::from(input).parse_all();
+    if let Some((pos, e)) = errors.into_iter().next() {
+        return Err(exec::Error::from(e).at(pos));
+    }
+    Ok(lint(&stmts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Analyzes `input` and checks that it yields the `expected` warnings, given as tuples of line,
+    /// column and message.
+    fn do_test(input: &str, expected: &[(usize, usize, &str)]) {
+        let warnings = lint_source(&mut input.as_bytes()).expect("Linting failed");
+        let expected: Vec<Warning> = expected
+            .iter()
+            .map(|(line, col, message)| Warning::new(LineCol { line: *line, col: *col }, *message))
+            .collect();
+        assert_eq!(expected, warnings);
+    }
+
+    #[test]
+    fn test_lint_clean_programs() {
+        do_test("", &[]);
+        do_test("a = 1\nPRINT a\nEND", &[]);
+        do_test("IF a THEN\n    END\nEND IF\nPRINT 1", &[]);
+        do_test("IF a THEN\n    END\nELSE\n    PRINT 1\nEND IF", &[]);
+        do_test("WHILE TRUE\n    END 1\nEND WHILE\nPRINT 1", &[]);
+        do_test("FOR i = 1 TO 1\nNEXT\nFOR i = 5 TO 1 STEP -1\nNEXT", &[]);
+        do_test("FOR i = a TO 1\nNEXT\nFOR i = 1 TO -a\nNEXT", &[]);
+        do_test("WHILE a = FALSE\nEND WHILE", &[]);
+    }
+
+    #[test]
+    fn test_lint_after_end() {
+        do_test("a = 1\nEND\nb = 2\nc = 3", &[(3, 1, "Unreachable statement after END")]);
+        do_test("END 3: PRINT", &[(1, 8, "Unreachable statement after END")]);
+        do_test(
+            "IF a THEN\n    END\n    PRINT 1\nEND IF\nPRINT 2",
+            &[(3, 5, "Unreachable statement after END")],
+        );
+    }
+
+    #[test]
+    fn test_lint_loops_that_never_run() {
+        do_test("FOR i = 10 TO 1\nNEXT", &[(1, 1, "FOR loop body never runs")]);
+        do_test("FOR i = -1 TO -5\nNEXT", &[(1, 1, "FOR loop body never runs")]);
+        do_test("FOR i = 1.5 TO 2 STEP -0.5\nNEXT", &[(1, 1, "FOR loop body never runs")]);
+        do_test("WHILE FALSE\nEND WHILE", &[(1, 1, "WHILE loop body never runs")]);
+    }
+
+    #[test]
+    fn test_lint_nested_and_sorted() {
+        do_test(
+            "WHILE FALSE\n    FOR i = 3 TO 2\n        END\n        a = 1\n    NEXT\nEND WHILE",
+            &[
+                (1, 1, "WHILE loop body never runs"),
+                (2, 5, "FOR loop body never runs"),
+                (4, 9, "Unreachable statement after END"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_lint_errors() {
+        let err = lint_source(&mut "a = 1\nb = (2".as_bytes()).unwrap_err();
+        assert_eq!("Unbalanced parenthesis", format!("{}", err));
+    }
+}
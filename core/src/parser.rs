@@ -15,9 +15,8 @@
 
 //! Statement and expression parser for the EndBASIC language.
 
-use crate::ast::{ArgSep, Expr, Statement, VarRef, VarType};
+use crate::ast::{ArgSep, Expr, LineCol, Statement, Value, VarRef, VarType};
 use crate::lexer::{Lexer, PeekableLexer, Token};
-use std::cmp::Ordering;
 use std::io;
 
 /// Parser errors.
@@ -35,6 +34,10 @@ pub enum Error {
 /// Result for parser return values.
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Statements extracted by `Parser::parse_all` and the errors found along the way, each paired
+/// with its position in the input.
+pub type Outcome = (Vec<(Statement, LineCol)>, Vec<(LineCol, Error)>);
+
 /// Operators that can appear within an expression.
 ///
 /// The main difference between this and `lexer::Token` is that, in here, we differentiate the
@@ -166,15 +169,57 @@ impl ExprOp {
     }
 }
 
+/// Returns true if `vref` refers to the `MID$` function, which can be the target of an assignment.
+fn is_mid(vref: &VarRef) -> bool {
+    vref.name().eq_ignore_ascii_case("MID")
+        && (vref.ref_type() == VarType::Auto || vref.ref_type() == VarType::Text)
+}
+
+/// Returns true if `vref` refers to the `OPEN` command, which has a syntax of its own.
+fn is_open(vref: &VarRef) -> bool {
+    vref.name().eq_ignore_ascii_case("OPEN") && vref.ref_type() == VarType::Auto
+}
+
+/// Returns true if `vref` and the `next` token form the two-word `LINE INPUT` command.
+fn is_line_input(vref: &VarRef, next: &Token) -> bool {
+    let is_word = |vref: &VarRef, word| {
+        vref.name().eq_ignore_ascii_case(word) && vref.ref_type() == VarType::Auto
+    };
+    match next {
+        Token::Symbol(next) => is_word(vref, "LINE") && is_word(next, "INPUT"),
+        _ => false,
+    }
+}
+
+/// Returns true if `vref` is the `PRINT` command and the `next` token is the `AT` word that may
+/// start its position clause.
+fn is_print_at(vref: &VarRef, next: &Token) -> bool {
+    vref.name().eq_ignore_ascii_case("PRINT") && vref.ref_type() == VarType::Auto && is_at(next)
+}
+
+/// Returns true if `token` is the `AT` word that may start the position clause of `PRINT`.
+fn is_at(token: &Token) -> bool {
+    match token {
+        Token::Symbol(vref) => {
+            vref.name().eq_ignore_ascii_case("AT") && vref.ref_type() == VarType::Auto
+        }
+        _ => false,
+    }
+}
+
 /// Iterator over the statements of the language.
 pub struct Parser<'a> {
     lexer: PeekableLexer<'a>,
+
+    /// Position of the token that was being processed when the innermost syntax error was raised,
+    /// recorded before skipping over the rest of the failing statement.
+    error_pos: Option<LineCol>,
 }
 
 impl<'a> Parser<'a> {
     /// Creates a new parser from the given readable.
     pub fn from(input: &'a mut dyn io::Read) -> Self {
-        Self { lexer: Lexer::from(input).peekable() }
+        Self { lexer: Lexer::from(input).peekable(), error_pos: None }
     }
 
     /// Expects the peeked token to be `t` and consumes it.  Otherwise, leaves the token in the
@@ -190,12 +235,14 @@ impl<'a> Parser<'a> {
 
     /// Reads statements until one of the `delims` keywords is found.  The delimiter is not
     /// consumed.
-    fn parse_until(&mut self, delims: &[Token]) -> Result<Vec<Statement>> {
+    fn parse_until(&mut self, delims: &[Token]) -> Result<Vec<(Statement, LineCol)>> {
         let mut stmts = vec![];
         loop {
             let peeked = self.lexer.peek()?;
             if delims.contains(peeked) {
-                break;
+                if *peeked != Token::End || self.is_block_end()? {
+                    break;
+                }
             } else if *peeked == Token::Eol {
                 self.lexer.consume_peeked();
                 continue;
@@ -208,6 +255,29 @@ impl<'a> Parser<'a> {
         Ok(stmts)
     }
 
+    /// Returns true if the upcoming `END` token terminates a block (as in `END IF`) instead of
+    /// starting an `END` statement.
+    fn is_block_end(&mut self) -> Result<bool> {
+        debug_assert_eq!(Token::End, *self.lexer.peek()?);
+        match self.lexer.peek2()? {
+            Token::If | Token::While => Ok(true),
+            _ => Ok(false),
+        }
+    }
+
+    /// Returns the number of tokens that make up the upcoming `ELSEIF` keyword, which can be
+    /// spelled as `ELSEIF` or as `ELSE IF`, or 0 if there is no such keyword.
+    fn peek_elseif(&mut self) -> Result<usize> {
+        match self.lexer.peek()? {
+            Token::Elseif => Ok(1),
+            Token::Else => match self.lexer.peek2()? {
+                Token::If => Ok(2),
+                _ => Ok(0),
+            },
+            _ => Ok(0),
+        }
+    }
+
     /// Parses an assignment for the variable reference `varref` already read.
     fn parse_assignment(&mut self, vref: VarRef) -> Result<Statement> {
         let expr = match self.parse_expr()? {
@@ -230,10 +300,53 @@ impl<'a> Parser<'a> {
             Err(e) => return Err(Error::Bad(format!("{}", e))),
         };
         name.make_ascii_uppercase();
+        self.parse_builtin_call_args(name)
+    }
+
+    /// Parses the arguments to the builtin call `name` already read.
+    fn parse_builtin_call_args(&mut self, name: String) -> Result<Statement> {
+        self.parse_more_builtin_call_args(name, vec![])
+    }
 
-        let mut args = vec![];
+    /// Parses the remaining arguments to the builtin call `name` already read, given that the
+    /// arguments in `args` have already been consumed.
+    fn parse_more_builtin_call_args(
+        &mut self,
+        name: String,
+        mut args: Vec<(Option<Expr>, ArgSep)>,
+    ) -> Result<Statement> {
         loop {
-            let expr = self.parse_expr()?;
+            let expr = match self.lexer.peek()? {
+                Token::Hash => {
+                    self.lexer.consume_peeked();
+                    Some(Expr::Channel(Box::from(self.parse_channel()?)))
+                }
+                // Keywords that cannot start an expression are taken as bare words, as in `HELP ?`.
+                Token::Step => {
+                    self.lexer.consume_peeked();
+                    Some(Expr::Symbol(VarRef::new("STEP", VarType::Auto)))
+                }
+                Token::QuestionMark => {
+                    self.lexer.consume_peeked();
+                    Some(Expr::Symbol(VarRef::new("?", VarType::Auto)))
+                }
+                Token::Symbol(vref) if vref.ref_type() == VarType::Auto => {
+                    let first = match self.lexer.consume_peeked() {
+                        Token::Symbol(vref) => Expr::Symbol(vref),
+                        _ => unreachable!(),
+                    };
+                    match self.lexer.peek()? {
+                        // Bare words separated by spaces, as in `HELP LANG OPERATORS`, are passed
+                        // as separate arguments so that commands can take multi-word options.
+                        Token::Symbol(_) | Token::Step | Token::QuestionMark => {
+                            args.push((Some(first), ArgSep::Space));
+                            continue;
+                        }
+                        _ => self.parse_expr_from(Some(first))?,
+                    }
+                }
+                _ => self.parse_expr()?,
+            };
 
             let peeked = self.lexer.peek()?;
             match peeked {
@@ -261,6 +374,14 @@ impl<'a> Parser<'a> {
         Ok(Statement::BuiltinCall(name, args))
     }
 
+    /// Parses the channel number that follows a `#` already read.
+    fn parse_channel(&mut self) -> Result<Expr> {
+        match self.parse_expr()? {
+            Some(expr) => Ok(expr),
+            None => Err(Error::Bad("Missing channel number after #".to_owned())),
+        }
+    }
+
     /// Parses an expression.
     ///
     /// It is important to single out the special `Empty` expression as a possible return value,
@@ -269,21 +390,33 @@ impl<'a> Parser<'a> {
     ///
     /// This is an implementation of the Shunting Yard Algorithm by Edgar Dijkstra.
     fn parse_expr(&mut self) -> Result<Option<Expr>> {
-        let mut exprs: Vec<Expr> = vec![];
+        self.parse_expr_from(None)
+    }
+
+    /// Parses an expression whose first operand, `first`, has already been read if present.
+    ///
+    /// See `parse_expr` for details.
+    fn parse_expr_from(&mut self, first: Option<Expr>) -> Result<Option<Expr>> {
+        self.parse_expr_until(first, None)
+    }
+
+    /// Parses an expression like `parse_expr_from` does, but also stops at the `until` token if
+    /// given, without consuming it, for statements that use a keyword as a separator.
+    ///
+    /// The `until` token only terminates the expression once it has a complete operand outside of
+    /// any function call, so that misplaced keywords are still reported as such.
+    fn parse_expr_until(
+        &mut self,
+        first: Option<Expr>,
+        until: Option<&Token>,
+    ) -> Result<Option<Expr>> {
+        let mut exprs: Vec<Expr> = first.into_iter().collect();
         let mut ops: Vec<ExprOp> = vec![];
         let mut arity: Vec<usize> = vec![];
 
-        let mut need_operand = true; // Also tracks whether an upcoming minus is unary.
+        // Also tracks whether an upcoming minus is unary.
+        let mut need_operand = exprs.is_empty();
         loop {
-            let mut handle_operand = |e| {
-                if !need_operand {
-                    return Err(Error::Bad("Unexpected value in expression".to_owned()));
-                }
-                need_operand = false;
-                exprs.push(e);
-                Ok(())
-            };
-
             // Stop processing if we encounter an expression separator, but don't consume it because
             // the caller needs to have access to it.
             match self.lexer.peek()? {
@@ -301,9 +434,19 @@ impl<'a> Parser<'a> {
                         break;
                     }
                 }
+                token if !need_operand && arity.is_empty() && Some(token) == until => break,
                 _ => (),
             };
 
+            let mut handle_operand = |e| {
+                if !need_operand {
+                    return Err(Error::Bad("Unexpected value in expression".to_owned()));
+                }
+                need_operand = false;
+                exprs.push(e);
+                Ok(())
+            };
+
             let token = self.lexer.consume_peeked();
             match token {
                 Token::Boolean(b) => handle_operand(Expr::Boolean(b))?,
@@ -430,6 +573,11 @@ impl<'a> Parser<'a> {
 
                 Token::Bad(e) => return Err(Error::Bad(e)),
 
+                Token::Hash => return Err(Error::Bad("Unexpected # in expression".to_owned())),
+                Token::QuestionMark => {
+                    return Err(Error::Bad("Unexpected ? in expression".to_owned()))
+                }
+
                 Token::Eof
                 | Token::Eol
                 | Token::Semicolon
@@ -443,6 +591,7 @@ impl<'a> Parser<'a> {
                 | Token::Else
                 | Token::Elseif
                 | Token::End
+                | Token::Wend
                 | Token::While
                 | Token::For
                 | Token::Next => {
@@ -465,6 +614,150 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses an `END` statement.
+    fn parse_end(&mut self) -> Result<Statement> {
+        match self.lexer.peek()? {
+            Token::If => return Err(Error::Bad("END IF without IF".to_owned())),
+            Token::While => return Err(Error::Bad("END WHILE without WHILE".to_owned())),
+            _ => (),
+        }
+
+        let code = self.parse_expr()?;
+        match self.lexer.peek()? {
+            Token::Eof | Token::Eol => Ok(Statement::End(code)),
+            _ => Err(Error::Bad("Unexpected token in END statement".to_owned())),
+        }
+    }
+
+    /// Parses a `MID$` assignment for the `MID$` reference `vref` already read.
+    fn parse_mid_assignment(&mut self, vref: VarRef) -> Result<Statement> {
+        let (lhs, value) = match self.parse_expr_from(Some(Expr::Symbol(vref)))? {
+            Some(Expr::Equal(lhs, value)) => (lhs, value),
+            _ => return Err(Error::Bad("Expected assignment to MID$".to_owned())),
+        };
+
+        let mut args = match *lhs {
+            Expr::Call(_, args) => args.into_iter(),
+            _ => return Err(Error::Bad("Expected assignment to MID$".to_owned())),
+        };
+        let (target, start, length) = match (args.next(), args.next(), args.next(), args.next()) {
+            (Some(Expr::Symbol(target)), Some(start), length, None) => (target, start, length),
+            _ => {
+                return Err(Error::Bad(
+                    "MID$ assignment requires a variable, a start position and an optional \
+                     length"
+                        .to_owned(),
+                ))
+            }
+        };
+
+        match self.lexer.peek()? {
+            Token::Eof | Token::Eol => Ok(Statement::MidAssignment(target, start, length, *value)),
+            _ => Err(Error::Bad("Unexpected token in MID$ assignment".to_owned())),
+        }
+    }
+
+    /// Parses an `OPEN` statement, which has the form `OPEN file FOR mode AS #channel`.
+    ///
+    /// The statement is turned into a call to the `OPEN` builtin command with the file name, the
+    /// mode as an uppercase string, and the channel number as arguments.
+    fn parse_open(&mut self) -> Result<Statement> {
+        let file = match self.parse_expr_until(None, Some(&Token::For))? {
+            Some(expr) => expr,
+            None => return Err(Error::Bad("Missing file name in OPEN statement".to_owned())),
+        };
+        self.expect_and_consume(Token::For, "Expected FOR after file name in OPEN statement")?;
+
+        let mode = match self.lexer.read()? {
+            Token::Symbol(vref) if vref.ref_type() == VarType::Auto => {
+                vref.name().to_ascii_uppercase()
+            }
+            _ => {
+                return Err(Error::Bad("Expected file mode after FOR in OPEN statement".to_owned()))
+            }
+        };
+
+        match self.lexer.read()? {
+            Token::Symbol(vref)
+                if vref.ref_type() == VarType::Auto && vref.name().eq_ignore_ascii_case("AS") => {}
+            _ => {
+                return Err(Error::Bad("Expected AS after file mode in OPEN statement".to_owned()))
+            }
+        }
+        if *self.lexer.peek()? == Token::Hash {
+            self.lexer.consume_peeked();
+        }
+        let channel = self.parse_channel()?;
+
+        match self.lexer.peek()? {
+            Token::Eof | Token::Eol => Ok(Statement::BuiltinCall(
+                "OPEN".to_owned(),
+                vec![
+                    (Some(file), ArgSep::Long),
+                    (Some(Expr::Text(mode)), ArgSep::Long),
+                    (Some(channel), ArgSep::End),
+                ],
+            )),
+            _ => Err(Error::Bad("Unexpected token in OPEN statement".to_owned())),
+        }
+    }
+
+    /// Parses the arguments to the `PRINT` builtin `name`, given that the next token is `AT`.
+    ///
+    /// If `AT` is followed by a parenthesized position, as in `PRINT AT (col, row); expr`, the
+    /// statement is turned into a call to the `PRINT AT` builtin command with the column and the
+    /// row as its first two arguments followed by the expressions to print.  Otherwise, `AT` is
+    /// just the beginning of the first expression to print, as in `PRINT at + 1`.
+    fn parse_print_at(&mut self, name: &str) -> Result<Statement> {
+        let at = match self.lexer.read()? {
+            Token::Symbol(vref) => vref,
+            _ => unreachable!("Caller must have checked for AT"),
+        };
+        let first = self.parse_expr_from(Some(Expr::Symbol(at)))?;
+
+        let (column, row) = match first {
+            Some(Expr::Call(vref, args)) if vref.name().eq_ignore_ascii_case("AT") => {
+                let mut args = args.into_iter();
+                match (args.next(), args.next(), args.next()) {
+                    (Some(column), Some(row), None) => (column, row),
+                    _ => return Err(Error::Bad("PRINT AT requires a column and a row".to_owned())),
+                }
+            }
+            first => {
+                let sep = match self.lexer.peek()? {
+                    Token::Eof | Token::Eol => {
+                        return Ok(Statement::BuiltinCall(
+                            name.to_owned(),
+                            vec![(first, ArgSep::End)],
+                        ))
+                    }
+                    Token::Semicolon => ArgSep::Short,
+                    Token::Comma => ArgSep::Long,
+                    _ => {
+                        return Err(Error::Bad(
+                            "Expected comma, semicolon, or end of statement".to_owned(),
+                        ))
+                    }
+                };
+                self.lexer.consume_peeked();
+                return self.parse_more_builtin_call_args(name.to_owned(), vec![(first, sep)]);
+            }
+        };
+
+        match self.lexer.peek()? {
+            Token::Eof | Token::Eol => Ok(Statement::BuiltinCall(
+                "PRINT AT".to_owned(),
+                vec![(Some(column), ArgSep::Long), (Some(row), ArgSep::End)],
+            )),
+            Token::Semicolon => {
+                self.lexer.consume_peeked();
+                let args = vec![(Some(column), ArgSep::Long), (Some(row), ArgSep::Short)];
+                self.parse_more_builtin_call_args("PRINT AT".to_owned(), args)
+            }
+            _ => Err(Error::Bad("Expected semicolon after position in PRINT AT".to_owned())),
+        }
+    }
+
     /// Parses an `IF` statement.
     fn parse_if(&mut self) -> Result<Statement> {
         let expr = match self.parse_expr()? {
@@ -477,23 +770,22 @@ impl<'a> Parser<'a> {
         let mut branches = vec![];
         branches.push((expr, self.parse_until(&[Token::Elseif, Token::Else, Token::End])?));
         loop {
-            let peeked = self.lexer.peek()?;
-            match peeked {
-                Token::Elseif => {
-                    self.lexer.consume_peeked();
-                    let expr = match self.parse_expr()? {
-                        Some(expr) => expr,
-                        None => {
-                            return Err(Error::Bad("No expression in ELSEIF statement".to_owned()))
-                        }
-                    };
-                    self.expect_and_consume(Token::Then, "No THEN in ELSEIF statement")?;
-                    self.expect_and_consume(Token::Eol, "Expecting newline after THEN")?;
-                    let stmts2 = self.parse_until(&[Token::Elseif, Token::Else, Token::End])?;
-                    branches.push((expr, stmts2));
-                }
-                _ => break,
+            let n = self.peek_elseif()?;
+            if n == 0 {
+                break;
+            }
+            for _ in 0..n {
+                self.lexer.consume_peeked();
             }
+
+            let expr = match self.parse_expr()? {
+                Some(expr) => expr,
+                None => return Err(Error::Bad("No expression in ELSEIF statement".to_owned())),
+            };
+            self.expect_and_consume(Token::Then, "No THEN in ELSEIF statement")?;
+            self.expect_and_consume(Token::Eol, "Expecting newline after THEN")?;
+            let stmts2 = self.parse_until(&[Token::Elseif, Token::Else, Token::End])?;
+            branches.push((expr, stmts2));
         }
 
         let peeked = self.lexer.peek()?;
@@ -501,11 +793,10 @@ impl<'a> Parser<'a> {
             self.lexer.consume_peeked();
             self.expect_and_consume(Token::Eol, "Expecting newline after ELSE")?;
             let stmts2 = self.parse_until(&[Token::Elseif, Token::Else, Token::End])?;
-            let peeked = self.lexer.peek()?;
-            match *peeked {
-                Token::Elseif => return Err(Error::Bad("Unexpected ELSEIF after ELSE".to_owned())),
-                Token::Else => return Err(Error::Bad("Duplicate ELSE after ELSE".to_owned())),
-                _ => (),
+            if self.peek_elseif()? > 0 {
+                return Err(Error::Bad("Unexpected ELSEIF after ELSE".to_owned()));
+            } else if *self.lexer.peek()? == Token::Else {
+                return Err(Error::Bad("Duplicate ELSE after ELSE".to_owned()));
             }
             branches.push((Expr::Boolean(true), stmts2));
         }
@@ -516,7 +807,8 @@ impl<'a> Parser<'a> {
         Ok(Statement::If(branches))
     }
 
-    /// Advances until the next statement after failing to parse an `IF` statement.
+    /// Advances past the end of an `IF` statement after failing to parse it.  The caller is
+    /// responsible for advancing to the next statement afterwards.
     fn reset_if(&mut self) -> Result<()> {
         loop {
             match self.lexer.peek()? {
@@ -531,46 +823,47 @@ impl<'a> Parser<'a> {
                 }
             }
         }
-        self.reset()
+        Ok(())
     }
 
     /// Extracts the optional `STEP` part of a `FOR` statement, with a default of 1.
-    fn parse_step(&mut self) -> Result<i32> {
+    fn parse_step(&mut self) -> Result<Value> {
         match self.lexer.peek()? {
             Token::Step => self.lexer.consume_peeked(),
-            _ => return Ok(1),
+            _ => return Ok(Value::Integer(1)),
         };
 
-        match self.lexer.peek()? {
-            Token::Integer(i) => {
-                let i = *i;
-                self.lexer.consume_peeked();
-                Ok(i)
-            }
+        let negate = match self.lexer.peek()? {
             Token::Minus => {
                 self.lexer.consume_peeked();
-                match self.lexer.peek()? {
-                    Token::Integer(i) => {
-                        let i = *i;
-                        self.lexer.consume_peeked();
-                        Ok(-i)
-                    }
-                    _ => Err(Error::Bad("STEP needs an integer".to_owned())),
-                }
+                true
             }
-            _ => Err(Error::Bad("STEP needs an integer".to_owned())),
+            _ => false,
+        };
+
+        let step = match self.lexer.peek()? {
+            Token::Double(d) if negate => Value::Double(-d),
+            Token::Double(d) => Value::Double(*d),
+            Token::Integer(i) if negate => Value::Integer(-i),
+            Token::Integer(i) => Value::Integer(*i),
+            _ => return Err(Error::Bad("STEP needs a number".to_owned())),
+        };
+        self.lexer.consume_peeked();
+
+        if step == Value::Integer(0) || step == Value::Double(0.0) {
+            return Err(Error::Bad("Infinite FOR loop; STEP cannot be 0".to_owned()));
         }
+        Ok(step)
     }
 
     /// Parses a `FOR` statement.
     fn parse_for(&mut self) -> Result<Statement> {
         let iterator = match self.lexer.read()? {
             Token::Symbol(iterator) => match iterator.ref_type() {
-                // TODO(jmmv): Should we support doubles in for loops?
-                VarType::Auto | VarType::Integer => iterator,
+                VarType::Auto | VarType::Double | VarType::Integer => iterator,
                 _ => {
                     return Err(Error::Bad(
-                        "Iterator name in FOR statement must be an integer reference".to_owned(),
+                        "Iterator name in FOR statement must be a numeric reference".to_owned(),
                     ))
                 }
             },
@@ -590,30 +883,17 @@ impl<'a> Parser<'a> {
         };
 
         let step = self.parse_step()?;
-        let end_condition = match step.cmp(&0) {
-            Ordering::Greater => {
-                Expr::LessEqual(Box::from(Expr::Symbol(iterator.clone())), Box::from(end))
-            }
-            Ordering::Less => {
-                Expr::GreaterEqual(Box::from(Expr::Symbol(iterator.clone())), Box::from(end))
-            }
-            Ordering::Equal => {
-                return Err(Error::Bad("Infinite FOR loop; STEP cannot be 0".to_owned()))
-            }
-        };
-
-        let next_value =
-            Expr::Add(Box::from(Expr::Symbol(iterator.clone())), Box::from(Expr::Integer(step)));
 
         self.expect_and_consume(Token::Eol, "Expecting newline after FOR")?;
 
         let stmts = self.parse_until(&[Token::Next])?;
         self.expect_and_consume(Token::Next, "FOR without NEXT")?;
 
-        Ok(Statement::For(iterator, start, end_condition, next_value, stmts))
+        Ok(Statement::For(iterator, start, end, step, stmts))
     }
 
-    /// Advances until the next statement after failing to parse a `FOR` statement.
+    /// Advances past the end of a `FOR` statement after failing to parse it.  The caller is
+    /// responsible for advancing to the next statement afterwards.
     fn reset_for(&mut self) -> Result<()> {
         loop {
             match self.lexer.peek()? {
@@ -627,7 +907,7 @@ impl<'a> Parser<'a> {
                 }
             }
         }
-        self.reset()
+        Ok(())
     }
 
     /// Parses a `WHILE` statement.
@@ -638,14 +918,19 @@ impl<'a> Parser<'a> {
         };
         self.expect_and_consume(Token::Eol, "Expecting newline after WHILE")?;
 
-        let stmts = self.parse_until(&[Token::End])?;
-        self.expect_and_consume(Token::End, "WHILE without END WHILE")?;
-        self.expect_and_consume(Token::While, "WHILE without END WHILE")?;
+        let stmts = self.parse_until(&[Token::End, Token::Wend])?;
+        if *self.lexer.peek()? == Token::Wend {
+            self.lexer.consume_peeked();
+        } else {
+            self.expect_and_consume(Token::End, "WHILE without END WHILE")?;
+            self.expect_and_consume(Token::While, "WHILE without END WHILE")?;
+        }
 
         Ok(Statement::While(expr, stmts))
     }
 
-    /// Advances until the next statement after failing to parse a `WHILE` statement.
+    /// Advances past the end of a `WHILE` statement after failing to parse it.  The caller is
+    /// responsible for advancing to the next statement afterwards.
     fn reset_while(&mut self) -> Result<()> {
         loop {
             match self.lexer.peek()? {
@@ -655,19 +940,23 @@ impl<'a> Parser<'a> {
                     self.expect_and_consume(Token::While, "WHILE without END WHILE")?;
                     break;
                 }
+                Token::Wend => {
+                    self.lexer.consume_peeked();
+                    break;
+                }
                 _ => {
                     self.lexer.consume_peeked();
                 }
             }
         }
-        self.reset()
+        Ok(())
     }
 
     /// Extracts the next available statement from the input stream, or `None` if none is available.
     ///
     /// On success, the stream is left in a position where the next statement can be extracted.
     /// On failure, the caller must advance the stream to the next statement by calling `reset`.
-    fn parse_one(&mut self) -> Result<Option<Statement>> {
+    fn parse_one(&mut self) -> Result<Option<(Statement, LineCol)>> {
         loop {
             match self.lexer.peek()? {
                 Token::Eol => {
@@ -677,19 +966,24 @@ impl<'a> Parser<'a> {
                 _ => break,
             }
         }
-        let res = match self.lexer.read()? {
+        let token = self.lexer.read()?;
+        let pos = self.lexer.last_pos();
+        let res: Result<Option<Statement>> = match token {
             Token::Eof => return Ok(None),
             Token::Eol => Ok(None),
             Token::If => {
                 let result = self.parse_if();
                 if result.is_err() {
+                    self.record_error_pos();
                     self.reset_if()?;
                 }
                 Ok(Some(result?))
             }
+            Token::End => Ok(Some(self.parse_end()?)),
             Token::For => {
                 let result = self.parse_for();
                 if result.is_err() {
+                    self.record_error_pos();
                     self.reset_for()?;
                 }
                 Ok(Some(result?))
@@ -699,6 +993,15 @@ impl<'a> Parser<'a> {
                 if *peeked == Token::Equal {
                     self.lexer.consume_peeked();
                     Ok(Some(self.parse_assignment(vref)?))
+                } else if *peeked == Token::LeftParen && is_mid(&vref) {
+                    Ok(Some(self.parse_mid_assignment(vref)?))
+                } else if is_open(&vref) {
+                    Ok(Some(self.parse_open()?))
+                } else if is_print_at(&vref, peeked) {
+                    Ok(Some(self.parse_print_at("PRINT")?))
+                } else if is_line_input(&vref, peeked) {
+                    self.lexer.consume_peeked();
+                    Ok(Some(self.parse_builtin_call_args("LINE INPUT".to_owned())?))
                 } else {
                     Ok(Some(self.parse_builtin_call(vref)?))
                 }
@@ -706,10 +1009,26 @@ impl<'a> Parser<'a> {
             Token::While => {
                 let result = self.parse_while();
                 if result.is_err() {
+                    self.record_error_pos();
                     self.reset_while()?;
                 }
                 Ok(Some(result?))
             }
+            Token::Step => {
+                // STEP is a keyword within FOR loops but, at the beginning of a statement, it is
+                // the name of the command to debug programs one statement at a time.
+                Ok(Some(self.parse_builtin_call_args("STEP".to_owned())?))
+            }
+            Token::QuestionMark => {
+                // The question mark is the classic abbreviation for PRINT, which the machine
+                // resolves via its table of aliases.
+                if is_at(self.lexer.peek()?) {
+                    Ok(Some(self.parse_print_at("?")?))
+                } else {
+                    Ok(Some(self.parse_builtin_call_args("?".to_owned())?))
+                }
+            }
+            Token::Wend => return Err(Error::Bad("WEND without WHILE".to_owned())),
             t => return Err(Error::Bad(format!("Unexpected token {:?} in statement", t))),
         };
 
@@ -721,7 +1040,15 @@ impl<'a> Parser<'a> {
             _ => return Err(Error::Bad("Expected newline".to_owned())),
         };
 
-        res
+        Ok(res?.map(|stmt| (stmt, pos)))
+    }
+
+    /// Records the current position as the location of a syntax error unless a more specific one
+    /// has already been recorded by a nested statement.
+    fn record_error_pos(&mut self) {
+        if self.error_pos.is_none() {
+            self.error_pos = Some(self.lexer.last_pos());
+        }
     }
 
     /// Advances until the next statement after failing to parse a single statement.
@@ -741,16 +1068,59 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
-    /// Extracts the next available statement from the input stream, or `None` if none is available.
+    /// Extracts the next available statement and its position from the input stream, or `None` if
+    /// none is available.
     ///
     /// The stream is always left in a position where the next statement extraction can be tried.
-    pub fn parse(&mut self) -> Result<Option<Statement>> {
+    pub fn parse(&mut self) -> Result<Option<(Statement, LineCol)>> {
         let result = self.parse_one();
         if result.is_err() {
+            self.record_error_pos();
             self.reset()?;
         }
         result
     }
+
+    /// Parses the whole input stream as a single expression.
+    ///
+    /// Fails if the input is empty or if it contains anything else after the expression, such as
+    /// a second statement.
+    pub fn parse_single_expr(&mut self) -> Result<Expr> {
+        let expr = match self.parse_expr()? {
+            Some(expr) => expr,
+            None => return Err(Error::Bad("Missing expression".to_owned())),
+        };
+        match self.lexer.peek()? {
+            Token::Eof => Ok(expr),
+            _ => Err(Error::Bad("Expected a single expression".to_owned())),
+        }
+    }
+
+    /// Extracts all statements from the input stream, recovering from syntax errors at statement
+    /// boundaries instead of stopping at the first one.
+    ///
+    /// Returns the statements that could be parsed and all the errors found, each with the
+    /// position where it was detected.  I/O errors terminate parsing.
+    pub fn parse_all(&mut self) -> Outcome {
+        let mut stmts = vec![];
+        let mut errors = vec![];
+        loop {
+            self.error_pos = None;
+            match self.parse() {
+                Ok(Some(stmt)) => stmts.push(stmt),
+                Ok(None) => break,
+                Err(e) => {
+                    let pos = self.error_pos.unwrap_or_else(|| self.lexer.last_pos());
+                    let is_io = matches!(e, Error::Io(_));
+                    errors.push((pos, e));
+                    if is_io {
+                        break;
+                    }
+                }
+            }
+        }
+        (stmts, errors)
+    }
 }
 
 #[cfg(test)]
@@ -770,12 +1140,17 @@ mod tests {
             if statement.is_none() {
                 break;
             }
-            statements.push(statement.unwrap());
+            statements.push(statement.unwrap().0);
         }
 
         assert_eq!(exp_statements, statements.as_slice());
     }
 
+    /// Syntactic sugar to instantiate a `LineCol` for testing.
+    fn lc(line: usize, col: usize) -> LineCol {
+        LineCol { line, col }
+    }
+
     /// Runs the parser on the given `input` and expects the `err` error message.
     fn do_error_test(input: &str, expected_err: &str) {
         let mut input = input.as_bytes();
@@ -813,6 +1188,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_statement_separators_empty_statements() {
+        do_ok_test(
+            "x=1::y=2",
+            &[
+                Statement::Assignment(VarRef::new("x", VarType::Auto), Expr::Integer(1)),
+                Statement::Assignment(VarRef::new("y", VarType::Auto), Expr::Integer(2)),
+            ],
+        );
+        do_ok_test(
+            ":x=1:",
+            &[Statement::Assignment(VarRef::new("x", VarType::Auto), Expr::Integer(1))],
+        );
+        do_ok_test(
+            "IF a THEN: PRINT 1: END IF",
+            &[Statement::If(vec![(
+                Expr::Symbol(VarRef::new("a", VarType::Auto)),
+                vec![(
+                    Statement::BuiltinCall(
+                        "PRINT".to_owned(),
+                        vec![(Some(Expr::Integer(1)), ArgSep::End)],
+                    ),
+                    lc(1, 12),
+                )],
+            )])],
+        );
+        do_ok_test(
+            "IF a THEN:\n: :END IF:",
+            &[Statement::If(vec![(Expr::Symbol(VarRef::new("a", VarType::Auto)), vec![])])],
+        );
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        do_ok_test(
+            "a = 1 + _\n    2 * _\n    3\nPRINT \"x _\ny\", _\n    a",
+            &[
+                Statement::Assignment(
+                    VarRef::new("a", VarType::Auto),
+                    Expr::Add(
+                        Box::from(Expr::Integer(1)),
+                        Box::from(Expr::Multiply(
+                            Box::from(Expr::Integer(2)),
+                            Box::from(Expr::Integer(3)),
+                        )),
+                    ),
+                ),
+                Statement::BuiltinCall(
+                    "PRINT".to_owned(),
+                    vec![
+                        (Some(Expr::Text("x _\ny".to_owned())), ArgSep::Long),
+                        (Some(Expr::Symbol(VarRef::new("a", VarType::Auto))), ArgSep::End),
+                    ],
+                ),
+            ],
+        );
+    }
+
     #[test]
     fn test_assignments() {
         do_ok_test(
@@ -875,6 +1308,300 @@ mod tests {
         do_error_test("PRINT 3 THEN\n", "Expected comma, semicolon, or end of statement");
     }
 
+    #[test]
+    fn test_builtin_calls_with_channels() {
+        do_ok_test(
+            "PRINT #1, a\nPRINT #n + 1\nCLOSE #1, #2",
+            &[
+                Statement::BuiltinCall(
+                    "PRINT".to_owned(),
+                    vec![
+                        (Some(Expr::Channel(Box::from(Expr::Integer(1)))), ArgSep::Long),
+                        (Some(Expr::Symbol(VarRef::new("a", VarType::Auto))), ArgSep::End),
+                    ],
+                ),
+                Statement::BuiltinCall(
+                    "PRINT".to_owned(),
+                    vec![(
+                        Some(Expr::Channel(Box::from(Expr::Add(
+                            Box::from(Expr::Symbol(VarRef::new("n", VarType::Auto))),
+                            Box::from(Expr::Integer(1)),
+                        )))),
+                        ArgSep::End,
+                    )],
+                ),
+                Statement::BuiltinCall(
+                    "CLOSE".to_owned(),
+                    vec![
+                        (Some(Expr::Channel(Box::from(Expr::Integer(1)))), ArgSep::Long),
+                        (Some(Expr::Channel(Box::from(Expr::Integer(2)))), ArgSep::End),
+                    ],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_builtin_calls_with_channels_errors() {
+        do_error_test("PRINT #\n", "Missing channel number after #");
+        do_error_test("PRINT #, 3\n", "Missing channel number after #");
+        do_error_test("PRINT 3 #1\n", "Unexpected # in expression");
+        do_error_test("PRINT 3 + #1\n", "Unexpected # in expression");
+    }
+
+    #[test]
+    fn test_statement_positions() {
+        let mut input = "A\n  B: C\n\n\tD ' comment\nREM\n   E".as_bytes();
+        let mut parser = Parser::from(&mut input);
+        let mut positions = vec![];
+        while let Some((_, pos)) = parser.parse().expect("Parsing failed") {
+            positions.push(pos);
+        }
+        assert_eq!(vec![lc(1, 1), lc(2, 3), lc(2, 6), lc(4, 2), lc(6, 4)], positions);
+    }
+
+    #[test]
+    fn test_bare_words() {
+        let word = |name| Some(Expr::Symbol(VarRef::new(name, VarType::Auto)));
+        do_ok_test(
+            "CMD lang\nCMD LANG operators\nCMD a b c\nCMD a, b\nCMD a$; 3\nCMD step\nCMD a b, 3",
+            &[
+                Statement::BuiltinCall("CMD".to_owned(), vec![(word("lang"), ArgSep::End)]),
+                Statement::BuiltinCall(
+                    "CMD".to_owned(),
+                    vec![(word("LANG"), ArgSep::Space), (word("operators"), ArgSep::End)],
+                ),
+                Statement::BuiltinCall(
+                    "CMD".to_owned(),
+                    vec![
+                        (word("a"), ArgSep::Space),
+                        (word("b"), ArgSep::Space),
+                        (word("c"), ArgSep::End),
+                    ],
+                ),
+                Statement::BuiltinCall(
+                    "CMD".to_owned(),
+                    vec![(word("a"), ArgSep::Long), (word("b"), ArgSep::End)],
+                ),
+                Statement::BuiltinCall(
+                    "CMD".to_owned(),
+                    vec![
+                        (Some(Expr::Symbol(VarRef::new("a", VarType::Text))), ArgSep::Short),
+                        (Some(Expr::Integer(3)), ArgSep::End),
+                    ],
+                ),
+                Statement::BuiltinCall("CMD".to_owned(), vec![(word("STEP"), ArgSep::End)]),
+                Statement::BuiltinCall(
+                    "CMD".to_owned(),
+                    vec![
+                        (word("a"), ArgSep::Space),
+                        (word("b"), ArgSep::Long),
+                        (Some(Expr::Integer(3)), ArgSep::End),
+                    ],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_bare_words_errors() {
+        do_error_test("CMD a + 1 b", "Unexpected value in expression");
+        do_error_test("CMD a$ b", "Unexpected value in expression");
+        do_error_test("CMD 3 b", "Unexpected value in expression");
+    }
+
+    #[test]
+    fn test_question_mark() {
+        do_ok_test(
+            "?\n? 1; \"a\"\nCMD ?",
+            &[
+                Statement::BuiltinCall("?".to_owned(), vec![]),
+                Statement::BuiltinCall(
+                    "?".to_owned(),
+                    vec![
+                        (Some(Expr::Integer(1)), ArgSep::Short),
+                        (Some(Expr::Text("a".to_owned())), ArgSep::End),
+                    ],
+                ),
+                Statement::BuiltinCall(
+                    "CMD".to_owned(),
+                    vec![(Some(Expr::Symbol(VarRef::new("?", VarType::Auto))), ArgSep::End)],
+                ),
+            ],
+        );
+        do_error_test("a = ?", "Unexpected ? in expression");
+    }
+
+    #[test]
+    fn test_step_command() {
+        do_ok_test(
+            "STEP\nstep 3",
+            &[
+                Statement::BuiltinCall("STEP".to_owned(), vec![]),
+                Statement::BuiltinCall(
+                    "STEP".to_owned(),
+                    vec![(Some(Expr::Integer(3)), ArgSep::End)],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_line_input() {
+        do_ok_test(
+            "LINE INPUT #1, a$\nline input\nLINE input2",
+            &[
+                Statement::BuiltinCall(
+                    "LINE INPUT".to_owned(),
+                    vec![
+                        (Some(Expr::Channel(Box::from(Expr::Integer(1)))), ArgSep::Long),
+                        (Some(Expr::Symbol(VarRef::new("a", VarType::Text))), ArgSep::End),
+                    ],
+                ),
+                Statement::BuiltinCall("LINE INPUT".to_owned(), vec![]),
+                Statement::BuiltinCall(
+                    "LINE".to_owned(),
+                    vec![(Some(Expr::Symbol(VarRef::new("input2", VarType::Auto))), ArgSep::End)],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_print_at() {
+        let at = |column, row, mut rest: Vec<(Option<Expr>, ArgSep)>| {
+            let mut args = vec![(Some(column), ArgSep::Long)];
+            if rest.is_empty() {
+                args.push((Some(row), ArgSep::End));
+            } else {
+                args.push((Some(row), ArgSep::Short));
+                args.append(&mut rest);
+            }
+            Statement::BuiltinCall("PRINT AT".to_owned(), args)
+        };
+        do_ok_test(
+            "PRINT AT (1, 2); \"a\"; b\nprint at(x + 1, 3)\n? AT (0, 0); 5, 6;",
+            &[
+                at(
+                    Expr::Integer(1),
+                    Expr::Integer(2),
+                    vec![
+                        (Some(Expr::Text("a".to_owned())), ArgSep::Short),
+                        (Some(Expr::Symbol(VarRef::new("b", VarType::Auto))), ArgSep::End),
+                    ],
+                ),
+                at(
+                    Expr::Add(
+                        Box::from(Expr::Symbol(VarRef::new("x", VarType::Auto))),
+                        Box::from(Expr::Integer(1)),
+                    ),
+                    Expr::Integer(3),
+                    vec![],
+                ),
+                at(
+                    Expr::Integer(0),
+                    Expr::Integer(0),
+                    vec![
+                        (Some(Expr::Integer(5)), ArgSep::Long),
+                        (Some(Expr::Integer(6)), ArgSep::Short),
+                        (None, ArgSep::End),
+                    ],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_print_at_as_expression() {
+        do_ok_test(
+            "PRINT at\nPRINT AT; 1\nPRINT at + 1",
+            &[
+                Statement::BuiltinCall(
+                    "PRINT".to_owned(),
+                    vec![(Some(Expr::Symbol(VarRef::new("at", VarType::Auto))), ArgSep::End)],
+                ),
+                Statement::BuiltinCall(
+                    "PRINT".to_owned(),
+                    vec![
+                        (Some(Expr::Symbol(VarRef::new("AT", VarType::Auto))), ArgSep::Short),
+                        (Some(Expr::Integer(1)), ArgSep::End),
+                    ],
+                ),
+                Statement::BuiltinCall(
+                    "PRINT".to_owned(),
+                    vec![(
+                        Some(Expr::Add(
+                            Box::from(Expr::Symbol(VarRef::new("at", VarType::Auto))),
+                            Box::from(Expr::Integer(1)),
+                        )),
+                        ArgSep::End,
+                    )],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_print_at_errors() {
+        do_error_test("PRINT AT (1)\n", "PRINT AT requires a column and a row");
+        do_error_test("PRINT AT (1, 2, 3); 4\n", "PRINT AT requires a column and a row");
+        do_error_test("PRINT AT (1, 2), 3\n", "Expected semicolon after position in PRINT AT");
+        do_error_test("PRINT AT 1\n", "Unexpected value in expression");
+    }
+
+    #[test]
+    fn test_open() {
+        let open = |file, mode: &str, channel| {
+            Statement::BuiltinCall(
+                "OPEN".to_owned(),
+                vec![
+                    (Some(file), ArgSep::Long),
+                    (Some(Expr::Text(mode.to_owned())), ArgSep::Long),
+                    (Some(channel), ArgSep::End),
+                ],
+            )
+        };
+        do_ok_test(
+            "OPEN \"out.bas\" FOR OUTPUT AS #1\nopen f$ + \"x\" for output as 2",
+            &[
+                open(Expr::Text("out.bas".to_owned()), "OUTPUT", Expr::Integer(1)),
+                open(
+                    Expr::Add(
+                        Box::from(Expr::Symbol(VarRef::new("f", VarType::Text))),
+                        Box::from(Expr::Text("x".to_owned())),
+                    ),
+                    "OUTPUT",
+                    Expr::Integer(2),
+                ),
+            ],
+        );
+
+        do_ok_test(
+            "OPEN = 3",
+            &[Statement::Assignment(VarRef::new("OPEN", VarType::Auto), Expr::Integer(3))],
+        );
+    }
+
+    #[test]
+    fn test_open_errors() {
+        do_error_test("OPEN\n", "Missing file name in OPEN statement");
+        do_error_test("OPEN \"a\"\n", "Expected FOR after file name in OPEN statement");
+        do_error_test("OPEN \"a\" FOR\n", "Expected file mode after FOR in OPEN statement");
+        do_error_test("OPEN \"a\" FOR 1\n", "Expected file mode after FOR in OPEN statement");
+        do_error_test("OPEN \"a\" FOR OUTPUT\n", "Expected AS after file mode in OPEN statement");
+        do_error_test(
+            "OPEN \"a\" FOR OUTPUT TO #1\n",
+            "Expected AS after file mode in OPEN statement",
+        );
+        do_error_test("OPEN \"a\" FOR OUTPUT AS\n", "Missing channel number after #");
+        do_error_test("OPEN \"a\" FOR OUTPUT AS #1, 2\n", "Unexpected token in OPEN statement");
+        do_error_test("OPEN 1 + FOR OUTPUT AS #1\n", "Unexpected keyword in expression");
+
+        // FOR only terminates the expression for the file name in OPEN.
+        do_error_test("PRINT 1 FOR\n", "Unexpected keyword in expression");
+        do_error_test("a = 1 FOR\n", "Unexpected keyword in expression");
+    }
+
     /// Wrapper around `do_ok_test` to parse an expression.  Given that expressions alone are not
     /// valid statements, we have to put them in a statement to parse them.  In doing so, we can
     /// also put an extra statement after them to ensure we detect their end properly.
@@ -895,6 +1622,48 @@ mod tests {
         do_error_test(&format!("PRINT {}, 1", input), msg)
     }
 
+    #[test]
+    fn test_end() {
+        do_ok_test("END", &[Statement::End(None)]);
+        do_ok_test("END 8", &[Statement::End(Some(Expr::Integer(8)))]);
+        do_ok_test(
+            "END a + 1",
+            &[Statement::End(Some(Expr::Add(
+                Box::from(Expr::Symbol(VarRef::new("a", VarType::Auto))),
+                Box::from(Expr::Integer(1)),
+            )))],
+        );
+    }
+
+    #[test]
+    fn test_end_within_blocks() {
+        do_ok_test(
+            "IF TRUE THEN\nEND\nEND IF",
+            &[Statement::If(vec![(Expr::Boolean(true), vec![(Statement::End(None), lc(2, 1))])])],
+        );
+        do_ok_test(
+            "IF TRUE THEN\nEND 2\nELSE\nEND\nEND IF",
+            &[Statement::If(vec![
+                (Expr::Boolean(true), vec![(Statement::End(Some(Expr::Integer(2))), lc(2, 1))]),
+                (Expr::Boolean(true), vec![(Statement::End(None), lc(4, 1))]),
+            ])],
+        );
+        do_ok_test(
+            "WHILE TRUE\nEND: END\nEND WHILE",
+            &[Statement::While(
+                Expr::Boolean(true),
+                vec![(Statement::End(None), lc(2, 1)), (Statement::End(None), lc(2, 6))],
+            )],
+        );
+    }
+
+    #[test]
+    fn test_end_errors() {
+        do_error_test("END 1, 2", "Unexpected token in END statement");
+        do_error_test("END IF", "END IF without IF");
+        do_error_test("END WHILE", "END WHILE without WHILE");
+    }
+
     #[test]
     fn test_expr_literals() {
         use Expr::*;
@@ -1147,14 +1916,17 @@ mod tests {
             "IF 1 THEN\nPRINT\nEND IF",
             &[Statement::If(vec![(
                 Expr::Integer(1),
-                vec![Statement::BuiltinCall("PRINT".to_owned(), vec![])],
+                vec![(Statement::BuiltinCall("PRINT".to_owned(), vec![]), lc(2, 1))],
             )])],
         );
         do_ok_test(
             "IF 1 THEN\nREM foo\nELSEIF 2 THEN\nPRINT\nEND IF",
             &[Statement::If(vec![
                 (Expr::Integer(1), vec![]),
-                (Expr::Integer(2), vec![Statement::BuiltinCall("PRINT".to_owned(), vec![])]),
+                (
+                    Expr::Integer(2),
+                    vec![(Statement::BuiltinCall("PRINT".to_owned(), vec![]), lc(4, 1))],
+                ),
             ])],
         );
         do_ok_test(
@@ -1162,14 +1934,20 @@ mod tests {
             &[Statement::If(vec![
                 (Expr::Integer(1), vec![]),
                 (Expr::Integer(2), vec![]),
-                (Expr::Boolean(true), vec![Statement::BuiltinCall("PRINT".to_owned(), vec![])]),
+                (
+                    Expr::Boolean(true),
+                    vec![(Statement::BuiltinCall("PRINT".to_owned(), vec![]), lc(5, 1))],
+                ),
             ])],
         );
         do_ok_test(
             "IF 1 THEN\n\n\nELSE\nPRINT\nEND IF",
             &[Statement::If(vec![
                 (Expr::Integer(1), vec![]),
-                (Expr::Boolean(true), vec![Statement::BuiltinCall("PRINT".to_owned(), vec![])]),
+                (
+                    Expr::Boolean(true),
+                    vec![(Statement::BuiltinCall("PRINT".to_owned(), vec![]), lc(5, 1))],
+                ),
             ])],
         );
     }
@@ -1197,35 +1975,62 @@ mod tests {
                 (
                     Expr::Integer(1),
                     vec![
-                        Statement::BuiltinCall("A".to_owned(), vec![]),
-                        Statement::BuiltinCall("B".to_owned(), vec![]),
+                        (Statement::BuiltinCall("A".to_owned(), vec![]), lc(3, 17)),
+                        (Statement::BuiltinCall("B".to_owned(), vec![]), lc(4, 17)),
                     ],
                 ),
                 (
                     Expr::Integer(2),
                     vec![
-                        Statement::BuiltinCall("C".to_owned(), vec![]),
-                        Statement::BuiltinCall("D".to_owned(), vec![]),
+                        (Statement::BuiltinCall("C".to_owned(), vec![]), lc(6, 17)),
+                        (Statement::BuiltinCall("D".to_owned(), vec![]), lc(7, 17)),
                     ],
                 ),
                 (
                     Expr::Integer(3),
                     vec![
-                        Statement::BuiltinCall("E".to_owned(), vec![]),
-                        Statement::BuiltinCall("F".to_owned(), vec![]),
+                        (Statement::BuiltinCall("E".to_owned(), vec![]), lc(9, 17)),
+                        (Statement::BuiltinCall("F".to_owned(), vec![]), lc(10, 17)),
                     ],
                 ),
                 (
                     Expr::Boolean(true),
                     vec![
-                        Statement::BuiltinCall("G".to_owned(), vec![]),
-                        Statement::BuiltinCall("H".to_owned(), vec![]),
+                        (Statement::BuiltinCall("G".to_owned(), vec![]), lc(12, 17)),
+                        (Statement::BuiltinCall("H".to_owned(), vec![]), lc(13, 17)),
                     ],
                 ),
             ])],
         );
     }
 
+    #[test]
+    fn test_if_else_if_is_elseif() {
+        let code = r#"
+            IF 1 THEN
+                A
+            ELSEIF 2 THEN
+                B
+            else if 3 THEN
+                C
+            ELSE
+                D
+            END IF
+        "#;
+        let exp_statements = [Statement::If(vec![
+            (Expr::Integer(1), vec![(Statement::BuiltinCall("A".to_owned(), vec![]), lc(3, 17))]),
+            (Expr::Integer(2), vec![(Statement::BuiltinCall("B".to_owned(), vec![]), lc(5, 17))]),
+            (Expr::Integer(3), vec![(Statement::BuiltinCall("C".to_owned(), vec![]), lc(7, 17))]),
+            (
+                Expr::Boolean(true),
+                vec![(Statement::BuiltinCall("D".to_owned(), vec![]), lc(9, 17))],
+            ),
+        ])];
+        do_ok_test(code, &exp_statements);
+        do_ok_test(&code.replace("else if", "ELSEIF"), &exp_statements);
+        do_ok_test(&code.replace("ELSEIF", "ELSE IF"), &exp_statements);
+    }
+
     #[test]
     fn test_if_nested() {
         let code = r#"
@@ -1240,13 +2045,19 @@ mod tests {
         do_ok_test(
             code,
             &[Statement::If(vec![
-                (Expr::Integer(1), vec![Statement::BuiltinCall("A".to_owned(), vec![])]),
+                (
+                    Expr::Integer(1),
+                    vec![(Statement::BuiltinCall("A".to_owned(), vec![]), lc(3, 17))],
+                ),
                 (
                     Expr::Integer(2),
-                    vec![Statement::If(vec![(
-                        Expr::Integer(3),
-                        vec![Statement::BuiltinCall("B".to_owned(), vec![])],
-                    )])],
+                    vec![(
+                        Statement::If(vec![(
+                            Expr::Integer(3),
+                            vec![(Statement::BuiltinCall("B".to_owned(), vec![]), lc(6, 21))],
+                        )]),
+                        lc(5, 17),
+                    )],
                 ),
             ])],
         );
@@ -1280,6 +2091,9 @@ mod tests {
         do_error_test("IF 1 THEN\nEND IF foo", "Expected newline");
 
         do_error_test("IF 1 THEN\nELSE\nELSEIF 2 THEN\nEND IF", "Unexpected ELSEIF after ELSE");
+        do_error_test("IF 1 THEN\nELSE\nELSE IF 2 THEN\nEND IF", "Unexpected ELSEIF after ELSE");
+        do_error_test("IF 1 THEN\nELSE IF\n", "No expression in ELSEIF statement");
+        do_error_test("IF 1 THEN\nELSE IF 3 + 1\n", "No THEN in ELSEIF statement");
         do_error_test("IF 1 THEN\nELSE\nELSE\nEND IF", "Duplicate ELSE after ELSE");
 
         do_error_test_no_reset("ELSEIF 1 THEN\nEND IF", "Unexpected token Elseif in statement");
@@ -1292,13 +2106,10 @@ mod tests {
         do_ok_test(
             "FOR i = 1 TO 10\nNEXT",
             &[Statement::For(
-                auto_iter.clone(),
+                auto_iter,
                 Expr::Integer(1),
-                Expr::LessEqual(
-                    Box::from(Expr::Symbol(auto_iter.clone())),
-                    Box::from(Expr::Integer(10)),
-                ),
-                Expr::Add(Box::from(Expr::Symbol(auto_iter)), Box::from(Expr::Integer(1))),
+                Expr::Integer(10),
+                Value::Integer(1),
                 vec![],
             )],
         );
@@ -1307,13 +2118,10 @@ mod tests {
         do_ok_test(
             "FOR i% = 1 TO 10\nREM Nothing to do\nNEXT",
             &[Statement::For(
-                typed_iter.clone(),
+                typed_iter,
                 Expr::Integer(1),
-                Expr::LessEqual(
-                    Box::from(Expr::Symbol(typed_iter.clone())),
-                    Box::from(Expr::Integer(10)),
-                ),
-                Expr::Add(Box::from(Expr::Symbol(typed_iter)), Box::from(Expr::Integer(1))),
+                Expr::Integer(10),
+                Value::Integer(1),
                 vec![],
             )],
         );
@@ -1325,13 +2133,13 @@ mod tests {
         do_ok_test(
             "FOR i = 0 TO 5\nA\nB\nNEXT",
             &[Statement::For(
-                iter.clone(),
+                iter,
                 Expr::Integer(0),
-                Expr::LessEqual(Box::from(Expr::Symbol(iter.clone())), Box::from(Expr::Integer(5))),
-                Expr::Add(Box::from(Expr::Symbol(iter)), Box::from(Expr::Integer(1))),
+                Expr::Integer(5),
+                Value::Integer(1),
                 vec![
-                    Statement::BuiltinCall("A".to_owned(), vec![]),
-                    Statement::BuiltinCall("B".to_owned(), vec![]),
+                    (Statement::BuiltinCall("A".to_owned(), vec![]), lc(2, 1)),
+                    (Statement::BuiltinCall("B".to_owned(), vec![]), lc(3, 1)),
                 ],
             )],
         );
@@ -1343,11 +2151,11 @@ mod tests {
         do_ok_test(
             "FOR i = 0 TO 5 STEP 2\nA\nNEXT",
             &[Statement::For(
-                iter.clone(),
+                iter,
                 Expr::Integer(0),
-                Expr::LessEqual(Box::from(Expr::Symbol(iter.clone())), Box::from(Expr::Integer(5))),
-                Expr::Add(Box::from(Expr::Symbol(iter)), Box::from(Expr::Integer(2))),
-                vec![Statement::BuiltinCall("A".to_owned(), vec![])],
+                Expr::Integer(5),
+                Value::Integer(2),
+                vec![(Statement::BuiltinCall("A".to_owned(), vec![]), lc(2, 1))],
             )],
         );
     }
@@ -1358,14 +2166,35 @@ mod tests {
         do_ok_test(
             "FOR i = 5 TO 0 STEP -1\nA\nNEXT",
             &[Statement::For(
-                iter.clone(),
+                iter,
                 Expr::Integer(5),
-                Expr::GreaterEqual(
-                    Box::from(Expr::Symbol(iter.clone())),
-                    Box::from(Expr::Integer(0)),
-                ),
-                Expr::Add(Box::from(Expr::Symbol(iter)), Box::from(Expr::Integer(-1))),
-                vec![Statement::BuiltinCall("A".to_owned(), vec![])],
+                Expr::Integer(0),
+                Value::Integer(-1),
+                vec![(Statement::BuiltinCall("A".to_owned(), vec![]), lc(2, 1))],
+            )],
+        );
+    }
+
+    #[test]
+    fn test_for_doubles() {
+        do_ok_test(
+            "FOR d# = 0.5 TO -1 STEP -0.25\nNEXT",
+            &[Statement::For(
+                VarRef::new("d", VarType::Double),
+                Expr::Double(0.5),
+                Expr::Negate(Box::from(Expr::Integer(1))),
+                Value::Double(-0.25),
+                vec![],
+            )],
+        );
+        do_ok_test(
+            "FOR x = 0 TO 1 STEP 0.1\nNEXT",
+            &[Statement::For(
+                VarRef::new("x", VarType::Auto),
+                Expr::Integer(0),
+                Expr::Integer(1),
+                Value::Double(0.1),
+                vec![],
             )],
         );
     }
@@ -1374,8 +2203,8 @@ mod tests {
     fn test_for_errors() {
         do_error_test("FOR\n", "No iterator name in FOR statement");
         do_error_test("FOR =\n", "No iterator name in FOR statement");
-        do_error_test("FOR a$\n", "Iterator name in FOR statement must be an integer reference");
-        do_error_test("FOR d#\n", "Iterator name in FOR statement must be an integer reference");
+        do_error_test("FOR a$\n", "Iterator name in FOR statement must be a numeric reference");
+        do_error_test("FOR b?\n", "Iterator name in FOR statement must be a numeric reference");
 
         do_error_test("FOR i 3\n", "No equal sign in FOR statement");
         do_error_test("FOR i = TO\n", "No start expression in FOR statement");
@@ -1385,10 +2214,13 @@ mod tests {
         do_error_test("FOR i = 3 TO STEP\n", "No end expression in FOR statement");
         do_error_test("FOR i = 3 TO NEXT\n", "Unexpected keyword in expression");
 
-        do_error_test("FOR i = 3 TO 1 STEP a\n", "STEP needs an integer");
-        do_error_test("FOR i = 3 TO 1 STEP -a\n", "STEP needs an integer");
-        do_error_test("FOR i = 3 TO 1 STEP NEXT\n", "STEP needs an integer");
+        do_error_test("FOR i = 3 TO 1 STEP a\n", "STEP needs a number");
+        do_error_test("FOR i = 3 TO 1 STEP -a\n", "STEP needs a number");
+        do_error_test("FOR i = 3 TO 1 STEP NEXT\n", "STEP needs a number");
+        do_error_test("FOR i = 3 TO 1 STEP \"1\"\n", "STEP needs a number");
         do_error_test("FOR i = 3 TO 1 STEP 0\n", "Infinite FOR loop; STEP cannot be 0");
+        do_error_test("FOR i = 3 TO 1 STEP 0.0\n", "Infinite FOR loop; STEP cannot be 0");
+        do_error_test("FOR i = 3 TO 1 STEP -0.0\n", "Infinite FOR loop; STEP cannot be 0");
 
         do_error_test("FOR i = 3 TO 1", "Expecting newline after FOR");
         do_error_test("FOR i = 1 TO 3 STEP 1", "Expecting newline after FOR");
@@ -1397,6 +2229,44 @@ mod tests {
         do_error_test("FOR i = 0 TO 10\nPRINT i\n", "FOR without NEXT");
     }
 
+    #[test]
+    fn test_mid_assignment() {
+        do_ok_test(
+            "MID$(s$, 2) = \"ab\"\nmid(t, 1 + 2, n) = u$",
+            &[
+                Statement::MidAssignment(
+                    VarRef::new("s", VarType::Text),
+                    Expr::Integer(2),
+                    None,
+                    Expr::Text("ab".to_owned()),
+                ),
+                Statement::MidAssignment(
+                    VarRef::new("t", VarType::Auto),
+                    Expr::Add(Box::from(Expr::Integer(1)), Box::from(Expr::Integer(2))),
+                    Some(Expr::Symbol(VarRef::new("n", VarType::Auto))),
+                    Expr::Symbol(VarRef::new("u", VarType::Text)),
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_mid_assignment_errors() {
+        do_error_test("MID$(s$, 1)", "Expected assignment to MID$");
+        do_error_test("MID$(s$, 1) + 3", "Expected assignment to MID$");
+        do_error_test("MID$(s$, 1) AND 3 = \"a\"", "Expected assignment to MID$");
+        do_error_test("MID$(s$, 1)\n", "Expected assignment to MID$");
+
+        let message =
+            "MID$ assignment requires a variable, a start position and an optional length";
+        do_error_test("MID$() = \"a\"", message);
+        do_error_test("MID$(s$) = \"a\"", message);
+        do_error_test("MID$(\"abc\", 1) = \"a\"", message);
+        do_error_test("MID$(s$, 1, 2, 3) = \"a\"", message);
+
+        do_error_test("MID$(s$, 1) = \"a\", 3", "Unexpected token in MID$ assignment");
+    }
+
     #[test]
     fn test_while_empty() {
         do_ok_test(
@@ -1419,8 +2289,8 @@ mod tests {
             &[Statement::While(
                 Expr::Boolean(true),
                 vec![
-                    Statement::BuiltinCall("A".to_owned(), vec![]),
-                    Statement::BuiltinCall("B".to_owned(), vec![]),
+                    (Statement::BuiltinCall("A".to_owned(), vec![]), lc(2, 1)),
+                    (Statement::BuiltinCall("B".to_owned(), vec![]), lc(3, 1)),
                 ],
             )],
         );
@@ -1442,12 +2312,15 @@ mod tests {
             &[Statement::While(
                 Expr::Boolean(true),
                 vec![
-                    Statement::BuiltinCall("A".to_owned(), vec![]),
-                    Statement::While(
-                        Expr::Boolean(false),
-                        vec![Statement::BuiltinCall("B".to_owned(), vec![])],
+                    (Statement::BuiltinCall("A".to_owned(), vec![]), lc(3, 17)),
+                    (
+                        Statement::While(
+                            Expr::Boolean(false),
+                            vec![(Statement::BuiltinCall("B".to_owned(), vec![]), lc(5, 21))],
+                        ),
+                        lc(4, 17),
                     ),
-                    Statement::BuiltinCall("C".to_owned(), vec![]),
+                    (Statement::BuiltinCall("C".to_owned(), vec![]), lc(7, 17)),
                 ],
             )],
         );
@@ -1462,5 +2335,124 @@ mod tests {
         do_error_test("WHILE TRUE\nEND\n", "WHILE without END WHILE");
 
         do_error_test("WHILE ,\nEND WHILE", "No expression in WHILE statement");
+        do_error_test("WHILE ,\nWEND", "No expression in WHILE statement");
+    }
+
+    #[test]
+    fn test_while_wend() {
+        let code = r#"
+            WHILE TRUE
+                A
+                WHILE FALSE
+                    B
+                WEND
+                WHILE FALSE
+                END WHILE
+            wend
+        "#;
+        do_ok_test(
+            code,
+            &[Statement::While(
+                Expr::Boolean(true),
+                vec![
+                    (Statement::BuiltinCall("A".to_owned(), vec![]), lc(3, 17)),
+                    (
+                        Statement::While(
+                            Expr::Boolean(false),
+                            vec![(Statement::BuiltinCall("B".to_owned(), vec![]), lc(5, 21))],
+                        ),
+                        lc(4, 17),
+                    ),
+                    (Statement::While(Expr::Boolean(false), vec![]), lc(7, 17)),
+                ],
+            )],
+        );
+    }
+
+    #[test]
+    fn test_while_wend_errors() {
+        do_error_test("WEND", "WEND without WHILE");
+        do_error_test("WHILE TRUE\nWEND WHILE", "Expected newline");
+        do_error_test("WHILE TRUE\nEND IF", "WHILE without END WHILE");
+        do_error_test("IF TRUE THEN\nWEND\nEND IF", "WEND without WHILE");
+        do_error_test("FOR i = 1 TO 10\nWEND\nNEXT", "WEND without WHILE");
+    }
+
+    #[test]
+    fn test_parse_all_reports_multiple_errors() {
+        let mut input =
+            "a = 1\nb = (2\nPRINT a\nc = 3 +\nIF a THEN\n  d = )\nEND IF\ne = 5\nWEND".as_bytes();
+        let mut parser = Parser::from(&mut input);
+        let (stmts, errors) = parser.parse_all();
+        let errors: Vec<(LineCol, String)> =
+            errors.into_iter().map(|(pos, e)| (pos, format!("{}", e))).collect();
+        assert_eq!(
+            vec![
+                (lc(2, 6), "Unbalanced parenthesis".to_owned()),
+                (lc(4, 7), "Not enough values to apply operator".to_owned()),
+                (lc(6, 7), "Unbalanced parenthesis".to_owned()),
+                (lc(9, 1), "WEND without WHILE".to_owned()),
+            ],
+            errors
+        );
+        assert_eq!(
+            vec![
+                (
+                    Statement::Assignment(VarRef::new("a", VarType::Auto), Expr::Integer(1)),
+                    lc(1, 1)
+                ),
+                (
+                    Statement::BuiltinCall(
+                        "PRINT".to_owned(),
+                        vec![(Some(Expr::Symbol(VarRef::new("a", VarType::Auto))), ArgSep::End)]
+                    ),
+                    lc(3, 1)
+                ),
+                (
+                    Statement::Assignment(VarRef::new("e", VarType::Auto), Expr::Integer(5)),
+                    lc(8, 1)
+                ),
+            ],
+            stmts
+        );
+    }
+
+    #[test]
+    fn test_parse_all_without_errors() {
+        let mut input = "a = 1\n\nb = 2".as_bytes();
+        let mut parser = Parser::from(&mut input);
+        let (stmts, errors) = parser.parse_all();
+        assert_eq!(2, stmts.len());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_single_expr() {
+        let mut input = "a + 1".as_bytes();
+        assert_eq!(
+            Expr::Add(
+                Box::from(Expr::Symbol(VarRef::new("a", VarType::Auto))),
+                Box::from(Expr::Integer(1))
+            ),
+            Parser::from(&mut input).parse_single_expr().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_single_expr_errors() {
+        for (exp_error, input) in &[
+            ("Missing expression", ""),
+            ("Expected a single expression", "1: PRINT 2"),
+            ("Expected a single expression", "1, 2"),
+            ("Expected a single expression", "1\n2"),
+            ("Unexpected keyword in expression", "IF a THEN b"),
+            ("Not enough values to apply operator", "1 +"),
+        ] {
+            let mut input = input.as_bytes();
+            match Parser::from(&mut input).parse_single_expr() {
+                Err(e) => assert_eq!(*exp_error, format!("{}", e)),
+                Ok(expr) => panic!("Expected error but got {:?}", expr),
+            }
+        }
     }
 }
@@ -37,7 +37,7 @@ fn safe_main() -> i32 {
     };
 
     let console = Rc::from(RefCell::from(TerminalConsole::from_stdio().unwrap()));
-    let mut machine = endbasic_std::scripting_machine(console);
+    let mut machine = endbasic_std::scripting_machine(console, vec![]);
 
     let mut input = match fs::File::open(path) {
         Ok(file) => file,
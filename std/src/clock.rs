@@ -0,0 +1,503 @@
+// EndBASIC
+// Copyright 2020 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Time-based pacing of program execution.
+
+use crate::console::{blank_answer, read_line, ClearType, Console, Key, Palette, Position};
+use async_trait::async_trait;
+use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
+use endbasic_core::eval::{CallableMetadata, CallableMetadataBuilder};
+use endbasic_core::exec::{self, Command, Machine};
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Largest frame rate accepted by `WAIT`.
+const MAX_FPS: i32 = 1000;
+
+/// Time to wait between checks for key presses while waiting for input with a deadline.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Abstract operations to measure time and to wait for it to pass.
+#[async_trait(?Send)]
+pub trait Clock {
+    /// Returns the time elapsed since an arbitrary but fixed point in the past.
+    fn now(&self) -> Duration;
+
+    /// Waits for `duration` to pass, yielding control to the host environment if it needs it.
+    async fn sleep(&self, duration: Duration);
+}
+
+/// A clock backed by the operating system that sleeps by blocking the current thread.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct SystemClock {
+    /// Instant against which all times are measured.
+    origin: std::time::Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self { origin: std::time::Instant::now() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait(?Send)]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration)
+    }
+}
+
+/// The `WAIT` command.
+pub struct WaitCommand {
+    metadata: CallableMetadata,
+    clock: Rc<dyn Clock>,
+    last_frame: Cell<Option<Duration>>,
+}
+
+impl WaitCommand {
+    /// Creates a new `WAIT` command that paces execution using `clock`.
+    pub fn new(clock: Rc<dyn Clock>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("WAIT", VarType::Void)
+                .with_syntax("fps%")
+                .with_category("Interpreter manipulation")
+                .with_description(
+                    "Waits for the next frame to limit execution to a frame rate.
+Waits until 1/fps% seconds have passed since the previous call to WAIT returned, which makes \
+loops that call WAIT once per iteration run at most fps% times per second without busy-waiting.  \
+The first call returns immediately.  fps% must be between 1 and 1000.",
+                )
+                .build(),
+            clock,
+            last_frame: Cell::new(None),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for WaitCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let fps = match args {
+            [(Some(expr), ArgSep::End)] => {
+                match expr.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Integer(i) if (1..=MAX_FPS).contains(&i) => i as u32,
+                    Value::Integer(_) => {
+                        return exec::new_usage_error(format!(
+                            "Frame rate must be between 1 and {}",
+                            MAX_FPS
+                        ))
+                    }
+                    _ => return exec::new_usage_error("Frame rate must be an integer"),
+                }
+            }
+            _ => return exec::new_usage_error("WAIT takes one argument"),
+        };
+
+        let interval = Duration::from_secs(1) / fps;
+        let remaining = match self.last_frame.get() {
+            Some(last_frame) => interval.saturating_sub(self.clock.now() - last_frame),
+            None => Duration::default(),
+        };
+        self.clock.sleep(remaining).await;
+        self.last_frame.set(Some(self.clock.now()));
+        Ok(())
+    }
+}
+
+/// Console that forwards all operations to another `console` except for `read_key`, which gives
+/// up with an `io::ErrorKind::TimedOut` error once `clock` reaches `deadline`.
+struct DeadlineConsole<'a> {
+    console: &'a mut dyn Console,
+    clock: &'a dyn Clock,
+    deadline: Duration,
+}
+
+#[async_trait(?Send)]
+impl Console for DeadlineConsole<'_> {
+    fn clear(&mut self, how: ClearType) -> io::Result<()> {
+        self.console.clear(how)
+    }
+
+    fn color(&mut self, fg: Option<u8>, bg: Option<u8>) -> io::Result<()> {
+        self.console.color(fg, bg)
+    }
+
+    fn enter_alt(&mut self) -> io::Result<()> {
+        self.console.enter_alt()
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        self.console.hide_cursor()
+    }
+
+    fn is_interactive(&self) -> bool {
+        self.console.is_interactive()
+    }
+
+    fn leave_alt(&mut self) -> io::Result<()> {
+        self.console.leave_alt()
+    }
+
+    fn locate(&mut self, pos: Position) -> io::Result<()> {
+        self.console.locate(pos)
+    }
+
+    fn move_within_line(&mut self, off: i16) -> io::Result<()> {
+        self.console.move_within_line(off)
+    }
+
+    fn poll_key(&mut self) -> io::Result<Option<Key>> {
+        self.console.poll_key()
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        self.console.print(text)
+    }
+
+    async fn read_key(&mut self) -> io::Result<Key> {
+        loop {
+            if let Some(key) = self.console.poll_key()? {
+                return Ok(key);
+            }
+            let now = self.clock.now();
+            if now >= self.deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "Timed out waiting for input"));
+            }
+            self.clock.sleep(POLL_INTERVAL.min(self.deadline - now)).await;
+        }
+    }
+
+    fn set_palette(&mut self, palette: Palette) -> io::Result<()> {
+        self.console.set_palette(palette)
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        self.console.show_cursor()
+    }
+
+    fn size(&self) -> io::Result<Position> {
+        self.console.size()
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.console.write(bytes)
+    }
+}
+
+/// The `TIMEDINPUT` command.
+pub struct TimedInputCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+    clock: Rc<dyn Clock>,
+}
+
+impl TimedInputCommand {
+    /// Creates a new `TIMEDINPUT` command that uses `console` to gather user input and `clock` to
+    /// give up waiting for it.
+    pub fn new(console: Rc<RefCell<dyn Console>>, clock: Rc<dyn Clock>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("TIMEDINPUT", VarType::Void)
+                .with_syntax("seconds, [\"prompt\"] <;|,> variableref, ok?ref")
+                .with_category("Console manipulation")
+                .with_description(
+                    "Obtains user input from the console, giving up after a number of seconds.
+This behaves like INPUT but stops waiting once the given number of seconds, which may be \
+fractional, has passed.  The ok?ref variable is set to true if the user entered a line in time \
+and to false otherwise.  On timeout, variableref is left unchanged and anything the user had \
+typed is discarded.",
+                )
+                .build(),
+            console,
+            clock,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for TimedInputCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        if args.len() != 4 {
+            return exec::new_usage_error("TIMEDINPUT requires four arguments");
+        }
+        let timeout = match &args[0] {
+            (Some(timeout), ArgSep::Long) => timeout,
+            _ => return exec::new_usage_error("TIMEDINPUT requires a timeout"),
+        };
+        let (prompt, prompt_sep) = (&args[1].0, &args[1].1);
+        let vref = match &args[2] {
+            (Some(Expr::Symbol(vref)), ArgSep::Long) => vref,
+            _ => return exec::new_usage_error("TIMEDINPUT requires a variable reference"),
+        };
+        let ok_vref = match &args[3].0 {
+            Some(Expr::Symbol(vref)) => vref,
+            _ => return exec::new_usage_error("TIMEDINPUT requires a result variable reference"),
+        };
+
+        let timeout = match timeout.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Double(d) if d >= 0.0 => Duration::from_secs_f64(d),
+            Value::Integer(i) if i >= 0 => Duration::from_secs(i as u64),
+            Value::Double(_) | Value::Integer(_) => {
+                return exec::new_usage_error("Timeout cannot be negative")
+            }
+            _ => return exec::new_usage_error("Timeout must be a number"),
+        };
+
+        let mut prompt = match prompt {
+            Some(e) => match e.eval(machine.get_vars(), machine.get_functions())? {
+                Value::Text(t) => t,
+                _ => return exec::new_usage_error("TIMEDINPUT prompt must be a string"),
+            },
+            None => "".to_owned(),
+        };
+        if let ArgSep::Short = prompt_sep {
+            prompt += "? ";
+        }
+
+        // The console must be released before assigning the variables because watching them may
+        // print to the console.
+        let value = {
+            let mut console = self.console.borrow_mut();
+            let mut console = DeadlineConsole {
+                console: &mut *console,
+                clock: &*self.clock,
+                deadline: self.clock.now() + timeout,
+            };
+            let mut previous_answer = String::new();
+            loop {
+                match read_line(&mut console, &prompt, &previous_answer, None, None).await {
+                    Ok(answer) if answer.trim_end().is_empty() => break Some(blank_answer(vref)),
+                    Ok(answer) => match Value::parse_as(vref.ref_type(), answer.trim_end()) {
+                        Ok(value) => break Some(value),
+                        Err(e) => {
+                            console.print(&format!("Retry input: {}", e))?;
+                            previous_answer = answer;
+                        }
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                        console.print(&format!("Retry input: {}", e))?
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                        if console.is_interactive() {
+                            console.write(b"\r\n")?;
+                        }
+                        break None;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        };
+        let answered = value.is_some();
+        if let Some(value) = value {
+            machine.set_var(vref, value)?;
+        }
+        machine.set_var(ok_vref, Value::Boolean(answered))?;
+        Ok(())
+    }
+}
+
+/// Adds all time-related commands to the `machine`, using `clock` to measure and wait for time and
+/// `console` to gather user input.
+pub fn add_all(
+    machine: &mut Machine,
+    console: Rc<RefCell<dyn Console>>,
+    clock: Rc<dyn Clock>,
+) -> exec::Result<()> {
+    machine.add_command(TimedInputCommand::new(console, clock.clone()))?;
+    machine.add_command(WaitCommand::new(clock))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::*;
+
+    /// Clock whose time only advances when sleeping or when explicitly told to.
+    #[derive(Default)]
+    struct FakeClock {
+        now: Cell<Duration>,
+        sleeps: RefCell<Vec<Duration>>,
+    }
+
+    impl FakeClock {
+        /// Advances the time by `duration` without recording a sleep, as if work was done.
+        fn advance(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration);
+        }
+
+        /// Returns the durations of all the sleeps requested so far.
+        fn sleeps(&self) -> Vec<Duration> {
+            self.sleeps.borrow().clone()
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl Clock for FakeClock {
+        fn now(&self) -> Duration {
+            self.now.get()
+        }
+
+        async fn sleep(&self, duration: Duration) {
+            self.sleeps.borrow_mut().push(duration);
+            self.now.set(self.now.get() + duration);
+        }
+    }
+
+    /// Creates a tester with the `WAIT` command backed by `clock`.
+    fn wait_tester(clock: Rc<FakeClock>) -> Tester {
+        Tester::from(Machine::default()).add_command(WaitCommand::new(clock))
+    }
+
+    #[test]
+    fn test_wait_first_call_does_not_wait() {
+        let clock = Rc::from(FakeClock::default());
+        wait_tester(clock.clone()).run("WAIT 1").check();
+        assert_eq!(vec![Duration::default()], clock.sleeps());
+        assert_eq!(Duration::default(), clock.now());
+    }
+
+    #[test]
+    fn test_wait_spaces_consecutive_calls() {
+        let clock = Rc::from(FakeClock::default());
+        let mut t = wait_tester(clock.clone());
+        t.run("WAIT 20").check();
+        t.run("WAIT 20").check();
+        t.run("WAIT 20: WAIT 20").check();
+        clock.advance(Duration::from_millis(10));
+        t.run("WAIT 20").check();
+
+        assert_eq!(
+            vec![
+                Duration::default(),
+                Duration::from_millis(50),
+                Duration::from_millis(50),
+                Duration::from_millis(50),
+                Duration::from_millis(40),
+            ],
+            clock.sleeps()
+        );
+        assert_eq!(Duration::from_millis(200), clock.now());
+    }
+
+    #[test]
+    fn test_wait_does_not_wait_if_late() {
+        let clock = Rc::from(FakeClock::default());
+        let mut t = wait_tester(clock.clone());
+        t.run("WAIT 10").check();
+        clock.advance(Duration::from_millis(150));
+        t.run("WAIT 10").check();
+        assert_eq!(vec![Duration::default(), Duration::default()], clock.sleeps());
+        assert_eq!(Duration::from_millis(150), clock.now());
+    }
+
+    /// Creates a tester with the `TIMEDINPUT` command backed by `clock`.
+    fn timed_input_tester(clock: Rc<FakeClock>) -> Tester {
+        let t = Tester::from(Machine::default());
+        let console = t.get_console();
+        t.add_command(TimedInputCommand::new(console, clock))
+    }
+
+    #[test]
+    fn test_timedinput_ok() {
+        let clock = Rc::from(FakeClock::default());
+        let mut t = timed_input_tester(clock.clone());
+        t.get_console().borrow_mut().add_input_chars("42\n");
+        t.run("TIMEDINPUT 5, \"Number\"; n%, ok?")
+            .expect_var("n", 42)
+            .expect_var("ok", true)
+            .check();
+        assert_eq!(Duration::default(), clock.now());
+
+        t.get_console().borrow_mut().add_input_chars("\n");
+        t.run("TIMEDINPUT 5, \"\", n, ok")
+            .expect_var("n", Value::Empty)
+            .expect_var("ok", true)
+            .check();
+    }
+
+    #[test]
+    fn test_timedinput_retries_until_timeout() {
+        let clock = Rc::from(FakeClock::default());
+        let mut t = timed_input_tester(clock.clone());
+        t.get_console().borrow_mut().add_input_chars("x\n");
+        t.run("n% = 3: TIMEDINPUT 0.5, , n%, ok")
+            .expect_prints(["Retry input: Invalid integer literal x"])
+            .expect_var("n", 3)
+            .expect_var("ok", false)
+            .check();
+        assert_eq!(Duration::from_millis(500), clock.now());
+    }
+
+    #[test]
+    fn test_timedinput_timeout() {
+        let clock = Rc::from(FakeClock::default());
+        let mut t = timed_input_tester(clock.clone());
+        t.get_console().borrow_mut().add_input_chars("12");
+        t.run("n = 7: TIMEDINPUT 2, \"\", n, ok")
+            .expect_var("n", 7)
+            .expect_var("ok", false)
+            .check();
+        assert_eq!(Duration::from_secs(2), clock.now());
+
+        t.get_console().borrow_mut().add_input_chars("5\n");
+        t.run("TIMEDINPUT 0, \"\", n, ok").expect_var("n", 5).expect_var("ok", true).check();
+        assert_eq!(Duration::from_secs(2), clock.now());
+    }
+
+    #[test]
+    fn test_timedinput_errors() {
+        check_stmt_err("TIMEDINPUT requires four arguments", "TIMEDINPUT 1, \"\", a");
+        check_stmt_err("TIMEDINPUT requires a timeout", "TIMEDINPUT , \"\", a, ok");
+        check_stmt_err("TIMEDINPUT requires a timeout", "TIMEDINPUT 1; \"\", a, ok");
+        check_stmt_err("TIMEDINPUT requires a variable reference", "TIMEDINPUT 1, \"\", 3, ok");
+        check_stmt_err("TIMEDINPUT requires a variable reference", "TIMEDINPUT 1, \"\", a; ok");
+        check_stmt_err("TIMEDINPUT requires a result variable reference", "TIMEDINPUT 1, , a, 1");
+        check_stmt_err("Timeout cannot be negative", "TIMEDINPUT -1, , a, ok");
+        check_stmt_err("Timeout must be a number", "TIMEDINPUT \"1\", , a, ok");
+        check_stmt_err("TIMEDINPUT prompt must be a string", "TIMEDINPUT 1, 2, a, ok");
+    }
+
+    #[test]
+    fn test_wait_errors() {
+        check_stmt_err("WAIT takes one argument", "WAIT");
+        check_stmt_err("WAIT takes one argument", "WAIT 1, 2");
+        check_stmt_err("Frame rate must be between 1 and 1000", "WAIT 0");
+        check_stmt_err("Frame rate must be between 1 and 1000", "WAIT 1001");
+        check_stmt_err("Frame rate must be an integer", "WAIT 2.5");
+    }
+}
@@ -0,0 +1,132 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Completion of command and file names for the interactive interpreter.
+
+use crate::console::Completer;
+use crate::help::compute_callables;
+use crate::store::Store;
+use endbasic_core::exec::Machine;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Commands that take a file name as their argument.
+const FILE_COMMANDS: &[&str] = &["LOAD", "SAVE"];
+
+/// Returns true if the `context` that precedes a word indicates that the word is the file name
+/// argument of a command that takes one.
+fn is_file_argument(context: &str) -> bool {
+    let statement = context.rsplit(':').next().unwrap_or(context).trim();
+    match statement.strip_suffix('"') {
+        Some(command) => {
+            let command = command.trim_end();
+            FILE_COMMANDS.iter().any(|name| command.eq_ignore_ascii_case(name))
+        }
+        None => false,
+    }
+}
+
+/// Returns true if `name` starts with `prefix`, ignoring case differences.
+fn has_prefix(name: &str, prefix: &str) -> bool {
+    name.len() >= prefix.len()
+        && name.is_char_boundary(prefix.len())
+        && name[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// Completes the names of the callables known to a machine and, when in argument position of the
+/// commands that take a file name, the names of the files in a store.
+pub struct MachineCompleter<'a> {
+    machine: &'a Machine,
+    store: Rc<RefCell<dyn Store>>,
+}
+
+impl<'a> MachineCompleter<'a> {
+    /// Creates a new completer for the callables in `machine` and the files in `store`.
+    pub fn new(machine: &'a Machine, store: Rc<RefCell<dyn Store>>) -> Self {
+        Self { machine, store }
+    }
+}
+
+impl<'a> Completer for MachineCompleter<'a> {
+    fn complete(&self, context: &str, word: &str) -> Vec<String> {
+        if is_file_argument(context) {
+            // Errors while listing the files just mean that there is nothing to offer.
+            match self.store.borrow().enumerate() {
+                Ok(entries) => {
+                    entries.keys().filter(|name| has_prefix(name, word)).cloned().collect()
+                }
+                Err(_) => vec![],
+            }
+        } else if word.is_empty() {
+            vec![]
+        } else {
+            let callables =
+                compute_callables(self.machine.get_commands(), self.machine.get_functions());
+            // Multi-word names, like `LINE INPUT` or `PRINT AT`, are not single words and thus
+            // cannot be offered as completions.
+            let mut names: Vec<String> = callables
+                .keys()
+                .filter(|name| !name.contains(' ') && has_prefix(name, word))
+                .map(|name| (*name).to_owned())
+                .collect();
+            names.sort();
+            names
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::*;
+
+    /// Runs the completer on a machine with all standard callables and the given `files`.
+    fn complete(files: &[&str], context: &str, word: &str) -> Vec<String> {
+        let mut tester = Tester::default();
+        for file in files {
+            tester = tester.write_file(file, "");
+        }
+        let store = tester.get_store();
+        let completer = MachineCompleter::new(tester.get_machine(), store);
+        completer.complete(context, word)
+    }
+
+    #[test]
+    fn test_complete_callables() {
+        assert_eq!(vec!["PRINT"], complete(&[], "", "pri"));
+        assert_eq!(vec!["LEFT", "LEN", "LEX"], complete(&[], "a = ", "LE"));
+        assert_eq!(vec!["LOCATE"], complete(&[], "PRINT 1: ", "loc"));
+        assert_eq!(vec!["LINE", "LIST"], complete(&[], "", "li"));
+        assert!(complete(&[], "", "xyz").is_empty());
+        assert!(complete(&[], "", "").is_empty());
+    }
+
+    #[test]
+    fn test_complete_files() {
+        let files = &["first.bas", "foo.bas", "second.bas"];
+        assert_eq!(vec!["first.bas", "foo.bas"], complete(files, "LOAD \"", "f"));
+        assert_eq!(vec!["second.bas"], complete(files, "  save  \"", "SE"));
+        assert_eq!(vec!["first.bas"], complete(files, "a = 1: LOAD \"", "fi"));
+        assert_eq!(files.to_vec(), complete(files, "LOAD \"", ""));
+        assert!(complete(files, "LOAD \"", "x").is_empty());
+    }
+
+    #[test]
+    fn test_complete_files_only_for_file_commands() {
+        let files = &["print.bas"];
+        assert_eq!(vec!["PRINT"], complete(files, "", "pri"));
+        assert_eq!(vec!["PRINT"], complete(files, "LOAD ", "pri"));
+    }
+}
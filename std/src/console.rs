@@ -15,11 +15,15 @@
 
 //! Console representation and manipulation.
 
+use crate::files::{eval_channel, Channels};
 use async_trait::async_trait;
-use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
-use endbasic_core::eval::{CallableMetadata, CallableMetadataBuilder};
+use endbasic_core::ast::{ArgSep, Expr, Value, VarRef, VarType};
+use endbasic_core::eval::{
+    CallableMetadata, CallableMetadataBuilder, Function, FunctionError, FunctionResult,
+};
 use endbasic_core::exec::{self, Command, Machine};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
 use std::io;
 use std::rc::Rc;
 
@@ -62,6 +66,9 @@ pub enum Key {
     /// Accepts the current line.
     NewLine,
 
+    /// The tab key.
+    Tab,
+
     /// An unknown character or sequence. The text describes what went wrong.
     Unknown(String),
 }
@@ -89,6 +96,16 @@ pub struct Position {
     pub column: usize,
 }
 
+/// Represents the position of a pixel in the graphics surface of a console.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PixelPos {
+    /// The horizontal coordinate, starting from zero at the left.
+    pub x: usize,
+
+    /// The vertical coordinate, starting from zero at the top.
+    pub y: usize,
+}
+
 impl std::ops::Sub for Position {
     type Output = Self;
 
@@ -97,6 +114,58 @@ impl std::ops::Sub for Position {
     }
 }
 
+/// Representation of a color as displayed by a console.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DisplayColor {
+    /// The default color of the console.
+    Default,
+
+    /// A color number in the console's native palette.
+    Native(u8),
+
+    /// An explicit color given by its red, green and blue components.
+    Rgb(u8, u8, u8),
+}
+
+/// Mapping of color numbers, as used by `COLOR`, to the RGB values that a console displays.
+///
+/// Color numbers without a mapping are displayed using the console's native palette, so the
+/// default (empty) palette leaves all colors untouched.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Palette {
+    colors: HashMap<u8, (u8, u8, u8)>,
+}
+
+impl Palette {
+    /// Creates a palette that maps the color numbers starting at zero to the given `rgbs`, in
+    /// order.  This is useful to replace the 16 standard colors with a custom theme.
+    pub fn from_rgbs(rgbs: &[(u8, u8, u8)]) -> Self {
+        let colors = rgbs.iter().take(usize::from(u8::MAX) + 1).enumerate();
+        Self { colors: colors.map(|(i, rgb)| (i as u8, *rgb)).collect() }
+    }
+
+    /// Maps the color number `color` to the `rgb` triplet.
+    pub fn set(&mut self, color: u8, rgb: (u8, u8, u8)) {
+        self.colors.insert(color, rgb);
+    }
+
+    /// Resolves the optional color number `color` into the color to display.
+    pub fn resolve(&self, color: Option<u8>) -> DisplayColor {
+        match color {
+            None => DisplayColor::Default,
+            Some(color) => match self.colors.get(&color) {
+                Some((r, g, b)) => DisplayColor::Rgb(*r, *g, *b),
+                None => DisplayColor::Native(color),
+            },
+        }
+    }
+}
+
+/// Returns the error reported by consoles that do not have a graphics surface.
+fn no_graphics_error() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "Graphics are not supported by this console")
+}
+
 /// Hooks to implement the commands that manipulate the console.
 #[async_trait(?Send)]
 pub trait Console {
@@ -106,12 +175,49 @@ pub trait Console {
     /// Sets the console's foreground and background colors to `fg` and `bg`.
     ///
     /// If any of the colors is `None`, the color is left unchanged.
+    ///
+    /// The color numbers are resolved through the palette set by `set_palette`, if any.
     fn color(&mut self, fg: Option<u8>, bg: Option<u8>) -> io::Result<()>;
 
+    /// Returns the text currently visible on the console, one string per row.
+    ///
+    /// Trailing whitespace is removed from every row and trailing empty rows are omitted.  Consoles
+    /// that do not keep track of what they display, such as those redirected to a file, return an
+    /// error.
+    fn contents(&self) -> io::Result<Vec<String>> {
+        Err(io::Error::new(io::ErrorKind::Other, "Console contents are not available"))
+    }
+
+    /// Draws a straight line between the `from` and `to` pixels, both included, using the color
+    /// number `color` or the current foreground color if `None`.
+    ///
+    /// Both pixels must be within the size returned by `graphics_size`.  Consoles without a
+    /// graphics surface return an error.
+    fn draw_line(&mut self, _from: PixelPos, _to: PixelPos, _color: Option<u8>) -> io::Result<()> {
+        Err(no_graphics_error())
+    }
+
+    /// Sets the pixel at `pos` to the color number `color` or to the current foreground color if
+    /// `None`.
+    ///
+    /// The pixel must be within the size returned by `graphics_size`.  Consoles without a graphics
+    /// surface return an error.
+    fn draw_pixel(&mut self, _pos: PixelPos, _color: Option<u8>) -> io::Result<()> {
+        Err(no_graphics_error())
+    }
+
     /// Enters the alternate console.
     // TODO(jmmv): This API leads to misuse as callers can forget to leave the alternate console.
     fn enter_alt(&mut self) -> io::Result<()>;
 
+    /// Queries the size of the graphics surface of the console.
+    ///
+    /// The returned position represents the first x and y coordinates that lay *outside* of the
+    /// surface.  Consoles without a graphics surface return an error.
+    fn graphics_size(&self) -> io::Result<PixelPos> {
+        Err(no_graphics_error())
+    }
+
     /// Hides the cursor.
     // TODO(jmmv): This API leads to misuse as callers can forget to show the cursor again.
     fn hide_cursor(&mut self) -> io::Result<()>;
@@ -129,6 +235,9 @@ pub trait Console {
     /// Moves the cursor within the line.  Positive values move right, negative values move left.
     fn move_within_line(&mut self, off: i16) -> io::Result<()>;
 
+    /// Returns the next key press if one is available without waiting, or `None` otherwise.
+    fn poll_key(&mut self) -> io::Result<Option<Key>>;
+
     /// Writes `text` to the console, followed by a newline or CRLF pair depending on the needs of
     /// the console to advance a line.
     // TODO(jmmv): Remove this in favor of write?
@@ -137,6 +246,10 @@ pub trait Console {
     /// Waits for and returns the next key press.
     async fn read_key(&mut self) -> io::Result<Key>;
 
+    /// Replaces the palette used to display the color numbers given to `color`.  Only affects
+    /// colors set after this call.
+    fn set_palette(&mut self, palette: Palette) -> io::Result<()>;
+
     /// Shows the cursor.
     fn show_cursor(&mut self) -> io::Result<()>;
 
@@ -149,12 +262,214 @@ pub trait Console {
     fn write(&mut self, bytes: &[u8]) -> io::Result<()>;
 }
 
+/// Maximum number of lines kept in the history of `read_line`.
+pub const HISTORY_SIZE: usize = 100;
+
+/// Bounded list of the most recent lines read by `read_line`.
+///
+/// Entries are numbered from 1 in the order in which they were added, and they keep their numbers
+/// even after older entries are discarded to stay within the capacity.
+pub struct History {
+    /// The lines in the history, oldest first.
+    lines: VecDeque<String>,
+
+    /// Maximum number of lines to keep in `lines`.
+    capacity: usize,
+
+    /// Number of the oldest entry in `lines`.
+    first: usize,
+}
+
+impl Default for History {
+    /// Creates an empty history that keeps up to `HISTORY_SIZE` lines.
+    fn default() -> Self {
+        Self::with_capacity(HISTORY_SIZE)
+    }
+}
+
+impl History {
+    /// Creates an empty history that keeps up to `capacity` lines.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "History must be able to hold at least one line");
+        Self { lines: VecDeque::with_capacity(capacity), capacity, first: 1 }
+    }
+
+    /// Appends `line` to the history, discarding the oldest entry if the history is full.
+    pub fn push<S: Into<String>>(&mut self, line: S) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+            self.first += 1;
+        }
+        self.lines.push_back(line.into());
+    }
+
+    /// Returns the number of entries in the history.
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Returns true if the history has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Returns the entry with the given `number`, if it is still in the history.
+    pub fn get(&self, number: usize) -> Option<&str> {
+        match number.checked_sub(self.first) {
+            Some(i) => self.lines.get(i).map(String::as_str),
+            None => None,
+        }
+    }
+
+    /// Returns all entries in the history along with their numbers, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        let first = self.first;
+        self.lines.iter().enumerate().map(move |(i, line)| (first + i, line.as_str()))
+    }
+
+    /// Expands `line` if it is a request to recall a previous entry, like `!5`, or returns it as
+    /// is otherwise.
+    ///
+    /// The recall request is expected to be the newest entry in the history, and is replaced by the
+    /// recalled text so that the history only contains the lines that were actually run.
+    pub fn expand(&mut self, line: String) -> io::Result<String> {
+        let number = match line.trim().strip_prefix('!').map(str::parse::<usize>) {
+            Some(Ok(number)) => number,
+            _ => return Ok(line),
+        };
+        let text = match self.get(number) {
+            Some(text) => text.to_owned(),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No history entry {}", number),
+                ))
+            }
+        };
+        if let Some(last) = self.lines.back_mut() {
+            if *last == line {
+                *last = text.clone();
+            }
+        }
+        Ok(text)
+    }
+}
+
+/// Source of candidates to complete the word being typed in `read_line`.
+pub trait Completer {
+    /// Returns the candidates that can replace `word`, which is the partial word that precedes the
+    /// cursor and which is in turn preceded by `context` within the line.
+    fn complete(&self, context: &str, word: &str) -> Vec<String>;
+}
+
+/// Returns the position within `text` where the word that ends at the end of `text` starts.
+fn word_start(text: &str) -> usize {
+    match text.rfind(|ch: char| ch.is_whitespace() || "\"'():;,=+*/<>^".contains(ch)) {
+        Some(i) => i + 1,
+        None => 0,
+    }
+}
+
+/// Returns the longest prefix shared by all `candidates`, ignoring case differences.  The casing
+/// of the returned prefix is the one of the first candidate.
+fn common_prefix(candidates: &[String]) -> &str {
+    let first = &candidates[0];
+    let mut len = first.len();
+    for candidate in &candidates[1..] {
+        len = first
+            .chars()
+            .zip(candidate.chars())
+            .take_while(|(a, b)| a.eq_ignore_ascii_case(b))
+            .map(|(a, _)| a.len_utf8())
+            .sum::<usize>()
+            .min(len);
+    }
+    &first[..len]
+}
+
+/// Completes the word that precedes the cursor at `pos` within `line` using `completer`.
+///
+/// The word is extended up to the longest prefix shared by all candidates, which means that a
+/// unique candidate is inserted in full.  If there is more than one candidate, these are listed
+/// on a separate line and the prompt and input are printed again after them.
+fn complete_word(
+    console: &mut dyn Console,
+    prompt: &str,
+    width: usize,
+    line: &mut String,
+    pos: &mut usize,
+    completer: &dyn Completer,
+) -> io::Result<()> {
+    let start = word_start(&line[..*pos]);
+    let candidates = completer.complete(&line[..start], &line[start..*pos]);
+    if candidates.is_empty() {
+        return Ok(());
+    }
+
+    let prefix = common_prefix(&candidates);
+    if prefix.len() >= *pos - start && line.len() - (*pos - start) + prefix.len() < width {
+        let rest = line.split_off(*pos);
+        line.truncate(start);
+        line.push_str(prefix);
+        console.hide_cursor()?;
+        if *pos > start {
+            console.move_within_line(-((*pos - start) as i16))?;
+        }
+        console.write(format!("{}{}", prefix, rest).as_bytes())?;
+        if !rest.is_empty() {
+            console.move_within_line(-(rest.len() as i16))?;
+        }
+        console.show_cursor()?;
+        *pos = line.len();
+        line.push_str(&rest);
+    }
+
+    if candidates.len() > 1 {
+        if *pos < line.len() {
+            console.move_within_line((line.len() - *pos) as i16)?;
+        }
+        console.write(b"\r\n")?;
+        console.print(&candidates.join("  "))?;
+        console.write(format!("{}{}", prompt, line).as_bytes())?;
+        if *pos < line.len() {
+            console.move_within_line(-((line.len() - *pos) as i16))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the contents of the input `line` with `text`, given that the cursor is at `pos` within
+/// the line, and leaves the cursor at the end of the new line.
+fn replace_line(
+    console: &mut dyn Console,
+    line: &mut String,
+    pos: &mut usize,
+    text: &str,
+) -> io::Result<()> {
+    if *pos > 0 {
+        console.move_within_line(-(*pos as i16))?;
+    }
+    console.clear(ClearType::UntilNewLine)?;
+    if !text.is_empty() {
+        console.write(text.as_bytes())?;
+    }
+    *line = text.to_owned();
+    *pos = line.len();
+    Ok(())
+}
+
 /// Reads a line of text interactively from the console, using the given `prompt` and pre-filling
 /// the input with `previous`.
+///
+/// If a `history` is given, the up and down keys navigate through it, replacing the input with the
+/// recalled lines.  If a `completer` is given, the tab key completes the word before the cursor.
 async fn read_line_interactive(
     console: &mut dyn Console,
     prompt: &str,
     previous: &str,
+    history: Option<&History>,
+    completer: Option<&dyn Completer>,
 ) -> io::Result<String> {
     let mut line = String::from(previous);
     console.clear(ClearType::UntilNewLine)?;
@@ -172,10 +487,32 @@ async fn read_line_interactive(
     // Insertion position *within* the line, without accounting for the prompt.
     let mut pos = line.len();
 
+    // Position within the history of the line being edited, where the length of the history
+    // represents the new line, and the contents of the new line while navigating the history.
+    let no_history = VecDeque::new();
+    let history = history.map(|h| &h.lines).unwrap_or(&no_history);
+    let mut history_pos = history.len();
+    let mut draft = String::new();
+
     loop {
         match console.read_key().await? {
-            Key::ArrowUp | Key::ArrowDown => {
-                // TODO(jmmv): Implement history tracking.
+            Key::ArrowUp => {
+                if history_pos > 0 {
+                    if history_pos == history.len() {
+                        draft = line.clone();
+                    }
+                    history_pos -= 1;
+                    replace_line(console, &mut line, &mut pos, &history[history_pos])?;
+                }
+            }
+
+            Key::ArrowDown => {
+                if history_pos < history.len() {
+                    history_pos += 1;
+                    let text =
+                        if history_pos == history.len() { &draft } else { &history[history_pos] };
+                    replace_line(console, &mut line, &mut pos, text)?;
+                }
             }
 
             Key::ArrowLeft => {
@@ -250,6 +587,12 @@ async fn read_line_interactive(
                 break;
             }
 
+            Key::Tab => {
+                if let Some(completer) = completer {
+                    complete_word(console, prompt, width, &mut line, &mut pos, completer)?;
+                }
+            }
+
             // TODO(jmmv): Should do something smarter with unknown keys.
             Key::Unknown(_) => (),
         }
@@ -282,6 +625,7 @@ async fn read_line_raw(console: &mut dyn Console) -> io::Result<String> {
             Key::Eof => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "EOF")),
             Key::Interrupt => return Err(io::Error::new(io::ErrorKind::Interrupted, "Ctrl+C")),
             Key::NewLine => break,
+            Key::Tab => line.push('\t'),
             Key::Unknown(bad_input) => line += &bad_input,
         }
     }
@@ -290,15 +634,54 @@ async fn read_line_raw(console: &mut dyn Console) -> io::Result<String> {
 
 /// Reads a line from the console.  If the console is interactive, this does fancy line editing and
 /// uses the given `prompt` and pre-fills the input with `previous`.
+///
+/// If a `history` is given, the user can recall previous lines from it while editing, and the line
+/// read is appended to it unless it is blank.
+///
+/// If a `completer` is given, the user can ask for the completion of the word before the cursor
+/// with the tab key.
 pub async fn read_line(
     console: &mut dyn Console,
     prompt: &str,
     previous: &str,
+    history: Option<&mut History>,
+    completer: Option<&dyn Completer>,
 ) -> io::Result<String> {
-    if console.is_interactive() {
-        read_line_interactive(console, prompt, previous).await
+    let line = if console.is_interactive() {
+        read_line_interactive(console, prompt, previous, history.as_deref(), completer).await?
     } else {
-        read_line_raw(console).await
+        read_line_raw(console).await?
+    };
+
+    if let Some(history) = history {
+        if !line.trim().is_empty() {
+            history.push(line.clone());
+        }
+    }
+
+    Ok(line)
+}
+
+/// Returns the value to store into `vref` when the user gives a blank answer to an input request.
+///
+/// String variables receive the empty string so that comparisons against `""` keep working, and
+/// any other variable receives the empty value.
+pub(crate) fn blank_answer(vref: &VarRef) -> Value {
+    match vref.ref_type() {
+        VarType::Text => Value::Text("".to_owned()),
+        _ => Value::Empty,
+    }
+}
+
+/// Evaluates the optional color expression `e`, ensuring it is within the valid range.
+pub(crate) fn get_color(e: &Option<Expr>, machine: &Machine) -> exec::Result<Option<u8>> {
+    match e {
+        Some(e) => match e.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Integer(i) if i >= 0 && i <= u8::MAX as i32 => Ok(Some(i as u8)),
+            Value::Integer(_) => exec::new_usage_error("Color out of range"),
+            _ => exec::new_usage_error("Color must be an integer"),
+        },
+        None => Ok(None),
     }
 }
 
@@ -313,9 +696,14 @@ impl ClsCommand {
     pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
         Rc::from(Self {
             metadata: CallableMetadataBuilder::new("CLS", VarType::Void)
-                .with_syntax("")
+                .with_syntax("[bg%]")
                 .with_category("Console manipulation")
-                .with_description("Clears the screen.")
+                .with_description(
+                    "Clears the screen.
+If a color number is given, the background color is set to it before clearing the screen so \
+that the whole screen is filled with it.  The color number has the same meaning as in COLOR.  \
+Otherwise, the screen is filled with the current background color.",
+                )
                 .build(),
             console,
         })
@@ -331,12 +719,19 @@ impl Command for ClsCommand {
     async fn exec(
         &self,
         args: &[(Option<Expr>, ArgSep)],
-        _machine: &mut Machine,
+        machine: &mut Machine,
     ) -> exec::Result<()> {
-        if !args.is_empty() {
-            return exec::new_usage_error("CLS takes no arguments");
+        let bg = match args {
+            [] => None,
+            [(bg, ArgSep::End)] => get_color(bg, machine)?,
+            _ => return exec::new_usage_error("CLS takes at most one argument"),
+        };
+
+        let mut console = self.console.borrow_mut();
+        if bg.is_some() {
+            console.color(None, bg)?;
         }
-        self.console.borrow_mut().clear(ClearType::All)?;
+        console.clear(ClearType::All)?;
         Ok(())
     }
 }
@@ -388,17 +783,6 @@ impl Command for ColorCommand {
             }
         };
 
-        fn get_color(e: &Option<Expr>, machine: &Machine) -> exec::Result<Option<u8>> {
-            match e {
-                Some(e) => match e.eval(machine.get_vars(), machine.get_functions())? {
-                    Value::Integer(i) if i >= 0 && i <= std::u8::MAX as i32 => Ok(Some(i as u8)),
-                    Value::Integer(_) => exec::new_usage_error("Color out of range"),
-                    _ => exec::new_usage_error("Color must be an integer"),
-                },
-                None => Ok(None),
-            }
-        }
-
         let fg = get_color(fg_expr, machine)?;
         let bg = get_color(bg_expr, machine)?;
 
@@ -407,18 +791,70 @@ impl Command for ColorCommand {
     }
 }
 
+/// The `HISTORY` command.
+pub struct HistoryCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+    history: Rc<RefCell<History>>,
+}
+
+impl HistoryCommand {
+    /// Creates a new `HISTORY` command that lists the lines in `history` on the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>, history: Rc<RefCell<History>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("HISTORY", VarType::Void)
+                .with_syntax("")
+                .with_category("Interpreter manipulation")
+                .with_description(
+                    "Lists the most recently entered commands.
+Each command is shown along with its number.  Type ! followed by a number, as in !5, to run the \
+corresponding command again.  Only the last 100 commands are kept.",
+                )
+                .build(),
+            console,
+            history,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for HistoryCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        _machine: &mut Machine,
+    ) -> exec::Result<()> {
+        if !args.is_empty() {
+            return exec::new_usage_error("HISTORY takes no arguments");
+        }
+
+        let history = self.history.borrow();
+        let mut console = self.console.borrow_mut();
+        for (number, line) in history.iter() {
+            console.print(&format!("{:5}  {}", number, line))?;
+        }
+        Ok(())
+    }
+}
+
 /// The `INPUT` command.
 pub struct InputCommand {
     metadata: CallableMetadata,
     console: Rc<RefCell<dyn Console>>,
+    channels: Rc<RefCell<Channels>>,
 }
 
 impl InputCommand {
-    /// Creates a new `INPUT` command that uses `console` to gather user input.
-    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+    /// Creates a new `INPUT` command that uses `console` to gather user input, or reads from the
+    /// files open in `channels`.
+    pub fn new(console: Rc<RefCell<dyn Console>>, channels: Rc<RefCell<Channels>>) -> Rc<Self> {
         Rc::from(Self {
             metadata: CallableMetadataBuilder::new("INPUT", VarType::Void)
-                .with_syntax("[\"prompt\"] <;|,> variableref")
+                .with_syntax("<[\"prompt\"] <;|,>|#channel%,> variableref")
                 .with_category("Console manipulation")
                 .with_description(
                     "Obtains user input from the console.
@@ -426,10 +862,16 @@ The first expression to this function must be empty or evaluate to a string, and
 the prompt to print.  If this first argument is followed by the short `;` separator, the \
 prompt is extended with a question mark.
 The second expression to this function must be a bare variable reference and indicates the \
-variable to update with the obtained input.",
+variable to update with the obtained input.
+If the user enters nothing, the variable is set to the empty string if it is a string variable \
+(with a $ annotation) or to the empty value otherwise.  Use ISEMPTY to detect the latter.
+If a channel is given instead of a prompt, the next line is read from the file open for input \
+on that channel and converted to the type of the variable.  Reading past the end of the file is \
+an error, so use EOF to check if there are more lines to read.",
                 )
                 .build(),
             console,
+            channels,
         })
     }
 }
@@ -449,6 +891,28 @@ impl Command for InputCommand {
             return exec::new_usage_error("INPUT requires two arguments");
         }
 
+        if let (Some(Expr::Channel(channel)), sep) = &args[0] {
+            if *sep != ArgSep::Long {
+                return exec::new_usage_error("INPUT requires a comma after the channel");
+            }
+            let vref = match &args[1].0 {
+                Some(Expr::Symbol(vref)) => vref,
+                _ => return exec::new_usage_error("INPUT requires a variable reference"),
+            };
+            let channel = eval_channel(channel, machine)?;
+            let line = self.channels.borrow_mut().read_line(channel)?;
+            let value = if line.trim_end().is_empty() {
+                blank_answer(vref)
+            } else {
+                match Value::parse_as(vref.ref_type(), line.trim_end()) {
+                    Ok(value) => value,
+                    Err(e) => return exec::new_usage_error(format!("Bad data in file: {}", e)),
+                }
+            };
+            machine.set_var(vref, value)?;
+            return Ok(());
+        }
+
         let mut prompt = match &args[0].0 {
             Some(e) => match e.eval(machine.get_vars(), machine.get_functions())? {
                 Value::Text(t) => t,
@@ -465,25 +929,106 @@ impl Command for InputCommand {
             _ => return exec::new_usage_error("INPUT requires a variable reference"),
         };
 
-        let mut console = self.console.borrow_mut();
-        let mut previous_answer = String::new();
-        loop {
-            match read_line(&mut *console, &prompt, &previous_answer).await {
-                Ok(answer) => match Value::parse_as(vref.ref_type(), answer.trim_end()) {
-                    Ok(value) => {
-                        machine.get_mut_vars().set(vref, value)?;
-                        return Ok(());
+        // The console must be released before assigning the variable because watching it may
+        // print to the console.
+        let value = {
+            let mut console = self.console.borrow_mut();
+            let mut previous_answer = String::new();
+            loop {
+                match read_line(&mut *console, &prompt, &previous_answer, None, None).await {
+                    Ok(answer) if answer.trim_end().is_empty() => break blank_answer(vref),
+                    Ok(answer) => match Value::parse_as(vref.ref_type(), answer.trim_end()) {
+                        Ok(value) => break value,
+                        Err(e) => {
+                            console.print(&format!("Retry input: {}", e))?;
+                            previous_answer = answer;
+                        }
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                        console.print(&format!("Retry input: {}", e))?
                     }
-                    Err(e) => {
-                        console.print(&format!("Retry input: {}", e))?;
-                        previous_answer = answer;
-                    }
-                },
-                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
-                    console.print(&format!("Retry input: {}", e))?
+                    Err(e) => return Err(e.into()),
                 }
-                Err(e) => return Err(e.into()),
             }
+        };
+        machine.set_var(vref, value)?;
+        Ok(())
+    }
+}
+
+/// The `ISEMPTY` function.
+pub struct IsEmptyFunction {
+    metadata: CallableMetadata,
+}
+
+impl IsEmptyFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("ISEMPTY", VarType::Boolean)
+                .with_syntax("expr")
+                .with_category("Console manipulation")
+                .with_description(
+                    "Checks if a value is empty.
+Returns true if expr evaluates to the empty value, such as the one stored by INPUT when the \
+user enters nothing.  Returns false for any other value, including zero and the empty string.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for IsEmptyFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Empty] => Ok(Value::Boolean(true)),
+            [_] => Ok(Value::Boolean(false)),
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
+/// The `SCREENSHOT$` function.
+pub struct ScreenshotFunction {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl ScreenshotFunction {
+    /// Creates a new instance of the function that captures the contents of `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("SCREENSHOT", VarType::Text)
+                .with_syntax("")
+                .with_category("Console manipulation")
+                .with_description(
+                    "Returns the text visible on the console.
+Rows are separated by newline characters.  Trailing whitespace is removed from every row and \
+trailing empty rows are omitted.  This is only available when the console keeps track of what \
+it displays, which is not the case when the output is redirected.",
+                )
+                .build(),
+            console,
+        })
+    }
+}
+
+impl Function for ScreenshotFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [] => match self.console.borrow().contents() {
+                Ok(rows) => Ok(Value::Text(rows.join("\n"))),
+                Err(e) => Err(FunctionError::InternalError(e.to_string())),
+            },
+            _ => Err(FunctionError::SyntaxError),
         }
     }
 }
@@ -559,24 +1104,22 @@ impl Command for LocateCommand {
     }
 }
 
-/// The `PRINT` command.
-pub struct PrintCommand {
+/// The `PAUSE` command.
+pub struct PauseCommand {
     metadata: CallableMetadata,
     console: Rc<RefCell<dyn Console>>,
 }
 
-impl PrintCommand {
-    /// Creates a new `PRINT` command that writes to `console`.
+impl PauseCommand {
+    /// Creates a new `PAUSE` command that waits for a key press on `console`.
     pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
         Rc::from(Self {
-            metadata: CallableMetadataBuilder::new("PRINT", VarType::Void)
-                .with_syntax("[expr1 [<;|,> .. exprN]]")
+            metadata: CallableMetadataBuilder::new("PAUSE", VarType::Void)
+                .with_syntax("[prompt$]")
                 .with_category("Console manipulation")
                 .with_description(
-                    "Prints a message to the console.
-The expressions given as arguments are all evaluated and converted to strings.  Arguments \
-separated by the short `;` separator are concatenated with a single space, while arguments \
-separated by the long `,` separator are concatenated with a tab character.",
+                    "Waits until any key is pressed.
+If prompt$ is given, it is printed before waiting.  The key that is pressed is discarded.",
                 )
                 .build(),
             console,
@@ -585,7 +1128,7 @@ separated by the long `,` separator are concatenated with a tab character.",
 }
 
 #[async_trait(?Send)]
-impl Command for PrintCommand {
+impl Command for PauseCommand {
     fn metadata(&self) -> &CallableMetadata {
         &self.metadata
     }
@@ -595,55 +1138,706 @@ impl Command for PrintCommand {
         args: &[(Option<Expr>, ArgSep)],
         machine: &mut Machine,
     ) -> exec::Result<()> {
-        let mut text = String::new();
-        for arg in args.iter() {
-            if let Some(expr) = arg.0.as_ref() {
-                text += &expr.eval(machine.get_vars(), machine.get_functions())?.to_string();
-            }
-            match arg.1 {
-                ArgSep::End => break,
-                ArgSep::Short => text += " ",
-                ArgSep::Long => text += "\t",
+        let prompt = match args {
+            [] => None,
+            [(Some(expr), ArgSep::End)] => {
+                match expr.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Text(t) => Some(t),
+                    _ => return exec::new_usage_error("PAUSE prompt must be a string"),
+                }
             }
+            _ => return exec::new_usage_error("PAUSE takes zero or one argument"),
+        };
+
+        let mut console = self.console.borrow_mut();
+        if let Some(prompt) = prompt {
+            console.print(&prompt)?;
+        }
+        match console.read_key().await? {
+            Key::Interrupt => Err(io::Error::new(io::ErrorKind::Interrupted, "Ctrl+C").into()),
+            _ => Ok(()),
         }
-        self.console.borrow_mut().print(&text)?;
-        Ok(())
     }
 }
 
-/// Adds all console-related commands for the given `console` to the `machine`.
-pub fn add_all(machine: &mut Machine, console: Rc<RefCell<dyn Console>>) {
-    machine.add_command(ClsCommand::new(console.clone()));
-    machine.add_command(ColorCommand::new(console.clone()));
-    machine.add_command(InputCommand::new(console.clone()));
-    machine.add_command(LocateCommand::new(console.clone()));
-    machine.add_command(PrintCommand::new(console));
+/// Default width of the zones that `PRINT` uses to align arguments separated by commas.
+pub const DEFAULT_ZONE_WIDTH: usize = 14;
+
+/// Default width of the tab stops used to display program listings.
+pub const DEFAULT_TAB_WIDTH: usize = 8;
+
+/// Replaces the tab characters in `line` with spaces up to the next multiple of `tab_width`.
+pub(crate) fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for ch in line.chars() {
+        if ch == '\t' {
+            let pad = tab_width - column % tab_width;
+            expanded += &" ".repeat(pad);
+            column += pad;
+        } else {
+            expanded.push(ch);
+            column += 1;
+        }
+    }
+    expanded
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::testutils::*;
-    use futures_lite::future::block_on;
+/// Splits `text` into the lines to display on a console that is `width` characters wide.
+///
+/// Any newlines already in `text` are honored and each line is fitted separately.  If `wrap` is
+/// true, long lines are broken at the last space that fits, which is dropped, or at
+/// the console width if a single word is longer than that.  Otherwise, long lines are truncated at
+/// the console width and the excess is discarded.
+fn fit_to_width(text: &str, width: usize, wrap: bool) -> Vec<String> {
+    let mut lines = vec![];
+    for line in text.split('\n') {
+        fit_line_to_width(line, width, wrap, &mut lines);
+    }
+    lines
+}
 
-    /// Builder pattern to construct a test for `read_line_interactive`.
-    #[must_use]
-    struct ReadLineInteractiveTest {
-        keys: Vec<Key>,
-        prompt: &'static str,
-        previous: &'static str,
-        exp_line: &'static str,
-        exp_output: Vec<CapturedOut>,
+/// Fits a single `line` without newlines to `width` characters as described in `fit_to_width`,
+/// appending the results to `lines`.
+fn fit_line_to_width(line: &str, width: usize, wrap: bool, lines: &mut Vec<String>) {
+    let chars: Vec<char> = line.chars().collect();
+    if width == 0 || chars.len() <= width {
+        lines.push(line.to_owned());
+        return;
+    }
+    if !wrap {
+        lines.push(chars[..width].iter().collect());
+        return;
     }
 
-    impl Default for ReadLineInteractiveTest {
-        /// Constructs a new test that feeds no input to the function, with no prompt or previous
-        /// text, and expects an empty return line and no changes to the console.
-        fn default() -> Self {
-            Self {
+    let mut rest = &chars[..];
+    while rest.len() > width {
+        let (end, next) = match rest[..=width].iter().rposition(|ch| *ch == ' ') {
+            Some(pos) if pos > 0 => (pos, pos + 1),
+            _ => (width, width),
+        };
+        lines.push(rest[..end].iter().collect());
+        rest = &rest[next..];
+    }
+    lines.push(rest.iter().collect());
+}
+
+/// Formats `value` for human-readable output as done by the `PRINT` command.
+///
+/// Doubles are rounded to `precision` significant digits, or printed in their shortest form that
+/// reads back as the same number if `precision` is zero.  Either way, whole-valued doubles are
+/// printed without a fractional part.  Integers are printed in the given numeric `base`, using
+/// their two's complement representation if negative and not in base 10.  Booleans are printed
+/// as -1 and 0 if `numeric_booleans` is true.
+fn format_for_print(value: Value, precision: usize, base: u32, numeric_booleans: bool) -> String {
+    match value {
+        Value::Boolean(b) if numeric_booleans => (if b { "-1" } else { "0" }).to_owned(),
+        Value::Integer(i) if base == 2 => format!("{:b}", i),
+        Value::Integer(i) if base == 8 => format!("{:o}", i),
+        Value::Integer(i) if base == 16 => format!("{:X}", i),
+        Value::Double(d) if precision > 0 && d.is_finite() => {
+            let rounded: f64 = format!("{:.*e}", precision - 1, d)
+                .parse()
+                .expect("Formatted doubles must be parseable");
+            rounded.to_string()
+        }
+        value => value.to_string(),
+    }
+}
+
+/// Evaluates the `args` to `PRINT` and concatenates them into a single line of text, padding the
+/// arguments separated by commas to multiples of `zone_width`, formatting doubles with
+/// `precision` significant digits and formatting integers in `base`.
+fn format_print_args(
+    args: &[(Option<Expr>, ArgSep)],
+    machine: &Machine,
+    zone_width: usize,
+    precision: usize,
+    base: u32,
+) -> exec::Result<String> {
+    if args.iter().any(|(_, sep)| *sep == ArgSep::Space) {
+        return exec::new_usage_error("Expected comma or semicolon between arguments");
+    }
+
+    let mut text = String::new();
+    for arg in args.iter() {
+        if let Some(expr) = arg.0.as_ref() {
+            let value = expr.eval(machine.get_vars(), machine.get_functions())?;
+            let numeric_booleans = machine.get_vars().numeric_booleans();
+            text += &format_for_print(value, precision, base, numeric_booleans);
+        }
+        match arg.1 {
+            ArgSep::End => break,
+            ArgSep::Short => text += " ",
+            ArgSep::Long => {
+                let pad = zone_width - text.chars().count() % zone_width;
+                text += &" ".repeat(pad);
+            }
+            ArgSep::Space => unreachable!("Checked above"),
+        }
+    }
+    Ok(text)
+}
+
+/// The `PRINT` command.
+pub struct PrintCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+    zone_width: Rc<Cell<usize>>,
+    precision: Rc<Cell<usize>>,
+    base: Rc<Cell<u32>>,
+    wrap: Rc<Cell<bool>>,
+    channels: Rc<RefCell<Channels>>,
+}
+
+impl PrintCommand {
+    /// Creates a new `PRINT` command that writes to `console`, or to the files open in `channels`,
+    /// aligns arguments separated by commas to multiples of `zone_width`, shows doubles with
+    /// `precision` significant digits and integers in `base`, and word-wraps long lines on the
+    /// console if `wrap` is set.
+    pub fn new(
+        console: Rc<RefCell<dyn Console>>,
+        zone_width: Rc<Cell<usize>>,
+        precision: Rc<Cell<usize>>,
+        base: Rc<Cell<u32>>,
+        wrap: Rc<Cell<bool>>,
+        channels: Rc<RefCell<Channels>>,
+    ) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("PRINT", VarType::Void)
+                .with_syntax("[#channel%,] [expr1 [<;|,> .. exprN]]")
+                .with_category("Console manipulation")
+                .with_description(
+                    "Prints a message to the console.
+The expressions given as arguments are all evaluated and converted to strings.  Booleans are \
+converted to TRUE or FALSE, the same literals used to write them in the language.  Arguments \
+separated by the short `;` separator are concatenated with a single space, while arguments \
+separated by the long `,` separator are padded with spaces up to the beginning of the next \
+print zone.  Print zones are 14 characters wide by default; use ZONEWIDTH to change this.
+Doubles are printed in the shortest form that represents their exact value, without a \
+fractional part if they are whole numbers; use PRECISION to round them to fewer digits.  \
+Integers are printed in decimal; use BASE to show them in binary, octal or hexadecimal.
+If a channel is given, the line is written to the file open on that channel instead.
+The `?` character can be used as an abbreviation for PRINT.",
+                )
+                .build(),
+            console,
+            zone_width,
+            precision,
+            base,
+            wrap,
+            channels,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for PrintCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let (channel, args) = match args {
+            [(Some(Expr::Channel(channel)), ArgSep::End)] => {
+                (Some(eval_channel(channel, machine)?), &args[1..])
+            }
+            [(Some(Expr::Channel(channel)), ArgSep::Long), ..] => {
+                (Some(eval_channel(channel, machine)?), &args[1..])
+            }
+            [(Some(Expr::Channel(_)), ArgSep::Short), ..] => {
+                return exec::new_usage_error("PRINT requires a comma after the channel")
+            }
+            args => (None, args),
+        };
+
+        let text = format_print_args(
+            args,
+            machine,
+            self.zone_width.get(),
+            self.precision.get(),
+            self.base.get(),
+        )?;
+        match channel {
+            Some(channel) => self.channels.borrow_mut().print(channel, &text)?,
+            None => {
+                let mut console = self.console.borrow_mut();
+                // Consoles that cannot report their size, such as those not attached to a
+                // terminal, get the text as is.
+                match console.size() {
+                    Ok(size) => {
+                        for line in fit_to_width(&text, size.column, self.wrap.get()) {
+                            console.print(&line)?;
+                        }
+                    }
+                    Err(_) => console.print(&text)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `PRINT AT` command.
+pub struct PrintAtCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+    zone_width: Rc<Cell<usize>>,
+    precision: Rc<Cell<usize>>,
+    base: Rc<Cell<u32>>,
+}
+
+impl PrintAtCommand {
+    /// Creates a new `PRINT AT` command that writes to `console` at a given position, formatting
+    /// its arguments like `PRINT` does with `zone_width`, `precision` and `base`.
+    pub fn new(
+        console: Rc<RefCell<dyn Console>>,
+        zone_width: Rc<Cell<usize>>,
+        precision: Rc<Cell<usize>>,
+        base: Rc<Cell<u32>>,
+    ) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("PRINT AT", VarType::Void)
+                .with_syntax("(column%, row%)[; expr1 [<;|,> .. exprN]]")
+                .with_category("Console manipulation")
+                .with_description(
+                    "Prints a message at a given position of the console.
+This is a shorthand for LOCATE followed by PRINT, but note that the column comes first.  \
+Positions outside of the console are clipped to its edges.  The expressions are formatted as \
+PRINT does but the cursor is left right after the printed text instead of moving to the next \
+line, and long lines are not word-wrapped.",
+                )
+                .build(),
+            console,
+            zone_width,
+            precision,
+            base,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for PrintAtCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let (column, row, args) = match args {
+            [(Some(column), ArgSep::Long), (Some(row), ArgSep::End)] => (column, row, &args[2..]),
+            [(Some(column), ArgSep::Long), (Some(row), ArgSep::Short), ..] => {
+                (column, row, &args[2..])
+            }
+            _ => return exec::new_usage_error("PRINT AT requires a column and a row"),
+        };
+        let column = match column.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Integer(i) => i,
+            _ => return exec::new_usage_error("Column must be an integer"),
+        };
+        let row = match row.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Integer(i) => i,
+            _ => return exec::new_usage_error("Row must be an integer"),
+        };
+
+        let text = format_print_args(
+            args,
+            machine,
+            self.zone_width.get(),
+            self.precision.get(),
+            self.base.get(),
+        )?;
+
+        let mut console = self.console.borrow_mut();
+        let clip = |value: i32, size: Option<usize>| {
+            let value = value.max(0) as usize;
+            match size {
+                Some(size) => value.min(size.saturating_sub(1)),
+                None => value,
+            }
+        };
+        let size = console.size().ok();
+        let pos = Position {
+            row: clip(row, size.map(|size| size.row)),
+            column: clip(column, size.map(|size| size.column)),
+        };
+        console.locate(pos)?;
+        console.write(text.as_bytes())?;
+        Ok(())
+    }
+}
+/// The `BASE` command.
+pub struct BaseCommand {
+    metadata: CallableMetadata,
+    base: Rc<Cell<u32>>,
+}
+
+impl BaseCommand {
+    /// Creates a new `BASE` command that updates the numeric `base` used by `PRINT`.
+    pub fn new(base: Rc<Cell<u32>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("BASE", VarType::Void)
+                .with_syntax("[radix%]")
+                .with_category("Console manipulation")
+                .with_description(
+                    "Sets the numeric base used by PRINT to show integers.
+The base must be 2, 8, 10 or 16 to show integers in binary, octal, decimal or hexadecimal.  \
+Integers in bases other than 10 are shown without a prefix and, if negative, in their 32-bit \
+two's complement form, so -1 is shown as FFFFFFFF in hexadecimal.  Doubles and booleans are not \
+affected.  If no base is given, integers go back to being printed in decimal.",
+                )
+                .build(),
+            base,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for BaseCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let base = match args {
+            [] => 10,
+            [(Some(expr), ArgSep::End)] => {
+                match expr.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Integer(i) if i == 2 || i == 8 || i == 10 || i == 16 => i as u32,
+                    Value::Integer(_) => return exec::new_usage_error("Unsupported base"),
+                    _ => return exec::new_usage_error("Base must be an integer"),
+                }
+            }
+            _ => return exec::new_usage_error("BASE takes zero or one argument"),
+        };
+        self.base.set(base);
+        Ok(())
+    }
+}
+
+/// The `PRECISION` command.
+pub struct PrecisionCommand {
+    metadata: CallableMetadata,
+    precision: Rc<Cell<usize>>,
+}
+
+impl PrecisionCommand {
+    /// Creates a new `PRECISION` command that updates the `precision` used by `PRINT`.
+    pub fn new(precision: Rc<Cell<usize>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("PRECISION", VarType::Void)
+                .with_syntax("[digits%]")
+                .with_category("Console manipulation")
+                .with_description(
+                    "Sets the number of significant digits used by PRINT to show doubles.
+Doubles are rounded to the given number of digits, which must be between 1 and 17, and any \
+trailing zeros are dropped.  If no number is given, doubles go back to being printed in the \
+shortest form that represents their exact value.  This only affects how doubles are displayed, \
+not the precision of the computations.",
+                )
+                .build(),
+            precision,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for PrecisionCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let precision = match args {
+            [] => 0,
+            [(Some(expr), ArgSep::End)] => {
+                match expr.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Integer(i) if i > 0 && i <= 17 => i as usize,
+                    Value::Integer(_) => return exec::new_usage_error("Precision out of range"),
+                    _ => return exec::new_usage_error("Precision must be an integer"),
+                }
+            }
+            _ => return exec::new_usage_error("PRECISION takes zero or one argument"),
+        };
+        self.precision.set(precision);
+        Ok(())
+    }
+}
+
+/// The `WRITE` command.
+pub struct WriteCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl WriteCommand {
+    /// Creates a new `WRITE` command that writes to `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("WRITE", VarType::Void)
+                .with_syntax("[expr1[, .., exprN]]")
+                .with_category("Console manipulation")
+                .with_description(
+                    "Prints a machine-readable list of values to the console.
+The expressions given as arguments are all evaluated and printed separated by commas.  Strings \
+are wrapped in double quotes, with any embedded double quotes and backslashes escaped with a \
+backslash, while all other values are printed as they are.",
+                )
+                .build(),
+            console,
+        })
+    }
+}
+
+/// Formats `value` for machine-readable output as done by the `WRITE` command.
+fn format_for_write(value: Value) -> String {
+    match value {
+        Value::Text(s) => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+        value => value.to_string(),
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for WriteCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args.iter() {
+            match arg {
+                (Some(expr), ArgSep::Long) | (Some(expr), ArgSep::End) => {
+                    let value = expr.eval(machine.get_vars(), machine.get_functions())?;
+                    values.push(format_for_write(value));
+                }
+                (None, _) => return exec::new_usage_error("WRITE arguments cannot be empty"),
+                (_, ArgSep::Short) | (_, ArgSep::Space) => {
+                    return exec::new_usage_error("WRITE expects arguments separated by a comma")
+                }
+            }
+        }
+        self.console.borrow_mut().print(&values.join(","))?;
+        Ok(())
+    }
+}
+
+/// The `WORDWRAP` command.
+pub struct WordWrapCommand {
+    metadata: CallableMetadata,
+    wrap: Rc<Cell<bool>>,
+}
+
+impl WordWrapCommand {
+    /// Creates a new `WORDWRAP` command that updates the `wrap` setting used by `PRINT`.
+    pub fn new(wrap: Rc<Cell<bool>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("WORDWRAP", VarType::Void)
+                .with_syntax("[enabled?]")
+                .with_category("Console manipulation")
+                .with_description(
+                    "Enables or disables word-wrapping of long lines printed by PRINT.
+When enabled, lines longer than the console width are broken at the last space that fits, or in \
+the middle of a word if it does not fit on its own line.  When disabled, lines longer than the \
+console width are truncated and the excess is discarded.  Word-wrapping is enabled by default, \
+and it is enabled again if no argument is given.
+Line lengths are measured from the first column, even if the cursor was moved elsewhere with \
+LOCATE before printing.
+This does not affect output sent to files with PRINT #channel%.",
+                )
+                .build(),
+            wrap,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for WordWrapCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let wrap = match args {
+            [] => true,
+            [(Some(expr), ArgSep::End)] => {
+                match expr.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Boolean(b) => b,
+                    _ => return exec::new_usage_error("WORDWRAP requires a boolean"),
+                }
+            }
+            _ => return exec::new_usage_error("WORDWRAP takes zero or one argument"),
+        };
+        self.wrap.set(wrap);
+        Ok(())
+    }
+}
+
+/// The `ZONEWIDTH` command.
+pub struct ZoneWidthCommand {
+    metadata: CallableMetadata,
+    zone_width: Rc<Cell<usize>>,
+}
+
+impl ZoneWidthCommand {
+    /// Creates a new `ZONEWIDTH` command that updates the `zone_width` used by `PRINT`.
+    pub fn new(zone_width: Rc<Cell<usize>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("ZONEWIDTH", VarType::Void)
+                .with_syntax("[width%]")
+                .with_category("Console manipulation")
+                .with_description(
+                    "Sets the width of the print zones used by PRINT.
+Arguments to PRINT separated by the long `,` separator are aligned to the beginning of the next \
+print zone.  If no width is given, the width is reset to the default of 14 characters.",
+                )
+                .build(),
+            zone_width,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for ZoneWidthCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let width = match args {
+            [] => DEFAULT_ZONE_WIDTH,
+            [(Some(expr), ArgSep::End)] => {
+                match expr.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Integer(i) if i > 0 && i <= 255 => i as usize,
+                    Value::Integer(_) => return exec::new_usage_error("Zone width out of range"),
+                    _ => return exec::new_usage_error("Zone width must be an integer"),
+                }
+            }
+            _ => return exec::new_usage_error("ZONEWIDTH takes zero or one argument"),
+        };
+        self.zone_width.set(width);
+        Ok(())
+    }
+}
+
+/// Adds all console-related commands for the given `console` to the `machine`, using `channels`
+/// as the table of open files that `INPUT` can read from and `PRINT` can write to.
+pub fn add_all(
+    machine: &mut Machine,
+    console: Rc<RefCell<dyn Console>>,
+    channels: Rc<RefCell<Channels>>,
+) -> exec::Result<()> {
+    let base = Rc::from(Cell::new(10));
+    machine.add_command(BaseCommand::new(base.clone()))?;
+    machine.add_command(ClsCommand::new(console.clone()))?;
+    machine.add_command(ColorCommand::new(console.clone()))?;
+    machine.add_command(InputCommand::new(console.clone(), channels.clone()))?;
+    machine.add_function(IsEmptyFunction::new())?;
+    machine.add_command(LocateCommand::new(console.clone()))?;
+    machine.add_command(PauseCommand::new(console.clone()))?;
+    let zone_width = Rc::from(Cell::new(DEFAULT_ZONE_WIDTH));
+    let precision = Rc::from(Cell::new(0));
+    let wrap = Rc::from(Cell::new(true));
+    machine.add_command(PrecisionCommand::new(precision.clone()))?;
+    machine.add_function(ScreenshotFunction::new(console.clone()))?;
+    machine.add_command(PrintCommand::new(
+        console.clone(),
+        zone_width.clone(),
+        precision.clone(),
+        base.clone(),
+        wrap.clone(),
+        channels,
+    ))?;
+    machine.add_command(PrintAtCommand::new(
+        console.clone(),
+        zone_width.clone(),
+        precision,
+        base,
+    ))?;
+    machine.add_alias("?", "PRINT")?;
+    machine.add_command(WordWrapCommand::new(wrap))?;
+    machine.add_command(WriteCommand::new(console))?;
+    machine.add_command(ZoneWidthCommand::new(zone_width))
+}
+
+/// Adds the console-related commands that only make sense in the interactive interpreter to the
+/// `machine`, using `history` as the list of lines entered in the REPL.
+pub fn add_interactive(
+    machine: &mut Machine,
+    console: Rc<RefCell<dyn Console>>,
+    history: Rc<RefCell<History>>,
+) -> exec::Result<()> {
+    machine.add_command(HistoryCommand::new(console, history))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::*;
+    use futures_lite::future::block_on;
+
+    /// Completer that offers the words in a fixed list that start with the word being typed.
+    struct WordsCompleter(Vec<&'static str>);
+
+    impl Completer for WordsCompleter {
+        fn complete(&self, _context: &str, word: &str) -> Vec<String> {
+            self.0
+                .iter()
+                .filter(|w| w.to_ascii_uppercase().starts_with(&word.to_ascii_uppercase()))
+                .map(|w| (*w).to_owned())
+                .collect()
+        }
+    }
+
+    /// Builder pattern to construct a test for `read_line_interactive`.
+    #[must_use]
+    struct ReadLineInteractiveTest {
+        keys: Vec<Key>,
+        prompt: &'static str,
+        previous: &'static str,
+        history: Option<History>,
+        completer: Option<WordsCompleter>,
+        exp_line: &'static str,
+        exp_output: Vec<CapturedOut>,
+    }
+
+    impl Default for ReadLineInteractiveTest {
+        /// Constructs a new test that feeds no input to the function, with no prompt or previous
+        /// text, and expects an empty return line and no changes to the console.
+        fn default() -> Self {
+            Self {
                 keys: vec![],
                 prompt: "",
                 previous: "",
+                history: None,
+                completer: None,
                 exp_line: "",
                 exp_output: vec![CapturedOut::Clear(ClearType::UntilNewLine)],
             }
@@ -695,6 +1889,22 @@ mod tests {
             self
         }
 
+        /// Sets the history lines to use for the test.
+        fn set_history(mut self, history: &[&str]) -> Self {
+            let mut h = History::default();
+            for line in history {
+                h.push(*line);
+            }
+            self.history = Some(h);
+            self
+        }
+
+        /// Sets the words that the completer offers for the test.
+        fn set_completions(mut self, words: &[&'static str]) -> Self {
+            self.completer = Some(WordsCompleter(words.to_vec()));
+            self
+        }
+
         /// Sets the previous text to use for the test.
         fn set_previous(mut self, previous: &'static str) -> Self {
             self.previous = previous;
@@ -710,8 +1920,14 @@ mod tests {
             let mut console = MockConsole::default();
             console.add_input_keys(&self.keys);
             console.set_size(Position { row: 5, column: 15 });
-            let line =
-                block_on(read_line_interactive(&mut console, self.prompt, self.previous)).unwrap();
+            let line = block_on(read_line_interactive(
+                &mut console,
+                self.prompt,
+                self.previous,
+                self.history.as_ref(),
+                self.completer.as_ref().map(|c| c as &dyn Completer),
+            ))
+            .unwrap();
             assert_eq!(self.exp_line, &line);
             assert_eq!(self.exp_output.as_slice(), console.captured_out());
         }
@@ -879,53 +2095,294 @@ mod tests {
             .add_key(Key::ArrowRight)
             .add_key(Key::ArrowRight)
             // -
-            .add_key_chars("3")
-            .add_output_bytes(b"3")
+            .add_key_chars("3")
+            .add_output_bytes(b"3")
+            // -
+            .set_line("123")
+            .accept();
+    }
+
+    #[test]
+    fn test_read_line_interactive_horizontal_scrolling_not_implemented() {
+        ReadLineInteractiveTest::default()
+            .add_key_chars("1234567890123456789")
+            .add_output_bytes(b"12345678901234")
+            // -
+            .set_line("12345678901234")
+            .accept();
+
+        ReadLineInteractiveTest::default()
+            .add_key_chars("1234567890123456789")
+            .add_output_bytes(b"12345678901234")
+            // -
+            .add_key(Key::ArrowLeft)
+            .add_output(CapturedOut::MoveWithinLine(-1))
+            // -
+            .add_key(Key::ArrowLeft)
+            .add_output(CapturedOut::MoveWithinLine(-1))
+            // -
+            .add_key_chars("these will all be ignored")
+            // -
+            .set_line("12345678901234")
+            .accept();
+
+        ReadLineInteractiveTest::default()
+            .set_prompt("12345")
+            .set_previous("67890")
+            .add_output(CapturedOut::Write(b"1234567890".to_vec()))
+            // -
+            .add_key_chars("1234567890")
+            .add_output_bytes(b"1234")
+            // -
+            .set_line("678901234")
+            .accept();
+    }
+
+    #[test]
+    fn test_read_line_interactive_history_empty() {
+        ReadLineInteractiveTest::default().add_key(Key::ArrowUp).accept();
+        ReadLineInteractiveTest::default().add_key(Key::ArrowDown).accept();
+        ReadLineInteractiveTest::default().set_history(&[]).add_key(Key::ArrowUp).accept();
+        ReadLineInteractiveTest::default().set_history(&[]).add_key(Key::ArrowDown).accept();
+    }
+
+    #[test]
+    fn test_read_line_interactive_history_recall() {
+        ReadLineInteractiveTest::default()
+            .set_history(&["first", "second"])
+            .add_key(Key::ArrowUp)
+            .add_output(CapturedOut::Clear(ClearType::UntilNewLine))
+            .add_output(CapturedOut::Write(b"second".to_vec()))
+            // -
+            .add_key(Key::ArrowUp)
+            .add_output(CapturedOut::MoveWithinLine(-6))
+            .add_output(CapturedOut::Clear(ClearType::UntilNewLine))
+            .add_output(CapturedOut::Write(b"first".to_vec()))
+            // -
+            .add_key(Key::ArrowUp)
+            // -
+            .add_key_chars("!")
+            .add_output_bytes(b"!")
+            // -
+            .set_line("first!")
+            .accept();
+    }
+
+    #[test]
+    fn test_read_line_interactive_history_back_to_draft() {
+        ReadLineInteractiveTest::default()
+            .set_history(&["old"])
+            .add_key_chars("new")
+            .add_output_bytes(b"new")
+            // -
+            .add_key(Key::ArrowUp)
+            .add_output(CapturedOut::MoveWithinLine(-3))
+            .add_output(CapturedOut::Clear(ClearType::UntilNewLine))
+            .add_output(CapturedOut::Write(b"old".to_vec()))
+            // -
+            .add_key(Key::ArrowDown)
+            .add_output(CapturedOut::MoveWithinLine(-3))
+            .add_output(CapturedOut::Clear(ClearType::UntilNewLine))
+            .add_output(CapturedOut::Write(b"new".to_vec()))
+            // -
+            .add_key(Key::ArrowDown)
+            // -
+            .set_line("new")
+            .accept();
+    }
+
+    #[test]
+    fn test_read_line_interactive_complete_without_completer() {
+        ReadLineInteractiveTest::default()
+            .add_key_chars("pr")
+            .add_output_bytes(b"pr")
+            // -
+            .add_key(Key::Tab)
             // -
-            .set_line("123")
+            .set_line("pr")
             .accept();
     }
 
     #[test]
-    fn test_read_line_interactive_horizontal_scrolling_not_implemented() {
+    fn test_read_line_interactive_complete_unique() {
         ReadLineInteractiveTest::default()
-            .add_key_chars("1234567890123456789")
-            .add_output_bytes(b"12345678901234")
+            .set_completions(&["PLAY", "PRINT"])
+            .add_key_chars("pr")
+            .add_output_bytes(b"pr")
             // -
-            .set_line("12345678901234")
+            .add_key(Key::Tab)
+            .add_output(CapturedOut::HideCursor)
+            .add_output(CapturedOut::MoveWithinLine(-2))
+            .add_output(CapturedOut::Write(b"PRINT".to_vec()))
+            .add_output(CapturedOut::ShowCursor)
+            // -
+            .add_key_chars(" 1")
+            .add_output_bytes(b" 1")
+            // -
+            .set_line("PRINT 1")
             .accept();
+    }
 
+    #[test]
+    fn test_read_line_interactive_complete_unique_mid_line() {
         ReadLineInteractiveTest::default()
-            .add_key_chars("1234567890123456789")
-            .add_output_bytes(b"12345678901234")
+            .set_completions(&["LEN"])
+            .set_previous("a=l(b)")
+            .add_output(CapturedOut::Write(b"a=l(b)".to_vec()))
             // -
             .add_key(Key::ArrowLeft)
             .add_output(CapturedOut::MoveWithinLine(-1))
-            // -
+            .add_key(Key::ArrowLeft)
+            .add_output(CapturedOut::MoveWithinLine(-1))
             .add_key(Key::ArrowLeft)
             .add_output(CapturedOut::MoveWithinLine(-1))
             // -
-            .add_key_chars("these will all be ignored")
+            .add_key(Key::Tab)
+            .add_output(CapturedOut::HideCursor)
+            .add_output(CapturedOut::MoveWithinLine(-1))
+            .add_output(CapturedOut::Write(b"LEN(b)".to_vec()))
+            .add_output(CapturedOut::MoveWithinLine(-3))
+            .add_output(CapturedOut::ShowCursor)
             // -
-            .set_line("12345678901234")
+            .set_line("a=LEN(b)")
             .accept();
+    }
 
+    #[test]
+    fn test_read_line_interactive_complete_ambiguous() {
         ReadLineInteractiveTest::default()
-            .set_prompt("12345")
-            .set_previous("67890")
-            .add_output(CapturedOut::Write(b"1234567890".to_vec()))
+            .set_prompt("> ")
+            .add_output(CapturedOut::Write(b"> ".to_vec()))
+            .set_completions(&["LEFT", "LEN", "PRINT"])
+            .add_key_chars("a=l")
+            .add_output_bytes(b"a=l")
             // -
-            .add_key_chars("1234567890")
-            .add_output_bytes(b"1234")
+            .add_key(Key::Tab)
+            .add_output(CapturedOut::HideCursor)
+            .add_output(CapturedOut::MoveWithinLine(-1))
+            .add_output(CapturedOut::Write(b"LE".to_vec()))
+            .add_output(CapturedOut::ShowCursor)
+            .add_output(CapturedOut::Write(b"\r\n".to_vec()))
+            .add_output(CapturedOut::Print("LEFT  LEN".to_owned()))
+            .add_output(CapturedOut::Write(b"> a=LE".to_vec()))
             // -
-            .set_line("678901234")
+            .add_key_chars("n")
+            .add_output_bytes(b"n")
+            // -
+            .set_line("a=LEn")
             .accept();
     }
 
     #[test]
-    fn test_read_line_interactive_history_not_implemented() {
-        ReadLineInteractiveTest::default().add_key(Key::ArrowUp).accept();
-        ReadLineInteractiveTest::default().add_key(Key::ArrowDown).accept();
+    fn test_read_line_interactive_complete_no_candidates() {
+        ReadLineInteractiveTest::default()
+            .set_completions(&["PRINT"])
+            .add_key_chars("x")
+            .add_output_bytes(b"x")
+            // -
+            .add_key(Key::Tab)
+            // -
+            .set_line("x")
+            .accept();
+    }
+
+    #[test]
+    fn test_read_line_records_history() {
+        let mut console = MockConsole::default();
+        console.add_input_chars("first\n   \n\nsecond\n");
+        let mut history = History::default();
+        for exp_line in &["first", "   ", "", "second"] {
+            let line = block_on(read_line(&mut console, "", "", Some(&mut history), None)).unwrap();
+            assert_eq!(exp_line, &line);
+        }
+        assert_eq!(vec![(1, "first"), (2, "second")], history.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_read_line_history_is_bounded() {
+        let mut console = MockConsole::default();
+        let mut history = History::default();
+        for i in 0..HISTORY_SIZE + 2 {
+            console.add_input_chars(&format!("{}\n", i));
+            block_on(read_line(&mut console, "", "", Some(&mut history), None)).unwrap();
+        }
+        assert_eq!(HISTORY_SIZE, history.len());
+        assert_eq!(Some("2"), history.get(3));
+        assert_eq!(Some(format!("{}", HISTORY_SIZE + 1).as_str()), history.get(HISTORY_SIZE + 2));
+    }
+
+    #[test]
+    fn test_history_ring_buffer() {
+        let mut history = History::with_capacity(3);
+        assert!(history.is_empty());
+        assert_eq!(None, history.get(0));
+        assert_eq!(None, history.get(1));
+
+        for line in &["a", "b", "c"] {
+            history.push(*line);
+        }
+        assert_eq!(vec![(1, "a"), (2, "b"), (3, "c")], history.iter().collect::<Vec<_>>());
+
+        history.push("d");
+        history.push("e");
+        assert_eq!(3, history.len());
+        assert_eq!(vec![(3, "c"), (4, "d"), (5, "e")], history.iter().collect::<Vec<_>>());
+        assert_eq!(None, history.get(2));
+        assert_eq!(Some("c"), history.get(3));
+        assert_eq!(Some("e"), history.get(5));
+        assert_eq!(None, history.get(6));
+    }
+
+    #[test]
+    fn test_history_expand() {
+        let mut history = History::default();
+        history.push("PRINT 1");
+        history.push("a = 3");
+
+        assert_eq!("PRINT 2", history.expand("PRINT 2".to_owned()).unwrap());
+        assert_eq!("!x", history.expand("!x".to_owned()).unwrap());
+
+        history.push(" !1 ");
+        assert_eq!("PRINT 1", history.expand(" !1 ".to_owned()).unwrap());
+        assert_eq!(
+            vec![(1, "PRINT 1"), (2, "a = 3"), (3, "PRINT 1")],
+            history.iter().collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            "No history entry 4",
+            format!("{}", history.expand("!4".to_owned()).unwrap_err())
+        );
+        assert_eq!(
+            "No history entry 0",
+            format!("{}", history.expand("!0".to_owned()).unwrap_err())
+        );
+    }
+
+    #[test]
+    fn test_history_command() {
+        let history = Rc::from(RefCell::from(History::default()));
+        let tester = Tester::from(Machine::default());
+        let console = tester.get_console();
+        let mut tester = tester.add_command(HistoryCommand::new(console, history.clone()));
+
+        tester.run("HISTORY").check();
+
+        history.borrow_mut().push("PRINT 1");
+        history.borrow_mut().push("HISTORY");
+        tester.run("HISTORY").expect_prints(["    1  PRINT 1", "    2  HISTORY"]).check();
+    }
+
+    #[test]
+    fn test_history_errors() {
+        let tester = Tester::from(Machine::default());
+        let console = tester.get_console();
+        let history = Rc::from(RefCell::from(History::default()));
+        tester
+            .add_command(HistoryCommand::new(console, history))
+            .run("HISTORY 1")
+            .expect_err("HISTORY takes no arguments")
+            .check();
     }
 
     #[test]
@@ -946,11 +2403,27 @@ mod tests {
     #[test]
     fn test_cls_ok() {
         Tester::default().run("CLS").expect_output([CapturedOut::Clear(ClearType::All)]).check();
+        Tester::default()
+            .run("CLS 4")
+            .expect_output([CapturedOut::Color(None, Some(4)), CapturedOut::Clear(ClearType::All)])
+            .check();
+        Tester::default()
+            .run("c = 255: CLS c")
+            .expect_output([
+                CapturedOut::Color(None, Some(255)),
+                CapturedOut::Clear(ClearType::All),
+            ])
+            .expect_var("c", 255)
+            .check();
     }
 
     #[test]
     fn test_cls_errors() {
-        check_stmt_err("CLS takes no arguments", "CLS 1");
+        check_stmt_err("CLS takes at most one argument", "CLS 1, 2");
+        check_stmt_err("CLS takes at most one argument", "CLS 1; 2");
+        check_stmt_err("Color out of range", "CLS 1000");
+        check_stmt_err("Color out of range", "CLS -1");
+        check_stmt_err("Color must be an integer", "CLS TRUE");
     }
 
     #[test]
@@ -968,6 +2441,58 @@ mod tests {
         t().run("COLOR 255, 255").expect_output([CapturedOut::Color(Some(255), Some(255))]).check();
     }
 
+    #[test]
+    fn test_color_resolves_through_palette() {
+        let mut t = Tester::default();
+        let console = t.get_console();
+
+        t.run("COLOR 1, 2").expect_output([CapturedOut::Color(Some(1), Some(2))]).check();
+        assert_eq!(
+            (DisplayColor::Native(1), DisplayColor::Native(2)),
+            console.borrow().display_colors()
+        );
+
+        let mut palette = Palette::from_rgbs(&[(0, 0, 0), (255, 0, 0)]);
+        palette.set(200, (10, 20, 30));
+        console.borrow_mut().set_palette(palette).unwrap();
+
+        t.run("COLOR 1, 2")
+            .expect_output([
+                CapturedOut::Color(Some(1), Some(2)),
+                CapturedOut::Color(Some(1), Some(2)),
+            ])
+            .check();
+        assert_eq!(
+            (DisplayColor::Rgb(255, 0, 0), DisplayColor::Native(2)),
+            console.borrow().display_colors()
+        );
+
+        t.run("COLOR 200")
+            .expect_output([
+                CapturedOut::Color(Some(1), Some(2)),
+                CapturedOut::Color(Some(1), Some(2)),
+                CapturedOut::Color(Some(200), None),
+            ])
+            .check();
+        assert_eq!(
+            (DisplayColor::Rgb(10, 20, 30), DisplayColor::Default),
+            console.borrow().display_colors()
+        );
+    }
+
+    #[test]
+    fn test_palette_resolve() {
+        let palette = Palette::default();
+        assert_eq!(DisplayColor::Default, palette.resolve(None));
+        assert_eq!(DisplayColor::Native(3), palette.resolve(Some(3)));
+
+        let palette = Palette::from_rgbs(&[(1, 2, 3), (4, 5, 6)]);
+        assert_eq!(DisplayColor::Default, palette.resolve(None));
+        assert_eq!(DisplayColor::Rgb(1, 2, 3), palette.resolve(Some(0)));
+        assert_eq!(DisplayColor::Rgb(4, 5, 6), palette.resolve(Some(1)));
+        assert_eq!(DisplayColor::Native(2), palette.resolve(Some(2)));
+    }
+
     #[test]
     fn test_color_errors() {
         check_stmt_err("COLOR takes at most two arguments separated by a comma", "COLOR 1, 2, 3");
@@ -1013,13 +2538,6 @@ mod tests {
 
     #[test]
     fn test_input_retry() {
-        Tester::default()
-            .add_input_chars("\ntrue\n")
-            .run("INPUT ; b?")
-            .expect_prints(["Retry input: Invalid boolean literal "])
-            .expect_var("b", true)
-            .check();
-
         Tester::default()
             .add_input_chars("0\ntrue\n")
             .run("INPUT ; b?")
@@ -1028,17 +2546,51 @@ mod tests {
             .check();
 
         Tester::default()
-            .add_input_chars("\n7\n")
+            .add_input_chars("x\n7\n")
             .run("a = 3\nINPUT ; a")
-            .expect_prints(["Retry input: Invalid integer literal "])
+            .expect_prints(["Retry input: Invalid integer literal x"])
             .expect_var("a", 7)
             .check();
+    }
+
+    #[test]
+    fn test_input_empty() {
+        for (stmt, var) in &[("INPUT ; foo", "foo"), ("INPUT ; b?", "b"), ("a = 3\nINPUT ; a", "a")]
+        {
+            Tester::default()
+                .add_input_chars("\n")
+                .run(format!("{}\nPRINT {}; ISEMPTY({})", stmt, var, var))
+                .expect_prints([" TRUE"])
+                .expect_var(*var, Value::Empty)
+                .check();
+        }
+
+        Tester::default()
+            .add_input_chars("  \n")
+            .run("INPUT ; a\nb = a + 1")
+            .expect_var("a", Value::Empty)
+            .expect_err("Cannot add Empty and Integer(1)")
+            .check();
 
         Tester::default()
-            .add_input_chars("x\n7\n")
-            .run("a = 3\nINPUT ; a")
-            .expect_prints(["Retry input: Invalid integer literal x"])
-            .expect_var("a", 7)
+            .add_input_chars("\n4\n")
+            .run("INPUT ; a\nINPUT ; a\nb = a + 1")
+            .expect_var("a", 4)
+            .expect_var("b", 5)
+            .check();
+
+        Tester::default()
+            .add_input_chars("\n")
+            .run("INPUT \"Name\"; n$\nIF n$ = \"\" THEN\ne = ISEMPTY(n$)\nEND IF")
+            .expect_var("n", "")
+            .expect_var("e", false)
+            .check();
+
+        Tester::default()
+            .add_input_chars("\n")
+            .run("INPUT ; a%\na$ = \"x\"")
+            .expect_var("a", Value::Empty)
+            .expect_err("Incompatible types in a$ assignment")
             .check();
     }
 
@@ -1052,6 +2604,16 @@ mod tests {
         check_stmt_err("Cannot add Text(\"a\") and Boolean(true)", "INPUT \"a\" + TRUE; b?");
     }
 
+    #[test]
+    fn test_isempty() {
+        check_expr_ok(false, "ISEMPTY(0)");
+        check_expr_ok(false, "ISEMPTY(\"\")");
+        check_expr_ok(false, "ISEMPTY(FALSE)");
+
+        check_expr_error("Syntax error in call to ISEMPTY: expected expr", "ISEMPTY()");
+        check_expr_error("Syntax error in call to ISEMPTY: expected expr", "ISEMPTY(1, 2)");
+    }
+
     #[test]
     fn test_locate_ok() {
         Tester::default()
@@ -1065,6 +2627,95 @@ mod tests {
             .check();
     }
 
+    #[test]
+    fn test_pause_ok() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().add_input_keys(&[Key::Char('x'), Key::Char('y')]);
+        t.run("PAUSE").check();
+        t.run("PAUSE \"Press any key\"").expect_prints(["Press any key"]).check();
+        t.run("PAUSE").expect_prints(["Press any key"]).check();
+    }
+
+    #[test]
+    fn test_pause_consumes_one_key() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().add_input_keys(&[Key::ArrowUp]);
+        t.get_console().borrow_mut().add_input_chars("5\n");
+        t.run("PAUSE: INPUT ; a").expect_var("a", 5).check();
+    }
+
+    #[test]
+    fn test_pause_interrupted() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().add_input_keys(&[Key::Interrupt]);
+        t.run("PAUSE \"Waiting\": a = 1").expect_prints(["Waiting"]).expect_err("Ctrl+C").check();
+    }
+
+    #[test]
+    fn test_pause_errors() {
+        check_stmt_err("PAUSE takes zero or one argument", "PAUSE 1, 2");
+        check_stmt_err("PAUSE takes zero or one argument", "PAUSE ;");
+        check_stmt_err("PAUSE prompt must be a string", "PAUSE 3");
+    }
+
+    #[test]
+    fn test_screen_snapshot() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().set_size(Position { row: 5, column: 10 });
+        t.run(
+            r#"PRINT "discarded": CLS: LOCATE 1, 3: PRINT "abc": PRINT "0123456789xyz"
+            LOCATE 0, 8: PRINT "ok""#,
+        )
+        .expect_prints(["discarded"])
+        .expect_output([CapturedOut::Clear(ClearType::All)])
+        .expect_output([CapturedOut::Locate(Position { row: 1, column: 3 })])
+        .expect_prints(["abc", "0123456789", "xyz"])
+        .expect_output([CapturedOut::Locate(Position { row: 0, column: 8 })])
+        .expect_prints(["ok"])
+        .expect_snapshot(["        ok", "   abc", "0123456789", "xyz"])
+        .check();
+    }
+
+    #[test]
+    fn test_screen_snapshot_scrolls() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().set_size(Position { row: 3, column: 10 });
+        t.run(r#"FOR i = 1 TO 4: PRINT i: NEXT: LOCATE 0, 5: PRINT "top""#)
+            .expect_prints(["1", "2", "3", "4"])
+            .expect_output([CapturedOut::Locate(Position { row: 0, column: 5 })])
+            .expect_prints(["top"])
+            .expect_var("i", 5)
+            .expect_snapshot(["3    top", "4"])
+            .check();
+    }
+
+    #[test]
+    fn test_screenshot() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().set_size(Position { row: 5, column: 10 });
+        t.run(
+            r#"LOCATE 1, 3: PRINT "abc": PRINT "0123456789xyz": LOCATE 0, 8: PRINT "ok"
+            s = SCREENSHOT$: t = SCREENSHOT"#,
+        )
+        .expect_output([CapturedOut::Locate(Position { row: 1, column: 3 })])
+        .expect_prints(["abc", "0123456789", "xyz"])
+        .expect_output([CapturedOut::Locate(Position { row: 0, column: 8 })])
+        .expect_prints(["ok"])
+        .expect_var("s", "        ok\n   abc\n0123456789\nxyz")
+        .expect_var("t", "        ok\n   abc\n0123456789\nxyz")
+        .check();
+
+        Tester::default().run("s = SCREENSHOT$").expect_var("s", "").check();
+    }
+
+    #[test]
+    fn test_screenshot_errors() {
+        check_expr_error(
+            "Syntax error in call to SCREENSHOT: expected no arguments",
+            "SCREENSHOT$(1)",
+        );
+    }
+
     #[test]
     fn test_locate_errors() {
         check_stmt_err("LOCATE takes two arguments", "LOCATE");
@@ -1085,8 +2736,8 @@ mod tests {
     fn test_print_ok() {
         Tester::default().run("PRINT").expect_prints([""]).check();
         Tester::default().run("PRINT ;").expect_prints([" "]).check();
-        Tester::default().run("PRINT ,").expect_prints(["\t"]).check();
-        Tester::default().run("PRINT ;,;,").expect_prints([" \t \t"]).check();
+        Tester::default().run("PRINT ,").expect_prints([" ".repeat(14)]).check();
+        Tester::default().run("PRINT ;,;,").expect_prints([" ".repeat(28)]).check();
 
         Tester::default().run("PRINT 3").expect_prints(["3"]).check();
         Tester::default().run("PRINT 3 = 5").expect_prints(["FALSE"]).check();
@@ -1094,19 +2745,298 @@ mod tests {
             .run("PRINT true;123;\"foo bar\"")
             .expect_prints(["TRUE 123 foo bar"])
             .check();
-        Tester::default().run("PRINT 6,1;3,5").expect_prints(["6\t1 3\t5"]).check();
+        Tester::default()
+            .run("PRINT 6,1;3,5")
+            .expect_prints(["6             1 3           5"])
+            .check();
 
         Tester::default()
             .run(r#"word = "foo": PRINT word, word: PRINT word + "s""#)
-            .expect_prints(["foo\tfoo", "foos"])
+            .expect_prints(["foo           foo", "foos"])
             .expect_var("word", "foo")
             .check();
     }
 
+    #[test]
+    fn test_print_alias() {
+        Tester::default().run("?").expect_prints([""]).check();
+        Tester::default().run("? \"hi\"").expect_prints(["hi"]).check();
+        Tester::default().run("?1;2: ?\"x\",").expect_prints(["1 2", "x             "]).check();
+    }
+
+    #[test]
+    fn test_print_zones() {
+        Tester::default()
+            .run(r#"PRINT "a", "bc", "", "def""#)
+            .expect_prints(["a             bc                          def"])
+            .check();
+        Tester::default()
+            .run(r#"PRINT "1234567890123", "x": PRINT "12345678901234", "x""#)
+            .expect_prints(["1234567890123 x", "12345678901234              x"])
+            .check();
+        Tester::default().run(r#"PRINT "á", "b""#).expect_prints(["á             b"]).check();
+    }
+
+    #[test]
+    fn test_print_zones_custom_width() {
+        Tester::default()
+            .run(r#"ZONEWIDTH 4: PRINT "a", "bc", "", "def", 12345, 6"#)
+            .expect_prints(["a   bc      def 12345   6"])
+            .check();
+        Tester::default()
+            .run(r#"ZONEWIDTH 1: PRINT "a", "bc", "d""#)
+            .expect_prints(["a bc d"])
+            .check();
+        Tester::default()
+            .run(r#"ZONEWIDTH 4: PRINT 1, 2: ZONEWIDTH: PRINT 1, 2"#)
+            .expect_prints(["1   2", "1             2"])
+            .check();
+    }
+
+    #[test]
+    fn test_zonewidth_errors() {
+        check_stmt_err("ZONEWIDTH takes zero or one argument", "ZONEWIDTH 1, 2");
+        check_stmt_err("ZONEWIDTH takes zero or one argument", "ZONEWIDTH ,");
+        check_stmt_err("Zone width out of range", "ZONEWIDTH 0");
+        check_stmt_err("Zone width out of range", "ZONEWIDTH -5");
+        check_stmt_err("Zone width out of range", "ZONEWIDTH 256");
+        check_stmt_err("Zone width must be an integer", "ZONEWIDTH \"4\"");
+    }
+
+    #[test]
+    fn test_print_integers_in_base() {
+        Tester::default()
+            .run("BASE 16: PRINT 255; -1; 10; 1.5; TRUE: BASE 8: PRINT 8, 64: BASE 2: PRINT 5")
+            .expect_prints(["FF FFFFFFFF A 1.5 TRUE", "10            100", "101"])
+            .check();
+
+        Tester::default()
+            .run("BASE 16: BASE: PRINT 255: BASE 16: BASE 10: PRINT 16")
+            .expect_prints(["255", "16"])
+            .check();
+    }
+
+    #[test]
+    fn test_print_at_integers_in_base() {
+        Tester::default()
+            .run("BASE 16: PRINT AT (0, 0); 171")
+            .expect_output([
+                CapturedOut::Locate(Position { row: 0, column: 0 }),
+                CapturedOut::Write(b"AB".to_vec()),
+            ])
+            .check();
+    }
+
+    #[test]
+    fn test_base_errors() {
+        check_stmt_err("BASE takes zero or one argument", "BASE 2, 8");
+        check_stmt_err("Unsupported base", "BASE 0");
+        check_stmt_err("Unsupported base", "BASE 3");
+        check_stmt_err("Unsupported base", "BASE -16");
+        check_stmt_err("Base must be an integer", "BASE 16.0");
+        check_stmt_err("Base must be an integer", "BASE \"16\"");
+    }
+
+    #[test]
+    fn test_fit_to_width() {
+        assert_eq!(vec![""], fit_to_width("", 5, true));
+        assert_eq!(vec!["abcde"], fit_to_width("abcde", 5, true));
+        assert_eq!(vec!["abcde"], fit_to_width("abcde", 0, true));
+        assert_eq!(vec!["ab cd", "ef"], fit_to_width("ab cd ef", 5, true));
+        assert_eq!(vec!["ab", "cdef"], fit_to_width("ab cdef", 5, true));
+        assert_eq!(vec!["abcde", "fgh"], fit_to_width("abcdefgh", 5, true));
+        assert_eq!(vec!["abcde", " fg"], fit_to_width("abcde  fg", 5, true));
+        assert_eq!(vec![" abcd", "eabc"], fit_to_width(" abcdeabc", 5, true));
+        assert_eq!(vec!["ábcdé", "fg"], fit_to_width("ábcdé fg", 5, true));
+        assert_eq!(vec!["ab", "cd ef", "gh", ""], fit_to_width("ab\ncd ef gh\n", 5, true));
+
+        assert_eq!(vec!["ab cd"], fit_to_width("ab cd ef", 5, false));
+        assert_eq!(vec!["ábcdé"], fit_to_width("ábcdéfgh", 5, false));
+        assert_eq!(vec!["ab", "cd ef"], fit_to_width("ab\ncd ef gh", 5, false));
+    }
+
+    #[test]
+    fn test_expand_tabs() {
+        assert_eq!("", expand_tabs("", 4));
+        assert_eq!("abc", expand_tabs("abc", 4));
+        assert_eq!("    a", expand_tabs("\ta", 4));
+        assert_eq!("ab  c   d", expand_tabs("ab\tc\td", 4));
+        assert_eq!("abcd    e", expand_tabs("abcd\te", 4));
+        assert_eq!("        ", expand_tabs("\t\t", 4));
+        assert_eq!("a b", expand_tabs("a\tb", 2));
+        assert_eq!("á       b", expand_tabs("á\tb", 8));
+    }
+
+    #[test]
+    fn test_print_wraps_on_narrow_console() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().set_size(Position { row: 5, column: 10 });
+        t.run(r#"PRINT "The quick brown fox jumps": PRINT "abcdefghijklmnopq": PRINT "short""#)
+            .expect_prints(["The quick", "brown fox", "jumps", "abcdefghij", "klmnopq", "short"])
+            .check();
+    }
+
+    #[test]
+    fn test_print_truncates_without_wordwrap() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().set_size(Position { row: 5, column: 10 });
+        t.run(r#"WORDWRAP FALSE: PRINT "The quick brown fox jumps": PRINT "short""#)
+            .expect_prints(["The quick ", "short"])
+            .check();
+
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().set_size(Position { row: 5, column: 10 });
+        t.run(r#"WORDWRAP FALSE: WORDWRAP: PRINT "The quick brown fox""#)
+            .expect_prints(["The quick", "brown fox"])
+            .check();
+    }
+
+    #[test]
+    fn test_print_channel_ignores_wordwrap() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().set_size(Position { row: 5, column: 10 });
+        t.run(
+            r#"OPEN "out.bas" FOR OUTPUT AS #1: PRINT #1, "The quick brown fox jumps": CLOSE #1"#,
+        )
+        .expect_file("out.bas", "The quick brown fox jumps\n")
+        .check();
+    }
+
+    #[test]
+    fn test_wordwrap_errors() {
+        check_stmt_err("WORDWRAP takes zero or one argument", "WORDWRAP TRUE, FALSE");
+        check_stmt_err("WORDWRAP takes zero or one argument", "WORDWRAP ,");
+        check_stmt_err("WORDWRAP requires a boolean", "WORDWRAP 1");
+    }
+
+    #[test]
+    fn test_print_doubles() {
+        Tester::default()
+            .run("PRINT 1.0; 0.1; 1.0 / 3.0; 2.0 / 3.0; -12.5; 10000000000.0 * 10000000000.0")
+            .expect_prints([
+                "1 0.1 0.3333333333333333 0.6666666666666666 -12.5 100000000000000000000",
+            ])
+            .check();
+    }
+
+    #[test]
+    fn test_print_doubles_custom_precision() {
+        Tester::default()
+            .run("PRECISION 4: PRINT 1.0; 0.1; 1.0 / 3.0; 2.0 / 3.0; -12.5; 123456.0; 3")
+            .expect_prints(["1 0.1 0.3333 0.6667 -12.5 123500 3"])
+            .check();
+        Tester::default()
+            .run("PRECISION 1: PRINT 0.25; 0.35: PRECISION: PRINT 1.0 / 3.0")
+            .expect_prints(["0.2 0.3", "0.3333333333333333"])
+            .check();
+    }
+
+    #[test]
+    fn test_precision_errors() {
+        check_stmt_err("PRECISION takes zero or one argument", "PRECISION 1, 2");
+        check_stmt_err("Precision out of range", "PRECISION 0");
+        check_stmt_err("Precision out of range", "PRECISION 18");
+        check_stmt_err("Precision must be an integer", "PRECISION 2.0");
+    }
+
+    #[test]
+    fn test_print_booleans() {
+        Tester::default().run("PRINT TRUE; FALSE").expect_prints(["TRUE FALSE"]).check();
+        Tester::default().run("PRINT 1 < 2, 1 > 2").expect_prints(["TRUE          FALSE"]).check();
+        Tester::default()
+            .run("b? = NOT TRUE: PRINT b?: PRINT b? OR TRUE")
+            .expect_prints(["FALSE", "TRUE"])
+            .expect_var("b", false)
+            .check();
+    }
+
     #[test]
     fn test_print_errors() {
         // Ensure type errors from `Expr` and `Value` bubble up.
-        check_stmt_err("Unexpected value in expression", "PRINT a b");
+        check_stmt_err("Unexpected value in expression", "PRINT 3 b");
+        check_stmt_err("Expected comma or semicolon between arguments", "PRINT a b");
         check_stmt_err("Cannot add Integer(3) and Boolean(true)", "PRINT 3 + TRUE");
     }
+
+    #[test]
+    fn test_print_at() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().set_size(Position { row: 5, column: 10 });
+        t.run(r#"PRINT AT (3, 1); "abc"; 5: c = 0: PRINT AT (c, 2 + 1); "x", "y": ? AT (4, 0)"#)
+            .expect_output([
+                CapturedOut::Locate(Position { row: 1, column: 3 }),
+                CapturedOut::Write(b"abc 5".to_vec()),
+                CapturedOut::Locate(Position { row: 3, column: 0 }),
+                CapturedOut::Write(b"x             y".to_vec()),
+                CapturedOut::Locate(Position { row: 0, column: 4 }),
+                CapturedOut::Write(vec![]),
+            ])
+            .expect_var("c", 0)
+            .expect_snapshot(["", "   abc 5", "", "x", "    y"])
+            .check();
+    }
+
+    #[test]
+    fn test_print_at_leaves_cursor_after_text() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().set_size(Position { row: 5, column: 10 });
+        t.run(r#"PRINT AT (2, 1); "ab": PRINT "cd""#)
+            .expect_output([
+                CapturedOut::Locate(Position { row: 1, column: 2 }),
+                CapturedOut::Write(b"ab".to_vec()),
+            ])
+            .expect_prints(["cd"])
+            .expect_snapshot(["", "  abcd"])
+            .check();
+    }
+
+    #[test]
+    fn test_print_at_clips_position() {
+        let mut t = Tester::default();
+        t.get_console().borrow_mut().set_size(Position { row: 5, column: 10 });
+        t.run(r#"PRINT AT (20, -3); "a": PRINT AT (-1, 100); "b""#)
+            .expect_output([
+                CapturedOut::Locate(Position { row: 0, column: 9 }),
+                CapturedOut::Write(b"a".to_vec()),
+                CapturedOut::Locate(Position { row: 4, column: 0 }),
+                CapturedOut::Write(b"b".to_vec()),
+            ])
+            .expect_snapshot(["         a", "", "", "", "b"])
+            .check();
+    }
+
+    #[test]
+    fn test_print_at_errors() {
+        check_stmt_err("Column must be an integer", "PRINT AT (1.5, 2); 3");
+        check_stmt_err("Row must be an integer", "PRINT AT (1, \"2\")");
+        check_stmt_err("Cannot add Integer(3) and Boolean(true)", "PRINT AT (0, 0); 3 + TRUE");
+    }
+
+    #[test]
+    fn test_write_ok() {
+        Tester::default().run("WRITE").expect_prints([""]).check();
+        Tester::default().run("WRITE 3").expect_prints(["3"]).check();
+        Tester::default().run("WRITE \"foo\"").expect_prints(["\"foo\""]).check();
+        Tester::default()
+            .run("WRITE 1, \"two\", 3.5, TRUE, -4")
+            .expect_prints(["1,\"two\",3.5,TRUE,-4"])
+            .check();
+        Tester::default()
+            .run("a$ = \"x\": WRITE a$, a$ + \"y\", \"\"")
+            .expect_prints(["\"x\",\"xy\",\"\""])
+            .expect_var("a", "x")
+            .check();
+        Tester::default()
+            .run(r#"WRITE "say \"hi\"", "back\\slash""#)
+            .expect_prints([r#""say \"hi\"","back\\slash""#])
+            .check();
+    }
+
+    #[test]
+    fn test_write_errors() {
+        check_stmt_err("WRITE expects arguments separated by a comma", "WRITE 1; 2");
+        check_stmt_err("WRITE arguments cannot be empty", "WRITE 1, , 2");
+        check_stmt_err("WRITE arguments cannot be empty", "WRITE ,");
+        check_stmt_err("Cannot add Integer(3) and Boolean(true)", "WRITE 3 + TRUE");
+    }
 }
@@ -0,0 +1,1019 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Commands to debug and profile stored programs.
+
+use crate::console::{self, Console};
+use crate::store::Program;
+use async_trait::async_trait;
+use endbasic_core::ast::{ArgSep, Expr, LineCol, Value, VarType};
+use endbasic_core::dump;
+use endbasic_core::eval::{CallableMetadata, CallableMetadataBuilder};
+use endbasic_core::exec::{self, new_usage_error, Command, Debugger, Machine, Result, StopReason};
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+/// Category string for all commands provided by this module.
+const CATEGORY: &str = "Debugging";
+
+/// State shared between the debugger and the commands that control it.
+#[derive(Default)]
+struct DebugState {
+    /// Number of nested breakpoints the machine is currently stopped at.
+    depth: usize,
+
+    /// Whether the innermost stopped program was asked to continue.
+    resume: bool,
+}
+
+/// Debugger that stops at breakpoints and reads commands from the console until `CONT` or `STEP`
+/// run.
+struct ConsoleDebugger {
+    console: Rc<RefCell<dyn Console>>,
+    program: Rc<RefCell<dyn Program>>,
+    state: Rc<RefCell<DebugState>>,
+}
+
+impl ConsoleDebugger {
+    /// Prints `message` and hands control to the user until the program has to continue.
+    async fn stop(&self, machine: &mut Machine, message: &str) -> Result<Option<StopReason>> {
+        self.console.borrow_mut().print(message)?;
+        self.state.borrow_mut().depth += 1;
+        let result = self.prompt(machine).await;
+        let mut state = self.state.borrow_mut();
+        state.depth -= 1;
+        state.resume = false;
+        result
+    }
+
+    /// Reads and executes commands from the console until the program has to continue.
+    async fn prompt(&self, machine: &mut Machine) -> Result<Option<StopReason>> {
+        loop {
+            let line = {
+                let mut console = self.console.borrow_mut();
+                console::read_line(&mut *console, "", "", None, None).await
+            };
+            let line = match line {
+                Ok(line) => line,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => return Err(exec::Error::Break),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(Some(StopReason::End(0)))
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            match machine.exec(&mut line.as_bytes()).await {
+                Ok(StopReason::Eof) => (),
+                Ok(reason) => return Ok(Some(reason)),
+                Err(e) => self.console.borrow_mut().print(&crate::format_error(&e))?,
+            }
+
+            let mut state = self.state.borrow_mut();
+            if state.resume {
+                state.resume = false;
+                return Ok(None);
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Debugger for ConsoleDebugger {
+    async fn on_breakpoint(
+        &self,
+        machine: &mut Machine,
+        pos: LineCol,
+    ) -> Result<Option<StopReason>> {
+        let message = if machine.get_breakpoints().contains(&pos.line) {
+            format!("Break at line {}; type CONT to resume", pos.line)
+        } else {
+            let text = self.program.borrow().text();
+            let line = text.lines().nth(pos.line - 1).unwrap_or("").trim();
+            format!("Step at line {}: {}", pos.line, line)
+        };
+        self.stop(machine, &message).await
+    }
+
+    async fn on_stop(&self, machine: &mut Machine, pos: LineCol) -> Result<Option<StopReason>> {
+        self.stop(machine, &format!("Stopped at line {}; type CONT to resume", pos.line)).await
+    }
+
+    fn on_step_past_end(&self) -> Result<()> {
+        self.console.borrow_mut().print("Program finished")?;
+        Ok(())
+    }
+
+    fn on_watch(&self, name: &str, value: &Value) -> Result<()> {
+        let (vtype, formatted) = match value {
+            Value::Boolean(_) => (VarType::Boolean, value.to_string()),
+            Value::Double(_) => (VarType::Double, value.to_string()),
+            Value::Integer(_) => (VarType::Integer, value.to_string()),
+            Value::Text(s) => (VarType::Text, format!("\"{}\"", s)),
+            Value::Empty => (VarType::Auto, "(empty)".to_owned()),
+        };
+        self.console.borrow_mut().print(&format!(
+            "WATCH {}{} = {}",
+            name,
+            vtype.annotation(),
+            formatted
+        ))?;
+        Ok(())
+    }
+}
+
+/// Evaluates the single argument in `args` as a line number for the command `name`.
+fn parse_line(name: &str, args: &[(Option<Expr>, ArgSep)], machine: &Machine) -> Result<usize> {
+    match args {
+        [(Some(expr), ArgSep::End)] => {
+            match expr.eval(machine.get_vars(), machine.get_functions())? {
+                Value::Integer(i) if i > 0 => Ok(i as usize),
+                Value::Integer(_) => new_usage_error("Line number must be positive"),
+                _ => new_usage_error("Line number must be an integer"),
+            }
+        }
+        _ => new_usage_error(format!("{} requires a line number", name)),
+    }
+}
+
+/// The `BREAK` command.
+pub struct BreakCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl BreakCommand {
+    /// Creates a new `BREAK` command that sets breakpoints and lists them on the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("BREAK", VarType::Void)
+                .with_syntax("[line%]")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Sets a breakpoint or lists all breakpoints.
+With a line% number, sets a breakpoint on that line of the stored program.  When RUN reaches a \
+statement on that line, execution stops before running it and the interpreter accepts commands \
+to inspect or modify variables.  Type CONT to resume execution.
+Without arguments, lists the lines that have breakpoints set.",
+                )
+                .build(),
+            console,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for BreakCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        if args.is_empty() {
+            let mut console = self.console.borrow_mut();
+            if machine.get_breakpoints().is_empty() {
+                console.print("No breakpoints set")?;
+            }
+            for line in machine.get_breakpoints() {
+                console.print(&format!("Breakpoint at line {}", line))?;
+            }
+            return Ok(());
+        }
+
+        let line = parse_line("BREAK", args, machine)?;
+        machine.add_breakpoint(line);
+        Ok(())
+    }
+}
+
+/// The `CONT` command.
+pub struct ContCommand {
+    metadata: CallableMetadata,
+    state: Rc<RefCell<DebugState>>,
+}
+
+impl ContCommand {
+    /// Creates a new `CONT` command that resumes the program stopped by the debugger's `state`.
+    fn new(state: Rc<RefCell<DebugState>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("CONT", VarType::Void)
+                .with_syntax("")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Resumes a program stopped at a breakpoint or by STOP.
+Execution continues with the statement the program was stopped at, or with the statement that \
+follows STOP.",
+                )
+                .build(),
+            state,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for ContCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], _machine: &mut Machine) -> Result<()> {
+        if !args.is_empty() {
+            return new_usage_error("CONT takes no arguments");
+        }
+        let mut state = self.state.borrow_mut();
+        if state.depth == 0 {
+            return new_usage_error("No program is stopped at a breakpoint");
+        }
+        state.resume = true;
+        Ok(())
+    }
+}
+
+/// The `LEX` command.
+pub struct LexCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+    program: Rc<RefCell<dyn Program>>,
+}
+
+impl LexCommand {
+    /// Creates a new `LEX` command that prints the tokens of code, or of the stored `program`, on
+    /// the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>, program: Rc<RefCell<dyn Program>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("LEX", VarType::Void)
+                .with_syntax("[code$]")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Prints the tokens that the interpreter sees in a piece of code.
+Without arguments, prints the tokens of the stored program.  With a code$ string, prints the \
+tokens of that code instead.
+Each line shows the line and column where a token starts followed by the token itself.  \
+Literals and symbols also show their kind, and malformed tokens are reported as BAD along with \
+the reason for the problem.",
+                )
+                .build(),
+            console,
+            program,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for LexCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        let code = match args {
+            [] => self.program.borrow().text(),
+            [(Some(expr), ArgSep::End)] => {
+                match expr.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Text(code) => code,
+                    _ => return new_usage_error("LEX requires a string as the code"),
+                }
+            }
+            _ => return new_usage_error("LEX takes zero or one argument"),
+        };
+        let mut console = self.console.borrow_mut();
+        for line in dump::dump_tokens(&mut code.as_bytes())? {
+            console.print(&line)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `PARSETREE` command.
+pub struct ParsetreeCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+    program: Rc<RefCell<dyn Program>>,
+}
+
+impl ParsetreeCommand {
+    /// Creates a new `PARSETREE` command that prints the syntax tree of code, or of the stored
+    /// `program`, on the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>, program: Rc<RefCell<dyn Program>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("PARSETREE", VarType::Void)
+                .with_syntax("[code$]")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Prints the syntax tree that the interpreter builds for a piece of code.
+Without arguments, prints the tree of the stored program.  With a code$ string, prints the tree \
+of that code instead.
+Each statement shows the line and column where it starts, and the statements nested in it, such \
+as the bodies of IF and FOR, appear indented below it.  Expressions are shown with the operator \
+first and with all of their parenthesis, as in (+ a (* b 2)), to make their precedence explicit.",
+                )
+                .build(),
+            console,
+            program,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for ParsetreeCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        let code = match args {
+            [] => self.program.borrow().text(),
+            [(Some(expr), ArgSep::End)] => {
+                match expr.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Text(code) => code,
+                    _ => return new_usage_error("PARSETREE requires a string as the code"),
+                }
+            }
+            _ => return new_usage_error("PARSETREE takes zero or one argument"),
+        };
+        let lines = dump::dump_ast(&mut code.as_bytes())?;
+        let mut console = self.console.borrow_mut();
+        for line in lines {
+            console.print(&line)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `PROFILE` command.
+pub struct ProfileCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl ProfileCommand {
+    /// Creates a new `PROFILE` command that controls profiling and prints reports on the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("PROFILE", VarType::Void)
+                .with_syntax("ON | OFF | REPORT")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Counts how many statements run on each line of the stored program.
+PROFILE ON discards any previous counts and starts counting statements as RUN executes them.  \
+PROFILE OFF stops counting but keeps the counts collected so far.
+PROFILE REPORT prints the counts, with the busiest lines first.  Lines with more than one \
+statement count each of them separately.",
+                )
+                .build(),
+            console,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for ProfileCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        let action = match args {
+            [(Some(Expr::Symbol(vref)), ArgSep::End)] if vref.ref_type() == VarType::Auto => {
+                vref.name().to_ascii_uppercase()
+            }
+            _ => return new_usage_error("PROFILE requires ON, OFF or REPORT"),
+        };
+        match action.as_str() {
+            "ON" => machine.start_profiling(),
+            "OFF" => machine.stop_profiling(),
+            "REPORT" => {
+                let mut counts =
+                    machine.get_profile().iter().map(|(l, c)| (*l, *c)).collect::<Vec<_>>();
+                counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+                let mut console = self.console.borrow_mut();
+                if counts.is_empty() {
+                    console.print("No profiling data")?;
+                }
+                for (line, count) in counts {
+                    console.print(&format!("Line {}: {}", line, count))?;
+                }
+            }
+            _ => return new_usage_error("PROFILE requires ON, OFF or REPORT"),
+        }
+        Ok(())
+    }
+}
+
+/// The `STEP` command.
+pub struct StepCommand {
+    metadata: CallableMetadata,
+    state: Rc<RefCell<DebugState>>,
+}
+
+impl StepCommand {
+    /// Creates a new `STEP` command that advances the program stopped by the debugger's `state`.
+    fn new(state: Rc<RefCell<DebugState>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("STEP", VarType::Void)
+                .with_syntax("")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Runs the next statement of a program stopped at a breakpoint.
+Execution stops again right before the statement that follows, printing the line it is on, so \
+that repeated STEP commands advance through the program one statement at a time.  The \
+beginning of a block, such as an IF or a WHILE, counts as a statement on its own.
+If there are no more statements to run, the program finishes.",
+                )
+                .build(),
+            state,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for StepCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        if !args.is_empty() {
+            return new_usage_error("STEP takes no arguments");
+        }
+        let mut state = self.state.borrow_mut();
+        if state.depth == 0 {
+            return new_usage_error("No program is stopped at a breakpoint");
+        }
+        state.resume = true;
+        machine.step();
+        Ok(())
+    }
+}
+
+/// The `STOP` command.
+pub struct StopCommand {
+    metadata: CallableMetadata,
+}
+
+impl StopCommand {
+    /// Creates a new `STOP` command that hands control to the debugger.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("STOP", VarType::Void)
+                .with_syntax("")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Stops the program as if it had reached a breakpoint.
+Execution stops right after STOP runs and can be resumed with CONT or STEP, which continue with \
+the statement that follows STOP.
+If the program is not running under RUN, such as when STOP is typed at the prompt, this behaves \
+like END instead.",
+                )
+                .build(),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for StopCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        if !args.is_empty() {
+            return new_usage_error("STOP takes no arguments");
+        }
+        machine.request_stop();
+        Ok(())
+    }
+}
+
+/// The `UNBREAK` command.
+pub struct UnbreakCommand {
+    metadata: CallableMetadata,
+}
+
+impl UnbreakCommand {
+    /// Creates a new `UNBREAK` command that clears breakpoints.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("UNBREAK", VarType::Void)
+                .with_syntax("line%")
+                .with_category(CATEGORY)
+                .with_description("Clears the breakpoint on the given line of the stored program.")
+                .build(),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for UnbreakCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        let line = parse_line("UNBREAK", args, machine)?;
+        if !machine.remove_breakpoint(line) {
+            return new_usage_error(format!("No breakpoint at line {}", line));
+        }
+        Ok(())
+    }
+}
+
+/// Extracts the name of the variable given as the single argument in `args` to the command `name`.
+fn parse_var_name<'a>(name: &str, args: &'a [(Option<Expr>, ArgSep)]) -> Result<&'a str> {
+    match args {
+        [(Some(Expr::Symbol(vref)), ArgSep::End)] => Ok(vref.name()),
+        _ => new_usage_error(format!("{} requires a variable name", name)),
+    }
+}
+
+/// The `UNWATCH` command.
+pub struct UnwatchCommand {
+    metadata: CallableMetadata,
+}
+
+impl UnwatchCommand {
+    /// Creates a new `UNWATCH` command that stops watching variables.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("UNWATCH", VarType::Void)
+                .with_syntax("variable")
+                .with_category(CATEGORY)
+                .with_description("Stops printing the value of a variable when it changes.")
+                .build(),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for UnwatchCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        let name = parse_var_name("UNWATCH", args)?;
+        if !machine.remove_watch(name) {
+            return new_usage_error(format!("No watch on {}", name.to_ascii_uppercase()));
+        }
+        Ok(())
+    }
+}
+
+/// The `WATCH` command.
+pub struct WatchCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl WatchCommand {
+    /// Creates a new `WATCH` command that sets watches and lists them on the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("WATCH", VarType::Void)
+                .with_syntax("[variable]")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Prints the value of a variable whenever it changes, or lists all watches.
+With a variable name, prints a line of the form WATCH name = value every time the variable is \
+assigned a new value, including when it is the iterator of a FOR loop or when a command such as \
+INPUT stores a value into it.  The type annotation of \
+the variable, if any, is irrelevant.
+Without arguments, lists the variables that are being watched.",
+                )
+                .build(),
+            console,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for WatchCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        if args.is_empty() {
+            let mut console = self.console.borrow_mut();
+            if machine.get_watches().is_empty() {
+                console.print("No watches set")?;
+            }
+            for name in machine.get_watches() {
+                console.print(&format!("Watching {}", name))?;
+            }
+            return Ok(());
+        }
+
+        let name = parse_var_name("WATCH", args)?;
+        machine.add_watch(name);
+        Ok(())
+    }
+}
+
+/// Adds all debugging commands to the `machine` and makes breakpoints read further commands from
+/// the `console`.  The `program` is used to show the source of the lines being stepped through.
+pub fn add_all(
+    machine: &mut Machine,
+    console: Rc<RefCell<dyn Console>>,
+    program: Rc<RefCell<dyn Program>>,
+) -> Result<()> {
+    let state = Rc::from(RefCell::from(DebugState::default()));
+    machine.set_debugger(Rc::from(ConsoleDebugger {
+        console: console.clone(),
+        program: program.clone(),
+        state: state.clone(),
+    }));
+    machine.add_command(BreakCommand::new(console.clone()))?;
+    machine.add_command(ContCommand::new(state.clone()))?;
+    machine.add_command(LexCommand::new(console.clone(), program.clone()))?;
+    machine.add_command(ParsetreeCommand::new(console.clone(), program.clone()))?;
+    machine.add_command(ProfileCommand::new(console.clone()))?;
+    machine.add_command(StepCommand::new(state))?;
+    machine.add_command(StopCommand::new())?;
+    machine.add_command(UnbreakCommand::new())?;
+    machine.add_command(UnwatchCommand::new())?;
+    machine.add_command(WatchCommand::new(console))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutils::*;
+    use endbasic_core::exec::StopReason;
+
+    #[test]
+    fn test_break_list() {
+        Tester::default().run("BREAK").expect_prints(["No breakpoints set"]).check();
+        Tester::default()
+            .run("BREAK 20: BREAK 3: BREAK 20: BREAK")
+            .expect_prints(["Breakpoint at line 3", "Breakpoint at line 20"])
+            .check();
+    }
+
+    #[test]
+    fn test_break_errors() {
+        check_stmt_err("BREAK requires a line number", "BREAK 1, 2");
+        check_stmt_err("Line number must be positive", "BREAK 0");
+        check_stmt_err("Line number must be an integer", "BREAK \"a\"");
+    }
+
+    #[test]
+    fn test_lex_code() {
+        Tester::default()
+            .run(r#"LEX "PRINT a$; 2 <> 3.5": LEX "IF x THEN: END IF""#)
+            .expect_prints([
+                "1:1 SYMBOL PRINT",
+                "1:7 SYMBOL a$",
+                "1:9 ;",
+                "1:11 INTEGER 2",
+                "1:13 <>",
+                "1:16 DOUBLE 3.5",
+                "1:1 IF",
+                "1:4 SYMBOL x",
+                "1:6 THEN",
+                "1:10 EOL",
+                "1:12 END",
+                "1:16 IF",
+            ])
+            .check();
+    }
+
+    #[test]
+    fn test_lex_program() {
+        let program = "a = \"x\" ' Comment\nb = 1 @";
+        Tester::default()
+            .set_program(program)
+            .run("LEX")
+            .expect_prints([
+                "1:1 SYMBOL a",
+                "1:3 =",
+                "1:5 TEXT \"x\"",
+                "1:9 EOL",
+                "2:1 SYMBOL b",
+                "2:3 =",
+                "2:5 INTEGER 1",
+                "2:7 BAD Unknown character: @",
+            ])
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_lex_errors() {
+        check_stmt_err("LEX requires a string as the code", "LEX 1");
+        check_stmt_err("LEX takes zero or one argument", "LEX \"a\", \"b\"");
+    }
+
+    #[test]
+    fn test_parsetree_code() {
+        Tester::default()
+            .run(r#"PARSETREE "IF a THEN: b = 1 + 2 * c: END IF""#)
+            .expect_prints(["1:1 IF", "  BRANCH a", "    1:12 ASSIGNMENT b (+ 1 (* 2 c))"])
+            .check();
+    }
+
+    #[test]
+    fn test_parsetree_program() {
+        let program = "FOR i = 1 TO 3\n    PRINT i; \"x\"\nNEXT";
+        Tester::default()
+            .set_program(program)
+            .run("PARSETREE")
+            .expect_prints([
+                "1:1 FOR i 1 3 STEP 1",
+                "  2:5 BUILTIN PRINT",
+                "    ARG i SHORT",
+                "    ARG \"x\" END",
+            ])
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_parsetree_errors() {
+        check_stmt_err("PARSETREE requires a string as the code", "PARSETREE 1");
+        check_stmt_err("PARSETREE takes zero or one argument", "PARSETREE \"a\", \"b\"");
+        Tester::default()
+            .set_program("a = 1\nb = (")
+            .run("PARSETREE")
+            .expect_err("Unbalanced parenthesis")
+            .expect_program("a = 1\nb = (")
+            .check();
+    }
+
+    #[test]
+    fn test_profile() {
+        let program = "a = 0\nFOR i = 1 TO 7\n    a = a + i\nNEXT\nPRINT a: PRINT i";
+        Tester::default()
+            .set_program(program)
+            .run("PROFILE REPORT: PROFILE ON: RUN: PROFILE off: RUN: PROFILE REPORT")
+            .expect_prints([
+                "No profiling data",
+                "28",
+                "8",
+                "28",
+                "8",
+                "Line 3: 7",
+                "Line 5: 2",
+                "Line 1: 1",
+                "Line 2: 1",
+            ])
+            .expect_var("a", 28)
+            .expect_var("i", 8)
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_profile_resets_on_start() {
+        let program = "a = 1";
+        Tester::default()
+            .set_program(program)
+            .run("PROFILE ON: RUN: RUN: PROFILE REPORT: PROFILE ON: PROFILE REPORT")
+            .expect_prints(["Line 1: 2", "No profiling data"])
+            .expect_var("a", 1)
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_profile_errors() {
+        check_stmt_err("PROFILE requires ON, OFF or REPORT", "PROFILE");
+        check_stmt_err("PROFILE requires ON, OFF or REPORT", "PROFILE START");
+        check_stmt_err("PROFILE requires ON, OFF or REPORT", "PROFILE on$");
+        check_stmt_err("PROFILE requires ON, OFF or REPORT", "PROFILE ON, OFF");
+    }
+
+    #[test]
+    fn test_step() {
+        let program = "a = 1\nb = 2\nFOR i = 1 TO 2\n    a = a * 10\nNEXT\nPRINT a";
+        Tester::default()
+            .set_program(program)
+            .add_input_chars("STEP\nPRINT b\nSTEP\nSTEP\nPRINT a\nSTEP\nPRINT a\nCONT\n")
+            .run("BREAK 2: RUN")
+            .expect_prints([
+                "Break at line 2; type CONT to resume",
+                "Step at line 3: FOR i = 1 TO 2",
+                "2",
+                "Step at line 4: a = a * 10",
+                "Step at line 4: a = a * 10",
+                "10",
+                "Step at line 6: PRINT a",
+                "100",
+                "100",
+            ])
+            .expect_var("a", 100)
+            .expect_var("b", 2)
+            .expect_var("i", 3)
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_step_past_end() {
+        let program = "a = 1\nb = 2";
+        Tester::default()
+            .set_program(program)
+            .add_input_chars("STEP\n")
+            .run("BREAK 2: RUN")
+            .expect_prints(["Break at line 2; type CONT to resume", "Program finished"])
+            .expect_var("a", 1)
+            .expect_var("b", 2)
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_step_errors() {
+        check_stmt_err("No program is stopped at a breakpoint", "STEP");
+        check_stmt_err("STEP takes no arguments", "STEP 1");
+    }
+
+    #[test]
+    fn test_stop_and_cont() {
+        let program = "a = 1\nFOR i = 1 TO 2\n    a = a * 10: STOP\nNEXT\nPRINT a";
+        Tester::default()
+            .set_program(program)
+            .add_input_chars("PRINT a\nCONT\nSTEP\nCONT\n")
+            .run("RUN")
+            .expect_prints([
+                "Stopped at line 3; type CONT to resume",
+                "10",
+                "Stopped at line 3; type CONT to resume",
+                "Step at line 5: PRINT a",
+                "100",
+            ])
+            .expect_var("a", 100)
+            .expect_var("i", 3)
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_stop_outside_run() {
+        Tester::default()
+            .run("a = 1: STOP: a = 2")
+            .expect_ok(StopReason::End(0))
+            .expect_var("a", 1)
+            .check();
+    }
+
+    #[test]
+    fn test_stop_errors() {
+        check_stmt_err("STOP takes no arguments", "STOP 1");
+    }
+
+    #[test]
+    fn test_unbreak() {
+        Tester::default()
+            .run("BREAK 3: BREAK 5: UNBREAK 3: BREAK")
+            .expect_prints(["Breakpoint at line 5"])
+            .check();
+    }
+
+    #[test]
+    fn test_unbreak_errors() {
+        check_stmt_err("No breakpoint at line 3", "UNBREAK 3");
+        check_stmt_err("UNBREAK requires a line number", "UNBREAK");
+    }
+
+    #[test]
+    fn test_break_and_cont() {
+        let program = "a = 1\nFOR i = 1 TO 2\n    a = a * 10\nNEXT\nPRINT a";
+        Tester::default()
+            .set_program(program)
+            .add_input_chars("PRINT a\nCONT\na = 5: CONT\n")
+            .run("BREAK 3: RUN")
+            .expect_prints([
+                "Break at line 3; type CONT to resume",
+                "1",
+                "Break at line 3; type CONT to resume",
+                "50",
+            ])
+            .expect_var("a", 50)
+            .expect_var("i", 3)
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_break_and_end() {
+        let program = "a = 1\nb = 2\nc = 3";
+        Tester::default()
+            .set_program(program)
+            .add_input_chars("VARS\nEND\n")
+            .run("BREAK 2: RUN")
+            .expect_prints(["Break at line 2; type CONT to resume", "A% = 1"])
+            .expect_var("a", 1)
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_break_errors_while_stopped() {
+        let program = "a = 1\nb = 2";
+        Tester::default()
+            .set_program(program)
+            .add_input_chars("a = undef\nCONT\n")
+            .run("BREAK 2: RUN")
+            .expect_prints([
+                "Break at line 2; type CONT to resume",
+                "ERROR: Undefined variable undef",
+            ])
+            .expect_var("a", 1)
+            .expect_var("b", 2)
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_breakpoints_ignored_outside_run() {
+        Tester::default().run("BREAK 1: a = 1").expect_var("a", 1).check();
+    }
+
+    #[test]
+    fn test_watch_list() {
+        Tester::default().run("WATCH").expect_prints(["No watches set"]).check();
+        Tester::default()
+            .run("WATCH b: WATCH a$: WATCH B%: WATCH")
+            .expect_prints(["Watching A", "Watching B"])
+            .check();
+    }
+
+    #[test]
+    fn test_watch_notifications() {
+        Tester::default()
+            .run(
+                "WATCH a: WATCH s: WATCH i: b = 1: a = b + 1: FOR i = 1 TO 2: a = a * 2: NEXT: \
+                 s$ = \"x\": UNWATCH i: i = 10: a = 0.5",
+            )
+            .expect_prints([
+                "WATCH A% = 2",
+                "WATCH I% = 1",
+                "WATCH A% = 4",
+                "WATCH I% = 2",
+                "WATCH A% = 8",
+                "WATCH I% = 3",
+                "WATCH S$ = \"x\"",
+            ])
+            .expect_var("a", 8)
+            .expect_var("b", 1)
+            .expect_var("i", 10)
+            .expect_var("s", "x")
+            .expect_err("Incompatible types in a assignment")
+            .check();
+    }
+
+    #[test]
+    fn test_watch_notifications_from_commands() {
+        Tester::default()
+            .add_input_chars("5\n7\n")
+            .write_file("in.bas", "hello\n")
+            .run(
+                "WATCH a: WATCH s: WATCH ok: a = 1: INPUT \"\", a: \
+                 OPEN \"in.bas\" FOR INPUT AS #1: LINE INPUT #1, s$: CLOSE #1: \
+                 TIMEDINPUT 60, \"\", a, ok?",
+            )
+            .expect_prints([
+                "WATCH A% = 1",
+                "WATCH A% = 5",
+                "WATCH S$ = \"hello\"",
+                "WATCH A% = 7",
+                "WATCH OK? = TRUE",
+            ])
+            .expect_var("a", 7)
+            .expect_var("ok", true)
+            .expect_var("s", "hello")
+            .expect_file("in.bas", "hello\n")
+            .check();
+    }
+
+    #[test]
+    fn test_watch_errors() {
+        check_stmt_err("WATCH requires a variable name", "WATCH 3");
+        check_stmt_err("WATCH requires a variable name", "WATCH a, b");
+    }
+
+    #[test]
+    fn test_unwatch_errors() {
+        check_stmt_err("No watch on A", "UNWATCH a");
+        check_stmt_err("UNWATCH requires a variable name", "UNWATCH");
+    }
+
+    #[test]
+    fn test_cont_errors() {
+        check_stmt_err("No program is stopped at a breakpoint", "CONT");
+        check_stmt_err("CONT takes no arguments", "CONT 1");
+    }
+}
@@ -15,11 +15,13 @@
 
 //! Interactive console-based text editor.
 
-use crate::console::{ClearType, Console, Key, Position};
+use crate::console::{expand_tabs, ClearType, Console, Key, Position, DEFAULT_TAB_WIDTH};
 use crate::store::Program;
 use async_trait::async_trait;
+use std::cell::Cell;
 use std::cmp;
 use std::io;
+use std::rc::Rc;
 
 /// The color of the main editor window.
 const TEXT_COLOR: (Option<u8>, Option<u8>) = (Some(15), None);
@@ -55,21 +57,42 @@ pub struct Editor {
     /// Last edited column, used when moving vertically to preserve the insertion point even when
     /// traversing shorter lines.
     insert_col: usize,
+
+    /// Name of the file that backs the contents, if any.
+    name: Option<String>,
+
+    /// Width of the tab stops used to display tab characters.
+    tab_width: Rc<Cell<usize>>,
 }
 
 impl Default for Editor {
     /// Creates a new editor without any stored contents.
     fn default() -> Self {
+        Self::new(Rc::from(Cell::new(DEFAULT_TAB_WIDTH)))
+    }
+}
+
+impl Editor {
+    /// Creates a new editor without any stored contents that displays tabs up to the next
+    /// multiple of `tab_width`.
+    pub fn new(tab_width: Rc<Cell<usize>>) -> Self {
         Self {
             content: vec![],
             viewport_pos: Position { row: 0, column: 0 },
             file_pos: Position { row: 0, column: 0 },
             insert_col: 0,
+            name: None,
+            tab_width,
         }
     }
-}
 
-impl Editor {
+    /// Returns the column in the console where the insertion position is displayed, given that
+    /// tabs before it take more than one column.
+    fn display_column(&self) -> usize {
+        let line = &self.content[self.file_pos.row];
+        expand_tabs(&line[..self.file_pos.column], self.tab_width.get()).len()
+    }
+
     /// Rewrites the status line at the bottom of the `console`, using the previously queried
     /// `console_size`.
     ///
@@ -111,7 +134,7 @@ impl Editor {
         let mut row = self.viewport_pos.row;
         let mut printed_rows = 0;
         while row < self.content.len() && printed_rows < console_size.row - 1 {
-            let line = &self.content[row];
+            let line = &expand_tabs(&self.content[row], self.tab_width.get());
             if line.len() >= self.viewport_pos.column {
                 let last = cmp::min(line.len(), self.viewport_pos.column + console_size.column);
                 let view = &line[self.viewport_pos.column..last];
@@ -144,11 +167,14 @@ impl Editor {
                 self.viewport_pos.row += 1;
                 need_refresh = true;
             }
-            if self.file_pos.column < self.viewport_pos.column {
-                self.viewport_pos.column = self.file_pos.column;
+            // The horizontal viewport is tracked in console columns, which differ from the file
+            // columns when there are tabs in the line.
+            let display_column = self.display_column();
+            if display_column < self.viewport_pos.column {
+                self.viewport_pos.column = display_column;
                 need_refresh = true;
-            } else if self.file_pos.column >= self.viewport_pos.column + console_size.column {
-                self.viewport_pos.column = self.file_pos.column - console_size.column + 1;
+            } else if display_column >= self.viewport_pos.column + console_size.column {
+                self.viewport_pos.column = display_column - console_size.column + 1;
                 need_refresh = true;
             }
 
@@ -160,7 +186,10 @@ impl Editor {
                 self.refresh_status(console, console_size)?;
                 console.color(TEXT_COLOR.0, TEXT_COLOR.1)?;
             }
-            let cursor_pos = self.file_pos - self.viewport_pos;
+            let cursor_pos = Position {
+                row: self.file_pos.row - self.viewport_pos.row,
+                column: display_column - self.viewport_pos.column,
+            };
             console.locate(cursor_pos)?;
             console.show_cursor()?;
 
@@ -202,7 +231,9 @@ impl Editor {
                 Key::Backspace => {
                     if self.file_pos.column > 0 {
                         let line = &mut self.content[self.file_pos.row];
-                        if self.file_pos.column == line.len() {
+                        if self.file_pos.column == line.len()
+                            && line.as_bytes()[self.file_pos.column - 1] != b'\t'
+                        {
                             console.write(b"\x08 \x08")?;
                         } else {
                             // TODO(jmmv): Refresh only the affected line.
@@ -253,6 +284,9 @@ impl Editor {
                     self.insert_col = self.file_pos.column;
                 }
 
+                // TODO(jmmv): Should insert indentation.
+                Key::Tab => (),
+
                 // TODO(jmmv): Should do something smarter with unknown keys.
                 Key::Unknown(_) => (),
             }
@@ -281,6 +315,14 @@ impl Program for Editor {
     fn text(&self) -> String {
         self.content.iter().fold(String::new(), |contents, line| contents + line + "\n")
     }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<&str>) {
+        self.name = name.map(str::to_owned);
+    }
 }
 
 #[cfg(test)]
@@ -422,6 +464,28 @@ mod tests {
         run_editor("previous content", "previous content\n", cb, ob);
     }
 
+    #[test]
+    fn test_tabs_are_expanded_for_display() {
+        let mut console = MockConsole::default();
+        console.set_size(rowcol(10, 40));
+        let mut ob = OutputBuilder::new(rowcol(10, 40));
+        ob = ob.refresh(rowcol(0, 0), &["a   b", "        c"], rowcol(0, 0));
+
+        console.add_input_keys(&[Key::ArrowRight, Key::ArrowRight]);
+        ob = ob.quick_refresh(rowcol(0, 1), rowcol(0, 1));
+        ob = ob.quick_refresh(rowcol(0, 2), rowcol(0, 4));
+
+        console.add_input_keys(&[Key::ArrowDown]);
+        ob = ob.quick_refresh(rowcol(1, 2), rowcol(1, 8));
+
+        let mut editor = Editor::new(Rc::from(Cell::new(4)));
+        editor.load("a\tb\n\t\tc\n");
+        console.add_input_keys(&[Key::Escape]);
+        block_on(editor.edit(&mut console)).unwrap();
+        assert_eq!("a\tb\n\t\tc\n", editor.text());
+        assert_eq!(ob.build(), console.captured_out());
+    }
+
     #[test]
     fn test_insert_in_empty_file() {
         let mut cb = MockConsole::default();
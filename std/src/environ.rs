@@ -0,0 +1,409 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Access to the environment of the interpreter's process.
+//!
+//! The web interface runs without a process environment, so in there all variables read as empty,
+//! setting them does nothing, and there are no program arguments.
+
+use async_trait::async_trait;
+use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
+use endbasic_core::eval::{
+    CallableMetadata, CallableMetadataBuilder, Function, FunctionError, FunctionResult,
+};
+use endbasic_core::exec::{self, Command, Machine};
+use std::rc::Rc;
+
+/// Category string for all functions provided by this module.
+const CATEGORY: &str = "Environment manipulation";
+
+/// Returns true if `name` can be used as the name of an environment variable.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains('=') && !name.contains('\0')
+}
+
+/// Returns the value of the environment variable `name`, or an empty string if it is not set.
+#[cfg(not(target_arch = "wasm32"))]
+fn get_env(name: &str) -> String {
+    match std::env::var_os(name) {
+        Some(value) => value.to_string_lossy().into_owned(),
+        None => String::new(),
+    }
+}
+
+/// Returns the value of the environment variable `name`, which is always empty on the web.
+#[cfg(target_arch = "wasm32")]
+fn get_env(_name: &str) -> String {
+    String::new()
+}
+
+/// Sets the environment variable `name` to `value`.
+#[cfg(not(target_arch = "wasm32"))]
+fn set_env(name: &str, value: &str) {
+    std::env::set_var(name, value);
+}
+
+/// Ignores the request to set an environment variable, as there is none on the web.
+#[cfg(target_arch = "wasm32")]
+fn set_env(_name: &str, _value: &str) {}
+
+/// The `ARG` function.
+pub struct ArgFunction {
+    metadata: CallableMetadata,
+    args: Rc<Vec<String>>,
+}
+
+impl ArgFunction {
+    /// Creates a new instance of the function to query the program arguments in `args`.
+    pub fn new(args: Rc<Vec<String>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("ARG", VarType::Text)
+                .with_syntax("n%")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Returns the n%th argument given to the program.
+Arguments are numbered from 1 to ARGC.  Returns an empty string if n% is out of range.",
+                )
+                .build(),
+            args,
+        })
+    }
+}
+
+impl Function for ArgFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Integer(n)] => {
+                let arg = if *n < 1 { None } else { self.args.get(*n as usize - 1) };
+                Ok(Value::Text(arg.cloned().unwrap_or_default()))
+            }
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
+/// The `ARGC` function.
+pub struct ArgcFunction {
+    metadata: CallableMetadata,
+    args: Rc<Vec<String>>,
+}
+
+impl ArgcFunction {
+    /// Creates a new instance of the function to count the program arguments in `args`.
+    pub fn new(args: Rc<Vec<String>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("ARGC", VarType::Integer)
+                .with_syntax("")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Returns the number of arguments given to the program.
+Only programs run from the command line receive arguments, which are those that follow the \
+program's file name.",
+                )
+                .build(),
+            args,
+        })
+    }
+}
+
+impl Function for ArgcFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [] => {
+                if self.args.len() > i32::MAX as usize {
+                    Err(FunctionError::InternalError("Too many arguments".to_owned()))
+                } else {
+                    Ok(Value::Integer(self.args.len() as i32))
+                }
+            }
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
+/// The `COMMAND` function.
+pub struct CommandFunction {
+    metadata: CallableMetadata,
+    args: Rc<Vec<String>>,
+}
+
+impl CommandFunction {
+    /// Creates a new instance of the function to query the program arguments in `args`.
+    pub fn new(args: Rc<Vec<String>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("COMMAND", VarType::Text)
+                .with_syntax("")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Returns all arguments given to the program.
+The arguments are separated by a single space.  Use ARG$ to access them individually.",
+                )
+                .build(),
+            args,
+        })
+    }
+}
+
+impl Function for CommandFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [] => Ok(Value::Text(self.args.join(" "))),
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
+/// The `ENVIRON` function.
+pub struct EnvironFunction {
+    metadata: CallableMetadata,
+}
+
+impl EnvironFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("ENVIRON", VarType::Text)
+                .with_syntax("name$")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Returns the value of an environment variable.
+Returns an empty string if the variable is not set.  The web interface has no environment \
+variables, so this always returns an empty string in there.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for EnvironFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Text(name)] => {
+                if !is_valid_name(name) {
+                    return Err(FunctionError::ArgumentError(format!(
+                        "Invalid environment variable name {}",
+                        name
+                    )));
+                }
+                Ok(Value::Text(get_env(name)))
+            }
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
+/// The `SETENV` command.
+pub struct SetenvCommand {
+    metadata: CallableMetadata,
+}
+
+impl SetenvCommand {
+    /// Creates a new `SETENV` command.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("SETENV", VarType::Void)
+                .with_syntax("name$, value$")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Sets the value of an environment variable.
+The new value is visible to ENVIRON$ and to any commands started afterwards.  The web interface \
+has no environment variables, so this does nothing in there.",
+                )
+                .build(),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for SetenvCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let (name, value) = match args {
+            [(Some(name), ArgSep::Long), (Some(value), ArgSep::End)] => (
+                name.eval(machine.get_vars(), machine.get_functions())?,
+                value.eval(machine.get_vars(), machine.get_functions())?,
+            ),
+            _ => return exec::new_usage_error("SETENV requires a name and a value"),
+        };
+        match (name, value) {
+            (Value::Text(name), Value::Text(value)) => {
+                if !is_valid_name(&name) {
+                    return exec::new_usage_error(format!(
+                        "Invalid environment variable name {}",
+                        name
+                    ));
+                }
+                if value.contains('\0') {
+                    return exec::new_usage_error("Environment variable values cannot contain NUL");
+                }
+                set_env(&name, &value);
+                Ok(())
+            }
+            _ => exec::new_usage_error("SETENV requires strings as the name and the value"),
+        }
+    }
+}
+
+/// Adds all environment-related functions and commands to the `machine`, using `args` as the
+/// arguments given to the program.
+pub fn add_all(machine: &mut Machine, args: Vec<String>) -> exec::Result<()> {
+    let args: Rc<Vec<String>> = Rc::from(args);
+    machine.add_command(SetenvCommand::new())?;
+    machine.add_function(ArgFunction::new(args.clone()))?;
+    machine.add_function(ArgcFunction::new(args.clone()))?;
+    machine.add_function(CommandFunction::new(args))?;
+    machine.add_function(EnvironFunction::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::*;
+    use std::env;
+
+    fn tester(args: &[&str]) -> Tester {
+        let args: Rc<Vec<String>> =
+            Rc::from(args.iter().map(|a| a.to_string()).collect::<Vec<_>>());
+        Tester::from(Machine::default())
+            .add_function(ArgFunction::new(args.clone()))
+            .add_function(ArgcFunction::new(args.clone()))
+            .add_function(CommandFunction::new(args))
+    }
+
+    #[test]
+    fn test_args_none() {
+        Tester::default()
+            .run(r#"c$ = COMMAND$: n = ARGC: a$ = ARG$(1)"#)
+            .expect_var("c", "")
+            .expect_var("n", 0)
+            .expect_var("a", "")
+            .check();
+    }
+
+    #[test]
+    fn test_args_some() {
+        tester(&["first", "second arg", "-x"])
+            .run(
+                r#"c$ = COMMAND$: n = ARGC
+                a0$ = ARG$(0): a1$ = ARG$(1): a2$ = ARG$(2): a3$ = ARG$(3): a4$ = ARG$(4)
+                neg$ = ARG$(-1)"#,
+            )
+            .expect_var("c", "first second arg -x")
+            .expect_var("n", 3)
+            .expect_var("a0", "")
+            .expect_var("a1", "first")
+            .expect_var("a2", "second arg")
+            .expect_var("a3", "-x")
+            .expect_var("a4", "")
+            .expect_var("neg", "")
+            .check();
+    }
+
+    #[test]
+    fn test_args_with_parens() {
+        tester(&["a", "b"])
+            .run(r#"c$ = COMMAND$(): n = ARGC()"#)
+            .expect_var("c", "a b")
+            .expect_var("n", 2)
+            .check();
+    }
+
+    #[test]
+    fn test_args_errors() {
+        let t = || tester(&["a"]);
+        t().run("x = ARG$()").expect_err("Syntax error in call to ARG: expected n%").check();
+        t().run(r#"x = ARG$("1")"#).expect_err("Syntax error in call to ARG: expected n%").check();
+        t().run("x = ARGC(1)")
+            .expect_err("Syntax error in call to ARGC: expected no arguments")
+            .check();
+        t().run("x$ = COMMAND$(1)")
+            .expect_err("Syntax error in call to COMMAND: expected no arguments")
+            .check();
+    }
+
+    #[test]
+    fn test_environ_set() {
+        env::set_var("ENDBASIC_TEST_ENVIRON_SET", "some value");
+        check_expr_ok("some value", r#"ENVIRON$("ENDBASIC_TEST_ENVIRON_SET")"#);
+    }
+
+    #[test]
+    fn test_environ_unset() {
+        env::remove_var("ENDBASIC_TEST_ENVIRON_UNSET");
+        check_expr_ok("", r#"ENVIRON$("ENDBASIC_TEST_ENVIRON_UNSET")"#);
+    }
+
+    #[test]
+    fn test_environ_errors() {
+        check_expr_error("Syntax error in call to ENVIRON: expected name$", r#"ENVIRON()"#);
+        check_expr_error("Syntax error in call to ENVIRON: expected name$", r#"ENVIRON(3)"#);
+        check_expr_error(
+            "Syntax error in call to ENVIRON: Invalid environment variable name ",
+            r#"ENVIRON("")"#,
+        );
+        check_expr_error(
+            "Syntax error in call to ENVIRON: Invalid environment variable name A=B",
+            r#"ENVIRON("A=B")"#,
+        );
+    }
+
+    #[test]
+    fn test_setenv_ok() {
+        Tester::default()
+            .run(r#"SETENV "ENDBASIC_TEST_SETENV", "first": a$ = ENVIRON$("ENDBASIC_TEST_SETENV")"#)
+            .expect_var("a", "first")
+            .check();
+        assert_eq!("first", env::var("ENDBASIC_TEST_SETENV").unwrap());
+
+        Tester::default()
+            .run(r#"SETENV "ENDBASIC_TEST_SETENV", "": a$ = ENVIRON$("ENDBASIC_TEST_SETENV")"#)
+            .expect_var("a", "")
+            .check();
+    }
+
+    #[test]
+    fn test_setenv_errors() {
+        check_stmt_err("SETENV requires a name and a value", "SETENV");
+        check_stmt_err("SETENV requires a name and a value", r#"SETENV "A""#);
+        check_stmt_err("SETENV requires a name and a value", r#"SETENV "A"; "B""#);
+        check_stmt_err("SETENV requires strings as the name and the value", r#"SETENV "A", 1"#);
+        check_stmt_err("SETENV requires strings as the name and the value", r#"SETENV 1, "B""#);
+        check_stmt_err("Invalid environment variable name ", r#"SETENV "", "B""#);
+        check_stmt_err("Invalid environment variable name A=B", r#"SETENV "A=B", "C""#);
+    }
+}
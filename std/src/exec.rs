@@ -15,12 +15,65 @@
 
 //! Commands that directly manipulate the machine's state.
 
+use crate::console::Console;
 use async_trait::async_trait;
 use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
-use endbasic_core::eval::{CallableMetadata, CallableMetadataBuilder};
+use endbasic_core::eval::{
+    CallableMetadata, CallableMetadataBuilder, Function, FunctionError, FunctionResult,
+};
 use endbasic_core::exec::{new_usage_error, Command, Machine, Result};
+use std::cell::RefCell;
 use std::rc::Rc;
 
+/// The `ASSERT` command.
+pub struct AssertCommand {
+    metadata: CallableMetadata,
+}
+
+impl AssertCommand {
+    /// Creates a new command that fails execution if a condition is false.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("ASSERT", VarType::Void)
+                .with_syntax("cond?[, message$]")
+                .with_category("Interpreter manipulation")
+                .with_description(
+                    "Stops execution with an error if a condition is false.
+Does nothing if cond? is true.  Otherwise, raises an error with the given message$, or with a \
+default message if none is given.  This is useful to write programs that test themselves.",
+                )
+                .build(),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for AssertCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        let (cond, message) = match args {
+            [(Some(cond), ArgSep::End)] => (cond, None),
+            [(Some(cond), ArgSep::Long), (Some(message), ArgSep::End)] => (cond, Some(message)),
+            _ => return new_usage_error("ASSERT takes one or two arguments"),
+        };
+
+        match cond.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Boolean(true) => Ok(()),
+            Value::Boolean(false) => match message {
+                None => new_usage_error("Assertion failed"),
+                Some(message) => match message.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Text(message) => new_usage_error(message),
+                    _ => new_usage_error("ASSERT message must be a string"),
+                },
+            },
+            _ => new_usage_error("ASSERT condition must be a boolean"),
+        }
+    }
+}
+
 /// The `CLEAR` command.
 pub struct ClearCommand {
     metadata: CallableMetadata,
@@ -33,7 +86,11 @@ impl ClearCommand {
             metadata: CallableMetadataBuilder::new("CLEAR", VarType::Void)
                 .with_syntax("")
                 .with_category("Interpreter manipulation")
-                .with_description("Clears all variables to restore initial state.")
+                .with_description(
+                    "Clears all variables to restore initial state.
+Options set with OPTION are restored to their defaults too.  The stored program is left \
+untouched.  Use NEW to clear the program as well.",
+                )
                 .build(),
         })
     }
@@ -49,7 +106,7 @@ impl Command for ClearCommand {
         if !args.is_empty() {
             return new_usage_error("CLEAR takes no arguments");
         }
-        machine.clear();
+        machine.clear()?;
         Ok(())
     }
 }
@@ -105,27 +162,236 @@ impl Command for ExitCommand {
     }
 }
 
+/// The `OPTION` command.
+pub struct OptionCommand {
+    metadata: CallableMetadata,
+}
+
+impl OptionCommand {
+    /// Creates a new `OPTION` command that changes the behavior of the machine.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("OPTION", VarType::Void)
+                .with_syntax("<BOOLEANS <WORDS|NUMBERS>|OVERFLOW <ERROR|WRAP>>")
+                .with_category("Interpreter manipulation")
+                .with_description(
+                    "Changes how the interpreter behaves.
+OPTION BOOLEANS sets how PRINT displays booleans.  By default, they are shown as the TRUE and \
+FALSE words (WORDS).  With NUMBERS, they are shown as -1 and 0 to match dialects where booleans \
+are integers.  Booleans remain booleans in expressions and variables either way.
+OPTION OVERFLOW sets what happens when integer arithmetic overflows the 32-bit range.  By \
+default, the program stops with an error (ERROR).  With WRAP, results wrap around instead, so \
+adding 1 to the largest integer yields the smallest one.  Divisions by zero remain errors.
+Options stay in effect until changed again or until CLEAR or RUN restore their defaults.",
+                )
+                .build(),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for OptionCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        let (option, value) = match args {
+            [(Some(Expr::Symbol(option)), ArgSep::Space), (Some(Expr::Symbol(value)), ArgSep::End)]
+                if option.ref_type() == VarType::Auto && value.ref_type() == VarType::Auto =>
+            {
+                (option.name().to_ascii_uppercase(), value.name().to_ascii_uppercase())
+            }
+            _ => return new_usage_error("OPTION requires an option name and a value"),
+        };
+
+        match (option.as_str(), value.as_str()) {
+            ("BOOLEANS", "WORDS") => machine.get_mut_vars().set_numeric_booleans(false),
+            ("BOOLEANS", "NUMBERS") => machine.get_mut_vars().set_numeric_booleans(true),
+            ("BOOLEANS", value) => {
+                return new_usage_error(format!("Invalid BOOLEANS value {}", value))
+            }
+            ("OVERFLOW", "ERROR") => machine.get_mut_vars().set_overflow_wraps(false),
+            ("OVERFLOW", "WRAP") => machine.get_mut_vars().set_overflow_wraps(true),
+            ("OVERFLOW", value) => {
+                return new_usage_error(format!("Invalid OVERFLOW value {}", value))
+            }
+            (option, _) => return new_usage_error(format!("Unknown option {}", option)),
+        }
+        Ok(())
+    }
+}
+
+/// The `TYPEOF` function.
+pub struct TypeofFunction {
+    metadata: CallableMetadata,
+}
+
+impl TypeofFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("TYPEOF", VarType::Text)
+                .with_syntax("expr")
+                .with_category("Interpreter manipulation")
+                .with_description(
+                    "Returns the name of the type of a value.
+The result is one of BOOLEAN, DOUBLE, INTEGER or STRING, or EMPTY for the empty value, and \
+reflects the type of the value that expr evaluates to rather than any type annotation.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for TypeofFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        let name = match args.as_slice() {
+            [Value::Boolean(_)] => "BOOLEAN",
+            [Value::Double(_)] => "DOUBLE",
+            [Value::Integer(_)] => "INTEGER",
+            [Value::Text(_)] => "STRING",
+            [Value::Empty] => "EMPTY",
+            _ => return Err(FunctionError::SyntaxError),
+        };
+        Ok(Value::Text(name.to_owned()))
+    }
+}
+
+/// The `VARS` command.
+pub struct VarsCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl VarsCommand {
+    /// Creates a new command that dumps all defined variables to the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("VARS", VarType::Void)
+                .with_syntax("")
+                .with_category("Interpreter manipulation")
+                .with_description(
+                    "Lists all defined variables and their values.
+Variables are printed sorted by name and each is shown with the type annotation that matches \
+the type of its value.  This is intended as a debugging aid.",
+                )
+                .build(),
+            console,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for VarsCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(&self, args: &[(Option<Expr>, ArgSep)], machine: &mut Machine) -> Result<()> {
+        if !args.is_empty() {
+            return new_usage_error("VARS takes no arguments");
+        }
+
+        let mut vars = machine.get_vars().as_hashmap().iter().collect::<Vec<(&String, &Value)>>();
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut console = self.console.borrow_mut();
+        for (name, value) in vars {
+            let (vtype, formatted) = match value {
+                Value::Boolean(_) => (VarType::Boolean, value.to_string()),
+                Value::Double(_) => (VarType::Double, value.to_string()),
+                Value::Integer(_) => (VarType::Integer, value.to_string()),
+                Value::Text(s) => (VarType::Text, format!("\"{}\"", s)),
+                Value::Empty => (VarType::Auto, "(empty)".to_owned()),
+            };
+            console.print(&format!("{}{} = {}", name, vtype.annotation(), formatted))?;
+        }
+        Ok(())
+    }
+}
+
 /// Instantiates all REPL commands and adds them to the `machine`.
-pub fn add_all(machine: &mut Machine) {
-    machine.add_command(ClearCommand::new());
-    machine.add_command(ExitCommand::new());
+pub fn add_all(machine: &mut Machine, console: Rc<RefCell<dyn Console>>) -> Result<()> {
+    machine.add_command(AssertCommand::new())?;
+    machine.add_command(ClearCommand::new())?;
+    machine.add_command(ExitCommand::new())?;
+    machine.add_command(OptionCommand::new())?;
+    machine.add_function(TypeofFunction::new())?;
+    machine.add_command(VarsCommand::new(console))
 }
 
 #[cfg(test)]
 mod tests {
     use crate::testutils::*;
+    use endbasic_core::ast::Value;
     use endbasic_core::exec::StopReason;
 
+    #[test]
+    fn test_assert_ok() {
+        Tester::default().run("ASSERT TRUE: a = 1").expect_var("a", 1).check();
+        Tester::default().run("ASSERT 3 > 2, \"Not reached\": a = 1").expect_var("a", 1).check();
+    }
+
+    #[test]
+    fn test_assert_fails() {
+        Tester::default().run("ASSERT FALSE: a = 1").expect_err("Assertion failed").check();
+        Tester::default()
+            .run("a = 1: ASSERT a = 2, \"a is not 2\": a = 3")
+            .expect_err("a is not 2")
+            .expect_var("a", 1)
+            .check();
+    }
+
+    #[test]
+    fn test_assert_errors() {
+        check_stmt_err("ASSERT takes one or two arguments", "ASSERT");
+        check_stmt_err("ASSERT takes one or two arguments", "ASSERT TRUE; \"foo\"");
+        check_stmt_err("ASSERT takes one or two arguments", "ASSERT TRUE, \"foo\", 3");
+        check_stmt_err("ASSERT condition must be a boolean", "ASSERT 1");
+        check_stmt_err("ASSERT message must be a string", "ASSERT FALSE, 3");
+    }
+
     #[test]
     fn test_clear_ok() {
         Tester::default().run("a = 1: CLEAR").check();
     }
 
+    #[test]
+    fn test_clear_removes_variables() {
+        Tester::default().run("a = 1: b$ = \"text\": CLEAR: c = 3").expect_var("c", 3).check();
+
+        Tester::default().run("a = 1: CLEAR: b = a").expect_err("Undefined variable a").check();
+    }
+
+    #[test]
+    fn test_clear_keeps_program() {
+        let program = "a = 1\nPRINT a\n";
+        Tester::default().set_program(program).run("a = 1: CLEAR").expect_program(program).check();
+    }
+
     #[test]
     fn test_clear_errors() {
         check_stmt_err("CLEAR takes no arguments", "CLEAR 123");
     }
 
+    #[test]
+    fn test_clear_restores_options() {
+        Tester::default()
+            .run("OPTION OVERFLOW WRAP: CLEAR: a = 2147483647 + 1")
+            .expect_err("Overflow adding 2147483647 and 1")
+            .check();
+
+        Tester::default()
+            .run("OPTION BOOLEANS NUMBERS: CLEAR: PRINT TRUE")
+            .expect_prints(["TRUE"])
+            .check();
+    }
+
     #[test]
     fn test_exit_no_code() {
         Tester::default()
@@ -157,4 +423,99 @@ mod tests {
         check_stmt_err("Exit code must be a positive integer", "EXIT -3");
         check_stmt_err("Exit code cannot be larger than 127", "EXIT 128");
     }
+
+    #[test]
+    fn test_option_overflow() {
+        Tester::default()
+            .run("a = 2147483647: b = a + 1")
+            .expect_err("Overflow adding 2147483647 and 1")
+            .expect_var("a", 2147483647)
+            .check();
+        Tester::default()
+            .run("a = 65536: b = a * a")
+            .expect_err("Overflow multiplying 65536 by 65536")
+            .expect_var("a", 65536)
+            .check();
+
+        Tester::default()
+            .run("OPTION OVERFLOW WRAP: a = 2147483647: b = a + 1: c = 65536 * 65537: d = -b")
+            .expect_var("a", 2147483647)
+            .expect_var("b", -2147483648)
+            .expect_var("c", 65536)
+            .expect_var("d", -2147483648)
+            .check();
+
+        Tester::default()
+            .run("option overflow wrap: option overflow error: a = 2147483647 * 2")
+            .expect_err("Overflow multiplying 2147483647 by 2")
+            .check();
+    }
+
+    #[test]
+    fn test_option_booleans() {
+        Tester::default()
+            .run("PRINT TRUE; FALSE: OPTION BOOLEANS NUMBERS: a = 1 < 2: PRINT a; 1 > 2, \"x\"")
+            .expect_prints(["TRUE FALSE", "-1 0          x"])
+            .expect_var("a", true)
+            .check();
+
+        Tester::default()
+            .run("option booleans numbers: option booleans words: PRINT TRUE")
+            .expect_prints(["TRUE"])
+            .check();
+    }
+
+    #[test]
+    fn test_option_errors() {
+        check_stmt_err("OPTION requires an option name and a value", "OPTION");
+        check_stmt_err("OPTION requires an option name and a value", "OPTION OVERFLOW");
+        check_stmt_err("OPTION requires an option name and a value", "OPTION OVERFLOW WRAP ERROR");
+        check_stmt_err("OPTION requires an option name and a value", "OPTION OVERFLOW, WRAP");
+        check_stmt_err("OPTION requires an option name and a value", "OPTION OVERFLOW WRAP$");
+        check_stmt_err("Invalid OVERFLOW value SATURATE", "OPTION OVERFLOW SATURATE");
+        check_stmt_err("Invalid BOOLEANS value TEXT", "OPTION BOOLEANS TEXT");
+        check_stmt_err("Unknown option FOO", "OPTION FOO BAR");
+    }
+
+    #[test]
+    fn test_typeof() {
+        check_expr_ok("BOOLEAN", "TYPEOF(TRUE)");
+        check_expr_ok("DOUBLE", "TYPEOF(1.5)");
+        check_expr_ok("INTEGER", "TYPEOF(3)");
+        check_expr_ok("STRING", "TYPEOF(\"x\")");
+        check_expr_ok("DOUBLE", "TYPEOF(ITOD(3) * 2.0)");
+
+        Tester::default()
+            .add_input_chars("\n")
+            .run("a% = 3: t1$ = TYPEOF$(a%): INPUT ; a%: t2$ = TYPEOF$(a)")
+            .expect_var("a", Value::Empty)
+            .expect_var("t1", "INTEGER")
+            .expect_var("t2", "EMPTY")
+            .check();
+
+        check_expr_error("Syntax error in call to TYPEOF: expected expr", "TYPEOF()");
+        check_expr_error("Syntax error in call to TYPEOF: expected expr", "TYPEOF(1, 2)");
+    }
+
+    #[test]
+    fn test_vars_empty() {
+        Tester::default().run("VARS").check();
+    }
+
+    #[test]
+    fn test_vars_dump() {
+        Tester::default()
+            .run("text$ = \"Hello\": b = TRUE: a = 3: D# = 2.5: VARS")
+            .expect_var("text", "Hello")
+            .expect_var("b", true)
+            .expect_var("a", 3)
+            .expect_var("d", 2.5)
+            .expect_prints(["A% = 3", "B? = TRUE", "D# = 2.5", "TEXT$ = \"Hello\""])
+            .check();
+    }
+
+    #[test]
+    fn test_vars_errors() {
+        check_stmt_err("VARS takes no arguments", "VARS 1");
+    }
 }
@@ -0,0 +1,1049 @@
+// EndBASIC
+// Copyright 2020 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! File manipulation through numbered channels backed by the store.
+
+use crate::store::{to_filename, Store};
+use async_trait::async_trait;
+use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
+use endbasic_core::eval::{
+    CallableMetadata, CallableMetadataBuilder, Function, FunctionError, FunctionResult,
+};
+use endbasic_core::exec::{self, Clearable, Command, Machine};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::rc::Rc;
+
+/// Largest channel number that can be given to `OPEN`.
+const MAX_CHANNEL: i32 = 255;
+
+/// A file open on a channel.
+enum OpenFile {
+    /// A file opened for input.
+    Input {
+        /// Lines that have not been read yet.
+        lines: VecDeque<String>,
+
+        /// Length of the file in bytes when it was opened.
+        length: usize,
+    },
+
+    /// A file opened for output whose contents are buffered in memory until it is closed.
+    Output {
+        /// Name of the file in the store.
+        name: String,
+
+        /// Contents written so far.
+        content: String,
+    },
+}
+
+/// Table of open files indexed by their channel number.
+///
+/// Channels are identified by the numbers given to `OPEN` in its `AS #n` clause, which must be in
+/// the `1..=255` range, and remain open until they are closed with `CLOSE`, until the program
+/// that opened them finishes, or until the machine is cleared.
+#[derive(Default)]
+pub struct Channels {
+    files: BTreeMap<i32, OpenFile>,
+}
+
+impl Channels {
+    /// Registers `file` on `channel`, which must not be open yet.
+    fn open(&mut self, channel: i32, file: OpenFile) -> exec::Result<()> {
+        if self.files.contains_key(&channel) {
+            return exec::new_usage_error(format!("Channel #{} is already open", channel));
+        }
+        self.files.insert(channel, file);
+        Ok(())
+    }
+
+    /// Returns the file open on `channel`.
+    fn get_mut(&mut self, channel: i32) -> exec::Result<&mut OpenFile> {
+        match self.files.get_mut(&channel) {
+            Some(file) => Ok(file),
+            None => exec::new_usage_error(format!("Channel #{} is not open", channel)),
+        }
+    }
+
+    /// Returns the lines not yet read from the file open for input on `channel`.
+    fn get_input(&mut self, channel: i32) -> exec::Result<&mut VecDeque<String>> {
+        match self.get_mut(channel)? {
+            OpenFile::Input { lines, .. } => Ok(lines),
+            OpenFile::Output { .. } => {
+                exec::new_usage_error(format!("Channel #{} is not open for input", channel))
+            }
+        }
+    }
+
+    /// Returns true if there are no more lines to read from the file open on `channel`.
+    fn is_eof(&mut self, channel: i32) -> exec::Result<bool> {
+        Ok(self.get_input(channel)?.is_empty())
+    }
+
+    /// Returns the length in bytes of the file open on `channel`.
+    ///
+    /// For files open for input, this is the length of the file when it was opened regardless of
+    /// how much of it has been read.  For files open for output, this is the length of the
+    /// contents written so far.
+    fn length(&mut self, channel: i32) -> exec::Result<usize> {
+        match self.get_mut(channel)? {
+            OpenFile::Input { length, .. } => Ok(*length),
+            OpenFile::Output { content, .. } => Ok(content.len()),
+        }
+    }
+
+    /// Reads the next line from the file open on `channel`.
+    pub(crate) fn read_line(&mut self, channel: i32) -> exec::Result<String> {
+        match self.get_input(channel)?.pop_front() {
+            Some(line) => Ok(line),
+            None => exec::new_usage_error(format!("End of file reached on channel #{}", channel)),
+        }
+    }
+
+    /// Appends a line with `text` to the file open on `channel`.
+    pub fn print(&mut self, channel: i32, text: &str) -> exec::Result<()> {
+        match self.get_mut(channel)? {
+            OpenFile::Input { .. } => {
+                exec::new_usage_error(format!("Channel #{} is not open for output", channel))
+            }
+            OpenFile::Output { content, .. } => {
+                content.push_str(text);
+                content.push('\n');
+                Ok(())
+            }
+        }
+    }
+
+    /// Closes all `channels` and returns the files that were open on them.
+    ///
+    /// All channels are validated before any of them is closed so that an invalid channel does
+    /// not leave the table partially closed.
+    fn close(&mut self, channels: &[i32]) -> exec::Result<Vec<OpenFile>> {
+        for (i, channel) in channels.iter().enumerate() {
+            if !self.files.contains_key(channel) {
+                return exec::new_usage_error(format!("Channel #{} is not open", channel));
+            }
+            if channels[..i].contains(channel) {
+                return exec::new_usage_error(format!("Channel #{} given more than once", channel));
+            }
+        }
+        Ok(channels.iter().map(|channel| self.files.remove(channel).unwrap()).collect())
+    }
+
+    /// Closes all open channels and returns their files in channel order.
+    fn close_all(&mut self) -> Vec<OpenFile> {
+        std::mem::take(&mut self.files).into_values().collect()
+    }
+}
+
+/// Validates that `value` is a channel number and returns it, or returns an error message.
+fn to_channel(value: Value) -> Result<i32, String> {
+    match value {
+        Value::Integer(i) if (1..=MAX_CHANNEL).contains(&i) => Ok(i),
+        Value::Integer(_) => Err(format!("Channel number must be between 1 and {}", MAX_CHANNEL)),
+        _ => Err("Channel number must be an integer".to_owned()),
+    }
+}
+
+/// Evaluates `expr` as a channel number, accepting it with or without the `#` prefix.
+pub(crate) fn eval_channel(expr: &Expr, machine: &Machine) -> exec::Result<i32> {
+    let expr = match expr {
+        Expr::Channel(expr) => expr,
+        expr => expr,
+    };
+    to_channel(expr.eval(machine.get_vars(), machine.get_functions())?)
+        .or_else(exec::new_usage_error)
+}
+
+/// The `CLOSE` command.
+pub struct CloseCommand {
+    metadata: CallableMetadata,
+    store: Rc<RefCell<dyn Store>>,
+    channels: Rc<RefCell<Channels>>,
+}
+
+impl CloseCommand {
+    /// Creates a new `CLOSE` command that flushes the files open in `channels` into `store`.
+    pub fn new(store: Rc<RefCell<dyn Store>>, channels: Rc<RefCell<Channels>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("CLOSE", VarType::Void)
+                .with_syntax("[#channel1%[, .., #channelN%]]")
+                .with_category("File manipulation")
+                .with_description(
+                    "Closes open files.
+Closes the files open on the given channels, or all open files if no channels are given.  The \
+contents written to a file opened for output are only saved to the store when the file is \
+closed, at which point any previous file with the same name is replaced.
+Files that are still open when a program finishes, or when the machine is cleared with CLEAR \
+or NEW, are closed automatically.",
+                )
+                .build(),
+            store,
+            channels,
+        })
+    }
+
+    /// Saves the contents of the output `files` into the store.
+    ///
+    /// All files are saved even if some of them fail, in which case the first error is returned.
+    fn flush(&self, files: Vec<OpenFile>) -> exec::Result<()> {
+        let mut store = self.store.borrow_mut();
+        let mut result = Ok(());
+        for file in files {
+            if let OpenFile::Output { name, content } = file {
+                if let Err(e) = store.put(&name, &content) {
+                    if result.is_ok() {
+                        result = Err(e.into());
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for CloseCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let files = if args.is_empty() {
+            self.channels.borrow_mut().close_all()
+        } else {
+            let mut channels = vec![];
+            for arg in args {
+                match arg {
+                    (Some(expr), ArgSep::Long) | (Some(expr), ArgSep::End) => {
+                        channels.push(eval_channel(expr, machine)?)
+                    }
+                    _ => {
+                        return exec::new_usage_error(
+                            "CLOSE requires a list of channels separated by commas",
+                        )
+                    }
+                }
+            }
+
+            self.channels.borrow_mut().close(&channels)?
+        };
+        self.flush(files)
+    }
+}
+
+impl Clearable for CloseCommand {
+    fn reset_state(&self) -> exec::Result<()> {
+        let files = self.channels.borrow_mut().close_all();
+        self.flush(files)
+    }
+}
+
+/// The `EOF` function.
+pub struct EofFunction {
+    metadata: CallableMetadata,
+    channels: Rc<RefCell<Channels>>,
+}
+
+impl EofFunction {
+    /// Creates a new instance of the function that queries the files open in `channels`.
+    pub fn new(channels: Rc<RefCell<Channels>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("EOF", VarType::Boolean)
+                .with_syntax("channel%")
+                .with_category("File manipulation")
+                .with_description(
+                    "Checks if all lines of a file have been read.
+Returns true if the file opened for input on the given channel has no more lines to read with \
+LINE INPUT or INPUT.",
+                )
+                .build(),
+            channels,
+        })
+    }
+}
+
+impl Function for EofFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        let channel = match args.as_slice() {
+            [channel] => to_channel(channel.clone()).map_err(FunctionError::ArgumentError)?,
+            _ => return Err(FunctionError::SyntaxError),
+        };
+        match self.channels.borrow_mut().is_eof(channel) {
+            Ok(eof) => Ok(Value::Boolean(eof)),
+            Err(e) => Err(FunctionError::ArgumentError(e.to_string())),
+        }
+    }
+}
+
+/// The `FILEEXISTS` function.
+pub struct FileExistsFunction {
+    metadata: CallableMetadata,
+    store: Rc<RefCell<dyn Store>>,
+}
+
+impl FileExistsFunction {
+    /// Creates a new instance of the function that looks for files in `store`.
+    pub fn new(store: Rc<RefCell<dyn Store>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("FILEEXISTS", VarType::Boolean)
+                .with_syntax("filename$")
+                .with_category("File manipulation")
+                .with_description(
+                    "Checks if a file exists in the store.
+The filename follows the same rules as in OPEN, so its .BAS extension is optional.",
+                )
+                .build(),
+            store,
+        })
+    }
+}
+
+impl Function for FileExistsFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        let name = match args.as_slice() {
+            [Value::Text(name)] => {
+                to_filename(name).map_err(|e| FunctionError::ArgumentError(e.to_string()))?
+            }
+            _ => return Err(FunctionError::SyntaxError),
+        };
+        match self.store.borrow().exists(&name) {
+            Ok(exists) => Ok(Value::Boolean(exists)),
+            Err(e) => Err(FunctionError::InternalError(e.to_string())),
+        }
+    }
+}
+
+/// The `KILL` command.
+pub struct KillCommand {
+    metadata: CallableMetadata,
+    store: Rc<RefCell<dyn Store>>,
+}
+
+impl KillCommand {
+    /// Creates a new `KILL` command that deletes files from `store`.
+    pub fn new(store: Rc<RefCell<dyn Store>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("KILL", VarType::Void)
+                .with_syntax("filename$")
+                .with_category("File manipulation")
+                .with_description(
+                    "Deletes a file from the store.
+The filename follows the same rules as in OPEN, so its .BAS extension is optional.  Deleting a \
+file that does not exist is an error; use FILEEXISTS to check for it first.",
+                )
+                .build(),
+            store,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for KillCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let name = match args {
+            [(Some(name), ArgSep::End)] => name,
+            _ => return exec::new_usage_error("KILL requires a filename"),
+        };
+        let name = match name.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Text(t) => to_filename(t)?,
+            _ => return exec::new_usage_error("KILL requires a string as the filename"),
+        };
+        self.store.borrow_mut().delete(&name)?;
+        Ok(())
+    }
+}
+
+/// The `LINE INPUT` command.
+pub struct LineInputCommand {
+    metadata: CallableMetadata,
+    channels: Rc<RefCell<Channels>>,
+}
+
+impl LineInputCommand {
+    /// Creates a new `LINE INPUT` command that reads from the files open in `channels`.
+    pub fn new(channels: Rc<RefCell<Channels>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("LINE INPUT", VarType::Void)
+                .with_syntax("#channel%, variableref$")
+                .with_category("File manipulation")
+                .with_description(
+                    "Reads a line from a file.
+Reads the next line from the file opened for input on the given channel and stores it, without \
+the line terminator, in the given string variable.  Reading past the end of the file is an \
+error, so use EOF to check if there are more lines to read.",
+                )
+                .build(),
+            channels,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for LineInputCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let (channel, vref) = match args {
+            [(Some(c @ Expr::Channel(_)), ArgSep::Long), (Some(Expr::Symbol(v)), ArgSep::End)] => {
+                (c, v)
+            }
+            _ => {
+                return exec::new_usage_error(
+                    "LINE INPUT requires a channel and a variable reference",
+                )
+            }
+        };
+        if vref.ref_type() != VarType::Auto && vref.ref_type() != VarType::Text {
+            return exec::new_usage_error("LINE INPUT requires a string variable");
+        }
+
+        let channel = eval_channel(channel, machine)?;
+        let line = self.channels.borrow_mut().read_line(channel)?;
+        machine.set_var(vref, Value::Text(line))?;
+        Ok(())
+    }
+}
+
+/// The `LOF` function.
+pub struct LofFunction {
+    metadata: CallableMetadata,
+    channels: Rc<RefCell<Channels>>,
+}
+
+impl LofFunction {
+    /// Creates a new instance of the function that queries the files open in `channels`.
+    pub fn new(channels: Rc<RefCell<Channels>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("LOF", VarType::Integer)
+                .with_syntax("channel%")
+                .with_category("File manipulation")
+                .with_description(
+                    "Returns the length of a file in bytes.
+For a file opened for input, this is its length when it was opened, which does not change as \
+lines are read.  For a file opened for output or append, this is the length of the contents \
+written so far, including those that are only saved to the store by CLOSE.",
+                )
+                .build(),
+            channels,
+        })
+    }
+}
+
+impl Function for LofFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        let channel = match args.as_slice() {
+            [channel] => to_channel(channel.clone()).map_err(FunctionError::ArgumentError)?,
+            _ => return Err(FunctionError::SyntaxError),
+        };
+        let length = match self.channels.borrow_mut().length(channel) {
+            Ok(length) => length,
+            Err(e) => return Err(FunctionError::ArgumentError(e.to_string())),
+        };
+        if length > i32::MAX as usize {
+            Err(FunctionError::InternalError("File too large".to_owned()))
+        } else {
+            Ok(Value::Integer(length as i32))
+        }
+    }
+}
+
+/// The `OPEN` command.
+pub struct OpenCommand {
+    metadata: CallableMetadata,
+    store: Rc<RefCell<dyn Store>>,
+    channels: Rc<RefCell<Channels>>,
+}
+
+impl OpenCommand {
+    /// Creates a new `OPEN` command that registers files from `store` in `channels`.
+    pub fn new(store: Rc<RefCell<dyn Store>>, channels: Rc<RefCell<Channels>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("OPEN", VarType::Void)
+                .with_syntax("filename$ FOR <INPUT|OUTPUT|APPEND> AS #channel%")
+                .with_category("File manipulation")
+                .with_description(
+                    "Opens a file for reading or writing.
+The filename must be a basename (no directory components) and its .BAS extension is optional, \
+as with SAVE.  The channel must be a number between 1 and 255 that is not in use by another \
+open file.
+Files opened for INPUT must exist in the store and are read line by line with LINE INPUT or \
+INPUT.  Files opened for OUTPUT are written line by line with PRINT and are saved to the store by \
+CLOSE.  Files opened for APPEND behave like those opened for OUTPUT but keep their previous \
+contents, if any, before the new lines.",
+                )
+                .build(),
+            store,
+            channels,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for OpenCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let (name, mode, channel) = match args {
+            [(Some(n), ArgSep::Long), (Some(m), ArgSep::Long), (Some(c), ArgSep::End)] => (n, m, c),
+            _ => return exec::new_usage_error("OPEN requires a filename, a mode and a channel"),
+        };
+
+        let name = match name.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Text(t) => to_filename(t)?,
+            _ => return exec::new_usage_error("OPEN requires a string as the filename"),
+        };
+        let channel = eval_channel(channel, machine)?;
+        let file = match mode.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Text(t) if t == "INPUT" => {
+                let content = self.store.borrow().get(&name)?;
+                OpenFile::Input {
+                    lines: content.lines().map(str::to_owned).collect(),
+                    length: content.len(),
+                }
+            }
+            Value::Text(t) if t == "OUTPUT" => OpenFile::Output { name, content: String::new() },
+            Value::Text(t) if t == "APPEND" => {
+                let mut content = match self.store.borrow().get(&name) {
+                    Ok(content) => content,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                    Err(e) => return Err(e.into()),
+                };
+                if !content.is_empty() && !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                OpenFile::Output { name, content }
+            }
+            Value::Text(t) => return exec::new_usage_error(format!("Unsupported file mode {}", t)),
+            _ => return exec::new_usage_error("OPEN requires a string as the mode"),
+        };
+        self.channels.borrow_mut().open(channel, file)
+    }
+}
+
+/// Adds all file manipulation commands to the `machine`, using `store` as the backing storage for
+/// the files and `channels` as the table of open files.
+pub fn add_all(
+    machine: &mut Machine,
+    store: Rc<RefCell<dyn Store>>,
+    channels: Rc<RefCell<Channels>>,
+) -> exec::Result<()> {
+    let close = CloseCommand::new(store.clone(), channels.clone());
+    machine.add_clearable(close.clone());
+    machine.add_command(close)?;
+    machine.add_function(EofFunction::new(channels.clone()))?;
+    machine.add_function(FileExistsFunction::new(store.clone()))?;
+    machine.add_command(KillCommand::new(store.clone()))?;
+    machine.add_command(LineInputCommand::new(channels.clone()))?;
+    machine.add_function(LofFunction::new(channels.clone()))?;
+    machine.add_command(OpenCommand::new(store, channels))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutils::*;
+    use endbasic_core::ast::Value;
+
+    #[test]
+    fn test_print_to_file() {
+        Tester::default()
+            .run(
+                r#"OPEN "out.bas" FOR OUTPUT AS #1
+                PRINT #1, "First line"
+                PRINT #1
+                PRINT #1, 3; "and"; 4
+                PRINT #1, "a", "b"
+                CLOSE #1"#,
+            )
+            .expect_file("out.bas", "First line\n\n3 and 4\na             b\n")
+            .check();
+    }
+
+    #[test]
+    fn test_print_to_file_does_not_print_to_console() {
+        Tester::default()
+            .run("OPEN \"out\" FOR OUTPUT AS #1: PRINT \"a\": PRINT #1, \"b\": CLOSE")
+            .expect_prints(["a"])
+            .expect_file("out.bas", "b\n")
+            .check();
+    }
+
+    #[test]
+    fn test_several_channels() {
+        Tester::default()
+            .run(
+                r#"OPEN "one.bas" FOR OUTPUT AS #1
+                ch = 2
+                OPEN "two.bas" FOR OUTPUT AS ch
+                PRINT #ch, "to two"
+                PRINT #1, "to one"
+                PRINT #ch, "to two again"
+                CLOSE #1, #ch"#,
+            )
+            .expect_var("ch", 2)
+            .expect_file("one.bas", "to one\n")
+            .expect_file("two.bas", "to two\nto two again\n")
+            .check();
+    }
+
+    #[test]
+    fn test_close_all() {
+        Tester::default()
+            .run(
+                r#"OPEN "one.bas" FOR OUTPUT AS #1
+                OPEN "two.bas" FOR OUTPUT AS #2
+                PRINT #2, "a"
+                CLOSE
+                CLOSE"#,
+            )
+            .expect_file("one.bas", "")
+            .expect_file("two.bas", "a\n")
+            .check();
+    }
+
+    #[test]
+    fn test_contents_saved_on_close_only() {
+        let mut t = Tester::default();
+        t.run("OPEN \"out.bas\" FOR OUTPUT AS #1: PRINT #1, \"a\"").check();
+        t.run("PRINT #1, \"b\": CLOSE 1").expect_file("out.bas", "a\nb\n").check();
+    }
+
+    #[test]
+    fn test_close_validates_all_channels_first() {
+        let mut t = Tester::default();
+        t.run("OPEN \"out.bas\" FOR OUTPUT AS #1: PRINT #1, \"a\"").check();
+        t.run("CLOSE #1, #2").expect_err("Channel #2 is not open").check();
+        t.run("CLOSE #1, #1").expect_err("Channel #1 given more than once").check();
+        t.run("PRINT #1, \"b\": CLOSE #1").expect_file("out.bas", "a\nb\n").check();
+    }
+
+    #[test]
+    fn test_clear_closes_all() {
+        let mut t = Tester::default();
+        t.run("OPEN \"out.bas\" FOR OUTPUT AS #1: PRINT #1, \"a\"").check();
+        t.run("CLEAR").expect_file("out.bas", "a\n").check();
+        t.run("OPEN \"out.bas\" FOR INPUT AS #1: LINE INPUT #1, l$: CLOSE")
+            .expect_var("l", "a")
+            .expect_file("out.bas", "a\n")
+            .check();
+    }
+
+    #[test]
+    fn test_new_closes_all() {
+        let mut t = Tester::default();
+        t.run("OPEN \"out.bas\" FOR OUTPUT AS #1: PRINT #1, \"a\"").check();
+        t.run("NEW").expect_file("out.bas", "a\n").check();
+        t.run("CLOSE #1")
+            .expect_err("Channel #1 is not open")
+            .expect_file("out.bas", "a\n")
+            .check();
+    }
+
+    #[test]
+    fn test_run_closes_all_on_end() {
+        let program = "OPEN \"out.bas\" FOR OUTPUT AS #1: PRINT #1, \"a\"";
+        let mut t = Tester::default().set_program(program);
+        t.run("RUN").expect_program(program).expect_file("out.bas", "a\n").check();
+        t.run("RUN").expect_program(program).expect_file("out.bas", "a\n").check();
+    }
+
+    #[test]
+    fn test_run_closes_all_on_error() {
+        let program = "OPEN \"out.bas\" FOR OUTPUT AS #1: PRINT #1, \"a\": PRINT #2, \"b\"";
+        Tester::default()
+            .set_program(program)
+            .run("RUN")
+            .expect_program(program)
+            .expect_err("Channel #2 is not open")
+            .expect_file("out.bas", "a\n")
+            .check();
+    }
+
+    #[test]
+    fn test_reopen_replaces_file() {
+        Tester::default()
+            .write_file("out.bas", "old contents\n")
+            .run("OPEN \"out.bas\" FOR OUTPUT AS #1: CLOSE #1: OPEN \"out.bas\" FOR OUTPUT AS #1")
+            .expect_file("out.bas", "")
+            .check();
+    }
+
+    #[test]
+    fn test_read_file_line_by_line() {
+        Tester::default()
+            .write_file("data.bas", "first\nsecond line\n\nlast")
+            .run(
+                r#"OPEN "data.bas" FOR INPUT AS #1
+                e1 = EOF(1)
+                LINE INPUT #1, a$
+                LINE INPUT #1, b
+                LINE INPUT #1, c$
+                e2 = EOF(1)
+                LINE INPUT #1, d$
+                e3 = EOF(1)
+                CLOSE #1"#,
+            )
+            .expect_var("e1", false)
+            .expect_var("a", "first")
+            .expect_var("b", "second line")
+            .expect_var("c", "")
+            .expect_var("e2", false)
+            .expect_var("d", "last")
+            .expect_var("e3", true)
+            .expect_file("data.bas", "first\nsecond line\n\nlast")
+            .check();
+    }
+
+    #[test]
+    fn test_read_file_until_eof() {
+        Tester::default()
+            .write_file("data.bas", "one\ntwo\n")
+            .run(
+                r#"OPEN "data" FOR INPUT AS #2
+                WHILE NOT EOF(2)
+                    LINE INPUT #2, l$
+                    PRINT l$
+                WEND
+                CLOSE"#,
+            )
+            .expect_prints(["one", "two"])
+            .expect_var("l", "two")
+            .expect_file("data.bas", "one\ntwo\n")
+            .check();
+    }
+
+    #[test]
+    fn test_read_empty_file() {
+        Tester::default()
+            .write_file("empty.bas", "")
+            .run("OPEN \"empty.bas\" FOR INPUT AS #1: e = EOF(1)")
+            .expect_var("e", true)
+            .expect_file("empty.bas", "")
+            .check();
+    }
+
+    #[test]
+    fn test_read_back_written_file() {
+        Tester::default()
+            .run(
+                r#"OPEN "out.bas" FOR OUTPUT AS #1: PRINT #1, "hello": CLOSE #1
+                OPEN "out.bas" FOR INPUT AS #1: LINE INPUT #1, l$: CLOSE #1"#,
+            )
+            .expect_var("l", "hello")
+            .expect_file("out.bas", "hello\n")
+            .check();
+    }
+
+    #[test]
+    fn test_append_to_file() {
+        Tester::default()
+            .write_file("log.bas", "first\nsecond")
+            .run(
+                r#"OPEN "log" FOR APPEND AS #1: PRINT #1, "third": CLOSE #1
+                OPEN "new" FOR APPEND AS #1: PRINT #1, "only": CLOSE #1"#,
+            )
+            .expect_file("log.bas", "first\nsecond\nthird\n")
+            .expect_file("new.bas", "only\n")
+            .check();
+    }
+
+    #[test]
+    fn test_input_from_file() {
+        Tester::default()
+            .run(
+                r#"OPEN "data.bas" FOR OUTPUT AS #1
+                PRINT #1, 5
+                PRINT #1, 2.5
+                PRINT #1, "some text"
+                PRINT #1, TRUE
+                PRINT #1, ""
+                CLOSE #1
+                OPEN "data.bas" FOR INPUT AS #1
+                INPUT #1, i
+                INPUT #1, d#
+                INPUT #1, t$
+                INPUT #1, b?
+                e1 = EOF(1)
+                INPUT #1, empty
+                e2 = EOF(1)
+                CLOSE"#,
+            )
+            .expect_var("i", 5)
+            .expect_var("d", 2.5)
+            .expect_var("t", "some text")
+            .expect_var("b", true)
+            .expect_var("e1", false)
+            .expect_var("empty", Value::Empty)
+            .expect_var("e2", true)
+            .expect_file("data.bas", "5\n2.5\nsome text\nTRUE\n\n")
+            .check();
+    }
+
+    #[test]
+    fn test_input_from_file_errors() {
+        check_stmt_err("Channel #1 is not open", "INPUT #1, a");
+        check_stmt_err("INPUT requires a comma after the channel", "INPUT #1; a");
+        check_stmt_err("INPUT requires a variable reference", "INPUT #1, 3");
+
+        Tester::default()
+            .write_file("data.bas", "abc\n")
+            .run("OPEN \"data\" FOR INPUT AS #1: INPUT #1, a%")
+            .expect_file("data.bas", "abc\n")
+            .expect_err("Bad data in file: Invalid integer literal abc")
+            .check();
+
+        Tester::default()
+            .write_file("data.bas", "")
+            .run("OPEN \"data\" FOR INPUT AS #1: INPUT #1, a")
+            .expect_file("data.bas", "")
+            .expect_err("End of file reached on channel #1")
+            .check();
+    }
+
+    #[test]
+    fn test_eof_and_lof_while_reading() {
+        Tester::default()
+            .write_file("data.bas", "12345\nab\nlast\n")
+            .run(
+                r#"OPEN "data.bas" FOR INPUT AS #1
+                l1 = LOF(1): e1 = EOF(1)
+                LINE INPUT #1, a$
+                l2 = LOF(1): e2 = EOF(1)
+                LINE INPUT #1, a$
+                LINE INPUT #1, a$
+                l3 = LOF(1): e3 = EOF(1)
+                CLOSE"#,
+            )
+            .expect_var("a", "last")
+            .expect_var("l1", 14)
+            .expect_var("e1", false)
+            .expect_var("l2", 14)
+            .expect_var("e2", false)
+            .expect_var("l3", 14)
+            .expect_var("e3", true)
+            .expect_file("data.bas", "12345\nab\nlast\n")
+            .check();
+    }
+
+    #[test]
+    fn test_lof_while_writing() {
+        Tester::default()
+            .write_file("log.bas", "old\n")
+            .run(
+                r#"OPEN "new.bas" FOR OUTPUT AS #1
+                OPEN "log.bas" FOR APPEND AS #2
+                l1 = LOF(1): l2 = LOF(2)
+                PRINT #1, "abc"
+                PRINT #2, "abc"
+                l3 = LOF(1): l4 = LOF(2)
+                CLOSE"#,
+            )
+            .expect_var("l1", 0)
+            .expect_var("l2", 4)
+            .expect_var("l3", 4)
+            .expect_var("l4", 8)
+            .expect_file("new.bas", "abc\n")
+            .expect_file("log.bas", "old\nabc\n")
+            .check();
+    }
+
+    #[test]
+    fn test_kill_and_fileexists() {
+        Tester::default()
+            .write_file("data.bas", "x")
+            .write_file("other.bas", "y")
+            .run(
+                r#"e1 = FILEEXISTS("data")
+                e2 = FILEEXISTS("data.bas")
+                e3 = FILEEXISTS("missing")
+                KILL "data"
+                e4 = FILEEXISTS("data.bas")"#,
+            )
+            .expect_var("e1", true)
+            .expect_var("e2", true)
+            .expect_var("e3", false)
+            .expect_var("e4", false)
+            .expect_file("other.bas", "y")
+            .check();
+    }
+
+    #[test]
+    fn test_fileexists_errors() {
+        check_expr_error(
+            "Syntax error in call to FILEEXISTS: Invalid filename extension",
+            "FILEEXISTS(\"a.txt\")",
+        );
+        check_expr_error("Syntax error in call to FILEEXISTS: expected filename$", "FILEEXISTS()");
+        check_expr_error("Syntax error in call to FILEEXISTS: expected filename$", "FILEEXISTS(3)");
+    }
+
+    #[test]
+    fn test_kill_errors() {
+        check_stmt_err("Entry not found", "KILL \"missing.bas\"");
+        check_stmt_err("Invalid filename extension", "KILL \"a.txt\"");
+        check_stmt_err("KILL requires a filename", "KILL");
+        check_stmt_err("KILL requires a filename", "KILL \"a\", \"b\"");
+        check_stmt_err("KILL requires a string as the filename", "KILL 3");
+    }
+
+    #[test]
+    fn test_lof_errors() {
+        check_expr_error("Syntax error in call to LOF: Channel #1 is not open", "LOF(1)");
+        check_expr_error(
+            "Syntax error in call to LOF: Channel number must be between 1 and 255",
+            "LOF(256)",
+        );
+        check_expr_error(
+            "Syntax error in call to LOF: Channel number must be an integer",
+            "LOF(\"1\")",
+        );
+        check_expr_error("Syntax error in call to LOF: expected channel%", "LOF(1, 2)");
+
+        Tester::default()
+            .run("OPEN \"a\" FOR OUTPUT AS #1: CLOSE #1: l = LOF(1)")
+            .expect_file("a.bas", "")
+            .expect_err("Syntax error in call to LOF: Channel #1 is not open")
+            .check();
+    }
+
+    #[test]
+    fn test_eof_errors() {
+        check_expr_error("Syntax error in call to EOF: Channel #1 is not open", "EOF(1)");
+        check_expr_error(
+            "Syntax error in call to EOF: Channel number must be between 1 and 255",
+            "EOF(0)",
+        );
+        check_expr_error("Syntax error in call to EOF: expected channel%", "EOF()");
+
+        Tester::default()
+            .run("OPEN \"a\" FOR OUTPUT AS #1: e = EOF(1)")
+            .expect_err("Syntax error in call to EOF: Channel #1 is not open for input")
+            .check();
+    }
+
+    #[test]
+    fn test_line_input_errors() {
+        check_stmt_err("Channel #1 is not open", "LINE INPUT #1, a$");
+        check_stmt_err("LINE INPUT requires a channel and a variable reference", "LINE INPUT");
+        check_stmt_err("LINE INPUT requires a channel and a variable reference", "LINE INPUT #1");
+        check_stmt_err(
+            "LINE INPUT requires a channel and a variable reference",
+            "LINE INPUT 1, a$",
+        );
+        check_stmt_err(
+            "LINE INPUT requires a channel and a variable reference",
+            "LINE INPUT #1, 3",
+        );
+        check_stmt_err("LINE INPUT requires a string variable", "LINE INPUT #1, a%");
+
+        Tester::default()
+            .write_file("data.bas", "only\n")
+            .run("OPEN \"data\" FOR INPUT AS #1: LINE INPUT #1, a$: LINE INPUT #1, a$")
+            .expect_var("a", "only")
+            .expect_file("data.bas", "only\n")
+            .expect_err("End of file reached on channel #1")
+            .check();
+
+        Tester::default()
+            .run("OPEN \"a\" FOR OUTPUT AS #1: LINE INPUT #1, a$")
+            .expect_err("Channel #1 is not open for input")
+            .check();
+    }
+
+    #[test]
+    fn test_print_to_input_file_errors() {
+        Tester::default()
+            .write_file("data.bas", "")
+            .run("OPEN \"data\" FOR INPUT AS #1: PRINT #1, \"a\"")
+            .expect_file("data.bas", "")
+            .expect_err("Channel #1 is not open for output")
+            .check();
+    }
+
+    #[test]
+    fn test_open_errors() {
+        check_stmt_err("Entry not found", "OPEN \"missing.bas\" FOR INPUT AS #1");
+        check_stmt_err("Invalid filename extension", "OPEN \"a.txt\" FOR OUTPUT AS #1");
+        check_stmt_err("OPEN requires a string as the filename", "OPEN 3 FOR OUTPUT AS #1");
+        check_stmt_err("Unsupported file mode RANDOM", "OPEN \"a\" FOR RANDOM AS #1");
+        check_stmt_err("Channel number must be an integer", "OPEN \"a\" FOR OUTPUT AS #\"1\"");
+        check_stmt_err("Channel number must be between 1 and 255", "OPEN \"a\" FOR OUTPUT AS #0");
+        check_stmt_err("Channel number must be between 1 and 255", "OPEN \"a\" FOR OUTPUT AS #256");
+
+        Tester::default()
+            .run("OPEN \"a\" FOR OUTPUT AS #1: OPEN \"b\" FOR OUTPUT AS #1")
+            .expect_err("Channel #1 is already open")
+            .check();
+    }
+
+    #[test]
+    fn test_close_errors() {
+        check_stmt_err("Channel #1 is not open", "CLOSE #1");
+        check_stmt_err("CLOSE requires a list of channels separated by commas", "CLOSE #1; #2");
+        check_stmt_err("Channel number must be an integer", "CLOSE #TRUE");
+
+        Tester::default()
+            .run("OPEN \"a\" FOR OUTPUT AS #1: CLOSE #1: CLOSE #1")
+            .expect_file("a.bas", "")
+            .expect_err("Channel #1 is not open")
+            .check();
+    }
+
+    #[test]
+    fn test_print_to_file_errors() {
+        check_stmt_err("Channel #1 is not open", "PRINT #1, \"a\"");
+        check_stmt_err("Channel number must be between 1 and 255", "PRINT #-1, \"a\"");
+        check_stmt_err("PRINT requires a comma after the channel", "PRINT #1; \"a\"");
+        check_stmt_err("Channel references are only valid as command arguments", "PRINT 1, #2");
+    }
+}
@@ -0,0 +1,266 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Commands to draw on the graphics surface of the console.
+
+use crate::console::{get_color, Console, PixelPos};
+use async_trait::async_trait;
+use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
+use endbasic_core::eval::{CallableMetadata, CallableMetadataBuilder};
+use endbasic_core::exec::{self, Command, Machine};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Category string for all commands provided by this module.
+const CATEGORY: &str = "Graphics";
+
+/// Evaluates the `args` of a drawing command as `npixels` pairs of x and y coordinates followed
+/// by an optional color.
+///
+/// Returns `None` if the arguments do not have that shape so that the caller can report its own
+/// usage message.  Coordinates outside of the graphics surface of `console` are an error.
+fn eval_pixels_and_color(
+    args: &[(Option<Expr>, ArgSep)],
+    npixels: usize,
+    console: &dyn Console,
+    machine: &Machine,
+) -> exec::Result<Option<(Vec<PixelPos>, Option<u8>)>> {
+    let ncoords = npixels * 2;
+    if args.len() < ncoords || args.len() > ncoords + 1 {
+        return Ok(None);
+    }
+    for (i, (_, sep)) in args.iter().enumerate() {
+        let exp_sep = if i == args.len() - 1 { ArgSep::End } else { ArgSep::Long };
+        if *sep != exp_sep {
+            return Ok(None);
+        }
+    }
+
+    let size = console.graphics_size()?;
+    let mut coords = Vec::with_capacity(ncoords);
+    for (i, (expr, _)) in args[0..ncoords].iter().enumerate() {
+        let (name, limit) = if i % 2 == 0 { ("X", size.x) } else { ("Y", size.y) };
+        let coord = match expr {
+            Some(expr) => match expr.eval(machine.get_vars(), machine.get_functions())? {
+                Value::Integer(i) => i,
+                _ => {
+                    return exec::new_usage_error(format!("{} coordinate must be an integer", name))
+                }
+            },
+            None => return Ok(None),
+        };
+        if coord < 0 || coord as usize >= limit {
+            return exec::new_usage_error(format!(
+                "{} coordinate {} out of range [0, {}]",
+                name,
+                coord,
+                limit as i64 - 1
+            ));
+        }
+        coords.push(coord as usize);
+    }
+    let pixels = coords.chunks(2).map(|xy| PixelPos { x: xy[0], y: xy[1] }).collect();
+
+    let color = match args.get(ncoords) {
+        Some((expr, _)) => get_color(expr, machine)?,
+        None => None,
+    };
+
+    Ok(Some((pixels, color)))
+}
+
+/// The `LINE` command.
+pub struct LineCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl LineCommand {
+    /// Creates a new `LINE` command that draws on the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("LINE", VarType::Void)
+                .with_syntax("x1%, y1%, x2%, y2%[, color%]")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Draws a line between two pixels.
+Both the (x1%, y1%) and (x2%, y2%) ends are drawn.  See PSET for details on the coordinates and \
+the color.",
+                )
+                .build(),
+            console,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for LineCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let mut console = self.console.borrow_mut();
+        let (pixels, color) = match eval_pixels_and_color(args, 2, &*console, machine)? {
+            Some(pixels_and_color) => pixels_and_color,
+            None => {
+                return exec::new_usage_error(
+                    "LINE requires four coordinates and an optional color",
+                )
+            }
+        };
+        console.draw_line(pixels[0], pixels[1], color)?;
+        Ok(())
+    }
+}
+
+/// The `PSET` command.
+pub struct PsetCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl PsetCommand {
+    /// Creates a new `PSET` command that draws on the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("PSET", VarType::Void)
+                .with_syntax("x%, y%[, color%]")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Sets a pixel to a color.
+Coordinates start at (0, 0) on the top-left corner of the graphics surface and must fall within \
+it: out of range coordinates are an error, not clipped.  If color% is not given, the pixel is set \
+to the foreground color selected by COLOR.
+Graphics are only available in consoles that have a graphics surface, such as the one in the \
+web interface.",
+                )
+                .build(),
+            console,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for PsetCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let mut console = self.console.borrow_mut();
+        let (pixels, color) = match eval_pixels_and_color(args, 1, &*console, machine)? {
+            Some(pixels_and_color) => pixels_and_color,
+            None => {
+                return exec::new_usage_error("PSET requires two coordinates and an optional color")
+            }
+        };
+        console.draw_pixel(pixels[0], color)?;
+        Ok(())
+    }
+}
+
+/// Adds all graphics commands to the `machine`, drawing on the `console`.
+pub fn add_all(machine: &mut Machine, console: Rc<RefCell<dyn Console>>) -> exec::Result<()> {
+    machine.add_command(LineCommand::new(console.clone()))?;
+    machine.add_command(PsetCommand::new(console))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::*;
+
+    /// Creates a tester whose console has a graphics surface of 320x200 pixels.
+    fn tester() -> Tester {
+        let t = Tester::default();
+        t.get_console().borrow_mut().set_graphics_size(PixelPos { x: 320, y: 200 });
+        t
+    }
+
+    #[test]
+    fn test_pset_ok() {
+        tester()
+            .run("PSET 0, 0: PSET 319, 199, 4: c = 2: PSET 10 + 5, 20, c")
+            .expect_output([
+                CapturedOut::DrawPixel(PixelPos { x: 0, y: 0 }, None),
+                CapturedOut::DrawPixel(PixelPos { x: 319, y: 199 }, Some(4)),
+                CapturedOut::DrawPixel(PixelPos { x: 15, y: 20 }, Some(2)),
+            ])
+            .expect_var("c", 2)
+            .check();
+    }
+
+    #[test]
+    fn test_pset_errors() {
+        let mut t = tester();
+        t.run("PSET 320, 0").expect_err("X coordinate 320 out of range [0, 319]").check();
+        t.run("PSET 0, -1").expect_err("Y coordinate -1 out of range [0, 199]").check();
+        t.run("PSET 1.0, 0").expect_err("X coordinate must be an integer").check();
+        t.run("PSET 0, \"a\"").expect_err("Y coordinate must be an integer").check();
+        t.run("PSET 0, 0, 256").expect_err("Color out of range").check();
+        t.run("PSET 0").expect_err("PSET requires two coordinates and an optional color").check();
+        t.run("PSET 0; 0")
+            .expect_err("PSET requires two coordinates and an optional color")
+            .check();
+        t.run("PSET , 0").expect_err("PSET requires two coordinates and an optional color").check();
+        t.run("PSET 0, 0, 1, 2")
+            .expect_err("PSET requires two coordinates and an optional color")
+            .check();
+
+        Tester::default()
+            .run("PSET 0, 0")
+            .expect_err("Graphics are not supported by this console")
+            .check();
+    }
+
+    #[test]
+    fn test_line_ok() {
+        tester()
+            .run("LINE 0, 0, 319, 199: LINE 10, 5, 2, 5, 7")
+            .expect_output([
+                CapturedOut::DrawLine(PixelPos { x: 0, y: 0 }, PixelPos { x: 319, y: 199 }, None),
+                CapturedOut::DrawLine(PixelPos { x: 10, y: 5 }, PixelPos { x: 2, y: 5 }, Some(7)),
+            ])
+            .check();
+    }
+
+    #[test]
+    fn test_line_errors() {
+        let mut t = tester();
+        t.run("LINE 0, 0, 0, 200").expect_err("Y coordinate 200 out of range [0, 199]").check();
+        t.run("LINE -5, 0, 0, 0").expect_err("X coordinate -5 out of range [0, 319]").check();
+        t.run("LINE 0, 0, 0, 0, -1").expect_err("Color out of range").check();
+        t.run("LINE 0, 0, 0")
+            .expect_err("LINE requires four coordinates and an optional color")
+            .check();
+        t.run("LINE 0, 0, 0, 0, 1, 2")
+            .expect_err("LINE requires four coordinates and an optional color")
+            .check();
+
+        Tester::default()
+            .run("LINE 0, 0, 1, 1")
+            .expect_err("Graphics are not supported by this console")
+            .check();
+    }
+}
@@ -17,42 +17,73 @@
 
 use crate::console::Console;
 use async_trait::async_trait;
-use endbasic_core::ast::{ArgSep, Expr, VarType};
-use endbasic_core::eval::{CallableMetadata, CallableMetadataBuilder, Function};
-use endbasic_core::exec::{self, Command, Machine};
+use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
+use endbasic_core::eval::{
+    CallableMetadata, CallableMetadataBuilder, Function, FunctionError, FunctionResult,
+};
+use endbasic_core::exec::{self, Command, Machine, RegistrationListener};
 use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
 
-/// Cheat-sheet for the language syntax.
-const LANG_REFERENCE: &str = r"
-    Symbols (variable and function references):
+/// Cheat-sheet for the language syntax, split in sections keyed by their topic name.
+const LANG_SECTIONS: &[(&str, &str)] = &[
+    (
+        "SYMBOLS",
+        r"    Symbols (variable and function references):
         name?    Boolean (TRUE and FALSE).
         name%    Integer (32 bits).
         name$    String.
         name     Type determined by value or definition.
-
-    Assignments:
+        Names are case-insensitive: Count and COUNT are the same symbol.",
+    ),
+    (
+        "ASSIGNMENTS",
+        r"    Assignments:
         varref = expr
-
-    Expressions:
+        MID$(varref$, start%[, length%]) = expr$",
+    ),
+    (
+        "OPERATORS",
+        r"    Expressions:
         a + b      a - b       a * b     a / b      a MOD b    -a
         a AND b    NOT a       a OR b    a XOR b
         a = b      a <> b      a < b     a <= b     a > b      a >= b
         (a)        varref      funcref(a1[, ..., aN])
-
-    Flow control:
-        IF expr THEN: ...: ELSE IF expr THEN: ...: ELSE: ...: END IF
-        FOR varref = expr TO expr [STEP int]: ...: NEXT
+        IIF(cond?, a, b)        Evaluates to a if cond? is true or to b otherwise.
+        CHOOSE(i%, a1, ..., aN) Evaluates to the i%-th value, counting from 1.
+        MIN(a, b[, ..., z])     Evaluates to the smallest of the numeric values.
+        MAX(a, b[, ..., z])     Evaluates to the largest of the numeric values.
+        EVAL(expr$)             Evaluates the expression in expr$ using the current variables.
+        MIN and MAX yield an integer if all values are integers, or a double otherwise.
+        Unlike function calls, IIF and CHOOSE only evaluate the value they select.",
+    ),
+    (
+        "FLOW",
+        r"    Flow control:
+        IF expr THEN: ...: ELSEIF expr THEN: ...: ELSE: ...: END IF
+        FOR varref = expr TO expr [STEP num]: ...: NEXT
         WHILE expr: ...: END WHILE
-
-    Misc:
+        END [code%]
+        (ELSE IF is a synonym for ELSEIF, and WEND is a synonym for END WHILE.)",
+    ),
+    (
+        "MISC",
+        r"    Misc:
         st1: st2    Separates statements (same as a newline).
+        st1 _       Continues the statement on the next line (same as a space).
         REM text    Comment until end of line.
         ' text      Comment until end of line.
         ,           Long separator for arguments to builtin call.
-        ;           Short separator for arguments to builtin call.
-";
+        ;           Short separator for arguments to builtin call.",
+    ),
+];
+
+/// Color of the category headers in the help summary.
+const CATEGORY_COLOR: u8 = 11;
+
+/// Color of the callable names in the help summary.
+const NAME_COLOR: u8 = 14;
 
 /// Returns the header for the help summary.
 fn header() -> Vec<String> {
@@ -69,7 +100,7 @@ fn header() -> Vec<String> {
 /// Computes a unified collection of metadata objects for all given `commands` and `functions`.
 // TODO(jmmv): This is a code smell from the lack of genericity between commands and functions.
 // If we can homogenize their representation, this should go away.
-fn compute_callables<'a>(
+pub(crate) fn compute_callables<'a>(
     commands: &'a HashMap<&'static str, Rc<dyn Command>>,
     functions: &'a HashMap<&'static str, Rc<dyn Function>>,
 ) -> HashMap<&'static str, &'a CallableMetadata> {
@@ -105,15 +136,136 @@ fn build_index(
     (index, max_length)
 }
 
+/// Counts the commands and functions registered in `machine`, grouped by their category.
+///
+/// The returned map is keyed by category name and each value holds the number of commands and
+/// the number of functions in that category, in that order.
+pub fn count_callables(machine: &Machine) -> BTreeMap<&'static str, (usize, usize)> {
+    let callables = compute_callables(machine.get_commands(), machine.get_functions());
+    let mut counts = BTreeMap::default();
+    for metadata in callables.values() {
+        let entry = counts.entry(metadata.category()).or_insert((0, 0));
+        if metadata.return_type() == VarType::Void {
+            entry.0 += 1;
+        } else {
+            entry.1 += 1;
+        }
+    }
+    counts
+}
+
+/// Formats `n` followed by `noun`, pluralizing the latter when necessary.
+fn plural(n: usize, noun: &str) -> String {
+    if n == 1 {
+        format!("{} {}", n, noun)
+    } else {
+        format!("{} {}s", n, noun)
+    }
+}
+
+/// Formats the help text of the callable described by `metadata` as a list of paragraphs, the
+/// first of which is the usage line of the callable.
+fn describe(metadata: &CallableMetadata) -> Vec<String> {
+    let mut paragraphs = vec![];
+    if metadata.return_type() == VarType::Void {
+        if metadata.syntax().is_empty() {
+            paragraphs.push(metadata.name().to_owned());
+        } else {
+            paragraphs.push(format!("{} {}", metadata.name(), metadata.syntax()));
+        }
+    } else {
+        paragraphs.push(format!(
+            "{}{}({})",
+            metadata.name(),
+            metadata.return_type().annotation(),
+            metadata.syntax(),
+        ));
+    }
+    for line in metadata.description() {
+        paragraphs.push(line.to_owned());
+    }
+    paragraphs
+}
+
+/// Help texts of all callables registered in a machine, as returned by `DESCRIBE$`.
+///
+/// The texts are kept up to date by registering this as a listener of the machine.
+#[derive(Default)]
+pub struct Descriptions {
+    /// Mapping of uppercase callable names, with and without their type annotation, to their
+    /// help text.
+    texts: RefCell<HashMap<String, String>>,
+}
+
+impl RegistrationListener for Descriptions {
+    fn on_register(&self, metadata: &CallableMetadata) {
+        let text = describe(metadata).join("\n");
+        let mut texts = self.texts.borrow_mut();
+        let annotation = metadata.return_type().annotation();
+        if !annotation.is_empty() {
+            texts.insert(format!("{}{}", metadata.name(), annotation), text.clone());
+        }
+        texts.insert(metadata.name().to_owned(), text);
+    }
+}
+
+/// The `DESCRIBE` function.
+pub struct DescribeFunction {
+    metadata: CallableMetadata,
+    descriptions: Rc<Descriptions>,
+}
+
+impl DescribeFunction {
+    /// Creates a new instance of the function that looks up help texts in `descriptions`.
+    pub fn new(descriptions: Rc<Descriptions>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("DESCRIBE", VarType::Text)
+                .with_syntax("name$")
+                .with_category("Interpreter manipulation")
+                .with_description(
+                    "Returns the help text of a command or function.
+The text is the same that HELP prints for name$, starting with the syntax of the command or \
+function, and has one paragraph per line.",
+                )
+                .build(),
+            descriptions,
+        })
+    }
+}
+
+impl Function for DescribeFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Text(name)] => {
+                let name = name.to_ascii_uppercase();
+                match self.descriptions.texts.borrow().get(&name) {
+                    Some(text) => Ok(Value::Text(text.clone())),
+                    None => Err(FunctionError::ArgumentError(format!(
+                        "Cannot describe unknown command or function {}",
+                        name
+                    ))),
+                }
+            }
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
 /// The `HELP` command.
 pub struct HelpCommand {
     metadata: CallableMetadata,
     console: Rc<RefCell<dyn Console>>,
+    colors: bool,
 }
 
 impl HelpCommand {
-    /// Creates a new command that writes help messages to `output`.
-    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+    /// Creates a new command that writes help messages to `output`, highlighting the summary with
+    /// colors if `colors` is true.
+    pub fn new(console: Rc<RefCell<dyn Console>>, colors: bool) -> Rc<Self> {
         Rc::from(Self {
             metadata: CallableMetadataBuilder::new("HELP", VarType::Void)
                 .with_syntax("[topic]")
@@ -122,10 +274,13 @@ impl HelpCommand {
                     "Prints interactive help.
 Without arguments, shows a summary of all available help topics.
 With a single argument, shows detailed information about the given help topic, command, or \
-function.",
+function.
+HELP LANG shows a quick reference guide about the language.  Follow it with one of SYMBOLS, \
+ASSIGNMENTS, OPERATORS, FLOW, or MISC to show only that section of the guide.",
                 )
                 .build(),
             console,
+            colors,
         })
     }
 
@@ -140,10 +295,23 @@ function.",
 
         for (category, by_name) in index.iter() {
             console.print("")?;
-            console.print(&format!("    >> {} <<", category))?;
+            if self.colors {
+                console.color(Some(CATEGORY_COLOR), None)?;
+                console.print(&format!("    >> {} <<", category))?;
+                console.color(None, None)?;
+            } else {
+                console.print(&format!("    >> {} <<", category))?;
+            }
             for (name, blurb) in by_name.iter() {
                 let filler = " ".repeat(max_length - name.len());
-                console.print(&format!("    {}{}    {}", name, filler, blurb))?;
+                if self.colors {
+                    console.color(Some(NAME_COLOR), None)?;
+                    console.write(format!("    {}", name).as_bytes())?;
+                    console.color(None, None)?;
+                    console.print(&format!("{}    {}", filler, blurb))?;
+                } else {
+                    console.print(&format!("    {}{}    {}", name, filler, blurb))?;
+                }
             }
         }
 
@@ -157,35 +325,39 @@ function.",
     /// Describes one command or function.
     fn describe_callable(&self, metadata: &CallableMetadata) -> exec::Result<()> {
         let mut console = self.console.borrow_mut();
-        console.print("")?;
-        if metadata.return_type() == VarType::Void {
-            if metadata.syntax().is_empty() {
-                console.print(&format!("    {}", metadata.name()))?
-            } else {
-                console.print(&format!("    {} {}", metadata.name(), metadata.syntax()))?
-            }
-        } else {
-            console.print(&format!(
-                "    {}{}({})",
-                metadata.name(),
-                metadata.return_type().annotation(),
-                metadata.syntax(),
-            ))?;
-        }
-        for line in metadata.description() {
+        for paragraph in describe(metadata) {
             console.print("")?;
-            console.print(&format!("    {}", line))?;
+            console.print(&format!("    {}", paragraph))?;
         }
         console.print("")?;
         Ok(())
     }
 
-    /// Prints a quick reference of the language syntax.
-    fn describe_lang(&self) -> exec::Result<()> {
+    /// Prints a quick reference of the language syntax, limited to the section about `topic` if
+    /// given.
+    fn describe_lang(&self, topic: Option<&str>) -> exec::Result<()> {
+        let sections: Vec<&(&str, &str)> = match topic {
+            None => LANG_SECTIONS.iter().collect(),
+            Some(topic) => match LANG_SECTIONS.iter().find(|(name, _)| *name == topic) {
+                Some(section) => vec![section],
+                None => {
+                    let names: Vec<&str> = LANG_SECTIONS.iter().map(|(name, _)| *name).collect();
+                    return exec::new_usage_error(format!(
+                        "Unknown language topic {}; available topics are: {}",
+                        topic,
+                        names.join(", ")
+                    ));
+                }
+            },
+        };
+
         let mut console = self.console.borrow_mut();
-        for line in LANG_REFERENCE.lines() {
-            // Print line by line to honor any possible differences in line feeds.
-            console.print(line)?;
+        for (_, text) in sections {
+            console.print("")?;
+            for line in text.lines() {
+                // Print line by line to honor any possible differences in line feeds.
+                console.print(line)?;
+            }
         }
         console.print("")?;
         Ok(())
@@ -212,8 +384,12 @@ impl Command for HelpCommand {
                     if vref.ref_type() != VarType::Auto {
                         return exec::new_usage_error("Incompatible type annotation");
                     }
-                    self.describe_lang()?;
+                    self.describe_lang(None)?;
                 } else {
+                    let name = match machine.get_aliases().get(name.as_str()) {
+                        Some(target) => target.to_string(),
+                        None => name,
+                    };
                     match callables.get(name.as_str()) {
                         Some(metadata) => {
                             if vref.ref_type() != VarType::Auto
@@ -232,22 +408,98 @@ impl Command for HelpCommand {
                     }
                 }
             }
+            [(Some(Expr::Symbol(lang)), ArgSep::Space), (Some(Expr::Symbol(topic)), ArgSep::End)]
+                if lang.name().eq_ignore_ascii_case("LANG") =>
+            {
+                if lang.ref_type() != VarType::Auto || topic.ref_type() != VarType::Auto {
+                    return exec::new_usage_error("Incompatible type annotation");
+                }
+                self.describe_lang(Some(&topic.name().to_ascii_uppercase()))?;
+            }
             _ => return exec::new_usage_error("HELP takes zero or only one argument"),
         }
         Ok(())
     }
 }
 
+/// The `STATS` command.
+pub struct StatsCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl StatsCommand {
+    /// Creates a new command that writes callable statistics to `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("STATS", VarType::Void)
+                .with_syntax("")
+                .with_category("Interpreter manipulation")
+                .with_description(
+                    "Prints the number of available commands and functions.
+The counts are broken down by the same categories shown by HELP and are followed by the totals.",
+                )
+                .build(),
+            console,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for StatsCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        if !args.is_empty() {
+            return exec::new_usage_error("STATS takes no arguments");
+        }
+
+        let counts = count_callables(machine);
+        let (mut total_commands, mut total_functions) = (0, 0);
+        let mut console = self.console.borrow_mut();
+        console.print("")?;
+        for (category, (commands, functions)) in counts.iter() {
+            console.print(&format!(
+                "    {}: {}, {}",
+                category,
+                plural(*commands, "command"),
+                plural(*functions, "function")
+            ))?;
+            total_commands += commands;
+            total_functions += functions;
+        }
+        console.print("")?;
+        console.print(&format!(
+            "    Total: {}, {}",
+            plural(total_commands, "command"),
+            plural(total_functions, "function")
+        ))?;
+        console.print("")?;
+        Ok(())
+    }
+}
+
 /// Adds all help-related commands to the `machine` and makes them write to `console`.
-pub fn add_all(machine: &mut Machine, console: Rc<RefCell<dyn Console>>) {
-    machine.add_command(HelpCommand::new(console));
+///
+/// The help summary is highlighted with colors only if the `console` is interactive.
+pub fn add_all(machine: &mut Machine, console: Rc<RefCell<dyn Console>>) -> exec::Result<()> {
+    let colors = console.borrow().is_interactive();
+    let descriptions = Rc::from(Descriptions::default());
+    machine.add_registration_listener(descriptions.clone());
+    machine.add_command(HelpCommand::new(console.clone(), colors))?;
+    machine.add_command(StatsCommand::new(console))?;
+    machine.add_function(DescribeFunction::new(descriptions))
 }
 
 #[cfg(test)]
 pub(crate) mod testutils {
     use super::*;
-    use endbasic_core::ast::Value;
-    use endbasic_core::eval::{self, CallableMetadata, CallableMetadataBuilder};
 
     /// A command that does nothing.
     pub(crate) struct DoNothingCommand {
@@ -312,7 +564,7 @@ Second paragraph of the extended description.",
             &self.metadata
         }
 
-        fn exec(&self, _args: Vec<Value>) -> eval::FunctionResult {
+        fn exec(&self, _args: Vec<Value>) -> FunctionResult {
             Ok(Value::Text("irrelevant".to_owned()))
         }
     }
@@ -327,7 +579,7 @@ mod tests {
     fn tester() -> Tester {
         let tester = Tester::from(Machine::default());
         let console = tester.get_console();
-        tester.add_command(HelpCommand::new(console))
+        tester.add_command(HelpCommand::new(console, false))
     }
 
     #[test]
@@ -353,6 +605,69 @@ mod tests {
             .check();
     }
 
+    #[test]
+    fn test_help_summarize_callables_with_colors() {
+        let tester = Tester::from(Machine::default());
+        let console = tester.get_console();
+        tester
+            .add_command(HelpCommand::new(console, true))
+            .add_command(DoNothingCommand::new())
+            .add_function(EmptyFunction::new())
+            .run("HELP")
+            .expect_prints(header())
+            .expect_output([
+                CapturedOut::Print("".to_owned()),
+                CapturedOut::Color(Some(CATEGORY_COLOR), None),
+                CapturedOut::Print("    >> Interpreter manipulation <<".to_owned()),
+                CapturedOut::Color(None, None),
+                CapturedOut::Color(Some(NAME_COLOR), None),
+                CapturedOut::Write(b"    HELP".to_vec()),
+                CapturedOut::Color(None, None),
+                CapturedOut::Print("          Prints interactive help.".to_owned()),
+                CapturedOut::Print("".to_owned()),
+                CapturedOut::Color(Some(CATEGORY_COLOR), None),
+                CapturedOut::Print("    >> Testing <<".to_owned()),
+                CapturedOut::Color(None, None),
+                CapturedOut::Color(Some(NAME_COLOR), None),
+                CapturedOut::Write(b"    DO_NOTHING".to_vec()),
+                CapturedOut::Color(None, None),
+                CapturedOut::Print("    This is the blurb.".to_owned()),
+                CapturedOut::Color(Some(NAME_COLOR), None),
+                CapturedOut::Write(b"    EMPTY$".to_vec()),
+                CapturedOut::Color(None, None),
+                CapturedOut::Print("        This is the blurb.".to_owned()),
+            ])
+            .expect_prints([
+                "",
+                "    Type HELP followed by a command or function name for details.",
+                "    Type HELP LANG for a quick reference guide about the language.",
+                "",
+            ])
+            .check();
+    }
+
+    #[test]
+    fn test_help_describe_command_has_no_colors() {
+        let tester = Tester::from(Machine::default());
+        let console = tester.get_console();
+        tester
+            .add_command(HelpCommand::new(console, true))
+            .add_command(DoNothingCommand::new())
+            .run("HELP DO_NOTHING")
+            .expect_prints([
+                "",
+                "    DO_NOTHING this [would] <be|the> syntax \"specification\"",
+                "",
+                "    This is the blurb.",
+                "",
+                "    First paragraph of the extended description.",
+                "",
+                "    Second paragraph of the extended description.",
+                "",
+            ])
+            .check();
+    }
+
     #[test]
     fn test_help_describe_command() {
         tester()
@@ -372,6 +687,26 @@ mod tests {
             .check();
     }
 
+    #[test]
+    fn test_help_describe_alias() {
+        tester()
+            .add_command(DoNothingCommand::new())
+            .add_alias("?", "DO_NOTHING")
+            .run("HELP ?")
+            .expect_prints([
+                "",
+                "    DO_NOTHING this [would] <be|the> syntax \"specification\"",
+                "",
+                "    This is the blurb.",
+                "",
+                "    First paragraph of the extended description.",
+                "",
+                "    Second paragraph of the extended description.",
+                "",
+            ])
+            .check();
+    }
+
     fn do_help_describe_function_test(name: &str) {
         tester()
             .add_function(EmptyFunction::new())
@@ -402,19 +737,116 @@ mod tests {
 
     #[test]
     fn test_help_lang() {
+        let mut t = tester();
+        let mut c = t.run("help lang");
+        for (_, text) in LANG_SECTIONS {
+            c = c.expect_prints([""]).expect_prints(text.lines().collect::<Vec<&str>>());
+        }
+        c.expect_prints([""]).check();
+    }
+
+    #[test]
+    fn test_help_lang_topics() {
+        for (name, text) in LANG_SECTIONS {
+            tester()
+                .run(format!("HELP LANG {}", name.to_ascii_lowercase()))
+                .expect_prints([""])
+                .expect_prints(text.lines().collect::<Vec<&str>>())
+                .expect_prints([""])
+                .check();
+        }
+
         tester()
-            .run("help lang")
-            .expect_prints(LANG_REFERENCE.lines().collect::<Vec<&str>>())
+            .run("HELP Lang Operators")
+            .expect_prints(["", "    Expressions:"])
+            .expect_prints(LANG_SECTIONS[2].1.lines().skip(1).collect::<Vec<&str>>())
             .expect_prints([""])
             .check();
     }
 
+    #[test]
+    fn test_help_lang_topic_errors() {
+        let mut t = tester();
+        t.run("HELP LANG foo")
+            .expect_err(
+                "Unknown language topic FOO; available topics are: \
+                 SYMBOLS, ASSIGNMENTS, OPERATORS, FLOW, MISC",
+            )
+            .check();
+        t.run("HELP LANG flow$").expect_err("Incompatible type annotation").check();
+        t.run("HELP LANG flow misc").expect_err("HELP takes zero or only one argument").check();
+    }
+
+    /// Creates a tester with `DESCRIBE$` and test callables, registering the latter after the
+    /// former to verify that the index tracks new callables.
+    fn describe_tester() -> Tester {
+        let descriptions = Rc::from(Descriptions::default());
+        let mut t = tester().add_function(DescribeFunction::new(descriptions.clone()));
+        t.get_machine().add_registration_listener(descriptions);
+        t.add_command(DoNothingCommand::new()).add_function(EmptyFunction::new())
+    }
+
+    #[test]
+    fn test_describe_command() {
+        describe_tester()
+            .run("d$ = DESCRIBE$(\"do_nothing\")")
+            .expect_var(
+                "d",
+                "DO_NOTHING this [would] <be|the> syntax \"specification\"
+This is the blurb.
+First paragraph of the extended description.
+Second paragraph of the extended description.",
+            )
+            .check();
+    }
+
+    #[test]
+    fn test_describe_function() {
+        let text = "EMPTY$(this [would] <be|the> syntax \"specification\")
+This is the blurb.
+First paragraph of the extended description.
+Second paragraph of the extended description.";
+        describe_tester().run("d$ = DESCRIBE$(\"Empty\")").expect_var("d", text).check();
+        describe_tester().run("d$ = DESCRIBE$(\"EMPTY$\")").expect_var("d", text).check();
+    }
+
+    #[test]
+    fn test_describe_matches_help() {
+        describe_tester()
+            .run("d$ = DESCRIBE$(\"HELP\")")
+            .expect_var(
+                "d",
+                "HELP [topic]
+Prints interactive help.
+Without arguments, shows a summary of all available help topics.
+With a single argument, shows detailed information about the given help topic, command, or \
+function.
+HELP LANG shows a quick reference guide about the language.  Follow it with one of SYMBOLS, \
+ASSIGNMENTS, OPERATORS, FLOW, or MISC to show only that section of the guide.",
+            )
+            .check();
+    }
+
+    #[test]
+    fn test_describe_errors() {
+        check_expr_error(
+            "Syntax error in call to DESCRIBE: Cannot describe unknown command or function FOO",
+            "DESCRIBE$(\"foo\")",
+        );
+        check_expr_error(
+            "Syntax error in call to DESCRIBE: Cannot describe unknown command or function HELP$",
+            "DESCRIBE$(\"help$\")",
+        );
+        check_expr_error("Syntax error in call to DESCRIBE: expected name$", "DESCRIBE$()");
+        check_expr_error("Syntax error in call to DESCRIBE: expected name$", "DESCRIBE$(3)");
+    }
+
     #[test]
     fn test_help_errors() {
         let mut t =
             tester().add_command(DoNothingCommand::new()).add_function(EmptyFunction::new());
 
-        t.run("HELP foo bar").expect_err("Unexpected value in expression").check();
+        t.run("HELP foo bar").expect_err("HELP takes zero or only one argument").check();
         t.run("HELP foo, bar").expect_err("HELP takes zero or only one argument").check();
 
         t.run("HELP lang%").expect_err("Incompatible type annotation").check();
@@ -425,4 +857,54 @@ mod tests {
         t.run("HELP do_nothing$").expect_err("Incompatible type annotation").check();
         t.run("HELP empty?").expect_err("Incompatible type annotation").check();
     }
+
+    #[test]
+    fn test_count_callables() {
+        let mut machine = Machine::default();
+        machine.add_command(DoNothingCommand::new()).unwrap();
+        machine.add_function(EmptyFunction::new()).unwrap();
+        let expected: BTreeMap<&str, (usize, usize)> =
+            [("Testing", (1, 1))].iter().cloned().collect();
+        assert_eq!(expected, count_callables(&machine));
+
+        let mut machine =
+            crate::scripting_machine(Rc::from(RefCell::from(MockConsole::default())), vec![]);
+        let counts = count_callables(&machine);
+        let commands: usize = counts.values().map(|(commands, _)| commands).sum();
+        let functions: usize = counts.values().map(|(_, functions)| functions).sum();
+        assert_eq!(machine.get_commands().len(), commands);
+        assert_eq!(machine.get_functions().len(), functions);
+
+        machine.add_command(DoNothingCommand::new()).unwrap();
+        assert_eq!(Some(&(1, 0)), count_callables(&machine).get("Testing"));
+    }
+
+    #[test]
+    fn test_stats() {
+        let t = tester();
+        let console = t.get_console();
+        t.add_command(StatsCommand::new(console))
+            .add_command(DoNothingCommand::new())
+            .add_function(EmptyFunction::new())
+            .run("STATS")
+            .expect_prints([
+                "",
+                "    Interpreter manipulation: 2 commands, 0 functions",
+                "    Testing: 1 command, 1 function",
+                "",
+                "    Total: 3 commands, 1 function",
+                "",
+            ])
+            .check();
+    }
+
+    #[test]
+    fn test_stats_errors() {
+        let t = tester();
+        let console = t.get_console();
+        t.add_command(StatsCommand::new(console))
+            .run("STATS 1")
+            .expect_err("STATS takes no arguments")
+            .check();
+    }
 }
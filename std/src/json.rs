@@ -0,0 +1,294 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Functions to parse and produce JSON documents.
+//!
+//! EndBASIC has no compound types, so JSON documents are handled as plain strings and individual
+//! values are addressed via dotted paths such as `a.b.0`.
+
+use endbasic_core::ast::{Value, VarType};
+use endbasic_core::eval::{
+    CallableMetadata, CallableMetadataBuilder, Function, FunctionError, FunctionResult,
+};
+use endbasic_core::exec::{self, Machine};
+use serde_json::{Map, Number};
+use std::rc::Rc;
+
+/// Category string for all functions provided by this module.
+const CATEGORY: &str = "JSON manipulation";
+
+/// Returns the value within `root` at the dotted `path`, if any.
+///
+/// Path components index into objects by key and into arrays by position.  An empty path refers to
+/// `root` itself.
+fn lookup<'a>(root: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    if path.is_empty() {
+        return Some(root);
+    }
+
+    let mut current = root;
+    for key in path.split('.') {
+        current = match current {
+            serde_json::Value::Object(map) => map.get(key)?,
+            serde_json::Value::Array(array) => array.get(key.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// Inserts `value` into the `root` object at the dotted `path`, creating intermediate objects as
+/// necessary and replacing any previous value.
+fn insert(root: &mut Map<String, serde_json::Value>, path: &str, value: serde_json::Value) {
+    let mut current = root;
+    let mut keys = path.split('.').peekable();
+    while let Some(key) = keys.next() {
+        if keys.peek().is_none() {
+            current.insert(key.to_owned(), value);
+            return;
+        }
+
+        let entry = current.entry(key).or_insert_with(|| serde_json::Value::Object(Map::default()));
+        if !entry.is_object() {
+            *entry = serde_json::Value::Object(Map::default());
+        }
+        current = entry.as_object_mut().expect("Just ensured this was an object");
+    }
+}
+
+/// The `JSONGET` function.
+pub struct JsonGetFunction {
+    metadata: CallableMetadata,
+}
+
+impl JsonGetFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("JSONGET", VarType::Text)
+                .with_syntax("json$, path$")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Extracts a value from a JSON document.
+The path$ is a dot-separated list of object keys and array positions, such as \"a.b.0\", that \
+locates the value to return.  An empty path$ refers to the whole document.
+Strings are returned without quotes, numbers and booleans are returned in their textual form, \
+and objects and arrays are returned as JSON documents so that they can be queried further.  \
+Returns an empty string if the path does not exist or if the value is null.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for JsonGetFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Text(json), Value::Text(path)] => {
+                let root: serde_json::Value = match serde_json::from_str(json) {
+                    Ok(root) => root,
+                    Err(e) => {
+                        return Err(FunctionError::ArgumentError(format!("Invalid JSON: {}", e)))
+                    }
+                };
+                let text = match lookup(&root, path) {
+                    None | Some(serde_json::Value::Null) => String::new(),
+                    Some(serde_json::Value::Bool(true)) => "TRUE".to_owned(),
+                    Some(serde_json::Value::Bool(false)) => "FALSE".to_owned(),
+                    Some(serde_json::Value::Number(n)) => n.to_string(),
+                    Some(serde_json::Value::String(s)) => s.clone(),
+                    Some(value) => value.to_string(),
+                };
+                Ok(Value::Text(text))
+            }
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
+/// The `JSONSET` function.
+pub struct JsonSetFunction {
+    metadata: CallableMetadata,
+}
+
+impl JsonSetFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("JSONSET", VarType::Text)
+                .with_syntax("key1$, value1[, .., keyN$, valueN]")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Produces a JSON object from a list of key/value pairs.
+Each key can be a dot-separated path, such as \"a.b\", to place its value in nested objects.  \
+Values can be strings, numbers or booleans, and they are stored in the object with their \
+corresponding JSON types.  Empty values are stored as null.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for JsonSetFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        if args.is_empty() {
+            return Err(FunctionError::SyntaxError);
+        }
+
+        let mut root = Map::default();
+        for pair in args.chunks(2) {
+            let (key, value) = match pair {
+                [Value::Text(key), value] => (key, value),
+                _ => return Err(FunctionError::SyntaxError),
+            };
+            if key.is_empty() {
+                return Err(FunctionError::ArgumentError("Keys cannot be empty".to_owned()));
+            }
+            let value = match value {
+                Value::Boolean(b) => serde_json::Value::Bool(*b),
+                Value::Double(d) => match Number::from_f64(*d) {
+                    Some(n) => serde_json::Value::Number(n),
+                    None => {
+                        return Err(FunctionError::ArgumentError(format!(
+                            "Cannot represent {} in JSON",
+                            d
+                        )))
+                    }
+                },
+                Value::Integer(i) => serde_json::Value::Number(Number::from(*i)),
+                Value::Text(s) => serde_json::Value::String(s.clone()),
+                Value::Empty => serde_json::Value::Null,
+            };
+            insert(&mut root, key, value);
+        }
+        Ok(Value::Text(serde_json::Value::Object(root).to_string()))
+    }
+}
+
+/// Adds all JSON-related functions to the `machine`.
+pub fn add_all(machine: &mut Machine) -> exec::Result<()> {
+    machine.add_function(JsonGetFunction::new())?;
+    machine.add_function(JsonSetFunction::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutils::*;
+
+    #[test]
+    fn test_jsonget_scalars() {
+        let json =
+            r#"{\"s\": \"text\", \"i\": 3, \"d\": 2.5, \"t\": true, \"f\": false, \"n\": null}"#;
+        check_expr_ok("text", &format!(r#"JSONGET$("{}", "s")"#, json));
+        check_expr_ok("3", &format!(r#"JSONGET$("{}", "i")"#, json));
+        check_expr_ok("2.5", &format!(r#"JSONGET$("{}", "d")"#, json));
+        check_expr_ok("TRUE", &format!(r#"JSONGET$("{}", "t")"#, json));
+        check_expr_ok("FALSE", &format!(r#"JSONGET$("{}", "f")"#, json));
+        check_expr_ok("", &format!(r#"JSONGET$("{}", "n")"#, json));
+    }
+
+    #[test]
+    fn test_jsonget_nested() {
+        Tester::default()
+            .run(
+                r#"j$ = "{\"a\": {\"b\": [10, {\"c\": \"deep\"}]}}"
+                b0$ = JSONGET$(j$, "a.b.0")
+                c$ = JSONGET$(j$, "a.b.1.c")
+                b$ = JSONGET$(j$, "a.b")
+                all$ = JSONGET$(j$, "")"#,
+            )
+            .expect_var("j", r#"{"a": {"b": [10, {"c": "deep"}]}}"#)
+            .expect_var("b0", "10")
+            .expect_var("c", "deep")
+            .expect_var("b", r#"[10,{"c":"deep"}]"#)
+            .expect_var("all", r#"{"a":{"b":[10,{"c":"deep"}]}}"#)
+            .check();
+    }
+
+    #[test]
+    fn test_jsonget_missing() {
+        let json = r#"{\"a\": {\"b\": [10]}, \"s\": \"x\"}"#;
+        for path in &["z", "a.z", "a.b.1", "a.b.x", "s.t", "a."] {
+            check_expr_ok("", &format!(r#"JSONGET$("{}", "{}")"#, json, path));
+        }
+    }
+
+    #[test]
+    fn test_jsonget_errors() {
+        check_expr_error("Syntax error in call to JSONGET: expected json$, path$", r#"JSONGET$()"#);
+        check_expr_error(
+            "Syntax error in call to JSONGET: expected json$, path$",
+            r#"JSONGET$("{}")"#,
+        );
+        check_expr_error(
+            "Syntax error in call to JSONGET: expected json$, path$",
+            r#"JSONGET$("{}", 1)"#,
+        );
+        check_expr_error(
+            "Syntax error in call to JSONGET: Invalid JSON: EOF while parsing an object at line 1 column 1",
+            r#"JSONGET$("{", "a")"#,
+        );
+    }
+
+    #[test]
+    fn test_jsonset_ok() {
+        check_expr_ok(r#"{"a":"text"}"#, r#"JSONSET$("a", "text")"#);
+        check_expr_ok(
+            r#"{"b":true,"d":2.5,"i":3,"s":"x"}"#,
+            r#"JSONSET$("s", "x", "i", 3, "d", 2.5, "b", TRUE)"#,
+        );
+        check_expr_ok(r#"{"a":2}"#, r#"JSONSET$("a", 1, "a", 2)"#);
+    }
+
+    #[test]
+    fn test_jsonset_nested() {
+        check_expr_ok(
+            r#"{"a":{"b":{"c":1},"d":"x"},"e":false}"#,
+            r#"JSONSET$("a.b.c", 1, "a.d", "x", "e", FALSE)"#,
+        );
+        check_expr_ok(r#"{"a":{"b":2}}"#, r#"JSONSET$("a", 1, "a.b", 2)"#);
+    }
+
+    #[test]
+    fn test_jsonset_jsonget_round_trip() {
+        Tester::default()
+            .run(r#"j$ = JSONSET$("user.name", "Jo \"J\"", "user.age", 40): n$ = JSONGET$(j$, "user.name")"#)
+            .expect_var("j", r#"{"user":{"age":40,"name":"Jo \"J\""}}"#)
+            .expect_var("n", r#"Jo "J""#)
+            .check();
+    }
+
+    #[test]
+    fn test_jsonset_errors() {
+        for args in &["", r#""a""#, r#""a", 1, "b""#, "1, 2"] {
+            check_expr_error(
+                "Syntax error in call to JSONSET: expected key1$, value1[, .., keyN$, valueN]",
+                &format!("JSONSET$({})", args),
+            );
+        }
+        check_expr_error(
+            "Syntax error in call to JSONSET: Keys cannot be empty",
+            r#"JSONSET$("", 1)"#,
+        );
+    }
+}
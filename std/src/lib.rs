@@ -21,34 +21,90 @@
 #![warn(unused, unused_extern_crates, unused_import_braces, unused_qualifications)]
 #![warn(unsafe_code)]
 
-use endbasic_core::exec::Machine;
-use std::cell::RefCell;
+use endbasic_core::exec::{Error, Machine};
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 // TODO(jmmv): Should narrow the exposed interface by 1.0.0.
+pub mod clock;
+pub mod completion;
 pub mod console;
+pub mod debug;
 mod editor;
+pub mod environ;
 pub mod exec;
+pub mod files;
+pub mod gfx;
 pub mod help;
+pub mod json;
+pub mod memory;
 pub mod numerics;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod shell;
+pub mod sound;
 pub mod store;
 pub mod strings;
 #[cfg(feature = "crossterm")]
 pub mod terminal;
 pub mod testutils;
 
+/// Adds all scripting commands from the standard library to `machine`, using `channels` as the
+/// table of open files and `args` as the arguments given to the program.
+fn add_scripting(
+    machine: &mut Machine,
+    console: Rc<RefCell<dyn console::Console>>,
+    channels: Rc<RefCell<files::Channels>>,
+    args: Vec<String>,
+) -> endbasic_core::exec::Result<()> {
+    // The web interface cannot use the system clock nor play sounds natively so it must register
+    // its own versions of these.
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let clock: Rc<dyn clock::Clock> = Rc::from(clock::SystemClock::default());
+        clock::add_all(machine, console.clone(), clock.clone())?;
+        sound::add_all(machine, Rc::from(sound::SilentSpeaker::new(clock)))?;
+    }
+    console::add_all(machine, console.clone(), channels)?;
+    environ::add_all(machine, args)?;
+    exec::add_all(machine, console.clone())?;
+    gfx::add_all(machine, console)?;
+    json::add_all(machine)?;
+    memory::add_all(machine, memory::DEFAULT_SIZE)?;
+    numerics::add_all(machine)?;
+    strings::add_all(machine)
+}
+
 /// Creates a new machine populated with all scripting commands from the standard library.
-pub fn scripting_machine(console: Rc<RefCell<dyn console::Console>>) -> Machine {
+///
+/// The `args` are the arguments given to the program, which are exposed via `ARG$`, `ARGC` and
+/// `COMMAND$`.
+pub fn scripting_machine(console: Rc<RefCell<dyn console::Console>>, args: Vec<String>) -> Machine {
     let mut machine = Machine::default();
-
-    console::add_all(&mut machine, console);
-    exec::add_all(&mut machine);
-    numerics::add_all(&mut machine);
-    strings::add_all(&mut machine);
-
+    let channels = Rc::from(RefCell::from(files::Channels::default()));
+    add_scripting(&mut machine, console, channels, args)
+        .expect("Standard library names must be unique");
     machine
 }
 
+/// Adds all interactive commands from the standard library to `machine`, using `channels` as the
+/// table of open files, `history` as the list of lines entered in the REPL and `tab_width` as the
+/// width of the tab stops used to display the `program`.
+fn add_interactive(
+    machine: &mut Machine,
+    console: Rc<RefCell<dyn console::Console>>,
+    store: Rc<RefCell<dyn store::Store>>,
+    program: Rc<RefCell<dyn store::Program>>,
+    channels: Rc<RefCell<files::Channels>>,
+    history: Rc<RefCell<console::History>>,
+    tab_width: Rc<Cell<usize>>,
+) -> endbasic_core::exec::Result<()> {
+    console::add_interactive(machine, console.clone(), history)?;
+    debug::add_all(machine, console.clone(), program.clone())?;
+    files::add_all(machine, store.clone(), channels)?;
+    help::add_all(machine, console.clone())?;
+    store::add_all(machine, program, console, store, tab_width)
+}
+
 /// Creates a new machine populated with all scripting _and_ interactive commands from the
 /// standard library.  This function is private because it permits specifying the initial contents
 /// of the stored `program`, which by default should be empty for public users.
@@ -56,21 +112,48 @@ fn full_machine(
     console: Rc<RefCell<dyn console::Console>>,
     store: Rc<RefCell<dyn store::Store>>,
     program: Rc<RefCell<dyn store::Program>>,
+    history: Rc<RefCell<console::History>>,
+    tab_width: Rc<Cell<usize>>,
+    args: Vec<String>,
 ) -> Machine {
-    let mut machine = scripting_machine(console.clone());
-
-    help::add_all(&mut machine, console.clone());
-    store::add_all(&mut machine, program, console, store);
-
+    let mut machine = Machine::default();
+    let channels = Rc::from(RefCell::from(files::Channels::default()));
+    add_scripting(&mut machine, console.clone(), channels.clone(), args)
+        .expect("Standard library names must be unique");
+    add_interactive(&mut machine, console, store, program, channels, history, tab_width)
+        .expect("Standard library names must be unique");
     machine
 }
 
 /// Creates a new machine populated with all scripting _and_ interactive commands from the
 /// standard library.
+///
+/// The `history` is the list of lines entered in the REPL, which must be the same one given to
+/// the REPL loop for the `HISTORY` command to report it.  The `args` are the arguments given to
+/// the program, if any.
 pub fn interactive_machine(
     console: Rc<RefCell<dyn console::Console>>,
     store: Rc<RefCell<dyn store::Store>>,
+    history: Rc<RefCell<console::History>>,
+    args: Vec<String>,
 ) -> Machine {
-    let program = Rc::from(RefCell::from(editor::Editor::default()));
-    full_machine(console, store, program)
+    let tab_width = Rc::from(Cell::new(console::DEFAULT_TAB_WIDTH));
+    let program = Rc::from(RefCell::from(editor::Editor::new(tab_width.clone())));
+    full_machine(console, store, program, history, tab_width, args)
+}
+
+/// Formats the execution error `e` for display to the user.
+///
+/// The message is prefixed with the line of the statement that raised the error only if the error
+/// comes from a stored program, as the line typed interactively is always "line 1" and thus the
+/// prefix would be meaningless.  Interruptions requested by the user are not reported as errors
+/// but as a plain "Break" message.
+pub fn format_error(e: &Error) -> String {
+    let pos = if e.is_in_program() { e.position() } else { None };
+    match (e.is_break(), pos) {
+        (true, Some(pos)) => format!("Break at line {}", pos.line),
+        (true, None) => "Break".to_owned(),
+        (false, Some(pos)) => format!("ERROR at line {}: {}", pos.line, e),
+        (false, None) => format!("ERROR: {}", e),
+    }
 }
@@ -0,0 +1,249 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Simulated memory for programs that expect to `PEEK` and `POKE` bytes.
+
+use async_trait::async_trait;
+use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
+use endbasic_core::eval::{
+    CallableMetadata, CallableMetadataBuilder, Function, FunctionError, FunctionResult,
+};
+use endbasic_core::exec::{self, Command, Machine};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Category string for all functions provided by this module.
+const CATEGORY: &str = "Memory manipulation";
+
+/// Default number of bytes in the simulated memory.
+pub const DEFAULT_SIZE: usize = 64 * 1024;
+
+/// A flat array of bytes that programs can read and write by address.
+///
+/// This has no relation to the memory of the interpreter: it only exists to run programs that
+/// were written for machines where poking at memory was common.  All bytes start as zero.
+pub struct Memory {
+    bytes: Vec<u8>,
+}
+
+impl Memory {
+    /// Creates a new memory of `size` bytes, all set to zero.
+    pub fn new(size: usize) -> Self {
+        Self { bytes: vec![0; size] }
+    }
+
+    /// Converts `addr` into an index into `bytes`, or returns an error message if it falls out
+    /// of the memory.
+    fn index(&self, addr: i32) -> Result<usize, String> {
+        if addr < 0 || addr as usize >= self.bytes.len() {
+            return Err(format!(
+                "Address {} out of range [0, {}]",
+                addr,
+                self.bytes.len() as i64 - 1
+            ));
+        }
+        Ok(addr as usize)
+    }
+
+    /// Returns the byte at `addr`.
+    fn peek(&self, addr: i32) -> Result<u8, String> {
+        let i = self.index(addr)?;
+        Ok(self.bytes[i])
+    }
+
+    /// Sets the byte at `addr` to `value`, which must fit in a byte.
+    fn poke(&mut self, addr: i32, value: i32) -> Result<(), String> {
+        let i = self.index(addr)?;
+        if !(0..=255).contains(&value) {
+            return Err(format!("Value {} out of range [0, 255]", value));
+        }
+        self.bytes[i] = value as u8;
+        Ok(())
+    }
+}
+
+/// The `PEEK` function.
+pub struct PeekFunction {
+    metadata: CallableMetadata,
+    memory: Rc<RefCell<Memory>>,
+}
+
+impl PeekFunction {
+    /// Creates a new instance of the function that reads from `memory`.
+    pub fn new(memory: Rc<RefCell<Memory>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("PEEK", VarType::Integer)
+                .with_syntax("address%")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Returns the byte stored at the given address of the simulated memory.
+The result is in the [0, 255] range.  See POKE for details on the simulated memory.",
+                )
+                .build(),
+            memory,
+        })
+    }
+}
+
+impl Function for PeekFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Integer(addr)] => match self.memory.borrow().peek(*addr) {
+                Ok(byte) => Ok(Value::Integer(byte as i32)),
+                Err(message) => Err(FunctionError::ArgumentError(message)),
+            },
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
+/// The `POKE` command.
+pub struct PokeCommand {
+    metadata: CallableMetadata,
+    memory: Rc<RefCell<Memory>>,
+}
+
+impl PokeCommand {
+    /// Creates a new instance of the command that writes to `memory`.
+    pub fn new(memory: Rc<RefCell<Memory>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("POKE", VarType::Void)
+                .with_syntax("address%, value%")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Stores a byte at the given address of the simulated memory.
+The simulated memory is a flat array of bytes, all initially zero, that is unrelated to the \
+memory of the machine running the interpreter.  It only exists to support programs that use \
+PEEK and POKE to keep data.  Addresses start at 0 and the value must be in the [0, 255] range.",
+                )
+                .build(),
+            memory,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for PokeCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let (addr, value) = match args {
+            [(Some(addr), ArgSep::Long), (Some(value), ArgSep::End)] => (addr, value),
+            _ => return exec::new_usage_error("POKE requires an address and a value"),
+        };
+        let addr = match addr.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Integer(i) => i,
+            _ => return exec::new_usage_error("POKE requires an integer address"),
+        };
+        let value = match value.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Integer(i) => i,
+            _ => return exec::new_usage_error("POKE requires an integer value"),
+        };
+        match self.memory.borrow_mut().poke(addr, value) {
+            Ok(()) => Ok(()),
+            Err(message) => exec::new_usage_error(message),
+        }
+    }
+}
+
+/// Adds all symbols provided by this module to the given `machine`, backed by a simulated memory
+/// of `size` bytes.
+pub fn add_all(machine: &mut Machine, size: usize) -> exec::Result<()> {
+    let memory = Rc::from(RefCell::from(Memory::new(size)));
+    machine.add_command(PokeCommand::new(memory.clone()))?;
+    machine.add_function(PeekFunction::new(memory))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::*;
+
+    /// Creates a tester with the memory functions backed by a memory of `size` bytes.
+    fn tester(size: usize) -> Tester {
+        let memory = Rc::from(RefCell::from(Memory::new(size)));
+        Tester::from(Machine::default())
+            .add_command(PokeCommand::new(memory.clone()))
+            .add_function(PeekFunction::new(memory))
+    }
+
+    #[test]
+    fn test_peek_poke_round_trip() {
+        let mut t = tester(16);
+        t.run("a = PEEK(0): b = PEEK(15)").expect_var("a", 0).expect_var("b", 0).check();
+        t.run("POKE 0, 255: POKE 15, 1: POKE 3, 42: a = PEEK(0): b = PEEK(15): c = PEEK(3)")
+            .expect_var("a", 255)
+            .expect_var("b", 1)
+            .expect_var("c", 42)
+            .check();
+        t.run("POKE 3, 7: c = PEEK(3)")
+            .expect_var("a", 255)
+            .expect_var("b", 1)
+            .expect_var("c", 7)
+            .check();
+    }
+
+    #[test]
+    fn test_default_machine() {
+        Tester::default().run("POKE 65535, 9: a = PEEK(65535)").expect_var("a", 9).check();
+        check_expr_error(
+            "Syntax error in call to PEEK: Address 65536 out of range [0, 65535]",
+            "PEEK(65536)",
+        );
+    }
+
+    #[test]
+    fn test_peek_errors() {
+        let mut t = tester(16);
+        t.run("a = PEEK(16)")
+            .expect_err("Syntax error in call to PEEK: Address 16 out of range [0, 15]")
+            .check();
+        t.run("a = PEEK(-1)")
+            .expect_err("Syntax error in call to PEEK: Address -1 out of range [0, 15]")
+            .check();
+        t.run("a = PEEK()").expect_err("Syntax error in call to PEEK: expected address%").check();
+        t.run("a = PEEK(1.0)")
+            .expect_err("Syntax error in call to PEEK: expected address%")
+            .check();
+        t.run("a = PEEK(1, 2)")
+            .expect_err("Syntax error in call to PEEK: expected address%")
+            .check();
+    }
+
+    #[test]
+    fn test_poke_errors() {
+        let mut t = tester(16);
+        t.run("POKE 16, 0").expect_err("Address 16 out of range [0, 15]").check();
+        t.run("POKE -1, 0").expect_err("Address -1 out of range [0, 15]").check();
+        t.run("POKE 0, 256").expect_err("Value 256 out of range [0, 255]").check();
+        t.run("POKE 0, -1").expect_err("Value -1 out of range [0, 255]").check();
+        t.run("POKE 0").expect_err("POKE requires an address and a value").check();
+        t.run("POKE 0; 1").expect_err("POKE requires an address and a value").check();
+        t.run("POKE 0, 1, 2").expect_err("POKE requires an address and a value").check();
+        t.run("POKE 0.0, 1").expect_err("POKE requires an integer address").check();
+        t.run("POKE 0, \"a\"").expect_err("POKE requires an integer value").check();
+        t.run("a = PEEK(0)").expect_var("a", 0).check();
+    }
+}
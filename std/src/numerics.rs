@@ -35,22 +35,27 @@ const CATEGORY: &str = "Numerical manipulation";
 /// The PRNG implemented here is intentionally simplistic and has no cryptographical guarantees.
 pub struct Prng {
     prng: SmallRng,
+    seed: i32,
     last: u32,
 }
 
 impl Prng {
-    /// Generates a new PRNG based on system entropy.
+    /// Generates a new PRNG based on a seed obtained from system entropy.
     pub fn new_from_entryopy() -> Self {
-        let mut prng = SmallRng::from_entropy();
-        let last = prng.next_u32();
-        Self { prng, last }
+        let seed = SmallRng::from_entropy().next_u32() as i32;
+        Self::new_from_seed(seed)
     }
 
     /// Generates a new PRNG based on the given seed.
     pub fn new_from_seed(seed: i32) -> Self {
         let mut prng = SmallRng::seed_from_u64(seed as u64);
         let last = prng.next_u32();
-        Self { prng, last }
+        Self { prng, seed, last }
+    }
+
+    /// Returns the seed used to initialize this PRNG.
+    fn seed(&self) -> i32 {
+        self.seed
     }
 
     /// Returns the previously returned random number.
@@ -65,6 +70,37 @@ impl Prng {
     }
 }
 
+/// The `DEG` function.
+pub struct DegFunction {
+    metadata: CallableMetadata,
+}
+
+impl DegFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("DEG", VarType::Double)
+                .with_syntax("angle#")
+                .with_category(CATEGORY)
+                .with_description("Converts the given angle in radians to degrees.")
+                .build(),
+        })
+    }
+}
+
+impl Function for DegFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Double(n)] => Ok(Value::Double(n.to_degrees())),
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
 /// The `DTOI` function.
 pub struct DtoiFunction {
     metadata: CallableMetadata,
@@ -131,6 +167,71 @@ impl Function for ItodFunction {
     }
 }
 
+/// The `PI` function.
+pub struct PiFunction {
+    metadata: CallableMetadata,
+}
+
+impl PiFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("PI", VarType::Double)
+                .with_syntax("")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Returns the number pi, the ratio of a circle's circumference to its diameter.
+As this function takes no arguments, it can be written as a plain PI without parenthesis.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for PiFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [] => Ok(Value::Double(std::f64::consts::PI)),
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
+/// The `RAD` function.
+pub struct RadFunction {
+    metadata: CallableMetadata,
+}
+
+impl RadFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("RAD", VarType::Double)
+                .with_syntax("angle#")
+                .with_category(CATEGORY)
+                .with_description("Converts the given angle in degrees to radians.")
+                .build(),
+        })
+    }
+}
+
+impl Function for RadFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Double(n)] => Ok(Value::Double(n.to_radians())),
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
 /// The `RANDOMIZE` command.
 pub struct RandomizeCommand {
     metadata: CallableMetadata,
@@ -146,7 +247,8 @@ impl RandomizeCommand {
                 .with_category(CATEGORY)
                 .with_description(
                     "Reinitializes the pseudo-random number generator.
-If no seed is given, uses system entropy to create a new sequence of random numbers.
+The same seed always yields the same sequence of random numbers, which makes runs reproducible.  \
+If no seed is given, uses system entropy to pick a new seed, which can be queried with SEED%.
 WARNING: These random numbers offer no cryptographic guarantees.",
                 )
                 .build(),
@@ -231,18 +333,75 @@ impl Function for RndFunction {
     }
 }
 
+/// The `SEED` function.
+pub struct SeedFunction {
+    metadata: CallableMetadata,
+    prng: Rc<RefCell<Prng>>,
+}
+
+impl SeedFunction {
+    /// Creates a new instance of the function.
+    pub fn new(prng: Rc<RefCell<Prng>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("SEED", VarType::Integer)
+                .with_syntax("")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Returns the seed of the pseudo-random number generator.
+This is the seed given to the last RANDOMIZE call, or the one picked from system entropy if no \
+seed was given.  Passing it to RANDOMIZE restarts the same sequence of random numbers from its \
+beginning, which is useful to reproduce a run.
+As this function takes no arguments, it can be written as a plain SEED without parenthesis.",
+                )
+                .build(),
+            prng,
+        })
+    }
+}
+
+impl Function for SeedFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [] => Ok(Value::Integer(self.prng.borrow().seed())),
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
 /// Adds all symbols provided by this module to the given `machine`.
-pub fn add_all(machine: &mut Machine) {
+pub fn add_all(machine: &mut Machine) -> exec::Result<()> {
     let prng = Rc::from(RefCell::from(Prng::new_from_entryopy()));
-    machine.add_command(RandomizeCommand::new(prng.clone()));
-    machine.add_function(DtoiFunction::new());
-    machine.add_function(ItodFunction::new());
-    machine.add_function(RndFunction::new(prng));
+    machine.add_command(RandomizeCommand::new(prng.clone()))?;
+    machine.add_function(DegFunction::new())?;
+    machine.add_function(DtoiFunction::new())?;
+    machine.add_function(ItodFunction::new())?;
+    machine.add_function(PiFunction::new())?;
+    machine.add_function(RadFunction::new())?;
+    machine.add_function(RndFunction::new(prng.clone()))?;
+    machine.add_function(SeedFunction::new(prng))
 }
 
 #[cfg(test)]
 mod tests {
     use crate::testutils::*;
+    use endbasic_core::exec::StopReason;
+    use futures_lite::future::block_on;
+
+    #[test]
+    fn test_deg() {
+        check_expr_ok(0.0, "DEG(0.0)");
+        check_expr_ok(180.0, "DEG(PI)");
+        check_expr_ok(true, "DEG(1.0) > 57.29577 AND DEG(1.0) < 57.29578");
+        check_expr_ok(true, "DEG(-PI / 2.0) > -90.000001 AND DEG(-PI / 2.0) < -89.999999");
+
+        check_expr_error("Syntax error in call to DEG: expected angle#", "DEG()");
+        check_expr_error("Syntax error in call to DEG: expected angle#", "DEG(180)");
+        check_expr_error("Syntax error in call to DEG: expected angle#", "DEG(1.0, 2.0)");
+    }
 
     #[test]
     fn test_dtoi() {
@@ -280,6 +439,27 @@ mod tests {
         check_expr_error("Syntax error in call to ITOD: expected expr%", "ITOD(3, 4)");
     }
 
+    #[test]
+    fn test_pi() {
+        check_expr_ok(std::f64::consts::PI, "PI");
+        check_expr_ok(std::f64::consts::PI, "PI()");
+        check_expr_ok(true, "PI > 3.141592 AND PI < 3.141593");
+
+        check_expr_error("Syntax error in call to PI: expected no arguments", "PI(1)");
+    }
+
+    #[test]
+    fn test_rad() {
+        check_expr_ok(0.0, "RAD(0.0)");
+        check_expr_ok(true, "RAD(180.0) - PI < 0.000001 AND PI - RAD(180.0) < 0.000001");
+        check_expr_ok(true, "RAD(-90.0) > -1.570797 AND RAD(-90.0) < -1.570796");
+        check_expr_ok(true, "DEG(RAD(45.0)) > 44.999999 AND DEG(RAD(45.0)) < 45.000001");
+
+        check_expr_error("Syntax error in call to RAD: expected angle#", "RAD()");
+        check_expr_error("Syntax error in call to RAD: expected angle#", "RAD(180)");
+        check_expr_error("Syntax error in call to RAD: expected angle#", "RAD(1.0, 2.0)");
+    }
+
     #[test]
     fn test_randomize_and_rnd() {
         // These tests could lead to flakiness if the PRNG happens to yield the same number twice
@@ -304,4 +484,39 @@ mod tests {
         check_stmt_err("Random seed must be an integer", "RANDOMIZE 3.0");
         check_stmt_err("RANDOMIZE takes zero or one argument", "RANDOMIZE ,");
     }
+
+    #[test]
+    fn test_randomize_with_same_seed_is_reproducible() {
+        let code = "RANDOMIZE 1234: a = RND(1): b = RND(1): c = RND(0): d = RND(1)";
+
+        let mut t1 = Tester::default();
+        assert_eq!(StopReason::Eof, block_on(t1.get_machine().exec(&mut code.as_bytes())).unwrap());
+        let vars = t1.get_machine().get_vars().as_hashmap().clone();
+        assert_eq!(4, vars.len());
+
+        let mut t2 = Tester::default();
+        let mut checker = t2.run(code);
+        for (name, value) in vars {
+            checker = checker.expect_var(name, value);
+        }
+        checker.check();
+    }
+
+    #[test]
+    fn test_seed() {
+        Tester::default()
+            .run("RANDOMIZE 10: s1 = SEED: RANDOMIZE -7: s2 = SEED()")
+            .expect_var("s1", 10)
+            .expect_var("s2", -7)
+            .check();
+
+        Tester::default()
+            .run("s = SEED: a = RND(1): RANDOMIZE s: ASSERT a = RND(1): CLEAR")
+            .check();
+        Tester::default()
+            .run("RANDOMIZE: s = SEED: a = RND(1): RANDOMIZE s: ASSERT a = RND(1): CLEAR")
+            .check();
+
+        check_expr_error("Syntax error in call to SEED: expected no arguments", "SEED(1)");
+    }
 }
@@ -0,0 +1,133 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Commands to interact with the host operating system.
+//!
+//! These commands give programs unrestricted access to the host, so they are not part of any of
+//! the default machines: embedders must explicitly opt into them via `add_all`.
+
+use async_trait::async_trait;
+use endbasic_core::ast::{ArgSep, Expr, Value, VarRef, VarType};
+use endbasic_core::eval::{CallableMetadata, CallableMetadataBuilder};
+use endbasic_core::exec::{self, Command, Machine};
+use std::process;
+use std::rc::Rc;
+
+/// Name of the variable that receives the exit status of the last `SHELL` command.
+const RESULT_VAR: &str = "SHELLRESULT";
+
+/// Builds the invocation of the system shell to run `command`.
+fn shell_command(command: &str) -> process::Command {
+    if cfg!(target_os = "windows") {
+        let mut cmd = process::Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = process::Command::new("/bin/sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+/// The `SHELL` command.
+pub struct ShellCommand {
+    metadata: CallableMetadata,
+}
+
+impl ShellCommand {
+    /// Creates a new `SHELL` command that runs commands on the host.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("SHELL", VarType::Void)
+                .with_syntax("command$")
+                .with_category("Interpreter manipulation")
+                .with_description(
+                    "Runs a command in the system shell and waits for it to finish.
+The command shares the console with the interpreter, so anything it prints appears on the \
+screen and it can read from the keyboard.
+The exit status of the command is stored in the SHELLRESULT% variable.  If the command did not \
+exit cleanly, such as when it was killed by a signal, SHELLRESULT% is set to -1.",
+                )
+                .build(),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for ShellCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let command = match args {
+            [(Some(expr), ArgSep::End)] => {
+                match expr.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Text(t) => t,
+                    _ => return exec::new_usage_error("SHELL requires a string as the command"),
+                }
+            }
+            _ => return exec::new_usage_error("SHELL requires a command"),
+        };
+
+        let status = shell_command(&command).status()?;
+        let code = status.code().unwrap_or(-1);
+        machine.set_var(&VarRef::new(RESULT_VAR, VarType::Integer), Value::Integer(code))?;
+        Ok(())
+    }
+}
+
+/// Adds all host-related commands to the `machine`.
+pub fn add_all(machine: &mut Machine) -> exec::Result<()> {
+    machine.add_command(ShellCommand::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::*;
+
+    fn tester() -> Tester {
+        Tester::from(Machine::default()).add_command(ShellCommand::new())
+    }
+
+    #[test]
+    fn test_shell_ok() {
+        tester().run(r#"SHELL "exit 0""#).expect_var("SHELLRESULT", 0).check();
+        tester().run(r#"SHELL "exit 3""#).expect_var("SHELLRESULT", 3).check();
+        tester()
+            .run(r#"c$ = "exit 5": SHELL c$: r = SHELLRESULT%"#)
+            .expect_var("c", "exit 5")
+            .expect_var("SHELLRESULT", 5)
+            .expect_var("r", 5)
+            .check();
+    }
+
+    #[test]
+    fn test_shell_errors() {
+        tester().run("SHELL").expect_err("SHELL requires a command").check();
+        tester().run("SHELL 3").expect_err("SHELL requires a string as the command").check();
+        tester().run(r#"SHELL "a", "b""#).expect_err("SHELL requires a command").check();
+        tester()
+            .run(r#"SHELLRESULT$ = "x": SHELL "exit 0""#)
+            .expect_err("Incompatible types in SHELLRESULT% assignment")
+            .expect_var("SHELLRESULT", "x")
+            .check();
+    }
+}
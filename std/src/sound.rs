@@ -0,0 +1,498 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Commands to play sounds.
+
+use crate::clock::Clock;
+use async_trait::async_trait;
+use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
+use endbasic_core::eval::{CallableMetadata, CallableMetadataBuilder};
+use endbasic_core::exec::{self, Command, Machine};
+use std::io;
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::Chars;
+use std::time::Duration;
+
+/// Category string for all commands provided by this module.
+const CATEGORY: &str = "Sound";
+
+/// Abstract operations to play sounds.
+#[async_trait(?Send)]
+pub trait Speaker {
+    /// Stays silent for `duration`.
+    async fn rest(&self, duration: Duration) -> io::Result<()>;
+
+    /// Plays a tone of `frequency` Hz for `duration` and returns once it has finished playing.
+    async fn tone(&self, frequency: u32, duration: Duration) -> io::Result<()>;
+}
+
+/// Speaker for platforms without audio support.
+///
+/// Tones are not audible but still take as long to play as requested so that programs keep their
+/// timing.
+pub struct SilentSpeaker {
+    clock: Rc<dyn Clock>,
+}
+
+impl SilentSpeaker {
+    /// Creates a new speaker that uses `clock` to wait for tones to "play".
+    pub fn new(clock: Rc<dyn Clock>) -> Self {
+        Self { clock }
+    }
+}
+
+#[async_trait(?Send)]
+impl Speaker for SilentSpeaker {
+    async fn rest(&self, duration: Duration) -> io::Result<()> {
+        self.clock.sleep(duration).await;
+        Ok(())
+    }
+
+    async fn tone(&self, _frequency: u32, duration: Duration) -> io::Result<()> {
+        self.clock.sleep(duration).await;
+        Ok(())
+    }
+}
+
+/// A single step of a piece of music.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MusicEvent {
+    /// A tone of the given frequency, in Hz, that lasts for the given time.
+    Note(u32, Duration),
+
+    /// A silence that lasts for the given time.
+    Rest(Duration),
+}
+
+/// Consumes the decimal number at the front of `chars`, if any.
+fn parse_number(chars: &mut Peekable<Chars>) -> Result<Option<u32>, String> {
+    let mut digits = String::new();
+    while let Some(ch) = chars.peek() {
+        if !ch.is_ascii_digit() {
+            break;
+        }
+        digits.push(*ch);
+        chars.next();
+    }
+    if digits.is_empty() {
+        return Ok(None);
+    }
+    digits.parse::<u32>().map(Some).map_err(|_| format!("Number {} is too large", digits))
+}
+
+/// Consumes the decimal number at the front of `chars`, which must exist and be within the
+/// `min` and `max` bounds.  `what` names the quantity the number represents for error messages.
+fn parse_bounded_number(
+    chars: &mut Peekable<Chars>,
+    what: &str,
+    min: u32,
+    max: u32,
+) -> Result<Option<u32>, String> {
+    match parse_number(chars)? {
+        Some(n) if n < min || n > max => {
+            Err(format!("{} {} out of range [{}, {}]", what, n, min, max))
+        }
+        n => Ok(n),
+    }
+}
+
+/// Consumes the length of a note or rest at the front of `chars`, including any trailing dots,
+/// and computes its duration given the `default` length and the `tempo`.
+fn parse_duration(
+    chars: &mut Peekable<Chars>,
+    default: u32,
+    tempo: u32,
+) -> Result<Duration, String> {
+    let length = parse_bounded_number(chars, "Length", 1, 64)?.unwrap_or(default);
+    // A whole note lasts four beats, and each dot extends the note by half of the previous
+    // extension.
+    let mut duration = Duration::from_secs(4 * 60) / (length * tempo);
+    let mut extension = duration / 2;
+    while chars.peek() == Some(&'.') {
+        chars.next();
+        duration += extension;
+        extension /= 2;
+    }
+    Ok(duration)
+}
+
+/// Computes the frequency of the note `semitone` semitones above C in the given `octave`.
+///
+/// Frequencies are rounded to the closest integer and use equal temperament tuned so that the A
+/// of octave 4 is 440 Hz.
+fn note_frequency(octave: u32, semitone: i32) -> u32 {
+    let distance_to_a4 = (octave as i32 - 4) * 12 + semitone - 9;
+    (440.0 * 2f64.powf(f64::from(distance_to_a4) / 12.0)).round() as u32
+}
+
+/// Parses a string in the music macro language into the sequence of events it describes.
+///
+/// The language consists of the notes `A` to `G`, each optionally followed by `#` or `+` to
+/// raise it a semitone or `-` to lower it, by a length (1 for a whole note, 4 for a quarter note,
+/// etc.) and by dots to extend it.  `P` or `R` followed by a length is a rest.  `On` sets the
+/// octave between 0 and 6, `<` and `>` move one octave down or up, `Ln` sets the default length
+/// of notes and rests, and `Tn` sets the tempo in quarter notes per minute.  Letters are
+/// case-insensitive and spaces are ignored.
+pub fn parse_music(music: &str) -> Result<Vec<MusicEvent>, String> {
+    let mut events = vec![];
+    let mut octave = 4;
+    let mut length = 4;
+    let mut tempo = 120;
+
+    let mut chars = music.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch.to_ascii_uppercase() {
+            ' ' => (),
+
+            note @ 'A'..='G' => {
+                let mut semitone = match note {
+                    'C' => 0,
+                    'D' => 2,
+                    'E' => 4,
+                    'F' => 5,
+                    'G' => 7,
+                    'A' => 9,
+                    'B' => 11,
+                    _ => unreachable!("Range matched above"),
+                };
+                match chars.peek() {
+                    Some('#') | Some('+') => {
+                        chars.next();
+                        semitone += 1;
+                    }
+                    Some('-') => {
+                        chars.next();
+                        semitone -= 1;
+                    }
+                    _ => (),
+                }
+                let duration = parse_duration(&mut chars, length, tempo)?;
+                events.push(MusicEvent::Note(note_frequency(octave, semitone), duration));
+            }
+
+            'P' | 'R' => events.push(MusicEvent::Rest(parse_duration(&mut chars, length, tempo)?)),
+
+            'O' => match parse_bounded_number(&mut chars, "Octave", 0, 6)? {
+                Some(n) => octave = n,
+                None => return Err("Missing octave after O".to_owned()),
+            },
+            '<' if octave > 0 => octave -= 1,
+            '>' if octave < 6 => octave += 1,
+            '<' | '>' => {
+                return Err(format!("Cannot move to octave out of range [0, 6] with {}", ch))
+            }
+
+            'L' => match parse_bounded_number(&mut chars, "Length", 1, 64)? {
+                Some(n) => length = n,
+                None => return Err("Missing length after L".to_owned()),
+            },
+
+            'T' => match parse_bounded_number(&mut chars, "Tempo", 32, 255)? {
+                Some(n) => tempo = n,
+                None => return Err("Missing tempo after T".to_owned()),
+            },
+
+            _ => return Err(format!("Invalid character {} in music string", ch)),
+        }
+    }
+    Ok(events)
+}
+
+/// The `PLAY` command.
+pub struct PlayCommand {
+    metadata: CallableMetadata,
+    speaker: Rc<dyn Speaker>,
+}
+
+impl PlayCommand {
+    /// Creates a new `PLAY` command that plays music on `speaker`.
+    pub fn new(speaker: Rc<dyn Speaker>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("PLAY", VarType::Void)
+                .with_syntax("music$")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Plays music described in the music macro language.
+The notes A to G play in the current octave and can be followed by # or + for a sharp, by - for \
+a flat, by a length (1 for a whole note, 2 for a half note, 4 for a quarter note, and so on up \
+to 64) and by dots to extend them by half.  P or R followed by a length is a rest.
+On selects octave n between 0 and 6, and < and > move one octave down or up.  The default \
+octave is 4, in which A is 440 Hz.
+Ln sets the length of notes and rests that do not specify their own, which is 4 by default.
+Tn sets the tempo in quarter notes per minute, between 32 and 255, which is 120 by default.
+Letters can be in any case and spaces are ignored.  See SOUND for details on where the music is \
+audible.",
+                )
+                .build(),
+            speaker,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for PlayCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let music = match args {
+            [(Some(music), ArgSep::End)] => {
+                match music.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Text(music) => music,
+                    _ => return exec::new_usage_error("PLAY requires a string"),
+                }
+            }
+            _ => return exec::new_usage_error("PLAY requires a string"),
+        };
+
+        let events = match parse_music(&music) {
+            Ok(events) => events,
+            Err(e) => return exec::new_usage_error(e),
+        };
+        for event in events {
+            match event {
+                MusicEvent::Note(frequency, duration) => {
+                    self.speaker.tone(frequency, duration).await?
+                }
+                MusicEvent::Rest(duration) => self.speaker.rest(duration).await?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `SOUND` command.
+pub struct SoundCommand {
+    metadata: CallableMetadata,
+    speaker: Rc<dyn Speaker>,
+}
+
+impl SoundCommand {
+    /// Creates a new `SOUND` command that plays tones on `speaker`.
+    pub fn new(speaker: Rc<dyn Speaker>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("SOUND", VarType::Void)
+                .with_syntax("frequency%, duration#")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Plays a tone.
+Plays a tone of frequency% Hz for duration# seconds and waits for it to finish.  Both values \
+must be positive.  Tones are only audible in the web interface: elsewhere, SOUND just waits for \
+the requested duration.",
+                )
+                .build(),
+            speaker,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for SoundCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let (frequency, duration) = match args {
+            [(Some(frequency), ArgSep::Long), (Some(duration), ArgSep::End)] => {
+                (frequency, duration)
+            }
+            _ => return exec::new_usage_error("SOUND requires a frequency and a duration"),
+        };
+
+        let frequency = match frequency.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Integer(i) if i > 0 => i as u32,
+            Value::Integer(_) => return exec::new_usage_error("Frequency must be positive"),
+            _ => return exec::new_usage_error("Frequency must be an integer"),
+        };
+
+        let duration = match duration.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Double(d) if d > 0.0 => Duration::from_secs_f64(d),
+            Value::Integer(i) if i > 0 => Duration::from_secs(i as u64),
+            Value::Double(_) | Value::Integer(_) => {
+                return exec::new_usage_error("Duration must be positive")
+            }
+            _ => return exec::new_usage_error("Duration must be a number"),
+        };
+
+        self.speaker.tone(frequency, duration).await?;
+        Ok(())
+    }
+}
+
+/// Adds all sound-related commands to the `machine`, playing them on `speaker`.
+pub fn add_all(machine: &mut Machine, speaker: Rc<dyn Speaker>) -> exec::Result<()> {
+    machine.add_command(PlayCommand::new(speaker.clone()))?;
+    machine.add_command(SoundCommand::new(speaker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::*;
+    use std::cell::RefCell;
+
+    /// Speaker that records the tones and rests it is asked to play without waiting.
+    #[derive(Default)]
+    struct RecordingSpeaker {
+        events: RefCell<Vec<MusicEvent>>,
+    }
+
+    #[async_trait(?Send)]
+    impl Speaker for RecordingSpeaker {
+        async fn rest(&self, duration: Duration) -> io::Result<()> {
+            self.events.borrow_mut().push(MusicEvent::Rest(duration));
+            Ok(())
+        }
+
+        async fn tone(&self, frequency: u32, duration: Duration) -> io::Result<()> {
+            self.events.borrow_mut().push(MusicEvent::Note(frequency, duration));
+            Ok(())
+        }
+    }
+
+    /// Shorthand to construct a `MusicEvent::Note` that lasts `millis` milliseconds.
+    fn note(frequency: u32, millis: u64) -> MusicEvent {
+        MusicEvent::Note(frequency, Duration::from_millis(millis))
+    }
+
+    /// Shorthand to construct a `MusicEvent::Rest` that lasts `millis` milliseconds.
+    fn rest(millis: u64) -> MusicEvent {
+        MusicEvent::Rest(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn test_parse_music_note_frequencies() {
+        assert_eq!(
+            vec![
+                note(262, 500),
+                note(294, 500),
+                note(330, 500),
+                note(349, 500),
+                note(392, 500),
+                note(440, 500),
+                note(494, 500),
+            ],
+            parse_music("CDEFGAB").unwrap()
+        );
+        assert_eq!(
+            vec![note(466, 500), note(466, 500), note(466, 500), note(247, 500)],
+            parse_music("a#a+b-c-").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_music_octaves() {
+        assert_eq!(
+            vec![note(880, 500), note(1760, 500), note(880, 500), note(16, 500), note(28, 500)],
+            parse_music("O5 A > A < A O0 C A").unwrap()
+        );
+        assert_eq!(vec![note(1047, 500), note(523, 500)], parse_music("O6C<C").unwrap());
+    }
+
+    #[test]
+    fn test_parse_music_durations() {
+        assert_eq!(
+            vec![note(440, 2000), note(440, 250), note(440, 750), note(440, 875), rest(500)],
+            parse_music("A1 A8 A4. A4.. P").unwrap()
+        );
+        assert_eq!(
+            vec![note(440, 250), rest(250), note(440, 1000), rest(125)],
+            parse_music("L8 A R A2 P16").unwrap()
+        );
+        assert_eq!(vec![note(440, 1000), note(440, 250)], parse_music("T60 A A16").unwrap());
+    }
+
+    #[test]
+    fn test_parse_music_errors() {
+        for (exp_error, music) in &[
+            ("Invalid character X in music string", "CDX"),
+            ("Invalid character # in music string", "#"),
+            ("Octave 7 out of range [0, 6]", "O7"),
+            ("Missing octave after O", "OA"),
+            ("Cannot move to octave out of range [0, 6] with >", "O6>"),
+            ("Cannot move to octave out of range [0, 6] with <", "o0<"),
+            ("Length 0 out of range [1, 64]", "L0"),
+            ("Length 65 out of range [1, 64]", "A65"),
+            ("Missing length after L", "L"),
+            ("Tempo 31 out of range [32, 255]", "T31"),
+            ("Missing tempo after T", "T A"),
+            ("Number 99999999999 is too large", "A99999999999"),
+        ] {
+            assert_eq!(*exp_error, parse_music(music).unwrap_err());
+        }
+    }
+
+    #[test]
+    fn test_play_ok() {
+        let speaker = Rc::from(RecordingSpeaker::default());
+        Tester::from(Machine::default())
+            .add_command(PlayCommand::new(speaker.clone()))
+            .run("m$ = \"a P8\": PLAY m$ + \" >a\": PLAY \"\"")
+            .expect_var("m", "a P8")
+            .check();
+        assert_eq!(vec![note(440, 500), rest(250), note(880, 500)], *speaker.events.borrow());
+    }
+
+    #[test]
+    fn test_play_errors() {
+        check_stmt_err("PLAY requires a string", "PLAY");
+        check_stmt_err("PLAY requires a string", "PLAY \"A\", \"B\"");
+        check_stmt_err("PLAY requires a string", "PLAY 3");
+        check_stmt_err("Invalid character X in music string", "PLAY \"AX\"");
+    }
+
+    #[test]
+    fn test_sound_ok() {
+        let speaker = Rc::from(RecordingSpeaker::default());
+        Tester::from(Machine::default())
+            .add_command(SoundCommand::new(speaker.clone()))
+            .run("SOUND 440, 0.25: f = 220: SOUND f * 2 + 1, 2")
+            .expect_var("f", 220)
+            .check();
+        assert_eq!(vec![note(440, 250), note(441, 2000)], *speaker.events.borrow());
+    }
+
+    #[test]
+    fn test_sound_silent() {
+        let clock = crate::clock::SystemClock::default();
+        Tester::default().run("SOUND 1000, 0.1").check();
+        assert!(clock.now() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_sound_errors() {
+        check_stmt_err("SOUND requires a frequency and a duration", "SOUND");
+        check_stmt_err("SOUND requires a frequency and a duration", "SOUND 440");
+        check_stmt_err("SOUND requires a frequency and a duration", "SOUND 440; 1");
+        check_stmt_err("SOUND requires a frequency and a duration", "SOUND 440, 1, 2");
+        check_stmt_err("Frequency must be positive", "SOUND 0, 1");
+        check_stmt_err("Frequency must be positive", "SOUND -440, 1");
+        check_stmt_err("Frequency must be an integer", "SOUND 440.0, 1");
+        check_stmt_err("Duration must be positive", "SOUND 440, 0");
+        check_stmt_err("Duration must be positive", "SOUND 440, -0.5");
+        check_stmt_err("Duration must be a number", "SOUND 440, \"1\"");
+    }
+}
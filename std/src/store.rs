@@ -15,16 +15,17 @@
 
 //! Stored program manipulation and interactive editor.
 
-use crate::console::Console;
+use crate::console::{expand_tabs, Console, DEFAULT_TAB_WIDTH};
 use async_trait::async_trait;
 use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
 use endbasic_core::eval::{CallableMetadata, CallableMetadataBuilder};
 use endbasic_core::exec::{self, Command, Machine};
-use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap};
+use endbasic_core::lint;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::str;
 
@@ -46,11 +47,33 @@ pub trait Store {
     /// Returns a sorted list of the entries in the store and their metadata.
     fn enumerate(&self) -> io::Result<BTreeMap<String, Metadata>>;
 
+    /// Checks if the program given by `name` exists.
+    ///
+    /// The default implementation attempts to load the program, so stores that can answer this
+    /// more cheaply should override it.
+    fn exists(&self, name: &str) -> io::Result<bool> {
+        match self.get(name) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Loads the contents of the program given by `name`.
     fn get(&self, name: &str) -> io::Result<String>;
 
     /// Saves the in-memory program given by `content` into `name`.
     fn put(&mut self, name: &str, content: &str) -> io::Result<()>;
+
+    /// Returns a sorted list of the entries in the `drive` of the store and their metadata.
+    ///
+    /// Stores without drives do not recognize any drive name.
+    fn enumerate_drive(&self, drive: &str) -> io::Result<BTreeMap<String, Metadata>> {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Unknown drive {}", drive.to_ascii_uppercase()),
+        ))
+    }
 }
 
 /// An implementation of the store that records all data in memory only.
@@ -84,6 +107,10 @@ impl Store for InMemoryStore {
         Ok(entries)
     }
 
+    fn exists(&self, name: &str) -> io::Result<bool> {
+        Ok(self.programs.contains_key(name))
+    }
+
     fn get(&self, name: &str) -> io::Result<String> {
         match self.programs.get(name) {
             Some(content) => Ok(content.to_owned()),
@@ -97,6 +124,131 @@ impl Store for InMemoryStore {
     }
 }
 
+/// Wraps a `Store` and rejects any operation that would modify it.
+///
+/// This is useful for deployments (such as demos or kiosks) where users should be able to browse
+/// and run the stored programs but not change them.
+pub struct ReadOnlyStore<S: Store> {
+    /// The wrapped store.
+    delegate: S,
+}
+
+impl<S: Store> ReadOnlyStore<S> {
+    /// Creates a new read-only view of the `delegate` store.
+    pub fn new(delegate: S) -> Self {
+        Self { delegate }
+    }
+
+    /// Disowns and returns the underlying delegate store.
+    pub fn unmount(self) -> S {
+        self.delegate
+    }
+}
+
+impl<S: Store> Store for ReadOnlyStore<S> {
+    fn delete(&mut self, _name: &str) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "Store is read-only"))
+    }
+
+    fn enumerate(&self) -> io::Result<BTreeMap<String, Metadata>> {
+        self.delegate.enumerate()
+    }
+
+    fn get(&self, name: &str) -> io::Result<String> {
+        self.delegate.get(name)
+    }
+
+    fn put(&mut self, _name: &str, _content: &str) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "Store is read-only"))
+    }
+}
+
+/// Layers a writable `Store` on top of a read-only `Store`.
+///
+/// Reads prefer the upper layer and fall back to the base layer.  Writes always go to the upper
+/// layer so that the base layer is never modified.  Deleting a file that exists in the base layer
+/// records a "whiteout" so that the file appears gone even though it's still in the base layer.
+/// Whiteouts only live in memory and match names case-insensitively to mimic the behavior of other
+/// stores.
+pub struct OverlayStore<B: Store, U: Store> {
+    /// The read-only layer.
+    base: B,
+
+    /// The writable layer.
+    upper: U,
+
+    /// Uppercase names of the files in the base layer that have been deleted.
+    whiteouts: HashSet<String>,
+}
+
+impl<B: Store, U: Store> OverlayStore<B, U> {
+    /// Creates a new overlay of the writable `upper` store on top of the read-only `base` store.
+    pub fn new(base: B, upper: U) -> Self {
+        Self { base, upper, whiteouts: HashSet::default() }
+    }
+
+    /// Disowns and returns the underlying base and upper stores.
+    pub fn unmount(self) -> (B, U) {
+        (self.base, self.upper)
+    }
+}
+
+impl<B: Store, U: Store> Store for OverlayStore<B, U> {
+    fn delete(&mut self, name: &str) -> io::Result<()> {
+        let uc_name = name.to_ascii_uppercase();
+        if self.whiteouts.contains(&uc_name) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Entry not found"));
+        }
+
+        let in_upper = match self.upper.delete(name) {
+            Ok(()) => true,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+            Err(e) => return Err(e),
+        };
+        let in_base = match self.base.get(name) {
+            Ok(_) => true,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+            Err(e) => return Err(e),
+        };
+
+        if in_base {
+            self.whiteouts.insert(uc_name);
+        } else if !in_upper {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Entry not found"));
+        }
+        Ok(())
+    }
+
+    fn enumerate(&self) -> io::Result<BTreeMap<String, Metadata>> {
+        let mut entries = self.base.enumerate()?;
+        entries.retain(|name, _| !self.whiteouts.contains(&name.to_ascii_uppercase()));
+        for (name, metadata) in self.upper.enumerate()? {
+            entries.insert(name, metadata);
+        }
+        Ok(entries)
+    }
+
+    fn get(&self, name: &str) -> io::Result<String> {
+        match self.upper.get(name) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if self.whiteouts.contains(&name.to_ascii_uppercase()) {
+                    Err(e)
+                } else {
+                    self.base.get(name)
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn put(&mut self, name: &str, content: &str) -> io::Result<()> {
+        self.upper.put(name, content)?;
+        self.whiteouts.remove(&name.to_ascii_uppercase());
+        Ok(())
+    }
+}
+
 /// An implementation of `Store` backed by an on-disk directory.
 pub struct FileStore {
     /// Path to the directory containing all entries backed by this `Store`.  The directory may
@@ -105,17 +257,53 @@ pub struct FileStore {
     dir: PathBuf,
 }
 
+/// Annotates the I/O error `e` raised while trying to `action` the file or directory at `path`.
+///
+/// The kind of the original error is preserved so that callers can still react to it.
+fn with_context(e: io::Error, action: &str, path: &Path) -> io::Error {
+    io::Error::new(e.kind(), format!("Cannot {} {}: {}", action, path.display(), e))
+}
+
 impl FileStore {
     /// Creates a new store backed by the `dir` directory.
     pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
         Self { dir: dir.into() }
     }
+
+    /// Locates the on-disk file that backs the program `name`.
+    ///
+    /// Names are matched case-insensitively to mimic the behavior of other stores.  If there is
+    /// a file whose name matches `name` exactly, it is preferred over any other candidates.
+    /// Returns `None` if there is no matching file.
+    fn find(&self, name: &str) -> io::Result<Option<PathBuf>> {
+        let path = self.dir.join(name);
+        if path.is_file() {
+            return Ok(Some(path));
+        }
+
+        let uc_name = name.to_ascii_uppercase();
+        match fs::read_dir(&self.dir) {
+            Ok(dirents) => {
+                for de in dirents {
+                    let de = de.map_err(|e| with_context(e, "read directory", &self.dir))?;
+                    if de.file_name().to_string_lossy().to_ascii_uppercase() == uc_name {
+                        return Ok(Some(de.path()));
+                    }
+                }
+                Ok(None)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(with_context(e, "read directory", &self.dir)),
+        }
+    }
 }
 
 impl Store for FileStore {
     fn delete(&mut self, name: &str) -> io::Result<()> {
-        let path = self.dir.join(name);
-        fs::remove_file(path)
+        match self.find(name)? {
+            Some(path) => fs::remove_file(&path).map_err(|e| with_context(e, "delete", &path)),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "File not found")),
+        }
     }
 
     fn enumerate(&self) -> io::Result<BTreeMap<String, Metadata>> {
@@ -123,34 +311,41 @@ impl Store for FileStore {
         match fs::read_dir(&self.dir) {
             Ok(dirents) => {
                 for de in dirents {
-                    let de = de?;
+                    let de = de.map_err(|e| with_context(e, "read directory", &self.dir))?;
 
-                    let file_type = de.file_type()?;
+                    let file_type =
+                        de.file_type().map_err(|e| with_context(e, "stat", &de.path()))?;
                     if !file_type.is_file() && !file_type.is_symlink() {
                         // Silently ignore entries we cannot handle.
                         continue;
                     }
 
+                    let name = de.file_name().to_string_lossy().to_string();
+                    if !name.to_ascii_uppercase().ends_with(".BAS") {
+                        // Silently ignore files that cannot be programs.
+                        continue;
+                    }
+
                     // This follows symlinks for cross-platform simplicity, but it is ugly.  I don't
                     // expect symlinks in the programs directory anyway.  If we want to handle this
                     // better, we'll have to add a way to report file types.
-                    let metadata = fs::metadata(de.path())?;
+                    let metadata =
+                        fs::metadata(de.path()).map_err(|e| with_context(e, "stat", &de.path()))?;
                     let offset = match time::UtcOffset::try_current_local_offset() {
                         Ok(offset) => offset,
                         Err(_) => time::UtcOffset::UTC,
                     };
-                    let date = time::OffsetDateTime::from(metadata.modified()?).to_offset(offset);
+                    let modified =
+                        metadata.modified().map_err(|e| with_context(e, "stat", &de.path()))?;
+                    let date = time::OffsetDateTime::from(modified).to_offset(offset);
                     let length = metadata.len();
 
-                    entries.insert(
-                        de.file_name().to_string_lossy().to_string(),
-                        Metadata { date, length },
-                    );
+                    entries.insert(name, Metadata { date, length });
                 }
             }
             Err(e) => {
                 if e.kind() != io::ErrorKind::NotFound {
-                    return Err(e);
+                    return Err(with_context(e, "read directory", &self.dir));
                 }
             }
         }
@@ -158,21 +353,161 @@ impl Store for FileStore {
     }
 
     fn get(&self, name: &str) -> io::Result<String> {
-        let path = self.dir.join(name);
-        let input = File::open(&path)?;
+        let path = match self.find(name)? {
+            Some(path) => path,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "File not found")),
+        };
+        let input = File::open(&path).map_err(|e| with_context(e, "open", &path))?;
         let mut content = String::new();
-        io::BufReader::new(input).read_to_string(&mut content)?;
+        io::BufReader::new(input)
+            .read_to_string(&mut content)
+            .map_err(|e| with_context(e, "read", &path))?;
         Ok(content)
     }
 
     fn put(&mut self, name: &str, content: &str) -> io::Result<()> {
-        let path = self.dir.join(name);
+        // Overwrite any existing file that matches the name case-insensitively instead of creating
+        // a second file that would later shadow, or be shadowed by, the original one.
+        let path = match self.find(name)? {
+            Some(path) => path,
+            None => self.dir.join(name),
+        };
         let dir = path.parent().expect("Must be a filename with a directory");
-        fs::create_dir_all(&dir)?;
+        fs::create_dir_all(dir).map_err(|e| with_context(e, "create directory", dir))?;
 
-        let output = OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+        let output = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .map_err(|e| with_context(e, "create", &path))?;
         let mut writer = io::BufWriter::new(output);
-        writer.write_all(content.as_bytes())
+        writer
+            .write_all(content.as_bytes())
+            .and_then(|()| writer.flush())
+            .map_err(|e| with_context(e, "write", &path))
+    }
+}
+
+/// A `Store` that holds several named stores, or drives, and forwards all operations to the one
+/// that is currently active.
+///
+/// Drive names are case-insensitive.  Callers keep a single reference to this store and can
+/// switch the drive it targets at runtime.  File names can also be prefixed by a drive name and a
+/// colon, as in `MEMORY:GAME.BAS`, to operate on that drive regardless of the active one.
+pub struct DriveStore {
+    /// Mapping of uppercase drive names to their stores.
+    drives: BTreeMap<String, Box<dyn Store>>,
+
+    /// Name of the drive that all operations target.  Always a key in `drives`.
+    active: String,
+}
+
+impl DriveStore {
+    /// Creates a new store with a single drive `name` backed by `store`, which becomes active.
+    pub fn new<S: Into<String>>(name: S, store: Box<dyn Store>) -> Self {
+        let name = name.into().to_ascii_uppercase();
+        let mut drives = BTreeMap::default();
+        drives.insert(name.clone(), store);
+        Self { drives, active: name }
+    }
+
+    /// Adds a new drive `name` backed by `store`.  Fails if the drive already exists.
+    pub fn mount<S: Into<String>>(&mut self, name: S, store: Box<dyn Store>) -> io::Result<()> {
+        let name = name.into().to_ascii_uppercase();
+        if self.drives.contains_key(&name) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Drive {} is already mounted", name),
+            ));
+        }
+        self.drives.insert(name, store);
+        Ok(())
+    }
+
+    /// Returns the name of the active drive.
+    pub fn active(&self) -> &str {
+        &self.active
+    }
+
+    /// Makes the drive `name` the target of all subsequent operations.
+    pub fn set_active(&mut self, name: &str) -> io::Result<()> {
+        let name = name.to_ascii_uppercase();
+        if !self.drives.contains_key(&name) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("Unknown drive {}", name)));
+        }
+        self.active = name;
+        Ok(())
+    }
+
+    /// Returns the sorted names of all drives.
+    pub fn names(&self) -> Vec<&str> {
+        self.drives.keys().map(String::as_str).collect()
+    }
+
+    /// Splits an optional drive prefix off `name` and returns the uppercase name of the drive that
+    /// the file lives in and the file name to pass to that drive.
+    ///
+    /// Names without a prefix are handed as is to the active drive.  Fails if the prefix does not
+    /// match a mounted drive.
+    fn route<'a>(&self, name: &'a str) -> io::Result<(String, &'a str)> {
+        match name.find(':') {
+            Some(pos) => {
+                let drive = name[..pos].to_ascii_uppercase();
+                if !self.drives.contains_key(&drive) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Unknown drive {}", drive),
+                    ));
+                }
+                Ok((drive, &name[pos + 1..]))
+            }
+            None => Ok((self.active.clone(), name)),
+        }
+    }
+
+    /// Returns the store backing the drive that `name` lives in and the file name within it.
+    fn resolve<'a>(&self, name: &'a str) -> io::Result<(&dyn Store, &'a str)> {
+        let (drive, name) = self.route(name)?;
+        Ok((self.drives.get(&drive).expect("Drive must exist").as_ref(), name))
+    }
+
+    /// Returns the store backing the drive that `name` lives in, for modification, and the file
+    /// name within it.
+    fn resolve_mut<'a>(&mut self, name: &'a str) -> io::Result<(&mut dyn Store, &'a str)> {
+        let (drive, name) = self.route(name)?;
+        Ok((self.drives.get_mut(&drive).expect("Drive must exist").as_mut(), name))
+    }
+}
+
+impl Store for DriveStore {
+    fn delete(&mut self, name: &str) -> io::Result<()> {
+        let (store, name) = self.resolve_mut(name)?;
+        store.delete(name)
+    }
+
+    fn enumerate(&self) -> io::Result<BTreeMap<String, Metadata>> {
+        self.drives.get(&self.active).expect("Active drive must exist").enumerate()
+    }
+
+    fn get(&self, name: &str) -> io::Result<String> {
+        let (store, name) = self.resolve(name)?;
+        store.get(name)
+    }
+
+    fn put(&mut self, name: &str, content: &str) -> io::Result<()> {
+        let (store, name) = self.resolve_mut(name)?;
+        store.put(name, content)
+    }
+
+    fn enumerate_drive(&self, drive: &str) -> io::Result<BTreeMap<String, Metadata>> {
+        let drive = drive.trim_end_matches(':').to_ascii_uppercase();
+        match self.drives.get(&drive) {
+            Some(store) => store.enumerate(),
+            None => {
+                Err(io::Error::new(io::ErrorKind::NotFound, format!("Unknown drive {}", drive)))
+            }
+        }
     }
 }
 
@@ -187,10 +522,16 @@ pub trait Program {
 
     /// Gets the contents of the stored program as a single string.
     fn text(&self) -> String;
+
+    /// Gets the name of the file the stored program was last loaded from or saved to, if any.
+    fn name(&self) -> Option<&str>;
+
+    /// Sets the `name` of the file that backs the stored program.
+    fn set_name(&mut self, name: Option<&str>);
 }
 
 /// Computes the path to a source file given the `dir` where it lives and a `basename`.
-fn to_filename<S: Into<PathBuf>>(basename: S) -> io::Result<String> {
+pub(crate) fn to_filename<S: Into<PathBuf>>(basename: S) -> io::Result<String> {
     let mut basename = basename.into();
 
     if basename.components().fold(0, |count, _| count + 1) != 1 {
@@ -220,10 +561,8 @@ fn to_filename<S: Into<PathBuf>>(basename: S) -> io::Result<String> {
     Ok(basename.to_str().expect("Path came from a String").to_owned())
 }
 
-/// Shows the contents of directory `path`.
-fn show_dir(store: &dyn Store, console: &mut dyn Console) -> io::Result<()> {
-    let entries = store.enumerate()?;
-
+/// Shows the directory listing given by `entries`.
+fn show_dir(entries: BTreeMap<String, Metadata>, console: &mut dyn Console) -> io::Result<()> {
     console.print("")?;
     console.print("    Modified              Size    Name")?;
     let mut total_files = 0;
@@ -246,6 +585,76 @@ fn show_dir(store: &dyn Store, console: &mut dyn Console) -> io::Result<()> {
     Ok(())
 }
 
+/// Prints the syntax `errors` found in a program to the `console`.
+fn print_syntax_errors(console: &mut dyn Console, errors: Vec<exec::Error>) -> io::Result<()> {
+    for e in errors {
+        match e.position() {
+            Some(pos) => console.print(&format!("Syntax error at line {}: {}", pos.line, e))?,
+            None => console.print(&format!("Syntax error: {}", e))?,
+        }
+    }
+    Ok(())
+}
+
+/// The `CHECK` command.
+pub struct CheckCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+    program: Rc<RefCell<dyn Program>>,
+}
+
+impl CheckCommand {
+    /// Creates a new `CHECK` command that reports problems in the `program` to the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>, program: Rc<RefCell<dyn Program>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("CHECK", VarType::Void)
+                .with_syntax("")
+                .with_category("Stored program manipulation")
+                .with_description(
+                    "Checks the stored program for problems without running it.
+Reports all syntax errors in the program.  If there are none, also reports warnings about code that can never run, such as statements that follow an END in the same block or loops whose bodies cannot execute.  Warnings do not prevent the program from running.",
+                )
+                .build(),
+            console,
+            program,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for CheckCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        _machine: &mut Machine,
+    ) -> exec::Result<()> {
+        if !args.is_empty() {
+            return exec::new_usage_error("CHECK takes no arguments");
+        }
+        let program = self.program.borrow().text();
+        let mut console = self.console.borrow_mut();
+
+        let errors = exec::check_syntax(&mut program.as_bytes());
+        if !errors.is_empty() {
+            print_syntax_errors(&mut *console, errors)?;
+            return exec::new_usage_error("Program has syntax errors");
+        }
+
+        let warnings = lint::lint_source(&mut program.as_bytes())?;
+        if warnings.is_empty() {
+            console.print("No problems found")?;
+        }
+        for w in warnings {
+            console.print(&format!("Warning at line {}: {}", w.pos.line, w.message))?;
+        }
+        Ok(())
+    }
+}
+
 /// The `DEL` command.
 pub struct DelCommand {
     metadata: CallableMetadata,
@@ -296,6 +705,132 @@ impl Command for DelCommand {
     }
 }
 
+/// A single line in the output of `diff_lines`.
+#[derive(Debug, PartialEq)]
+enum DiffLine<'a> {
+    /// Line present in both inputs.
+    Same(&'a str),
+
+    /// Line only present in the old input.
+    Removed(&'a str),
+
+    /// Line only present in the new input.
+    Added(&'a str),
+}
+
+/// Computes a line-based diff between the `old` and `new` lines using their longest common
+/// subsequence.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    // lcs[i][j] holds the length of the longest common subsequence of old[i..] and new[j..].
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            diff.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old[i..].iter().map(|l| DiffLine::Removed(l)));
+    diff.extend(new[j..].iter().map(|l| DiffLine::Added(l)));
+    diff
+}
+
+/// The `DIFF` command.
+pub struct DiffCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+    store: Rc<RefCell<dyn Store>>,
+}
+
+impl DiffCommand {
+    /// Creates a new `DIFF` command that compares two programs in the `store` and prints their
+    /// differences to the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>, store: Rc<RefCell<dyn Store>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("DIFF", VarType::Void)
+                .with_syntax("filename1, filename2")
+                .with_category("Stored program manipulation")
+                .with_description(
+                    "Shows the differences between two stored programs.
+Prints every line of the programs, prefixed with - if it only appears in filename1, with + if \
+it only appears in filename2, or with spaces if it appears in both.
+The filenames must be strings and must be basenames (no directory components).  The .BAS \
+extension is optional, but if present, it must be .BAS.",
+                )
+                .build(),
+            console,
+            store,
+        })
+    }
+
+    /// Evaluates `expr` and converts it into the name of a stored program.
+    fn to_name(expr: &Expr, machine: &Machine) -> exec::Result<String> {
+        match expr.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Text(t) => Ok(to_filename(t)?),
+            _ => exec::new_usage_error("DIFF requires strings as the filenames"),
+        }
+    }
+
+    /// Loads the program `name` from the store.
+    fn load(&self, name: &str) -> io::Result<String> {
+        self.store
+            .borrow()
+            .get(name)
+            .map_err(|e| io::Error::new(e.kind(), format!("Cannot load {}: {}", name, e)))
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for DiffCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let (old, new) = match args {
+            [(Some(arg0), ArgSep::Long), (Some(arg1), ArgSep::End)] => {
+                (DiffCommand::to_name(arg0, machine)?, DiffCommand::to_name(arg1, machine)?)
+            }
+            _ => return exec::new_usage_error("DIFF requires two filenames"),
+        };
+        let (old, new) = (self.load(&old)?, self.load(&new)?);
+        let old: Vec<&str> = old.lines().collect();
+        let new: Vec<&str> = new.lines().collect();
+
+        let mut console = self.console.borrow_mut();
+        for line in diff_lines(&old, &new) {
+            match line {
+                DiffLine::Same(l) => console.print(&format!("  {}", l))?,
+                DiffLine::Removed(l) => console.print(&format!("- {}", l))?,
+                DiffLine::Added(l) => console.print(&format!("+ {}", l))?,
+            }
+        }
+        Ok(())
+    }
+}
+
 /// The `DIR` command.
 pub struct DirCommand {
     metadata: CallableMetadata,
@@ -308,9 +843,13 @@ impl DirCommand {
     pub fn new(console: Rc<RefCell<dyn Console>>, store: Rc<RefCell<dyn Store>>) -> Rc<Self> {
         Rc::from(Self {
             metadata: CallableMetadataBuilder::new("DIR", VarType::Void)
-                .with_syntax("")
+                .with_syntax("[drive$]")
                 .with_category("Stored program manipulation")
-                .with_description("Displays the list of files on disk.")
+                .with_description(
+                    "Displays the list of files on disk.
+Without arguments, lists the files in the active drive.  With a drive$ name, such as \"MEMORY\" \
+or \"MEMORY:\", lists the files in that drive instead.",
+                )
                 .build(),
             console,
             store,
@@ -327,12 +866,81 @@ impl Command for DirCommand {
     async fn exec(
         &self,
         args: &[(Option<Expr>, ArgSep)],
-        _machine: &mut Machine,
+        machine: &mut Machine,
     ) -> exec::Result<()> {
-        if !args.is_empty() {
-            return exec::new_usage_error("DIR takes no arguments");
+        let entries = match args {
+            [] => self.store.borrow().enumerate()?,
+            [(Some(drive), ArgSep::End)] => {
+                match drive.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Text(drive) => self.store.borrow().enumerate_drive(&drive)?,
+                    _ => return exec::new_usage_error("DIR requires a string as the drive name"),
+                }
+            }
+            _ => return exec::new_usage_error("DIR takes zero or one argument"),
+        };
+        show_dir(entries, &mut *self.console.borrow_mut())?;
+        Ok(())
+    }
+}
+
+/// The `DRIVE` command.
+pub struct DriveCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+    drives: Rc<RefCell<DriveStore>>,
+}
+
+impl DriveCommand {
+    /// Creates a new `DRIVE` command that switches the active drive of `drives` and that lists
+    /// them on the `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>, drives: Rc<RefCell<DriveStore>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("DRIVE", VarType::Void)
+                .with_syntax("[name$]")
+                .with_category("Stored program manipulation")
+                .with_description(
+                    "Lists the available drives or switches to another one.
+Each drive holds a separate set of files.  Without arguments, prints the name of all drives and \
+marks the active one with an asterisk.  With a name$, makes that drive the target of all commands \
+that manipulate files, such as DIR, LOAD and SAVE.",
+                )
+                .build(),
+            console,
+            drives,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for DriveCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        match args {
+            [] => {
+                let drives = self.drives.borrow();
+                let mut console = self.console.borrow_mut();
+                console.print("")?;
+                for name in drives.names() {
+                    let marker = if name == drives.active() { "*" } else { " " };
+                    console.print(&format!("  {} {}", marker, name))?;
+                }
+                console.print("")?;
+            }
+            [(Some(name), ArgSep::End)] => {
+                match name.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Text(name) => self.drives.borrow_mut().set_active(&name)?,
+                    _ => return exec::new_usage_error("DRIVE requires a string as the drive name"),
+                }
+            }
+            _ => return exec::new_usage_error("DRIVE takes zero or one argument"),
         }
-        show_dir(&*self.store.borrow(), &mut *self.console.borrow_mut())?;
         Ok(())
     }
 }
@@ -341,19 +949,33 @@ impl Command for DirCommand {
 pub struct EditCommand {
     metadata: CallableMetadata,
     console: Rc<RefCell<dyn Console>>,
+    store: Rc<RefCell<dyn Store>>,
     program: Rc<RefCell<dyn Program>>,
 }
 
 impl EditCommand {
-    /// Creates a new `EDIT` command that edits the stored `program` in the `console`.
-    pub fn new(console: Rc<RefCell<dyn Console>>, program: Rc<RefCell<dyn Program>>) -> Rc<Self> {
+    /// Creates a new `EDIT` command that edits the stored `program` in the `console`, loading it
+    /// first from the `store` if a filename is given.
+    pub fn new(
+        console: Rc<RefCell<dyn Console>>,
+        store: Rc<RefCell<dyn Store>>,
+        program: Rc<RefCell<dyn Program>>,
+    ) -> Rc<Self> {
         Rc::from(Self {
             metadata: CallableMetadataBuilder::new("EDIT", VarType::Void)
-                .with_syntax("")
+                .with_syntax("[filename]")
                 .with_category("Stored program manipulation")
-                .with_description("Interactively edits the stored program.")
+                .with_description(
+                    "Interactively edits the stored program.
+If a filename is given, the program is first loaded from it as LOAD would do, or starts empty if \
+the file does not exist yet.  Either way, a later SAVE without a filename writes the program back \
+to that file.
+The filename must be a string and must be a basename (no directory components).  The .BAS \
+extension is optional, but if present, it must be .BAS.",
+                )
                 .build(),
             console,
+            store,
             program,
         })
     }
@@ -368,10 +990,29 @@ impl Command for EditCommand {
     async fn exec(
         &self,
         args: &[(Option<Expr>, ArgSep)],
-        _machine: &mut Machine,
+        machine: &mut Machine,
     ) -> exec::Result<()> {
-        if !args.is_empty() {
-            return exec::new_usage_error("EDIT takes no arguments");
+        match args {
+            [] => (),
+            [(Some(arg0), ArgSep::End)] => {
+                let name = match arg0.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Text(t) => to_filename(t)?,
+                    _ => return exec::new_usage_error("EDIT requires a string as the filename"),
+                };
+                let content = match self.store.borrow().get(&name) {
+                    Ok(content) => content,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                        self.console.borrow_mut().print(&format!("New file {}", name))?;
+                        String::new()
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                let mut program = self.program.borrow_mut();
+                program.load(&content);
+                program.set_name(Some(&name));
+                machine.clear()?;
+            }
+            _ => return exec::new_usage_error("EDIT requires a filename or no arguments"),
         }
 
         let mut console = self.console.borrow_mut();
@@ -381,6 +1022,64 @@ impl Command for EditCommand {
     }
 }
 
+/// The `LIST` command.
+pub struct ListCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+    program: Rc<RefCell<dyn Program>>,
+    tab_width: Rc<Cell<usize>>,
+}
+
+impl ListCommand {
+    /// Creates a new `LIST` command that prints the stored `program` to the `console`, expanding
+    /// tabs to multiples of `tab_width`.
+    pub fn new(
+        console: Rc<RefCell<dyn Console>>,
+        program: Rc<RefCell<dyn Program>>,
+        tab_width: Rc<Cell<usize>>,
+    ) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("LIST", VarType::Void)
+                .with_syntax("")
+                .with_category("Stored program manipulation")
+                .with_description(
+                    "Prints the stored program.
+The program is kept exactly as it was typed or loaded, so the listing includes all comments and \
+blank lines, which also survive saving the program.  Tabs are displayed as spaces up to the next \
+tab stop; use TABWIDTH to change their width.",
+                )
+                .build(),
+            console,
+            program,
+            tab_width,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for ListCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        _machine: &mut Machine,
+    ) -> exec::Result<()> {
+        if !args.is_empty() {
+            return exec::new_usage_error("LIST takes no arguments");
+        }
+
+        let program = self.program.borrow().text();
+        let mut console = self.console.borrow_mut();
+        for line in program.lines() {
+            console.print(&expand_tabs(line, self.tab_width.get()))?;
+        }
+        Ok(())
+    }
+}
+
 /// The `LOAD` command.
 pub struct LoadCommand {
     metadata: CallableMetadata,
@@ -426,8 +1125,10 @@ impl Command for LoadCommand {
             Value::Text(t) => {
                 let name = to_filename(t)?;
                 let content = self.store.borrow().get(&name)?;
-                self.program.borrow_mut().load(&content);
-                machine.clear();
+                let mut program = self.program.borrow_mut();
+                program.load(&content);
+                program.set_name(Some(&name));
+                machine.clear()?;
             }
             _ => return exec::new_usage_error("LOAD requires a string as the filename"),
         }
@@ -435,6 +1136,68 @@ impl Command for LoadCommand {
     }
 }
 
+/// The `MERGE` command.
+pub struct MergeCommand {
+    metadata: CallableMetadata,
+    store: Rc<RefCell<dyn Store>>,
+    program: Rc<RefCell<dyn Program>>,
+}
+
+impl MergeCommand {
+    /// Creates a new `MERGE` command that appends a program from the `store` to `program`.
+    pub fn new(store: Rc<RefCell<dyn Store>>, program: Rc<RefCell<dyn Program>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("MERGE", VarType::Void)
+                .with_syntax("filename")
+                .with_category("Stored program manipulation")
+                .with_description(
+                    "Appends the given program to the current program in memory.
+Programs do not have line numbers, so there are no conflicts to resolve: the lines of the given \
+program are added, unmodified, after the last line of the current program.  Unlike LOAD, this \
+does not clear the machine state.
+The filename must be a string and must be a basename (no directory components).  The .BAS \
+extension is optional, but if present, it must be .BAS.",
+                )
+                .build(),
+            store,
+            program,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for MergeCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        if args.len() != 1 {
+            return exec::new_usage_error("MERGE requires a filename");
+        }
+        let arg0 = args[0].0.as_ref().expect("Single argument must be present");
+        match arg0.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Text(t) => {
+                let name = to_filename(t)?;
+                let content = self.store.borrow().get(&name)?;
+                let mut program = self.program.borrow_mut();
+                let mut text = program.text();
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                text.push_str(&content);
+                program.load(&text);
+            }
+            _ => return exec::new_usage_error("MERGE requires a string as the filename"),
+        }
+        Ok(())
+    }
+}
+
 /// The `NEW` command.
 pub struct NewCommand {
     metadata: CallableMetadata,
@@ -469,8 +1232,10 @@ impl Command for NewCommand {
         if !args.is_empty() {
             return exec::new_usage_error("NEW takes no arguments");
         }
-        self.program.borrow_mut().load("");
-        machine.clear();
+        let mut program = self.program.borrow_mut();
+        program.load("");
+        program.set_name(None);
+        machine.clear()?;
         Ok(())
     }
 }
@@ -493,6 +1258,8 @@ impl RunCommand {
                 .with_category("Stored program manipulation")
                 .with_description(
                     "Runs the stored program.
+The whole program is checked for syntax errors before it starts running.  If there are any, \
+they are all reported along with their line numbers and the program does not run.
 Note that the program runs in the context of the interpreter so it will pick up any variables \
 and other state that may already be set.",
                 )
@@ -518,7 +1285,17 @@ impl Command for RunCommand {
             return exec::new_usage_error("RUN takes no arguments");
         }
         let program = self.program.borrow().text();
-        let stop_reason = machine.exec(&mut program.as_bytes()).await?;
+
+        let errors = exec::check_syntax(&mut program.as_bytes());
+        if !errors.is_empty() {
+            print_syntax_errors(&mut *self.console.borrow_mut(), errors)?;
+            return exec::new_usage_error("Program has syntax errors");
+        }
+
+        let result = machine.exec_with_breakpoints(&mut program.as_bytes()).await;
+        let finish_result = machine.finish_program();
+        let stop_reason = result.map_err(exec::Error::in_program)?;
+        finish_result?;
         if stop_reason.as_exit_code() != 0 {
             self.console
                 .borrow_mut()
@@ -528,6 +1305,11 @@ impl Command for RunCommand {
     }
 }
 
+/// Converts all CRLF line endings in `text` to LF.
+fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
 /// The `SAVE` command.
 pub struct SaveCommand {
     metadata: CallableMetadata,
@@ -540,12 +1322,17 @@ impl SaveCommand {
     pub fn new(store: Rc<RefCell<dyn Store>>, program: Rc<RefCell<dyn Program>>) -> Rc<Self> {
         Rc::from(Self {
             metadata: CallableMetadataBuilder::new("SAVE", VarType::Void)
-                .with_syntax("filename")
+                .with_syntax("[filename[, keep_crlf?]]")
                 .with_category("Stored program manipulation")
                 .with_description(
                     "Saves the current program in memory to the given filename.
+If no filename is given, the program is saved to the file it was last loaded from, edited from \
+or saved to.
 The filename must be a string and must be a basename (no directory components).  The .BAS \
-extension is optional, but if present, it must be .BAS.",
+extension is optional, but if present, it must be .BAS.
+Any CRLF line endings in the program, such as those in files loaded from other platforms, are \
+converted to LF so that all saved files are consistent.  Pass TRUE as keep_crlf? to save the \
+program with its line endings untouched instead.",
                 )
                 .build(),
             store,
@@ -554,6 +1341,16 @@ extension is optional, but if present, it must be .BAS.",
     }
 }
 
+impl SaveCommand {
+    /// Evaluates the filename given in `arg` and converts it into the name of a stored file.
+    fn eval_name(&self, arg: &Expr, machine: &Machine) -> exec::Result<String> {
+        match arg.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Text(t) => Ok(to_filename(t)?),
+            _ => exec::new_usage_error("SAVE requires a string as the filename"),
+        }
+    }
+}
+
 #[async_trait(?Send)]
 impl Command for SaveCommand {
     fn metadata(&self) -> &CallableMetadata {
@@ -565,37 +1362,106 @@ impl Command for SaveCommand {
         args: &[(Option<Expr>, ArgSep)],
         machine: &mut Machine,
     ) -> exec::Result<()> {
-        if args.len() != 1 {
-            return exec::new_usage_error("SAVE requires a filename");
-        }
-        let arg0 = args[0].0.as_ref().expect("Single argument must be present");
-        match arg0.eval(machine.get_vars(), machine.get_functions())? {
-            Value::Text(t) => {
-                let name = to_filename(t)?;
-                let content = self.program.borrow().text();
-                self.store.borrow_mut().put(&name, &content)?;
+        let (name, keep_crlf) = match args {
+            [] => match self.program.borrow().name() {
+                Some(name) => (name.to_owned(), false),
+                None => return exec::new_usage_error("SAVE requires a filename"),
+            },
+            [(Some(arg0), ArgSep::End)] => (self.eval_name(arg0, machine)?, false),
+            [(Some(arg0), ArgSep::Long), (Some(arg1), ArgSep::End)] => {
+                let name = self.eval_name(arg0, machine)?;
+                match arg1.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Boolean(b) => (name, b),
+                    _ => return exec::new_usage_error("SAVE requires a boolean to keep CRLF"),
+                }
             }
-            _ => return exec::new_usage_error("SAVE requires a string as the filename"),
+            _ => return exec::new_usage_error("SAVE requires a filename"),
+        };
+
+        let mut content = self.program.borrow().text();
+        if !keep_crlf {
+            content = normalize_newlines(&content);
         }
+        self.store.borrow_mut().put(&name, &content)?;
+        self.program.borrow_mut().set_name(Some(&name));
+        Ok(())
+    }
+}
+
+/// The `TABWIDTH` command.
+pub struct TabWidthCommand {
+    metadata: CallableMetadata,
+    tab_width: Rc<Cell<usize>>,
+}
+
+impl TabWidthCommand {
+    /// Creates a new `TABWIDTH` command that updates the `tab_width` used to display programs.
+    pub fn new(tab_width: Rc<Cell<usize>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("TABWIDTH", VarType::Void)
+                .with_syntax("[width%]")
+                .with_category("Stored program manipulation")
+                .with_description(
+                    "Sets the width of the tab stops used to display programs.
+Tabs in the stored program are displayed by LIST and EDIT as spaces up to the next multiple of \
+this width, which only affects how the program looks and not its contents.  If no width is \
+given, the width is reset to the default of 8 characters.",
+                )
+                .build(),
+            tab_width,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for TabWidthCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let width = match args {
+            [] => DEFAULT_TAB_WIDTH,
+            [(Some(expr), ArgSep::End)] => {
+                match expr.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Integer(i) if i > 0 && i <= 255 => i as usize,
+                    Value::Integer(_) => return exec::new_usage_error("Tab width out of range"),
+                    _ => return exec::new_usage_error("Tab width must be an integer"),
+                }
+            }
+            _ => return exec::new_usage_error("TABWIDTH takes zero or one argument"),
+        };
+        self.tab_width.set(width);
         Ok(())
     }
 }
 
 /// Adds all program editing commands against the stored `program` to the `machine`, using
-/// `console` for interactive editing and using `store` as the on-disk storage for the programs.
+/// `console` for interactive editing, `store` as the on-disk storage for the programs and
+/// `tab_width` as the width of the tab stops used to display them.
 pub fn add_all(
     machine: &mut Machine,
     program: Rc<RefCell<dyn Program>>,
     console: Rc<RefCell<dyn Console>>,
     store: Rc<RefCell<dyn Store>>,
-) {
-    machine.add_command(DelCommand::new(store.clone()));
-    machine.add_command(DirCommand::new(console.clone(), store.clone()));
-    machine.add_command(EditCommand::new(console.clone(), program.clone()));
-    machine.add_command(LoadCommand::new(store.clone(), program.clone()));
-    machine.add_command(NewCommand::new(program.clone()));
-    machine.add_command(RunCommand::new(console, program.clone()));
-    machine.add_command(SaveCommand::new(store, program));
+    tab_width: Rc<Cell<usize>>,
+) -> exec::Result<()> {
+    machine.add_command(CheckCommand::new(console.clone(), program.clone()))?;
+    machine.add_command(DelCommand::new(store.clone()))?;
+    machine.add_command(DiffCommand::new(console.clone(), store.clone()))?;
+    machine.add_command(DirCommand::new(console.clone(), store.clone()))?;
+    machine.add_command(EditCommand::new(console.clone(), store.clone(), program.clone()))?;
+    machine.add_command(ListCommand::new(console.clone(), program.clone(), tab_width.clone()))?;
+    machine.add_command(LoadCommand::new(store.clone(), program.clone()))?;
+    machine.add_command(MergeCommand::new(store.clone(), program.clone()))?;
+    machine.add_command(NewCommand::new(program.clone()))?;
+    machine.add_command(RunCommand::new(console, program.clone()))?;
+    machine.add_command(SaveCommand::new(store, program))?;
+    machine.add_command(TabWidthCommand::new(tab_width))
 }
 
 #[cfg(test)]
@@ -634,6 +1500,129 @@ mod tests {
             .unwrap();
     }
 
+    /// Creates an overlay store whose base layer contains `a.bas` and `b.bas`.
+    fn new_overlay_store() -> OverlayStore<ReadOnlyStore<InMemoryStore>, InMemoryStore> {
+        let mut base = InMemoryStore::default();
+        base.put("a.bas", "base a").unwrap();
+        base.put("b.bas", "base b").unwrap();
+        OverlayStore::new(ReadOnlyStore::new(base), InMemoryStore::default())
+    }
+
+    #[test]
+    fn test_overlaystore_reads_fall_through() {
+        let store = new_overlay_store();
+        assert_eq!(vec!["a.bas", "b.bas"], store.enumerate().unwrap().keys().collect::<Vec<_>>());
+        assert_eq!("base a", store.get("a.bas").unwrap());
+        assert_eq!("base b", store.get("b.bas").unwrap());
+        assert_eq!(io::ErrorKind::NotFound, store.get("c.bas").unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_overlaystore_writes_go_to_upper() {
+        let mut store = new_overlay_store();
+        store.put("a.bas", "edited a").unwrap();
+        store.put("c.bas", "new c").unwrap();
+
+        assert_eq!(
+            vec!["a.bas", "b.bas", "c.bas"],
+            store.enumerate().unwrap().keys().collect::<Vec<_>>()
+        );
+        assert_eq!("edited a", store.get("a.bas").unwrap());
+        assert_eq!("base b", store.get("b.bas").unwrap());
+        assert_eq!("new c", store.get("c.bas").unwrap());
+
+        let (base, upper) = store.unmount();
+        assert_eq!("base a", base.get("a.bas").unwrap());
+        assert_eq!(io::ErrorKind::NotFound, base.get("c.bas").unwrap_err().kind());
+        assert_eq!(2, upper.as_hashmap().len());
+    }
+
+    #[test]
+    fn test_overlaystore_delete_hides_base_file() {
+        let mut store = new_overlay_store();
+        store.put("a.bas", "edited a").unwrap();
+        store.delete("a.bas").unwrap();
+        store.delete("b.bas").unwrap();
+
+        assert!(store.enumerate().unwrap().is_empty());
+        assert_eq!(io::ErrorKind::NotFound, store.get("a.bas").unwrap_err().kind());
+        assert_eq!(io::ErrorKind::NotFound, store.get("b.bas").unwrap_err().kind());
+        assert_eq!(io::ErrorKind::NotFound, store.delete("a.bas").unwrap_err().kind());
+
+        store.put("b.bas", "recreated b").unwrap();
+        assert_eq!(vec!["b.bas"], store.enumerate().unwrap().keys().collect::<Vec<_>>());
+        assert_eq!("recreated b", store.get("b.bas").unwrap());
+
+        let (base, _upper) = store.unmount();
+        assert_eq!(2, base.enumerate().unwrap().len());
+    }
+
+    #[test]
+    fn test_overlaystore_whiteouts_ignore_case() {
+        let dir = tempfile::tempdir().unwrap();
+        FileStore::new(dir.path()).put("a.bas", "base a").unwrap();
+        let mut store = OverlayStore::new(
+            ReadOnlyStore::new(FileStore::new(dir.path())),
+            InMemoryStore::default(),
+        );
+
+        store.delete("A.BAS").unwrap();
+        assert!(store.enumerate().unwrap().is_empty());
+        assert_eq!(io::ErrorKind::NotFound, store.get("a.bas").unwrap_err().kind());
+        assert_eq!(io::ErrorKind::NotFound, store.get("A.bas").unwrap_err().kind());
+        assert_eq!(io::ErrorKind::NotFound, store.delete("a.Bas").unwrap_err().kind());
+
+        store.put("A.bas", "recreated a").unwrap();
+        assert_eq!("recreated a", store.get("A.bas").unwrap());
+        store.delete("A.bas").unwrap();
+        assert_eq!(io::ErrorKind::NotFound, store.get("a.bas").unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_overlaystore_delete_missing_file() {
+        let mut store = new_overlay_store();
+        let err = store.delete("c.bas").unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+        assert_eq!("Entry not found", format!("{}", err));
+    }
+
+    #[test]
+    fn test_readonlystore_reads_pass_through() {
+        let mut delegate = InMemoryStore::default();
+        delegate.put("a.bas", "first").unwrap();
+        delegate.put("b.bas", "second").unwrap();
+
+        let store = ReadOnlyStore::new(delegate);
+        assert_eq!(vec!["a.bas", "b.bas"], store.enumerate().unwrap().keys().collect::<Vec<_>>());
+        assert_eq!("first", store.get("a.bas").unwrap());
+        assert_eq!("second", store.get("b.bas").unwrap());
+        assert_eq!(io::ErrorKind::NotFound, store.get("c.bas").unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_readonlystore_writes_are_rejected() {
+        let mut delegate = InMemoryStore::default();
+        delegate.put("a.bas", "original").unwrap();
+
+        let mut store = ReadOnlyStore::new(delegate);
+
+        let err = store.put("a.bas", "modified").unwrap_err();
+        assert_eq!(io::ErrorKind::PermissionDenied, err.kind());
+        assert_eq!("Store is read-only", format!("{}", err));
+
+        let err = store.put("new.bas", "content").unwrap_err();
+        assert_eq!(io::ErrorKind::PermissionDenied, err.kind());
+        assert_eq!("Store is read-only", format!("{}", err));
+
+        let err = store.delete("a.bas").unwrap_err();
+        assert_eq!(io::ErrorKind::PermissionDenied, err.kind());
+        assert_eq!("Store is read-only", format!("{}", err));
+
+        let delegate = store.unmount();
+        assert_eq!(1, delegate.as_hashmap().len());
+        assert_eq!("original", delegate.as_hashmap().get("a.bas").unwrap());
+    }
+
     #[test]
     fn test_filestore_delete_ok() {
         let dir = tempfile::tempdir().unwrap();
@@ -650,7 +1639,19 @@ mod tests {
     fn test_filestore_delete_missing_file() {
         let dir = tempfile::tempdir().unwrap();
         let mut store = FileStore::new(&dir.path());
-        assert_eq!(io::ErrorKind::NotFound, store.delete("a.bas").unwrap_err().kind());
+        let err = store.delete("a.bas").unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+        assert_eq!("File not found", format!("{}", err));
+    }
+
+    #[test]
+    fn test_filestore_delete_is_case_insensitive() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(&dir.path().join("Mixed.Bas"), &[]);
+
+        let mut store = FileStore::new(dir.path());
+        store.delete("MIXED.BAS").unwrap();
+        assert!(!dir.path().join("Mixed.Bas").exists());
     }
 
     #[test]
@@ -682,6 +1683,19 @@ mod tests {
         assert!(store.enumerate().unwrap().is_empty());
     }
 
+    #[test]
+    fn test_filestore_enumerate_ignores_non_programs() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(&dir.path().join("a.bas"), &[]);
+        write_file(&dir.path().join("B.BAS"), &[]);
+        write_file(&dir.path().join("c.txt"), &[]);
+        write_file(&dir.path().join("bas"), &[]);
+
+        let store = FileStore::new(dir.path());
+        let entries = store.enumerate().unwrap();
+        assert_eq!(vec!["B.BAS", "a.bas"], entries.keys().collect::<Vec<&String>>());
+    }
+
     #[test]
     fn test_filestore_enumerate_ignores_non_files() {
         let dir = tempfile::tempdir().unwrap();
@@ -717,6 +1731,122 @@ mod tests {
         assert_eq!(io::ErrorKind::Other, store.enumerate().unwrap_err().kind());
     }
 
+    #[test]
+    fn test_filestore_errors_carry_context() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("subdir.bas")).unwrap();
+        let file = dir.path().join("not-a-dir");
+        write_file(&file, &[]);
+
+        let mut store = FileStore::new(dir.path());
+        let err = store.get("subdir.bas").unwrap_err();
+        assert!(format!("{}", err)
+            .starts_with(&format!("Cannot read {}: ", dir.path().join("subdir.bas").display())));
+        let err = store.put("subdir.bas", "").unwrap_err();
+        assert!(format!("{}", err)
+            .starts_with(&format!("Cannot create {}: ", dir.path().join("subdir.bas").display())));
+
+        let mut store = FileStore::new(&file);
+        let err = store.put("x.bas", "").unwrap_err();
+        let message = format!("Cannot read directory {}: ", file.display());
+        assert!(format!("{}", err).starts_with(&message));
+        let err = store.enumerate().unwrap_err();
+        assert!(format!("{}", err).starts_with(&message));
+        assert_eq!(
+            fs::read_dir(&file).unwrap_err().kind(),
+            err.kind(),
+            "Error kind must be preserved"
+        );
+    }
+
+    #[test]
+    fn test_drivestore_isolates_drives() {
+        let mut store = DriveStore::new("local", Box::from(InMemoryStore::default()));
+        store.mount("Memory", Box::from(InMemoryStore::default())).unwrap();
+        assert_eq!(vec!["LOCAL", "MEMORY"], store.names());
+        assert_eq!("LOCAL", store.active());
+
+        store.put("a.bas", "local a").unwrap();
+        store.set_active("memory").unwrap();
+        assert_eq!("MEMORY", store.active());
+        assert_eq!(io::ErrorKind::NotFound, store.get("a.bas").unwrap_err().kind());
+        assert!(store.enumerate().unwrap().is_empty());
+        store.put("a.bas", "memory a").unwrap();
+        store.put("b.bas", "memory b").unwrap();
+        store.delete("b.bas").unwrap();
+
+        store.set_active("LOCAL").unwrap();
+        assert_eq!("local a", store.get("a.bas").unwrap());
+        assert_eq!(1, store.enumerate().unwrap().len());
+        store.set_active("MEMORY").unwrap();
+        assert_eq!("memory a", store.get("a.bas").unwrap());
+    }
+
+    #[test]
+    fn test_drivestore_prefixed_names() {
+        let mut store = DriveStore::new("LOCAL", Box::from(InMemoryStore::default()));
+        store.mount("MEMORY", Box::from(InMemoryStore::default())).unwrap();
+
+        store.put("game.bas", "local game").unwrap();
+        store.put("memory:scratch.bas", "memory scratch").unwrap();
+        store.put("Memory:game.bas", "memory game").unwrap();
+        assert_eq!("LOCAL", store.active());
+
+        assert_eq!(vec!["game.bas"], store.enumerate().unwrap().keys().collect::<Vec<_>>());
+        assert_eq!(
+            vec!["game.bas"],
+            store.enumerate_drive("local:").unwrap().keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["game.bas", "scratch.bas"],
+            store.enumerate_drive("MEMORY").unwrap().keys().collect::<Vec<_>>()
+        );
+
+        assert_eq!("local game", store.get("game.bas").unwrap());
+        assert_eq!("local game", store.get("LOCAL:game.bas").unwrap());
+        assert_eq!("memory game", store.get("MEMORY:game.bas").unwrap());
+
+        store.delete("MEMORY:game.bas").unwrap();
+        assert_eq!(1, store.enumerate_drive("MEMORY").unwrap().len());
+        assert_eq!("local game", store.get("game.bas").unwrap());
+    }
+
+    #[test]
+    fn test_drivestore_unknown_prefix() {
+        let mut store = DriveStore::new("LOCAL", Box::from(InMemoryStore::default()));
+        store.mount("MEMORY", Box::from(InMemoryStore::default())).unwrap();
+        store.set_active("MEMORY").unwrap();
+
+        let err = store.put("locl:a.bas", "typo").unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+        assert_eq!("Unknown drive LOCL", format!("{}", err));
+        assert_eq!("Unknown drive OTHER", format!("{}", store.get("other:a.bas").unwrap_err()));
+        assert_eq!("Unknown drive OTHER", format!("{}", store.delete("other:a.bas").unwrap_err()));
+        assert!(store.enumerate().unwrap().is_empty());
+        assert!(store.enumerate_drive("LOCAL").unwrap().is_empty());
+
+        let err = store.enumerate_drive("other:").unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+        assert_eq!("Unknown drive OTHER", format!("{}", err));
+
+        let err = InMemoryStore::default().enumerate_drive("local").unwrap_err();
+        assert_eq!("Unknown drive LOCAL", format!("{}", err));
+    }
+
+    #[test]
+    fn test_drivestore_errors() {
+        let mut store = DriveStore::new("A", Box::from(InMemoryStore::default()));
+
+        let err = store.mount("a", Box::from(InMemoryStore::default())).unwrap_err();
+        assert_eq!(io::ErrorKind::AlreadyExists, err.kind());
+        assert_eq!("Drive A is already mounted", format!("{}", err));
+
+        let err = store.set_active("b").unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+        assert_eq!("Unknown drive B", format!("{}", err));
+        assert_eq!("A", store.active());
+    }
+
     #[test]
     fn test_filestore_get() {
         let dir = tempfile::tempdir().unwrap();
@@ -726,6 +1856,25 @@ mod tests {
         assert_eq!("one line\ntwo lines\n", store.get("some file.bas").unwrap());
     }
 
+    #[test]
+    fn test_filestore_get_is_case_insensitive() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(&dir.path().join("Mixed.Bas"), &["the content"]);
+
+        let store = FileStore::new(dir.path());
+        assert_eq!("the content\n", store.get("mixed.bas").unwrap());
+        assert_eq!("the content\n", store.get("MIXED.BAS").unwrap());
+    }
+
+    #[test]
+    fn test_filestore_get_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path());
+        let err = store.get("a.bas").unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+        assert_eq!("File not found", format!("{}", err));
+    }
+
     #[test]
     fn test_filestore_put() {
         let dir = tempfile::tempdir().unwrap();
@@ -735,6 +1884,28 @@ mod tests {
         check_file(&dir.path().join("some file.bas"), &["a b c", "d e"]);
     }
 
+    #[test]
+    fn test_filestore_put_get_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut store = FileStore::new(dir.path());
+        store.put("first.bas", "PRINT 1\n").unwrap();
+        store.put("second.bas", "PRINT 2\n").unwrap();
+        assert_eq!("PRINT 1\n", store.get("first.bas").unwrap());
+        assert_eq!("PRINT 2\n", store.get("second.bas").unwrap());
+    }
+
+    #[test]
+    fn test_filestore_put_overwrites_case_insensitively() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(&dir.path().join("Mixed.Bas"), &["old content"]);
+
+        let mut store = FileStore::new(dir.path());
+        store.put("MIXED.BAS", "new content\n").unwrap();
+        check_file(&dir.path().join("Mixed.Bas"), &["new content"]);
+        assert_eq!(1, store.enumerate().unwrap().len());
+    }
+
     #[test]
     fn test_del_ok() {
         for p in &["foo", "foo.bas"] {
@@ -763,6 +1934,54 @@ mod tests {
             .check();
     }
 
+    #[test]
+    fn test_diff_lines() {
+        assert_eq!(Vec::<DiffLine>::new(), diff_lines(&[], &[]));
+        assert_eq!(
+            vec![DiffLine::Same("a"), DiffLine::Same("b")],
+            diff_lines(&["a", "b"], &["a", "b"])
+        );
+        assert_eq!(vec![DiffLine::Removed("a"), DiffLine::Added("b")], diff_lines(&["a"], &["b"]));
+        assert_eq!(
+            vec![
+                DiffLine::Same("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Same("c"),
+                DiffLine::Added("d"),
+                DiffLine::Same("e"),
+            ],
+            diff_lines(&["a", "b", "c", "e"], &["a", "c", "d", "e"])
+        );
+        assert_eq!(vec![DiffLine::Added("x"), DiffLine::Added("y")], diff_lines(&[], &["x", "y"]));
+    }
+
+    #[test]
+    fn test_diff_ok() {
+        Tester::default()
+            .write_file("old.bas", "a = 1\nPRINT a\nEND\n")
+            .write_file("NEW.BAS", "a = 1\nPRINT a + 1\nEND\n")
+            .run(r#"DIFF "old", "NEW.BAS""#)
+            .expect_prints(["  a = 1", "- PRINT a", "+ PRINT a + 1", "  END"])
+            .expect_file("old.bas", "a = 1\nPRINT a\nEND\n")
+            .expect_file("NEW.BAS", "a = 1\nPRINT a + 1\nEND\n")
+            .check();
+    }
+
+    #[test]
+    fn test_diff_errors() {
+        check_stmt_err("DIFF requires two filenames", "DIFF");
+        check_stmt_err("DIFF requires two filenames", r#"DIFF "a""#);
+        check_stmt_err("DIFF requires two filenames", r#"DIFF "a"; "b""#);
+        check_stmt_err("DIFF requires strings as the filenames", r#"DIFF "a", 3"#);
+
+        Tester::default()
+            .write_file("a.bas", "")
+            .run(r#"DIFF "a", "missing""#)
+            .expect_err("Cannot load missing.bas: Entry not found")
+            .expect_file("a.bas", "")
+            .check();
+    }
+
     #[test]
     fn test_dir_empty() {
         Tester::default()
@@ -802,9 +2021,110 @@ mod tests {
             .check();
     }
 
+    /// Creates a tester whose `DIR`, `DRIVE`, `LOAD` and `SAVE` commands operate on two in-memory drives, `LOCAL`
+    /// and `MEMORY`, with `LOCAL` containing one file.
+    fn drive_tester() -> (Tester, Rc<RefCell<DriveStore>>) {
+        let mut local = InMemoryStore::default();
+        local.put("local.bas", "PRINT 1\n").unwrap();
+        let mut drives = DriveStore::new("LOCAL", Box::from(local));
+        drives.mount("MEMORY", Box::from(InMemoryStore::default())).unwrap();
+        let drives = Rc::from(RefCell::from(drives));
+
+        let tester = Tester::from(Machine::default());
+        let console = tester.get_console();
+        let program = tester.get_program();
+        let tester = tester
+            .add_command(DirCommand::new(console.clone(), drives.clone()))
+            .add_command(DriveCommand::new(console, drives.clone()))
+            .add_command(LoadCommand::new(drives.clone(), program.clone()))
+            .add_command(SaveCommand::new(drives.clone(), program));
+        (tester, drives)
+    }
+
+    #[test]
+    fn test_drive_list() {
+        let (mut t, drives) = drive_tester();
+        t.run("DRIVE: DRIVE \"memory\": DRIVE")
+            .expect_prints(["", "  * LOCAL", "    MEMORY", "", "", "    LOCAL", "  * MEMORY", ""])
+            .check();
+        assert_eq!("MEMORY", drives.borrow().active());
+    }
+
+    #[test]
+    fn test_drive_switch() {
+        let (mut t, drives) = drive_tester();
+        t.run("DRIVE \"memory\": DIR: DRIVE \"LOCAL\": DIR")
+            .expect_prints([
+                "",
+                "    Modified              Size    Name",
+                "    0 file(s), 0 bytes",
+                "",
+                "",
+                "    Modified              Size    Name",
+                "    2020-05-06 09:37         8    local.bas",
+                "",
+                "    1 file(s), 8 bytes",
+                "",
+            ])
+            .check();
+        assert_eq!("LOCAL", drives.borrow().active());
+    }
+
+    #[test]
+    fn test_drive_errors() {
+        let (mut t, drives) = drive_tester();
+        t.run("DRIVE 1").expect_err("DRIVE requires a string as the drive name").check();
+        t.run("DRIVE \"a\", \"b\"").expect_err("DRIVE takes zero or one argument").check();
+        t.run("DRIVE \"other\"").expect_err("Unknown drive OTHER").check();
+        assert_eq!("LOCAL", drives.borrow().active());
+    }
+
+    #[test]
+    fn test_drive_prefixed_save_and_load() {
+        let (mut t, drives) = drive_tester();
+        t.get_program().borrow_mut().load("PRINT 2\n");
+        t.run("SAVE \"MEMORY:scratch\": SAVE \"other\"").expect_program("PRINT 2\n").check();
+        assert_eq!("LOCAL", drives.borrow().active());
+        assert_eq!("PRINT 2\n", drives.borrow().get("MEMORY:scratch.bas").unwrap());
+        assert_eq!("PRINT 2\n", drives.borrow().get("LOCAL:other.bas").unwrap());
+
+        let (mut t, _drives) = drive_tester();
+        t.run("SAVE \"memory:copy\": DIR \"memory:\": DIR \"local\"")
+            .expect_prints([
+                "",
+                "    Modified              Size    Name",
+                "    2020-05-06 09:37         0    copy.bas",
+                "",
+                "    1 file(s), 0 bytes",
+                "",
+                "",
+                "    Modified              Size    Name",
+                "    2020-05-06 09:37         8    local.bas",
+                "",
+                "    1 file(s), 8 bytes",
+                "",
+            ])
+            .check();
+
+        let (mut t, drives) = drive_tester();
+        t.run("LOAD \"LOCAL:local\": DRIVE \"MEMORY\"").expect_program("PRINT 1\n").check();
+        assert_eq!("MEMORY", drives.borrow().active());
+    }
+
+    #[test]
+    fn test_drive_prefixed_errors() {
+        let (mut t, _drives) = drive_tester();
+        t.run("DIR \"other\"").expect_err("Unknown drive OTHER").check();
+        t.run("LOAD \"other:a\"").expect_err("Unknown drive OTHER").check();
+        t.run("SAVE \"locl:game\"").expect_err("Unknown drive LOCL").check();
+        t.run("LOAD \"memory:a\"").expect_err("Entry not found").check();
+    }
+
     #[test]
     fn test_dir_errors() {
-        check_stmt_err("DIR takes no arguments", "DIR 2");
+        check_stmt_err("DIR requires a string as the drive name", "DIR 2");
+        check_stmt_err("DIR takes zero or one argument", "DIR \"a\", \"b\"");
+        check_stmt_err("Unknown drive A", "DIR \"a\"");
     }
 
     #[test]
@@ -817,9 +2137,85 @@ mod tests {
             .check();
     }
 
+    #[test]
+    fn test_edit_existing_file() {
+        Tester::default()
+            .set_program("previous\n")
+            .write_file("foo.bas", "line 1\nline 2\n")
+            .add_input_chars("new line\n")
+            .run(r#"a = 1: EDIT "foo""#)
+            .expect_program("line 1\nline 2\nnew line\n")
+            .expect_file("foo.bas", "line 1\nline 2\n")
+            .check();
+    }
+
+    #[test]
+    fn test_edit_new_file() {
+        Tester::default()
+            .set_program("previous\n")
+            .add_input_chars("new line\n")
+            .run(r#"EDIT "Bar""#)
+            .expect_prints(["New file Bar.bas"])
+            .expect_program("new line\n")
+            .check();
+    }
+
+    #[test]
+    fn test_edit_then_save_to_same_file() {
+        Tester::default()
+            .write_file("foo.bas", "line 1\n")
+            .add_input_chars("line 2\n")
+            .run(r#"EDIT "foo.bas": SAVE"#)
+            .expect_program("line 1\nline 2\n")
+            .expect_file("foo.bas", "line 1\nline 2\n")
+            .check();
+
+        Tester::default()
+            .add_input_chars("first\n")
+            .run(r#"EDIT "new": SAVE"#)
+            .expect_prints(["New file new.bas"])
+            .expect_program("first\n")
+            .expect_file("new.bas", "first\n")
+            .check();
+    }
+
     #[test]
     fn test_edit_errors() {
-        check_stmt_err("EDIT takes no arguments", "EDIT 1");
+        check_stmt_err("EDIT requires a string as the filename", "EDIT 1");
+        check_stmt_err("EDIT requires a filename or no arguments", r#"EDIT "a", "b""#);
+        check_stmt_err("EDIT requires a filename or no arguments", r#"EDIT "a"; "b""#);
+        check_stmt_err("Filename must be a single path component", r#"EDIT "a/b.bas""#);
+        check_stmt_err("Invalid filename extension", r#"EDIT "foo.bak""#);
+    }
+
+    #[test]
+    fn test_list_nothing() {
+        Tester::default().run("LIST").check();
+    }
+
+    #[test]
+    fn test_list_keeps_comments() {
+        let content = "REM A sample program.\n\n' Count up.\nFOR i = 1 TO 3 ' Inline.\n    PRINT i: REM Show it.\nNEXT\n";
+        Tester::default()
+            .write_file("commented.bas", content)
+            .run(r#"LOAD "commented.bas": LIST: SAVE "copy.bas""#)
+            .expect_prints([
+                "REM A sample program.",
+                "",
+                "' Count up.",
+                "FOR i = 1 TO 3 ' Inline.",
+                "    PRINT i: REM Show it.",
+                "NEXT",
+            ])
+            .expect_program(content)
+            .expect_file("commented.bas", content)
+            .expect_file("copy.bas", content)
+            .check();
+    }
+
+    #[test]
+    fn test_list_errors() {
+        check_stmt_err("LIST takes no arguments", "LIST 1");
     }
 
     #[test]
@@ -883,6 +2279,48 @@ mod tests {
             .check();
     }
 
+    #[test]
+    fn test_merge_ok() {
+        Tester::default()
+            .set_program("a = 1\nPRINT a\n")
+            .write_file("more.bas", "b = 2\nPRINT b\n")
+            .run(r#"c = 3: MERGE "more""#)
+            .expect_program("a = 1\nPRINT a\nb = 2\nPRINT b\n")
+            .expect_var("c", 3)
+            .expect_file("more.bas", "b = 2\nPRINT b\n")
+            .check();
+
+        Tester::default()
+            .set_program("no newline")
+            .write_file("MORE.BAS", "second\n")
+            .run(r#"MERGE "MORE.BAS""#)
+            .expect_program("no newline\nsecond\n")
+            .expect_file("MORE.BAS", "second\n")
+            .check();
+    }
+
+    #[test]
+    fn test_merge_into_empty_program() {
+        Tester::default()
+            .write_file("more.bas", "only\n")
+            .run(r#"MERGE "more.bas""#)
+            .expect_program("only\n")
+            .expect_file("more.bas", "only\n")
+            .check();
+    }
+
+    #[test]
+    fn test_merge_errors() {
+        check_load_save_common_errors("MERGE");
+
+        Tester::default()
+            .set_program("untouched\n")
+            .run(r#"MERGE "missing-file""#)
+            .expect_err("Entry not found")
+            .expect_program("untouched\n")
+            .check();
+    }
+
     #[test]
     fn test_new_nothing() {
         Tester::default().run("NEW").check();
@@ -898,6 +2336,51 @@ mod tests {
         check_stmt_err("NEW takes no arguments", "NEW 10");
     }
 
+    #[test]
+    fn test_check_clean_program() {
+        let program = "a = 1\nIF a = 1 THEN\n    END\nEND IF\nPRINT a";
+        Tester::default()
+            .set_program(program)
+            .run("CHECK")
+            .expect_prints(["No problems found"])
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_check_reports_warnings() {
+        let program = "FOR i = 5 TO 1\nNEXT\nEND\nPRINT \"unreachable\"";
+        Tester::default()
+            .set_program(program)
+            .run("CHECK")
+            .expect_prints([
+                "Warning at line 1: FOR loop body never runs",
+                "Warning at line 4: Unreachable statement after END",
+            ])
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_check_reports_syntax_errors() {
+        let program = "a = (1\nEND\nb = 2\nWEND";
+        Tester::default()
+            .set_program(program)
+            .run("CHECK")
+            .expect_prints([
+                "Syntax error at line 1: Unbalanced parenthesis",
+                "Syntax error at line 4: WEND without WHILE",
+            ])
+            .expect_err("Program has syntax errors")
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_check_errors() {
+        check_stmt_err("CHECK takes no arguments", "CHECK 10");
+    }
+
     #[test]
     fn test_run_nothing() {
         Tester::default().run("RUN").check();
@@ -926,6 +2409,41 @@ mod tests {
             .check();
     }
 
+    #[test]
+    fn test_run_something_that_ends() {
+        let program = "PRINT 5: END: PRINT 4";
+        Tester::default()
+            .set_program(program)
+            .run(r#"RUN: PRINT "after""#)
+            .expect_prints(["5", "after"])
+            .expect_program(program)
+            .check();
+
+        let program = "PRINT 5: END 2: PRINT 4";
+        Tester::default()
+            .set_program(program)
+            .run(r#"RUN: PRINT "after""#)
+            .expect_prints(["5", "Program exited with code 2", "after"])
+            .expect_program(program)
+            .check();
+    }
+
+    #[test]
+    fn test_run_reports_all_syntax_errors() {
+        let program = "PRINT \"before\"\na = (1\nb = 2\nIF TRUE THEN\nc = )\nEND IF\nWEND\n";
+        Tester::default()
+            .set_program(program)
+            .run("RUN")
+            .expect_prints([
+                "Syntax error at line 2: Unbalanced parenthesis",
+                "Syntax error at line 5: Unbalanced parenthesis",
+                "Syntax error at line 7: WEND without WHILE",
+            ])
+            .expect_err("Program has syntax errors")
+            .expect_program(program)
+            .check();
+    }
+
     #[test]
     fn test_run_errors() {
         check_stmt_err("RUN takes no arguments", "RUN 10");
@@ -950,8 +2468,90 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_save_to_last_name() {
+        Tester::default()
+            .write_file("foo.bas", "old\n")
+            .run(r#"LOAD "foo": MERGE "foo": SAVE"#)
+            .expect_program("old\nold\n")
+            .expect_file("foo.bas", "old\nold\n")
+            .check();
+
+        Tester::default()
+            .set_program("content\n")
+            .run(r#"SAVE "first": NEW: SAVE"#)
+            .expect_err("SAVE requires a filename")
+            .expect_file("first.bas", "content\n")
+            .check();
+    }
+
+    #[test]
+    fn test_save_normalizes_newlines() {
+        let content = "a = 1\r\nPRINT a\r\n\nPRINT \"x\r\"\r\n";
+        Tester::default()
+            .set_program(content)
+            .run(r#"SAVE "file": SAVE "same", FALSE"#)
+            .expect_program(content)
+            .expect_file("file.bas", "a = 1\nPRINT a\n\nPRINT \"x\r\"\n")
+            .expect_file("same.bas", "a = 1\nPRINT a\n\nPRINT \"x\r\"\n")
+            .check();
+    }
+
+    #[test]
+    fn test_save_keeps_crlf() {
+        let content = "a = 1\r\nPRINT a\n";
+        Tester::default()
+            .set_program(content)
+            .run(r#"SAVE "file", TRUE"#)
+            .expect_program(content)
+            .expect_file("file.bas", content)
+            .check();
+    }
+
     #[test]
     fn test_save_errors() {
         check_load_save_common_errors("SAVE");
+
+        check_stmt_err("SAVE requires a filename", r#"SAVE "a", TRUE, TRUE"#);
+        check_stmt_err("SAVE requires a filename", r#"SAVE "a"; TRUE"#);
+        check_stmt_err("SAVE requires a filename", r#"SAVE , TRUE"#);
+        check_stmt_err("SAVE requires a boolean to keep CRLF", r#"SAVE "a", 1"#);
+    }
+
+    #[test]
+    fn test_tabwidth_expands_tabs_in_list() {
+        let content = "a\tb\n\tc\nab\tcd\te\n";
+        Tester::default()
+            .set_program(content)
+            .run("LIST: TABWIDTH 4: LIST")
+            .expect_prints([
+                "a       b",
+                "        c",
+                "ab      cd      e",
+                "a   b",
+                "    c",
+                "ab  cd  e",
+            ])
+            .expect_program(content)
+            .check();
+    }
+
+    #[test]
+    fn test_tabwidth_resets_to_default() {
+        Tester::default()
+            .set_program("\tx\n")
+            .run("TABWIDTH 2: LIST: TABWIDTH: LIST")
+            .expect_prints(["  x", "        x"])
+            .expect_program("\tx\n")
+            .check();
+    }
+
+    #[test]
+    fn test_tabwidth_errors() {
+        check_stmt_err("Tab width out of range", "TABWIDTH 0");
+        check_stmt_err("Tab width out of range", "TABWIDTH 256");
+        check_stmt_err("Tab width must be an integer", "TABWIDTH \"4\"");
+        check_stmt_err("Tab width must be an integer", "TABWIDTH 4.0");
+        check_stmt_err("TABWIDTH takes zero or one argument", "TABWIDTH 4, 4");
     }
 }
@@ -19,13 +19,201 @@ use endbasic_core::ast::{Value, VarType};
 use endbasic_core::eval::{
     CallableMetadata, CallableMetadataBuilder, Function, FunctionError, FunctionResult,
 };
-use endbasic_core::exec::Machine;
+use endbasic_core::exec::{self, Machine};
+use regex::Regex;
 use std::cmp::min;
 use std::rc::Rc;
 
 /// Category string for all functions provided by this module.
 const CATEGORY: &str = "String manipulation";
 
+/// Compiles the regular expression in `pattern`, reporting syntax errors in a single line.
+fn compile_regex(pattern: &str) -> Result<Regex, FunctionError> {
+    Regex::new(pattern).map_err(|e| {
+        let message = match &e {
+            regex::Error::Syntax(details) => details
+                .lines()
+                .last()
+                .map(|line| line.trim_start_matches("error: ").to_owned())
+                .unwrap_or_default(),
+            e => e.to_string(),
+        };
+        FunctionError::ArgumentError(format!("Invalid regular expression: {}", message))
+    })
+}
+
+/// The `CAPTURE` function.
+pub struct CaptureFunction {
+    metadata: CallableMetadata,
+}
+
+impl CaptureFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("CAPTURE", VarType::Text)
+                .with_syntax("pattern$, text$, group%")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Returns a group captured by a regular expression.
+Searches text$ for the first match of pattern$, as MATCH does, and returns the text captured by \
+the parenthesized group number group%, counting from 1 in the order of the opening \
+parentheses.  Group 0 refers to the whole match.
+Returns an empty string if there is no match or if the group did not participate in it.  \
+Asking for a group that pattern$ does not define is an error.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for CaptureFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Text(pattern), Value::Text(text), Value::Integer(group)] => {
+                let re = compile_regex(pattern)?;
+                if *group < 0 || *group as usize >= re.captures_len() {
+                    return Err(FunctionError::ArgumentError(format!(
+                        "Group {} out of range [0, {}]",
+                        group,
+                        re.captures_len() - 1
+                    )));
+                }
+                let captured = re
+                    .captures(text)
+                    .and_then(|captures| captures.get(*group as usize))
+                    .map(|m| m.as_str().to_owned());
+                Ok(Value::Text(captured.unwrap_or_default()))
+            }
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
+/// Splits a line of comma-separated values into its fields.
+///
+/// Fields that start with a double quote extend until the matching closing quote and can contain
+/// commas; within them, two consecutive double quotes represent a literal one.  An empty line has
+/// no fields, but any other line has one more field than unquoted commas, so trailing commas yield
+/// empty fields.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    if line.is_empty() {
+        return fields;
+    }
+
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            ch => field.push(ch),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// The `CSVCOUNT` function.
+pub struct CsvCountFunction {
+    metadata: CallableMetadata,
+}
+
+impl CsvCountFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("CSVCOUNT", VarType::Integer)
+                .with_syntax("line$")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Returns the number of comma-separated fields in a line.
+Fields enclosed in double quotes can contain commas, and trailing commas count as empty fields.  \
+An empty line has no fields.
+See CSVFIELD$ to extract the fields.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for CsvCountFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Text(line)] => {
+                let count = split_csv(line).len();
+                if count > i32::MAX as usize {
+                    Err(FunctionError::InternalError("Too many fields".to_owned()))
+                } else {
+                    Ok(Value::Integer(count as i32))
+                }
+            }
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
+/// The `CSVFIELD` function.
+pub struct CsvFieldFunction {
+    metadata: CallableMetadata,
+}
+
+impl CsvFieldFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("CSVFIELD", VarType::Text)
+                .with_syntax("line$, n%")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Returns a field from a line of comma-separated values.
+n% is the 1-based position of the field to return.  If n% is greater than the number of fields \
+in line$, returns an empty string.
+Fields enclosed in double quotes can contain commas, and two consecutive double quotes within \
+them represent a literal double quote.  The enclosing quotes are not part of the returned value.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for CsvFieldFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Text(line), Value::Integer(n)] => {
+                if *n < 1 {
+                    return Err(FunctionError::ArgumentError("n% must be positive".to_owned()));
+                }
+                let field = split_csv(line).into_iter().nth((*n - 1) as usize);
+                Ok(Value::Text(field.unwrap_or_default()))
+            }
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
 /// The `LEFT` function.
 pub struct LeftFunction {
     metadata: CallableMetadata,
@@ -136,6 +324,44 @@ impl Function for LtrimFunction {
     }
 }
 
+/// The `MATCH` function.
+pub struct MatchFunction {
+    metadata: CallableMetadata,
+}
+
+impl MatchFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("MATCH", VarType::Boolean)
+                .with_syntax("pattern$, text$")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Checks if a string matches a regular expression.
+Returns true if pattern$ matches any part of text$.  Matching is not anchored, so use ^ and $ in \
+pattern$ to match against the whole string.
+Invalid patterns are an error.  See CAPTURE$ to extract the matched text.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for MatchFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Text(pattern), Value::Text(text)] => {
+                Ok(Value::Boolean(compile_regex(pattern)?.is_match(text)))
+            }
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
 /// The `MID` function.
 pub struct MidFunction {
     metadata: CallableMetadata,
@@ -152,7 +378,9 @@ impl MidFunction {
                     "Returns a portion of a string.
 start% indicates the starting position of the substring to extract and it is 1-indexed.
 length% indicates the number of characters to extract and, if not specified, defaults to extracting
-until the end of the string.",
+until the end of the string.
+MID$ can also be used as the target of an assignment, as in MID$(s$, start%[, length%]) = expr$, \
+to overwrite a portion of the string in s$ without changing its length.",
                 )
                 .build(),
         })
@@ -255,20 +483,222 @@ impl Function for RtrimFunction {
     }
 }
 
+/// Percent-encodes `s` for use in a URL.
+///
+/// All characters except the unreserved ones (ASCII letters and digits, `-`, `.`, `_` and `~`) are
+/// replaced by the `%XX` encoding of each of their UTF-8 bytes.
+fn url_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            b => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+/// Decodes a percent-encoded `s`, treating `+` as a space.
+///
+/// Returns an error if a `%` is not followed by two hexadecimal digits or if the decoded bytes are
+/// not valid UTF-8.
+fn url_decode(s: &str) -> Result<String, String> {
+    let mut decoded = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes().enumerate();
+    while let Some((i, b)) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex = s.get(i + 1..i + 3).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) if hex.len() == 2 && !hex.starts_with('+') => decoded.push(b),
+                    _ => return Err(format!("Invalid escape sequence at position {}", i + 1)),
+                }
+                bytes.nth(1);
+            }
+            b'+' => decoded.push(b' '),
+            b => decoded.push(b),
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| "Decoded string is not valid UTF-8".to_owned())
+}
+
+/// The `URLDECODE` function.
+pub struct UrlDecodeFunction {
+    metadata: CallableMetadata,
+}
+
+impl UrlDecodeFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("URLDECODE", VarType::Text)
+                .with_syntax("expr$")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Decodes a percent-encoded string.
+Replaces each %XX sequence with the byte of hexadecimal value XX and each + with a space.  \
+A % that is not followed by two hexadecimal digits is an error.
+This is the reverse of URLENCODE$.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for UrlDecodeFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Text(s)] => {
+                Ok(Value::Text(url_decode(s).map_err(FunctionError::ArgumentError)?))
+            }
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
+/// The `URLENCODE` function.
+pub struct UrlEncodeFunction {
+    metadata: CallableMetadata,
+}
+
+impl UrlEncodeFunction {
+    /// Creates a new instance of the function.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("URLENCODE", VarType::Text)
+                .with_syntax("expr$")
+                .with_category(CATEGORY)
+                .with_description(
+                    "Percent-encodes a string for use in a URL.
+Letters, digits and the -, ., _ and ~ characters are kept as is.  Any other character, \
+including spaces, is replaced by the %XX sequences of its UTF-8 bytes.
+See URLDECODE$ for the reverse operation.",
+                )
+                .build(),
+        })
+    }
+}
+
+impl Function for UrlEncodeFunction {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    fn exec(&self, args: Vec<Value>) -> FunctionResult {
+        match args.as_slice() {
+            [Value::Text(s)] => Ok(Value::Text(url_encode(s))),
+            _ => Err(FunctionError::SyntaxError),
+        }
+    }
+}
+
 /// Adds all symbols provided by this module to the given `machine`.
-pub fn add_all(machine: &mut Machine) {
-    machine.add_function(LeftFunction::new());
-    machine.add_function(LenFunction::new());
-    machine.add_function(LtrimFunction::new());
-    machine.add_function(MidFunction::new());
-    machine.add_function(RightFunction::new());
-    machine.add_function(RtrimFunction::new());
+pub fn add_all(machine: &mut Machine) -> exec::Result<()> {
+    machine.add_function(CaptureFunction::new())?;
+    machine.add_function(CsvCountFunction::new())?;
+    machine.add_function(CsvFieldFunction::new())?;
+    machine.add_function(LeftFunction::new())?;
+    machine.add_function(LenFunction::new())?;
+    machine.add_function(LtrimFunction::new())?;
+    machine.add_function(MatchFunction::new())?;
+    machine.add_function(MidFunction::new())?;
+    machine.add_function(RightFunction::new())?;
+    machine.add_function(RtrimFunction::new())?;
+    machine.add_function(UrlDecodeFunction::new())?;
+    machine.add_function(UrlEncodeFunction::new())
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::testutils::*;
 
+    #[test]
+    fn test_split_csv() {
+        assert!(split_csv("").is_empty());
+        assert_eq!(vec!["a"], split_csv("a"));
+        assert_eq!(vec!["a", "b c", " d"], split_csv("a,b c, d"));
+        assert_eq!(vec!["", "", ""], split_csv(",,"));
+        assert_eq!(vec!["a", "b", ""], split_csv("a,b,"));
+        assert_eq!(vec!["a,b", "c"], split_csv(r#""a,b",c"#));
+        assert_eq!(vec![r#"say "hi""#, ""], split_csv(r#""say ""hi""","#));
+        assert_eq!(vec![""], split_csv(r#""""#));
+        assert_eq!(vec![r#"a"b"#], split_csv(r#"a"b"#));
+        assert_eq!(vec!["ab c"], split_csv(r#""ab" c"#));
+        assert_eq!(vec!["unterminated, still"], split_csv(r#""unterminated, still"#));
+    }
+
+    #[test]
+    fn test_capture() {
+        check_expr_ok("2021-03-04", r#"CAPTURE("(\\d+)-(\\d+)-(\\d+)", "On 2021-03-04.", 0)"#);
+        check_expr_ok("2021", r#"CAPTURE("(\\d+)-(\\d+)-(\\d+)", "On 2021-03-04.", 1)"#);
+        check_expr_ok("04", r#"CAPTURE("(\\d+)-(\\d+)-(\\d+)", "On 2021-03-04.", 3)"#);
+        check_expr_ok("", r#"CAPTURE("(\\d+)-(\\d+)", "No dates", 1)"#);
+        check_expr_ok("", r#"CAPTURE("a(b)?c", "ac", 1)"#);
+
+        check_expr_error(
+            "Syntax error in call to CAPTURE: expected pattern$, text$, group%",
+            r#"CAPTURE("a", "b")"#,
+        );
+        check_expr_error(
+            "Syntax error in call to CAPTURE: expected pattern$, text$, group%",
+            r#"CAPTURE("a", "b", "1")"#,
+        );
+        check_expr_error(
+            "Syntax error in call to CAPTURE: Group 2 out of range [0, 1]",
+            r#"CAPTURE("(a)", "a", 2)"#,
+        );
+        check_expr_error(
+            "Syntax error in call to CAPTURE: Group -1 out of range [0, 0]",
+            r#"CAPTURE("a", "a", -1)"#,
+        );
+        check_expr_error(
+            "Syntax error in call to CAPTURE: Invalid regular expression: unclosed group",
+            r#"CAPTURE("(a", "a", 0)"#,
+        );
+    }
+
+    #[test]
+    fn test_csvcount() {
+        check_expr_ok(0, r#"CSVCOUNT("")"#);
+        check_expr_ok(1, r#"CSVCOUNT("abc")"#);
+        check_expr_ok(3, r#"CSVCOUNT("a,\"b,c\",d")"#);
+        check_expr_ok(3, r#"CSVCOUNT("a,b,")"#);
+
+        check_expr_error("Syntax error in call to CSVCOUNT: expected line$", r#"CSVCOUNT()"#);
+        check_expr_error("Syntax error in call to CSVCOUNT: expected line$", r#"CSVCOUNT(3)"#);
+        check_expr_error("Syntax error in call to CSVCOUNT: expected line$", r#"CSVCOUNT("", 1)"#);
+    }
+
+    #[test]
+    fn test_csvfield() {
+        check_expr_ok("a", r#"CSVFIELD("a,b", 1)"#);
+        check_expr_ok("b", r#"CSVFIELD("a,b", 2)"#);
+        check_expr_ok("b,c", r#"CSVFIELD("a,\"b,c\",d", 2)"#);
+        check_expr_ok("d", r#"CSVFIELD("a,\"b,c\",d", 3)"#);
+        check_expr_ok(r#"say "hi""#, r#"CSVFIELD("\"say \"\"hi\"\"\"", 1)"#);
+        check_expr_ok("", r#"CSVFIELD("a,b,", 3)"#);
+        check_expr_ok("", r#"CSVFIELD("a,b", 3)"#);
+        check_expr_ok("", r#"CSVFIELD("", 1)"#);
+
+        check_expr_error("Syntax error in call to CSVFIELD: expected line$, n%", r#"CSVFIELD()"#);
+        check_expr_error("Syntax error in call to CSVFIELD: expected line$, n%", r#"CSVFIELD("")"#);
+        check_expr_error(
+            "Syntax error in call to CSVFIELD: expected line$, n%",
+            r#"CSVFIELD(1, 2)"#,
+        );
+        check_expr_error(
+            "Syntax error in call to CSVFIELD: n% must be positive",
+            r#"CSVFIELD("a", 0)"#,
+        );
+    }
+
     #[test]
     fn test_left() {
         check_expr_ok("", r#"LEFT("", 0)"#);
@@ -309,6 +739,28 @@ mod tests {
         check_expr_error("Syntax error in call to LTRIM: expected expr$", r#"LTRIM(" ", 1)"#);
     }
 
+    #[test]
+    fn test_match() {
+        check_expr_ok(true, r#"MATCH("b+", "abbc")"#);
+        check_expr_ok(false, r#"MATCH("^b+$", "abbc")"#);
+        check_expr_ok(true, r#"MATCH("^a.*c$", "abbc")"#);
+        check_expr_ok(true, r#"MATCH("", "")"#);
+        check_expr_ok(false, r#"MATCH("[0-9]", "abc")"#);
+
+        check_expr_error(
+            "Syntax error in call to MATCH: expected pattern$, text$",
+            r#"MATCH("a")"#,
+        );
+        check_expr_error(
+            "Syntax error in call to MATCH: expected pattern$, text$",
+            r#"MATCH(1, "a")"#,
+        );
+        check_expr_error(
+            "Syntax error in call to MATCH: Invalid regular expression: unclosed character class",
+            r#"MATCH("[a", "a")"#,
+        );
+    }
+
     #[test]
     fn test_mid() {
         check_expr_ok("", r#"MID("", 0, 0)"#);
@@ -374,4 +826,59 @@ mod tests {
         check_expr_error("Syntax error in call to RTRIM: expected expr$", r#"RTRIM(3)"#);
         check_expr_error("Syntax error in call to RTRIM: expected expr$", r#"RTRIM(" ", 1)"#);
     }
+
+    #[test]
+    fn test_url_encode_decode() {
+        for (decoded, encoded) in &[
+            ("", ""),
+            ("AZaz09-._~", "AZaz09-._~"),
+            ("a b", "a%20b"),
+            ("a+b&c=d/e?f#g%", "a%2Bb%26c%3Dd%2Fe%3Ff%23g%25"),
+            ("año €", "a%C3%B1o%20%E2%82%AC"),
+        ] {
+            assert_eq!(*encoded, url_encode(decoded));
+            assert_eq!(*decoded, url_decode(encoded).unwrap());
+        }
+
+        assert_eq!("a b c", url_decode("a+b%20c").unwrap());
+        assert_eq!("\u{1F600}", url_decode("%f0%9f%98%80").unwrap());
+    }
+
+    #[test]
+    fn test_url_decode_errors() {
+        for s in &["%", "a%2", "%zz", "%+1", "%-1", "%2%41"] {
+            assert!(url_decode(s).unwrap_err().starts_with("Invalid escape sequence"), "{}", s);
+        }
+        assert_eq!("Invalid escape sequence at position 2", url_decode("a%").unwrap_err());
+        assert_eq!("Invalid escape sequence at position 1", url_decode("%é1").unwrap_err());
+        assert_eq!("Decoded string is not valid UTF-8", url_decode("%C3").unwrap_err());
+    }
+
+    #[test]
+    fn test_urldecode() {
+        check_expr_ok("", r#"URLDECODE("")"#);
+        check_expr_ok("a b/c", r#"URLDECODE("a+b%2Fc")"#);
+
+        check_expr_error("Syntax error in call to URLDECODE: expected expr$", r#"URLDECODE()"#);
+        check_expr_error("Syntax error in call to URLDECODE: expected expr$", r#"URLDECODE(3)"#);
+        check_expr_error(
+            "Syntax error in call to URLDECODE: Invalid escape sequence at position 3",
+            r#"URLDECODE("ab%g0")"#,
+        );
+    }
+
+    #[test]
+    fn test_urlencode() {
+        check_expr_ok("", r#"URLENCODE("")"#);
+        check_expr_ok("a%20b%2Fc", r#"URLENCODE("a b/c")"#);
+
+        check_expr_error("Syntax error in call to URLENCODE: expected expr$", r#"URLENCODE()"#);
+        check_expr_error("Syntax error in call to URLENCODE: expected expr$", r#"URLENCODE(3)"#);
+
+        Tester::default()
+            .run(r#"s$ = "50% off & más": r$ = URLDECODE$(URLENCODE$(s$))"#)
+            .expect_var("s", "50% off & más")
+            .expect_var("r", "50% off & más")
+            .check();
+    }
 }
@@ -15,13 +15,14 @@
 
 //! Console representation and manipulation.
 
-use crate::console::{ClearType, Console, Key, Position};
+use crate::console::{ClearType, Console, DisplayColor, Key, Palette, Position};
 use async_trait::async_trait;
 use crossterm::{cursor, event, execute, style, terminal, tty::IsTty, QueueableCommand};
 use std::cmp::Ordering;
 use std::collections::VecDeque;
 use std::env;
 use std::io::{self, Write};
+use std::time::Duration;
 
 //// Converts a `crossterm::ErrorKind` to an `io::Error`.
 fn crossterm_error_to_io_error(e: crossterm::ErrorKind) -> io::Error {
@@ -45,6 +46,15 @@ fn get_env_var_as_usize(name: &str) -> Option<usize> {
     }
 }
 
+/// Converts a `DisplayColor` to a `crossterm::style::Color`.
+fn display_color_to_crossterm(color: DisplayColor) -> style::Color {
+    match color {
+        DisplayColor::Default => style::Color::Reset,
+        DisplayColor::Native(color) => style::Color::AnsiValue(color),
+        DisplayColor::Rgb(r, g, b) => style::Color::Rgb { r, g, b },
+    }
+}
+
 /// Implementation of the EndBASIC console to interact with stdin and stdout.
 pub struct TerminalConsole {
     /// Whether stdin and stdout are attached to a TTY.  When this is true, the console is put in
@@ -57,6 +67,9 @@ pub struct TerminalConsole {
     /// Whether a background color is active.  If so, we need to flush the contents of every line
     /// we print so that the color applies to the whole line.
     need_line_flush: bool,
+
+    /// Mapping of color numbers to the colors to display.
+    palette: Palette,
 }
 
 impl Drop for TerminalConsole {
@@ -74,7 +87,12 @@ impl TerminalConsole {
         if is_tty {
             terminal::enable_raw_mode().map_err(crossterm_error_to_io_error)?;
         }
-        Ok(Self { is_tty, buffer: VecDeque::default(), need_line_flush: false })
+        Ok(Self {
+            is_tty,
+            buffer: VecDeque::default(),
+            need_line_flush: false,
+            palette: Palette::default(),
+        })
     }
 
     /// Converts a line of text read from stdin into a sequence of key presses.
@@ -116,35 +134,50 @@ impl TerminalConsole {
         }
     }
 
+    /// Converts a key event from the TTY into our own `Key` representation.
+    fn key_event_into_key(ev: event::KeyEvent) -> Key {
+        match ev.code {
+            event::KeyCode::Backspace => Key::Backspace,
+            event::KeyCode::Esc => Key::Escape,
+            event::KeyCode::Up => Key::ArrowUp,
+            event::KeyCode::Down => Key::ArrowDown,
+            event::KeyCode::Left => Key::ArrowLeft,
+            event::KeyCode::Right => Key::ArrowRight,
+            event::KeyCode::Char('c') if ev.modifiers == event::KeyModifiers::CONTROL => {
+                Key::Interrupt
+            }
+            event::KeyCode::Char('d') if ev.modifiers == event::KeyModifiers::CONTROL => Key::Eof,
+            event::KeyCode::Char('j') if ev.modifiers == event::KeyModifiers::CONTROL => {
+                Key::NewLine
+            }
+            event::KeyCode::Char('m') if ev.modifiers == event::KeyModifiers::CONTROL => {
+                Key::NewLine
+            }
+            event::KeyCode::Char(ch) => Key::Char(ch),
+            event::KeyCode::Enter => Key::NewLine,
+            event::KeyCode::Tab => Key::Tab,
+            _ => Key::Unknown(format!("{:?}", ev)),
+        }
+    }
+
     /// Reads a single key from the connected TTY.  This assumes the TTY is in raw mode.
     fn read_key_from_tty(&mut self) -> io::Result<Key> {
         loop {
             if let event::Event::Key(ev) = event::read().map_err(crossterm_error_to_io_error)? {
-                match ev.code {
-                    event::KeyCode::Backspace => return Ok(Key::Backspace),
-                    event::KeyCode::Esc => return Ok(Key::Escape),
-                    event::KeyCode::Up => return Ok(Key::ArrowUp),
-                    event::KeyCode::Down => return Ok(Key::ArrowDown),
-                    event::KeyCode::Left => return Ok(Key::ArrowLeft),
-                    event::KeyCode::Right => return Ok(Key::ArrowRight),
-                    event::KeyCode::Char('c') if ev.modifiers == event::KeyModifiers::CONTROL => {
-                        return Ok(Key::Interrupt)
-                    }
-                    event::KeyCode::Char('d') if ev.modifiers == event::KeyModifiers::CONTROL => {
-                        return Ok(Key::Eof)
-                    }
-                    event::KeyCode::Char('j') if ev.modifiers == event::KeyModifiers::CONTROL => {
-                        return Ok(Key::NewLine)
-                    }
-                    event::KeyCode::Char('m') if ev.modifiers == event::KeyModifiers::CONTROL => {
-                        return Ok(Key::NewLine)
-                    }
-                    event::KeyCode::Char(ch) => return Ok(Key::Char(ch)),
-                    event::KeyCode::Enter => return Ok(Key::NewLine),
-                    _ => return Ok(Key::Unknown(format!("{:?}", ev))),
-                }
+                return Ok(TerminalConsole::key_event_into_key(ev));
+            }
+        }
+    }
+
+    /// Reads a single key from the connected TTY if one is available without waiting.  This
+    /// assumes the TTY is in raw mode.
+    fn poll_key_from_tty(&mut self) -> io::Result<Option<Key>> {
+        while event::poll(Duration::default()).map_err(crossterm_error_to_io_error)? {
+            if let event::Event::Key(ev) = event::read().map_err(crossterm_error_to_io_error)? {
+                return Ok(Some(TerminalConsole::key_event_into_key(ev)));
             }
         }
+        Ok(None)
     }
 }
 
@@ -166,14 +199,8 @@ impl Console for TerminalConsole {
 
     fn color(&mut self, fg: Option<u8>, bg: Option<u8>) -> io::Result<()> {
         let mut output = io::stdout();
-        let fg = match fg {
-            None => style::Color::Reset,
-            Some(color) => style::Color::AnsiValue(color),
-        };
-        let bg = match bg {
-            None => style::Color::Reset,
-            Some(color) => style::Color::AnsiValue(color),
-        };
+        let fg = display_color_to_crossterm(self.palette.resolve(fg));
+        let bg = display_color_to_crossterm(self.palette.resolve(bg));
         output.queue(style::SetForegroundColor(fg)).map_err(crossterm_error_to_io_error)?;
         output.queue(style::SetBackgroundColor(bg)).map_err(crossterm_error_to_io_error)?;
         output.flush()?;
@@ -220,6 +247,16 @@ impl Console for TerminalConsole {
         .map_err(crossterm_error_to_io_error)
     }
 
+    fn poll_key(&mut self) -> io::Result<Option<Key>> {
+        if self.is_tty {
+            self.poll_key_from_tty()
+        } else {
+            // There is no portable way to check if stdin has data without blocking, but input that
+            // does not come from a TTY is usually a file or a pipe that is readily available.
+            self.read_key_from_stdin().map(Some)
+        }
+    }
+
     fn print(&mut self, text: &str) -> io::Result<()> {
         let stdout = io::stdout();
         let mut stdout = stdout.lock();
@@ -244,6 +281,11 @@ impl Console for TerminalConsole {
         }
     }
 
+    fn set_palette(&mut self, palette: Palette) -> io::Result<()> {
+        self.palette = palette;
+        Ok(())
+    }
+
     fn show_cursor(&mut self) -> io::Result<()> {
         execute!(io::stdout(), cursor::Show).map_err(crossterm_error_to_io_error)
     }
@@ -15,14 +15,17 @@
 
 //! Test utilities for consumers of the EndBASIC interpreter.
 
-use crate::console::{self, ClearType, Console, Key, Position};
+use crate::console::{
+    self, ClearType, Console, DisplayColor, History, Key, Palette, PixelPos, Position,
+    DEFAULT_TAB_WIDTH,
+};
 use crate::store::{InMemoryStore, Program, Store};
 use async_trait::async_trait;
 use endbasic_core::ast::Value;
 use endbasic_core::eval::Function;
 use endbasic_core::exec::{self, Command, Machine, StopReason};
 use futures_lite::future::block_on;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, VecDeque};
 use std::io;
 use std::rc::Rc;
@@ -37,6 +40,12 @@ pub enum CapturedOut {
     /// Represents a call to `Console::color`.
     Color(Option<u8>, Option<u8>),
 
+    /// Represents a call to `Console::draw_line`.
+    DrawLine(PixelPos, PixelPos, Option<u8>),
+
+    /// Represents a call to `Console::draw_pixel`.
+    DrawPixel(PixelPos, Option<u8>),
+
     /// Represents a call to `Console::enter_alt`.
     EnterAlt,
 
@@ -72,6 +81,22 @@ pub struct MockConsole {
 
     /// The size of the mock console.
     size: Position,
+
+    /// The size of the graphics surface of the mock console, if it has one.
+    graphics_size: Option<PixelPos>,
+
+    /// Contents of the screen as modeled by the operations applied to the console.  Rows and
+    /// columns are only allocated when written to, so this may be smaller than `size`.
+    screen: Vec<Vec<char>>,
+
+    /// Current position of the cursor within `screen`.
+    cursor: Position,
+
+    /// Mapping of color numbers to the colors to display.
+    palette: Palette,
+
+    /// Foreground and background colors currently displayed, as resolved through `palette`.
+    display_colors: (DisplayColor, DisplayColor),
 }
 
 impl Default for MockConsole {
@@ -80,6 +105,11 @@ impl Default for MockConsole {
             golden_in: VecDeque::new(),
             captured_out: vec![],
             size: Position { row: usize::MAX, column: usize::MAX },
+            graphics_size: None,
+            screen: vec![],
+            cursor: Position { row: 0, column: 0 },
+            palette: Palette::default(),
+            display_colors: (DisplayColor::Default, DisplayColor::Default),
         }
     }
 }
@@ -109,10 +139,71 @@ impl MockConsole {
         self.captured_out.as_slice()
     }
 
+    /// Returns the foreground and background colors currently displayed by the console, as
+    /// resolved through the palette.
+    pub fn display_colors(&self) -> (DisplayColor, DisplayColor) {
+        self.display_colors
+    }
+
     /// Sets the size of the mock console.
     pub fn set_size(&mut self, size: Position) {
         self.size = size;
     }
+
+    /// Gives the console a graphics surface of the given `size`.  Consoles start without one.
+    pub fn set_graphics_size(&mut self, size: PixelPos) {
+        self.graphics_size = Some(size);
+    }
+
+    /// Returns the current contents of the screen, one string per row.
+    ///
+    /// Trailing whitespace is removed from every row and trailing empty rows are omitted, so that
+    /// tests can compare the visible contents of the screen without having to care about its size.
+    pub fn snapshot(&self) -> Vec<String> {
+        let mut rows: Vec<String> = self
+            .screen
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end().to_owned())
+            .collect();
+        while rows.last().map(String::is_empty).unwrap_or(false) {
+            rows.pop();
+        }
+        rows
+    }
+
+    /// Moves the cursor to the beginning of the next line, scrolling the screen up if the cursor
+    /// was already on the last row.
+    fn newline(&mut self) {
+        self.cursor.column = 0;
+        if self.cursor.row + 1 < self.size.row {
+            self.cursor.row += 1;
+        } else if !self.screen.is_empty() {
+            self.screen.remove(0);
+        }
+    }
+
+    /// Puts `ch` on the screen at the cursor position and advances the cursor, wrapping to the
+    /// next line if the cursor goes past the last column.
+    fn put_char(&mut self, ch: char) {
+        match ch {
+            '\n' => self.newline(),
+            '\r' => self.cursor.column = 0,
+            ch => {
+                if self.cursor.column >= self.size.column {
+                    self.newline();
+                }
+                if self.screen.len() <= self.cursor.row {
+                    self.screen.resize(self.cursor.row + 1, vec![]);
+                }
+                let row = &mut self.screen[self.cursor.row];
+                if row.len() <= self.cursor.column {
+                    row.resize(self.cursor.column + 1, ' ');
+                }
+                row[self.cursor.column] = ch;
+                self.cursor.column += 1;
+            }
+        }
+    }
 }
 
 impl Drop for MockConsole {
@@ -128,12 +219,43 @@ impl Drop for MockConsole {
 #[async_trait(?Send)]
 impl Console for MockConsole {
     fn clear(&mut self, how: ClearType) -> io::Result<()> {
+        match how {
+            ClearType::All => {
+                self.screen.clear();
+                self.cursor = Position { row: 0, column: 0 };
+            }
+            ClearType::CurrentLine => {
+                if let Some(row) = self.screen.get_mut(self.cursor.row) {
+                    row.clear();
+                }
+            }
+            ClearType::UntilNewLine => {
+                if let Some(row) = self.screen.get_mut(self.cursor.row) {
+                    row.truncate(self.cursor.column);
+                }
+            }
+        }
         self.captured_out.push(CapturedOut::Clear(how));
         Ok(())
     }
 
     fn color(&mut self, fg: Option<u8>, bg: Option<u8>) -> io::Result<()> {
         self.captured_out.push(CapturedOut::Color(fg, bg));
+        self.display_colors = (self.palette.resolve(fg), self.palette.resolve(bg));
+        Ok(())
+    }
+
+    fn contents(&self) -> io::Result<Vec<String>> {
+        Ok(self.snapshot())
+    }
+
+    fn draw_line(&mut self, from: PixelPos, to: PixelPos, color: Option<u8>) -> io::Result<()> {
+        self.captured_out.push(CapturedOut::DrawLine(from, to, color));
+        Ok(())
+    }
+
+    fn draw_pixel(&mut self, pos: PixelPos, color: Option<u8>) -> io::Result<()> {
+        self.captured_out.push(CapturedOut::DrawPixel(pos, color));
         Ok(())
     }
 
@@ -142,6 +264,16 @@ impl Console for MockConsole {
         Ok(())
     }
 
+    fn graphics_size(&self) -> io::Result<PixelPos> {
+        match self.graphics_size {
+            Some(size) => Ok(size),
+            None => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Graphics are not supported by this console",
+            )),
+        }
+    }
+
     fn hide_cursor(&mut self) -> io::Result<()> {
         self.captured_out.push(CapturedOut::HideCursor);
         Ok(())
@@ -158,16 +290,24 @@ impl Console for MockConsole {
 
     fn locate(&mut self, pos: Position) -> io::Result<()> {
         self.captured_out.push(CapturedOut::Locate(pos));
+        self.cursor = pos;
         Ok(())
     }
 
     fn move_within_line(&mut self, off: i16) -> io::Result<()> {
         self.captured_out.push(CapturedOut::MoveWithinLine(off));
+        self.cursor.column = (self.cursor.column as isize + off as isize).max(0) as usize;
         Ok(())
     }
 
+    fn poll_key(&mut self) -> io::Result<Option<Key>> {
+        Ok(self.golden_in.pop_front())
+    }
+
     fn print(&mut self, text: &str) -> io::Result<()> {
         self.captured_out.push(CapturedOut::Print(text.to_owned()));
+        text.chars().for_each(|ch| self.put_char(ch));
+        self.newline();
         Ok(())
     }
 
@@ -178,6 +318,11 @@ impl Console for MockConsole {
         }
     }
 
+    fn set_palette(&mut self, palette: Palette) -> io::Result<()> {
+        self.palette = palette;
+        Ok(())
+    }
+
     fn show_cursor(&mut self) -> io::Result<()> {
         self.captured_out.push(CapturedOut::ShowCursor);
         Ok(())
@@ -189,6 +334,7 @@ impl Console for MockConsole {
 
     fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
         self.captured_out.push(CapturedOut::Write(bytes.to_owned()));
+        String::from_utf8_lossy(bytes).chars().for_each(|ch| self.put_char(ch));
         Ok(())
     }
 }
@@ -198,12 +344,13 @@ impl Console for MockConsole {
 #[derive(Default)]
 pub struct RecordedProgram {
     content: String,
+    name: Option<String>,
 }
 
 #[async_trait(?Send)]
 impl Program for RecordedProgram {
     async fn edit(&mut self, console: &mut dyn Console) -> io::Result<()> {
-        let append = console::read_line(console, "", "").await?;
+        let append = console::read_line(console, "", "", None, None).await?;
         self.content.push_str(&append);
         self.content.push('\n');
         Ok(())
@@ -216,6 +363,14 @@ impl Program for RecordedProgram {
     fn text(&self) -> String {
         self.content.clone()
     }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn set_name(&mut self, name: Option<&str>) {
+        self.name = name.map(str::to_owned);
+    }
 }
 
 /// Builder pattern to prepare an EndBASIC machine for testing purposes.
@@ -233,7 +388,16 @@ impl Default for Tester {
         let console = Rc::from(RefCell::from(MockConsole::default()));
         let store = Rc::from(RefCell::from(InMemoryStore::default()));
         let program = Rc::from(RefCell::from(RecordedProgram::default()));
-        let machine = crate::full_machine(console.clone(), store.clone(), program.clone());
+        let history = Rc::from(RefCell::from(History::default()));
+        let tab_width = Rc::from(Cell::new(DEFAULT_TAB_WIDTH));
+        let machine = crate::full_machine(
+            console.clone(),
+            store.clone(),
+            program.clone(),
+            history,
+            tab_width,
+            vec![],
+        );
 
         Self { console, store, program, machine }
     }
@@ -251,13 +415,19 @@ impl Tester {
 
     /// Registers the given builtin command into the machine, which must not yet be registered.
     pub fn add_command(mut self, command: Rc<dyn Command>) -> Self {
-        self.machine.add_command(command);
+        self.machine.add_command(command).expect("Command must not be registered yet");
         self
     }
 
     /// Registers the given builtin function into the machine, which must not yet be registered.
     pub fn add_function(mut self, function: Rc<dyn Function>) -> Self {
-        self.machine.add_function(function);
+        self.machine.add_function(function).expect("Function must not be registered yet");
+        self
+    }
+
+    /// Registers `alias` as an alternate name for the already-registered command `name`.
+    pub fn add_alias(mut self, alias: &'static str, name: &'static str) -> Self {
+        self.machine.add_alias(alias, name).expect("Alias must not be registered yet");
         self
     }
 
@@ -335,6 +505,7 @@ pub struct Checker<'a> {
     exp_store: HashMap<String, String>,
     exp_program: String,
     exp_vars: HashMap<String, Value>,
+    exp_snapshot: Option<Vec<String>>,
 }
 
 impl<'a> Checker<'a> {
@@ -351,6 +522,7 @@ impl<'a> Checker<'a> {
             exp_store: HashMap::default(),
             exp_program: String::new(),
             exp_vars: HashMap::default(),
+            exp_snapshot: None,
         }
     }
 
@@ -417,6 +589,16 @@ impl<'a> Checker<'a> {
         self
     }
 
+    /// Sets the expected contents of the screen at the end of the execution to `rows`, as returned
+    /// by `MockConsole::snapshot`.  Can only be called once.
+    ///
+    /// If not called, the contents of the screen are not validated.
+    pub fn expect_snapshot<S: Into<String>, V: Into<Vec<S>>>(mut self, rows: V) -> Self {
+        assert!(self.exp_snapshot.is_none());
+        self.exp_snapshot = Some(rows.into().into_iter().map(Into::into).collect());
+        self
+    }
+
     /// Validates all expectations.
     pub fn check(self) {
         match self.result {
@@ -428,6 +610,9 @@ impl<'a> Checker<'a> {
         assert_eq!(self.exp_output, self.tester.console.borrow().captured_out());
         assert_eq!(self.exp_program, self.tester.program.borrow().text());
         assert_eq!(self.exp_store, *self.tester.store.borrow().as_hashmap());
+        if let Some(exp_snapshot) = self.exp_snapshot {
+            assert_eq!(exp_snapshot, self.tester.console.borrow().snapshot());
+        }
     }
 }
 
@@ -32,6 +32,12 @@ term.loadAddon(fitAddon);
 term.open(document.getElementById('terminal'));
 fitAddon.fit();
 
+// Match the resolution of the graphics canvas to its displayed size so that each pixel drawn by
+// a program maps to exactly one pixel on the screen.
+var canvas = document.getElementById('graphics');
+canvas.width = canvas.clientWidth;
+canvas.height = canvas.clientHeight;
+
 var wt = new endbasic_web.WebTerminal();
 
 var UA = navigator.userAgent;
@@ -66,4 +72,4 @@ if (isMobile) {
 }
 
 term.focus();
-wt.run_repl_loop(term);
+wt.run_repl_loop(term, canvas);
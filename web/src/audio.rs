@@ -0,0 +1,105 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Sound support via the browser's Web Audio API.
+
+use async_trait::async_trait;
+use endbasic_std::clock::Clock;
+use endbasic_std::sound::Speaker;
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AudioContext, OscillatorNode};
+
+/// Converts an error raised by the Web Audio API into an I/O error.
+fn js_error(e: JsValue) -> io::Error {
+    let message = match e.dyn_into::<js_sys::Error>() {
+        Ok(e) => String::from(e.message()),
+        Err(e) => format!("{:?}", e),
+    };
+    io::Error::new(io::ErrorKind::Other, format!("Cannot play sound: {}", message))
+}
+
+/// Speaker that plays tones with oscillators of the Web Audio API.
+pub(crate) struct WebAudioSpeaker {
+    /// The audio context, created on first use because browsers refuse to start audio before the
+    /// user has interacted with the page.
+    context: RefCell<Option<AudioContext>>,
+
+    /// Clock used to wait for tones to finish playing.
+    clock: Rc<dyn Clock>,
+}
+
+impl WebAudioSpeaker {
+    /// Creates a new speaker that uses `clock` to wait for tones to finish playing.
+    pub(crate) fn new(clock: Rc<dyn Clock>) -> Self {
+        Self { context: RefCell::default(), clock }
+    }
+
+    /// Returns the audio context, creating it if necessary and resuming it if the browser had
+    /// suspended it.
+    async fn context(&self) -> Result<AudioContext, JsValue> {
+        let context = match &*self.context.borrow() {
+            Some(context) => context.clone(),
+            None => AudioContext::new()?,
+        };
+        *self.context.borrow_mut() = Some(context.clone());
+        JsFuture::from(context.resume()?).await?;
+        Ok(context)
+    }
+
+    /// Starts playing a tone of `frequency` Hz on `context` and returns the oscillator that
+    /// produces it, which the caller must stop.
+    fn start_tone(context: &AudioContext, frequency: u32) -> Result<OscillatorNode, JsValue> {
+        let oscillator = context.create_oscillator()?;
+        oscillator.frequency().set_value(frequency as f32);
+        oscillator.connect_with_audio_node(&context.destination())?;
+        oscillator.start()?;
+        Ok(oscillator)
+    }
+}
+
+#[async_trait(?Send)]
+impl Speaker for WebAudioSpeaker {
+    async fn rest(&self, duration: Duration) -> io::Result<()> {
+        self.clock.sleep(duration).await;
+        Ok(())
+    }
+
+    async fn tone(&self, frequency: u32, duration: Duration) -> io::Result<()> {
+        let context = self.context().await.map_err(js_error)?;
+        let oscillator = WebAudioSpeaker::start_tone(&context, frequency).map_err(js_error)?;
+        self.clock.sleep(duration).await;
+        oscillator.stop().map_err(js_error)?;
+        oscillator.disconnect().map_err(js_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_start_tone_sets_frequency() {
+        let context = AudioContext::new().unwrap();
+        let oscillator = WebAudioSpeaker::start_tone(&context, 440).unwrap();
+        assert_eq!(440.0, oscillator.frequency().value());
+        oscillator.stop().unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Commands to issue HTTP requests via the browser's `fetch` API.
+
+use async_trait::async_trait;
+use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
+use endbasic_core::eval::{CallableMetadata, CallableMetadataBuilder};
+use endbasic_core::exec::{self, Command, Machine};
+use std::io;
+use std::rc::Rc;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::Response;
+
+/// Converts a value rejected by a Javascript promise into an I/O error.
+fn js_error(e: JsValue) -> io::Error {
+    let message = match e.dyn_into::<js_sys::Error>() {
+        Ok(e) => String::from(e.message()),
+        Err(e) => format!("{:?}", e),
+    };
+    io::Error::new(io::ErrorKind::Other, format!("HTTP request failed: {}", message))
+}
+
+/// Fetches the contents of `url` and returns the body of the response as text.
+async fn fetch_text(url: &str) -> io::Result<String> {
+    let window = web_sys::window().expect("Must be running in a browser window");
+    let response = JsFuture::from(window.fetch_with_str(url)).await.map_err(js_error)?;
+    let response: Response = response.dyn_into().expect("fetch must resolve to a Response");
+    if !response.ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "HTTP request failed with status {} {}",
+                response.status(),
+                response.status_text()
+            ),
+        ));
+    }
+    let text = JsFuture::from(response.text().map_err(js_error)?).await.map_err(js_error)?;
+    Ok(text.as_string().expect("text must resolve to a string"))
+}
+
+/// The `HTTPGET` command.
+pub struct HttpGetCommand {
+    metadata: CallableMetadata,
+}
+
+impl HttpGetCommand {
+    /// Creates a new `HTTPGET` command.
+    pub fn new() -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("HTTPGET", VarType::Void)
+                .with_syntax("url$, variableref$")
+                .with_category("Networking")
+                .with_description(
+                    "Fetches a resource over HTTP.
+Issues a GET request for url$ and stores the body of the response, as text, in the given string \
+variable.  Requests are subject to the browser's cross-origin restrictions.  Network failures \
+and responses with a non-successful status are errors.",
+                )
+                .build(),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for HttpGetCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let (url, vref) = match args {
+            [(Some(url), ArgSep::Long), (Some(Expr::Symbol(vref)), ArgSep::End)] => (url, vref),
+            _ => return exec::new_usage_error("HTTPGET requires a URL and a variable reference"),
+        };
+        if vref.ref_type() != VarType::Auto && vref.ref_type() != VarType::Text {
+            return exec::new_usage_error("HTTPGET requires a string variable");
+        }
+
+        let url = match url.eval(machine.get_vars(), machine.get_functions())? {
+            Value::Text(url) => url,
+            _ => return exec::new_usage_error("HTTPGET requires a string as the URL"),
+        };
+
+        let body = fetch_text(&url).await?;
+        machine.set_var(vref, Value::Text(body))?;
+        Ok(())
+    }
+}
+
+/// Adds all HTTP-related commands to the `machine`.
+pub fn add_all(machine: &mut Machine) -> exec::Result<()> {
+    machine.add_command(HttpGetCommand::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use endbasic_core::ast::VarRef;
+    use wasm_bindgen_test::*;
+
+    /// Runs `script` on a machine that only knows about `HTTPGET`.
+    async fn run(script: &str) -> (Machine, exec::Result<()>) {
+        let mut machine = Machine::default();
+        add_all(&mut machine).unwrap();
+        let result = machine.exec(&mut script.as_bytes()).await.map(|_| ());
+        (machine, result)
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_httpget_data_url() {
+        let (machine, result) = run("HTTPGET \"data:text/plain,Hello%2C%20world\", body$").await;
+        result.unwrap();
+        assert_eq!(
+            Value::Text("Hello, world".to_owned()),
+            *machine.get_vars().get(&VarRef::new("body", VarType::Text)).unwrap()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_httpget_network_error() {
+        let (_machine, result) = run("HTTPGET \"invalid://x\", body$").await;
+        assert!(format!("{}", result.unwrap_err()).starts_with("HTTP request failed: "));
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_httpget_errors() {
+        for (script, message) in &[
+            ("HTTPGET", "HTTPGET requires a URL and a variable reference"),
+            ("HTTPGET \"a\"", "HTTPGET requires a URL and a variable reference"),
+            ("HTTPGET \"a\"; b$", "HTTPGET requires a URL and a variable reference"),
+            ("HTTPGET \"a\", b%", "HTTPGET requires a string variable"),
+            ("HTTPGET 3, b$", "HTTPGET requires a string as the URL"),
+        ] {
+            let (_machine, result) = run(script).await;
+            assert_eq!(*message, format!("{}", result.unwrap_err()));
+        }
+    }
+}
@@ -26,17 +26,26 @@ use wasm_bindgen_test::wasm_bindgen_test_configure;
 #[cfg(test)]
 wasm_bindgen_test_configure!(run_in_browser);
 
+mod audio;
+mod http;
+mod screen;
 mod store;
 
 use async_trait::async_trait;
-use endbasic::demos::DemoStoreOverlay;
-use endbasic_std::console::{ClearType, Console, Key, Position};
-use std::cell::RefCell;
+use endbasic::demos::DemoStore;
+use endbasic_std::clock::Clock;
+use endbasic_std::console::{
+    ClearType, Console, DisplayColor, History, Key, Palette, PixelPos, Position,
+};
+use endbasic_std::store::{DriveCommand, DriveStore, InMemoryStore, Store};
+use std::cell::{Cell, RefCell};
 use std::cmp::Ordering;
 use std::io;
 use std::rc::Rc;
+use std::time::Duration;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
 use xterm_js_rs::{OnKeyEvent, Terminal};
 
 /// Converts an xterm.js key event into our own `Key` representation.
@@ -44,6 +53,7 @@ fn on_key_event_into_key(event: OnKeyEvent) -> Key {
     let dom_event = event.dom_event();
     match dom_event.key_code() as u8 {
         8 => Key::Backspace,
+        9 => Key::Tab,
         10 => Key::NewLine,
         13 => Key::CarriageReturn,
         27 => Key::Escape,
@@ -67,10 +77,69 @@ fn on_key_event_into_key(event: OnKeyEvent) -> Key {
     }
 }
 
-/// Implementation of a console that talks directly to an xterm.js terminal.
+/// Converts a color number in xterm's native 256-color palette to its RGB components.
+fn native_to_rgb(color: u8) -> (u8, u8, u8) {
+    const BASIC: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match color {
+        0..=15 => BASIC[usize::from(color)],
+        16..=231 => {
+            let i = usize::from(color - 16);
+            (LEVELS[i / 36], LEVELS[(i / 6) % 6], LEVELS[i % 6])
+        }
+        _ => {
+            let gray = 8 + (color - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// Implementation of a console that talks directly to an xterm.js terminal and that draws
+/// graphics on a canvas laid over it.
 struct XtermJsConsole {
     terminal: Terminal,
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
     on_key_rx: async_channel::Receiver<Key>,
+    palette: Palette,
+
+    /// Foreground color last set by `color`, used to draw when no explicit color is requested.
+    fg: Option<u8>,
+}
+
+impl XtermJsConsole {
+    /// Sets the fill style of the canvas to the color number `color`, or to the current foreground
+    /// color if `None`.
+    fn set_fill_color(&self, color: Option<u8>) {
+        let (r, g, b) = match self.palette.resolve(color.or(self.fg)) {
+            DisplayColor::Default => (255, 255, 255),
+            DisplayColor::Native(color) => native_to_rgb(color),
+            DisplayColor::Rgb(r, g, b) => (r, g, b),
+        };
+        self.context.set_fill_style_str(&format!("rgb({}, {}, {})", r, g, b));
+    }
+
+    /// Fills the single pixel at `pos` with the current fill style.
+    fn fill_pixel(&self, pos: PixelPos) {
+        self.context.fill_rect(pos.x as f64, pos.y as f64, 1.0, 1.0);
+    }
 }
 
 #[async_trait(?Send)]
@@ -80,6 +149,12 @@ impl Console for XtermJsConsole {
             ClearType::All => {
                 self.terminal.write("\u{001b}[2J");
                 self.terminal.write("\u{001b}[0;0H");
+                self.context.clear_rect(
+                    0.0,
+                    0.0,
+                    self.canvas.width() as f64,
+                    self.canvas.height() as f64,
+                );
             }
             ClearType::CurrentLine => {
                 self.terminal.write("\u{001b}[2K");
@@ -92,23 +167,84 @@ impl Console for XtermJsConsole {
     }
 
     fn color(&mut self, fg: Option<u8>, bg: Option<u8>) -> io::Result<()> {
-        match fg {
-            None => self.terminal.write("\u{001b}[39m"),
-            Some(color) => self.terminal.write(&format!("\u{001b}[38;5;{}m", color)),
+        self.fg = fg;
+        match self.palette.resolve(fg) {
+            DisplayColor::Default => self.terminal.write("\u{001b}[39m"),
+            DisplayColor::Native(color) => {
+                self.terminal.write(&format!("\u{001b}[38;5;{}m", color))
+            }
+            DisplayColor::Rgb(r, g, b) => {
+                self.terminal.write(&format!("\u{001b}[38;2;{};{};{}m", r, g, b))
+            }
         };
-        match bg {
-            None => self.terminal.write("\u{001b}[49m"),
-            Some(color) => self.terminal.write(&format!("\u{001b}[48;5;{}m", color)),
+        match self.palette.resolve(bg) {
+            DisplayColor::Default => self.terminal.write("\u{001b}[49m"),
+            DisplayColor::Native(color) => {
+                self.terminal.write(&format!("\u{001b}[48;5;{}m", color))
+            }
+            DisplayColor::Rgb(r, g, b) => {
+                self.terminal.write(&format!("\u{001b}[48;2;{};{};{}m", r, g, b))
+            }
         };
         self.terminal.write("\u{001b}[0K");
         Ok(())
     }
 
+    fn contents(&self) -> io::Result<Vec<String>> {
+        // Only the visible screen is captured: the scrollback is not part of what the program
+        // drew and can be arbitrarily long.
+        let buffer = self.terminal.get_buffer().get_active();
+        let top = buffer.get_viewport_y();
+        let mut rows: Vec<String> = (top..top + self.terminal.get_rows())
+            .map(|y| buffer.get_line(y).translate_to_String(true, None, None))
+            .collect();
+        while rows.last().map(String::is_empty).unwrap_or(false) {
+            rows.pop();
+        }
+        Ok(rows)
+    }
+
+    fn draw_line(&mut self, from: PixelPos, to: PixelPos, color: Option<u8>) -> io::Result<()> {
+        self.set_fill_color(color);
+
+        // Bresenham's line algorithm, drawing pixel by pixel so that lines are not anti-aliased.
+        let (x1, y1, x2, y2) = (from.x as i64, from.y as i64, to.x as i64, to.y as i64);
+        let (dx, dy) = ((x2 - x1).abs(), -(y2 - y1).abs());
+        let (sx, sy) = (if x1 < x2 { 1 } else { -1 }, if y1 < y2 { 1 } else { -1 });
+        let (mut x, mut y, mut err) = (x1, y1, dx + dy);
+        loop {
+            self.fill_pixel(PixelPos { x: x as usize, y: y as usize });
+            if x == x2 && y == y2 {
+                break;
+            }
+            let err2 = 2 * err;
+            if err2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if err2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_pixel(&mut self, pos: PixelPos, color: Option<u8>) -> io::Result<()> {
+        self.set_fill_color(color);
+        self.fill_pixel(pos);
+        Ok(())
+    }
+
     fn enter_alt(&mut self) -> io::Result<()> {
         self.terminal.write("\u{001b}[?1049h");
         Ok(())
     }
 
+    fn graphics_size(&self) -> io::Result<PixelPos> {
+        Ok(PixelPos { x: self.canvas.width() as usize, y: self.canvas.height() as usize })
+    }
+
     fn hide_cursor(&mut self) -> io::Result<()> {
         self.terminal.write("\u{001b}[?25l");
         Ok(())
@@ -137,6 +273,10 @@ impl Console for XtermJsConsole {
         Ok(())
     }
 
+    fn poll_key(&mut self) -> io::Result<Option<Key>> {
+        Ok(self.on_key_rx.try_recv().ok())
+    }
+
     fn print(&mut self, text: &str) -> io::Result<()> {
         self.terminal.write(text);
         self.terminal.write("\u{001b}[K\r\n");
@@ -147,6 +287,11 @@ impl Console for XtermJsConsole {
         Ok(self.on_key_rx.recv().await.unwrap())
     }
 
+    fn set_palette(&mut self, palette: Palette) -> io::Result<()> {
+        self.palette = palette;
+        Ok(())
+    }
+
     fn show_cursor(&mut self) -> io::Result<()> {
         self.terminal.write("\u{001b}[?25h");
         Ok(())
@@ -167,6 +312,36 @@ impl Console for XtermJsConsole {
     }
 }
 
+/// Implementation of a clock that waits for animation frames to yield control to the browser.
+struct AnimationFrameClock {}
+
+#[async_trait(?Send)]
+impl Clock for AnimationFrameClock {
+    fn now(&self) -> Duration {
+        Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        // Always wait for at least one frame, even if the duration is zero, so that the browser
+        // gets a chance to process events and to render the screen.
+        let deadline = self.now() + duration;
+        loop {
+            let frame = js_sys::Promise::new(&mut |resolve, _reject| {
+                web_sys::window()
+                    .expect("Must be running in a browser window")
+                    .request_animation_frame(&resolve)
+                    .expect("Requesting an animation frame must succeed");
+            });
+            wasm_bindgen_futures::JsFuture::from(frame)
+                .await
+                .expect("Animation frame promises are never rejected");
+            if self.now() >= deadline {
+                break;
+            }
+        }
+    }
+}
+
 /// Interface to implement an on-screen keyboard to provide keys that may not be available on
 /// mobile keyboards.
 #[wasm_bindgen]
@@ -207,6 +382,7 @@ impl OnScreenKeyboard {
 pub struct WebTerminal {
     on_key_rx: async_channel::Receiver<Key>,
     on_key_tx: async_channel::Sender<Key>,
+    palette: Palette,
 }
 
 #[wasm_bindgen]
@@ -216,7 +392,15 @@ impl WebTerminal {
     #[allow(clippy::new_without_default)] // Cannot implement Default in wasm-bindgen.
     pub fn new() -> Self {
         let (on_key_tx, on_key_rx) = async_channel::unbounded();
-        Self { on_key_rx, on_key_tx }
+        Self { on_key_rx, on_key_tx, palette: Palette::default() }
+    }
+
+    /// Sets the palette used to display colors, where `rgbs` contains the `0xRRGGBB` values of
+    /// the color numbers starting at zero.
+    pub fn set_palette(&mut self, rgbs: Vec<u32>) {
+        let rgbs: Vec<(u8, u8, u8)> =
+            rgbs.into_iter().map(|rgb| ((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)).collect();
+        self.palette = Palette::from_rgbs(&rgbs);
     }
 
     /// Generates a new `OnScreenKeyboard` that can inject keypresses into this terminal.
@@ -224,26 +408,72 @@ impl WebTerminal {
         OnScreenKeyboard { on_key_tx: self.on_key_tx.clone() }
     }
 
-    /// Starts the EndBASIC interpreter loop on the specified `terminal`.
-    pub async fn run_repl_loop(self, terminal: Terminal) {
+    /// Starts the EndBASIC interpreter loop on the specified `terminal`, drawing graphics on
+    /// `canvas`.
+    pub async fn run_repl_loop(self, terminal: Terminal, canvas: HtmlCanvasElement) {
         let (on_key_tx, on_key_rx) = (self.on_key_tx, self.on_key_rx);
+        let interrupt = Rc::from(Cell::new(false));
         let on_key_callback = {
-            Closure::wrap(Box::new(move |e| {
-                on_key_tx
-                    .try_send(on_key_event_into_key(e))
-                    .expect("Send to unbounded channel must succeed")
+            let interrupt = interrupt.clone();
+            Closure::wrap(Box::new(move |e| match on_key_event_into_key(e) {
+                // Ctrl-C stops the running program instead of being delivered as a key press
+                // because the web interpreter cannot be exited anyway.
+                Key::Interrupt => interrupt.set(true),
+                key => on_key_tx.try_send(key).expect("Send to unbounded channel must succeed"),
             }) as Box<dyn FnMut(OnKeyEvent)>)
         };
         terminal.on_key(on_key_callback.as_ref().unchecked_ref());
 
-        let console = Rc::from(RefCell::from(XtermJsConsole { terminal, on_key_rx }));
-        let store = store::WebStore::from_window();
-        let store = Rc::from(RefCell::from(DemoStoreOverlay::new(store)));
-        let mut machine = endbasic_std::interactive_machine(console.clone(), store.clone());
+        let context = canvas
+            .get_context("2d")
+            .expect("Getting a 2D context must succeed")
+            .expect("Canvas must support 2D contexts")
+            .dyn_into::<CanvasRenderingContext2d>()
+            .expect("2D context must be a CanvasRenderingContext2d");
+        let console = Rc::from(RefCell::from(XtermJsConsole {
+            terminal,
+            canvas,
+            context,
+            on_key_rx,
+            palette: self.palette,
+            fg: None,
+        }));
+        let mut drives = DriveStore::new("LOCAL", Box::from(store::WebStore::from_window()));
+        drives.mount("DEMO", Box::from(DemoStore::default())).expect("Drive names must be unique");
+        drives
+            .mount("MEMORY", Box::from(InMemoryStore::default()))
+            .expect("Drive names must be unique");
+        let drives = Rc::from(RefCell::from(drives));
+        let store: Rc<RefCell<dyn Store>> = drives.clone();
+        let history = Rc::from(RefCell::from(History::default()));
+        let mut machine = endbasic_std::interactive_machine(
+            console.clone(),
+            store.clone(),
+            history.clone(),
+            vec![],
+        );
+        machine
+            .add_command(DriveCommand::new(console.clone(), drives))
+            .expect("DRIVE must not be registered yet");
+        machine.set_interrupt_source(interrupt);
+        let clock: Rc<dyn Clock> = Rc::from(AnimationFrameClock {});
+        endbasic_std::clock::add_all(&mut machine, console.clone(), clock.clone())
+            .expect("Clock commands must not be registered yet");
+        endbasic_std::sound::add_all(&mut machine, Rc::from(audio::WebAudioSpeaker::new(clock)))
+            .expect("Sound commands must not be registered yet");
+        http::add_all(&mut machine).expect("HTTP commands must not be registered yet");
+        screen::add_all(&mut machine, console.clone())
+            .expect("Screen commands must not be registered yet");
         endbasic::print_welcome(console.clone()).unwrap();
-        endbasic::try_load_autoexec(&mut machine, console.clone(), store).unwrap();
+        endbasic::try_load_autoexec(&mut machine, console.clone(), store.clone()).unwrap();
         loop {
-            let result = endbasic::run_repl_loop(&mut machine, console.clone()).await;
+            let result = endbasic::run_repl_loop(
+                &mut machine,
+                console.clone(),
+                store.clone(),
+                history.clone(),
+            )
+            .await;
             let mut console = console.borrow_mut();
             match result {
                 Ok(exit_code) => {
@@ -266,6 +496,20 @@ pub fn get_build_id() -> String {
     format!("{} built on {}", env!("VERGEN_SHA_SHORT"), env!("VERGEN_BUILD_DATE"))
 }
 
+/// Exports the stored program `name` as plain text, without any of the metadata that the local
+/// storage keeps for it.  This is intended to let users download their programs.
+#[wasm_bindgen]
+pub fn export_program(name: &str) -> Result<String, JsValue> {
+    store::WebStore::from_window().export(name).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Imports the plain `text` of a program into the local storage as `name`.  This is intended to
+/// let users upload programs they previously exported.
+#[wasm_bindgen]
+pub fn import_program(name: &str, text: &str) -> Result<(), JsValue> {
+    store::WebStore::from_window().import(name, text).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
 /// Module initialization.
 pub fn main() -> Result<(), JsValue> {
     #[cfg(feature = "console_error_panic_hook")]
@@ -0,0 +1,181 @@
+// EndBASIC
+// Copyright 2021 Julio Merino
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not
+// use this file except in compliance with the License.  You may obtain a copy
+// of the License at:
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.  See the
+// License for the specific language governing permissions and limitations
+// under the License.
+
+//! Commands to export the contents of the web console as images.
+
+use async_trait::async_trait;
+use endbasic_core::ast::{ArgSep, Expr, Value, VarType};
+use endbasic_core::eval::{CallableMetadata, CallableMetadataBuilder};
+use endbasic_core::exec::{self, Command, Machine};
+use endbasic_std::console::{Console, Position};
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, Document, HtmlAnchorElement, HtmlCanvasElement};
+
+/// Width of a character cell in the exported image, in pixels.
+const CELL_WIDTH: u32 = 8;
+
+/// Height of a character cell in the exported image, in pixels.
+const CELL_HEIGHT: u32 = 16;
+
+/// Font used to draw the characters in the exported image.  Must fit in a cell.
+const FONT: &str = "14px monospace";
+
+/// Default name of the file to download when none is given to `SAVESCREEN`.
+const DEFAULT_FILENAME: &str = "screen.png";
+
+/// Converts an error raised by the browser into an I/O error.
+fn js_error(e: JsValue) -> io::Error {
+    let message = match e.dyn_into::<js_sys::Error>() {
+        Ok(e) => String::from(e.message()),
+        Err(e) => format!("{:?}", e),
+    };
+    io::Error::new(io::ErrorKind::Other, format!("Cannot export screen: {}", message))
+}
+
+/// Returns the document of the browser window we are running in.
+fn document() -> Document {
+    web_sys::window()
+        .expect("Must be running in a browser window")
+        .document()
+        .expect("Window must have a document")
+}
+
+/// Renders the `lines` of text into a new canvas of `size` character cells, with white text on a
+/// black background.  Lines and characters that fall outside of `size` are ignored.
+fn render_grid(lines: &[String], size: Position) -> Result<HtmlCanvasElement, JsValue> {
+    let canvas = document().create_element("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+    canvas.set_width(size.column as u32 * CELL_WIDTH);
+    canvas.set_height(size.row as u32 * CELL_HEIGHT);
+
+    let context = canvas
+        .get_context("2d")?
+        .expect("Canvas must support 2D contexts")
+        .dyn_into::<CanvasRenderingContext2d>()?;
+    context.set_fill_style_str("black");
+    context.fill_rect(0.0, 0.0, canvas.width() as f64, canvas.height() as f64);
+    context.set_fill_style_str("white");
+    context.set_font(FONT);
+    context.set_text_baseline("top");
+    for (row, line) in lines.iter().take(size.row).enumerate() {
+        for (column, ch) in line.chars().take(size.column).enumerate() {
+            context.fill_text(
+                &ch.to_string(),
+                (column as u32 * CELL_WIDTH) as f64,
+                (row as u32 * CELL_HEIGHT) as f64,
+            )?;
+        }
+    }
+    Ok(canvas)
+}
+
+/// Asks the browser to download the resource at `url` as a file called `filename`.
+fn download(url: &str, filename: &str) -> Result<(), JsValue> {
+    let anchor = document().create_element("a")?.dyn_into::<HtmlAnchorElement>()?;
+    anchor.set_href(url);
+    anchor.set_download(filename);
+    anchor.click();
+    Ok(())
+}
+
+/// The `SAVESCREEN` command.
+pub struct SaveScreenCommand {
+    metadata: CallableMetadata,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl SaveScreenCommand {
+    /// Creates a new `SAVESCREEN` command that exports the contents of `console`.
+    pub fn new(console: Rc<RefCell<dyn Console>>) -> Rc<Self> {
+        Rc::from(Self {
+            metadata: CallableMetadataBuilder::new("SAVESCREEN", VarType::Void)
+                .with_syntax("[filename$]")
+                .with_category("Console manipulation")
+                .with_description(
+                    "Downloads an image of the console.
+Renders the text currently visible on the console as a PNG image, with white characters on a \
+black background, and asks the browser to download it as filename$, or as screen.png if no name \
+is given.  A .png extension is added to the name if it does not have one.
+Only the visible screen is captured: colors, graphics and lines that scrolled out of view are not \
+part of the image.",
+                )
+                .build(),
+            console,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Command for SaveScreenCommand {
+    fn metadata(&self) -> &CallableMetadata {
+        &self.metadata
+    }
+
+    async fn exec(
+        &self,
+        args: &[(Option<Expr>, ArgSep)],
+        machine: &mut Machine,
+    ) -> exec::Result<()> {
+        let filename = match args {
+            [] => DEFAULT_FILENAME.to_owned(),
+            [(Some(expr), ArgSep::End)] => {
+                match expr.eval(machine.get_vars(), machine.get_functions())? {
+                    Value::Text(t) if t.is_empty() => {
+                        return exec::new_usage_error("SAVESCREEN requires a non-empty filename")
+                    }
+                    Value::Text(t) if t.to_lowercase().ends_with(".png") => t,
+                    Value::Text(t) => t + ".png",
+                    _ => {
+                        return exec::new_usage_error(
+                            "SAVESCREEN requires a string as the filename",
+                        )
+                    }
+                }
+            }
+            _ => return exec::new_usage_error("SAVESCREEN takes zero or one arguments"),
+        };
+
+        let console = self.console.borrow();
+        let canvas = render_grid(&console.contents()?, console.size()?).map_err(js_error)?;
+        let url = canvas.to_data_url().map_err(js_error)?;
+        download(&url, &filename).map_err(js_error)?;
+        Ok(())
+    }
+}
+
+/// Adds all screen-related commands for the `console` to the `machine`.
+pub fn add_all(machine: &mut Machine, console: Rc<RefCell<dyn Console>>) -> exec::Result<()> {
+    machine.add_command(SaveScreenCommand::new(console))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_render_grid_dimensions() {
+        let lines = vec!["Hello".to_owned(), "".to_owned(), "this line is too long".to_owned()];
+        let canvas = render_grid(&lines, Position { row: 3, column: 10 }).unwrap();
+        assert_eq!(10 * CELL_WIDTH, canvas.width());
+        assert_eq!(3 * CELL_HEIGHT, canvas.height());
+
+        let url = canvas.to_data_url().unwrap();
+        assert!(url.starts_with("data:image/png;base64,"));
+        assert!(url.len() > "data:image/png;base64,".len());
+    }
+}
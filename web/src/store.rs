@@ -106,6 +106,11 @@ impl Entry {
         Self { version: Entry::VERSION, content: content.into(), mtime }
     }
 
+    /// Returns the raw program text of this entry, without the serialization envelope.
+    fn to_plain_text(&self) -> &str {
+        &self.content
+    }
+
     /// Returns the generic `Metadata` object for this entry.
     fn metadata(&self) -> Metadata {
         // I'm sure there is something wrong with this timezone adjustment.
@@ -122,15 +127,34 @@ pub struct WebStore {
 
     /// Clock used by this store to generate mtime values.
     clock: Box<dyn Clock>,
+
+    /// Maximum length in bytes of the content of a single program.
+    max_size: usize,
 }
 
 impl WebStore {
-    /// Creates a new store for the current window.
+    /// Default maximum length in bytes of the content of a single program.
+    ///
+    /// Browsers typically cap the local storage at around 5MB per origin, and strings are stored
+    /// in UTF-16, so this leaves plenty of room for other programs while still being far larger
+    /// than any program anyone would reasonably type.
+    pub const DEFAULT_MAX_SIZE: usize = 512 * 1024;
+
+    /// Creates a new store for the current window that accepts programs of up to
+    /// `DEFAULT_MAX_SIZE` bytes.
     pub fn from_window() -> Self {
+        WebStore::from_window_with_max_size(WebStore::DEFAULT_MAX_SIZE)
+    }
+
+    /// Creates a new store for the current window that accepts programs of up to `max_size` bytes.
+    ///
+    /// Attempts to store larger programs fail before touching the local storage, which is much
+    /// friendlier than the opaque quota errors the browser would raise when the storage fills up.
+    pub fn from_window_with_max_size(max_size: usize) -> Self {
         // TODO(jmmv): Should probably do something fancier here instead of these unwraps...
         let window = web_sys::window().unwrap();
         let storage = window.local_storage().unwrap().unwrap();
-        let mut store = Self { clock: Box::from(JsClock::default()), storage };
+        let mut store = Self { clock: Box::from(JsClock::default()), storage, max_size };
         store.fixup_names().unwrap();
         store
     }
@@ -199,6 +223,47 @@ impl WebStore {
         Ok(())
     }
 
+    /// Exports the program `name` as plain text, without the JSON envelope used to keep it in the
+    /// local storage.  This is suitable for sharing the program or for version control.
+    pub fn export(&self, name: &str) -> io::Result<String> {
+        let entry = self.get_entry(&Key::for_name(name))?;
+        Ok(entry.to_plain_text().to_owned())
+    }
+
+    /// Imports the plain `text` of a program as `name`, wrapping it in a new entry whose
+    /// modification time is the current time.  Any existing program with the same name is
+    /// replaced.
+    pub fn import(&mut self, name: &str, text: &str) -> io::Result<()> {
+        let key = Key::for_name(name);
+        let entry = Entry::new(text, self.clock.now());
+        self.put_entry(&key, &entry)
+    }
+
+    /// Serializes and stores the `entry` under `key`.
+    ///
+    /// Fails if the content of the `entry` is larger than the maximum size configured in the store.
+    fn put_entry(&self, key: &Key, entry: &Entry) -> io::Result<()> {
+        if entry.content.len() > self.max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Program too large: {} bytes exceeds the limit of {} bytes",
+                    entry.content.len(),
+                    self.max_size
+                ),
+            ));
+        }
+
+        let key = key.serialized();
+        match self.storage.set(key, &serde_json::to_string(entry)?) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Failed to put local storage entry with key {}: {:?}", key, e),
+            )),
+        }
+    }
+
     /// Obtains and parses the entry given by `key`.
     fn get_entry(&self, key: &Key) -> io::Result<Entry> {
         let key = key.serialized();
@@ -282,15 +347,7 @@ impl Store for WebStore {
         // There is no information we care about the old entry so we can replace it all in one go
         // with a new one.
         let entry = Entry::new(content, self.clock.now());
-
-        let key = key.serialized();
-        match self.storage.set(key, &serde_json::to_string(&entry)?) {
-            Ok(()) => Ok(()),
-            Err(e) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Failed to put local storage entry with key {}: {:?}", key, e),
-            )),
-        }
+        self.put_entry(&key, &entry)
     }
 }
 
@@ -452,4 +509,74 @@ mod tests {
             webstore.storage.get("endbasic-program:CODE.BAS").unwrap().unwrap()
         );
     }
+
+    #[wasm_bindgen_test]
+    fn test_webstore_put_max_size() {
+        let mut webstore = WebStore::from_window_with_max_size(10);
+        webstore.storage.clear().unwrap();
+
+        webstore.put("fits.bas", "0123456789").unwrap();
+        assert_eq!("0123456789", webstore.get("fits.bas").unwrap());
+
+        let err = webstore.put("big.bas", "0123456789A").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert_eq!("Program too large: 11 bytes exceeds the limit of 10 bytes", format!("{}", err));
+        assert!(webstore.storage.get("endbasic-program:BIG.BAS").unwrap().is_none());
+
+        let err = webstore.import("big.bas", "0123456789A").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert!(webstore.storage.get("endbasic-program:BIG.BAS").unwrap().is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_webstore_export() {
+        let entry = Entry {
+            version: Entry::VERSION,
+            content: "PRINT \"hello\"\n".to_owned(),
+            mtime: time::OffsetDateTime::from_unix_timestamp(1234),
+        };
+
+        let webstore = WebStore::from_window();
+        webstore.storage.clear().unwrap();
+        webstore
+            .storage
+            .set("endbasic-program:HELLO.BAS", &serde_json::to_string(&entry).unwrap())
+            .unwrap();
+
+        assert_eq!(entry.content, webstore.export("hello.bas").unwrap());
+        assert_eq!("File not found", format!("{}", webstore.export("other.bas").unwrap_err()));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_webstore_import() {
+        let entry = Entry {
+            version: Entry::VERSION,
+            content: "PRINT 1\r\nPRINT \"two\"\n\n".to_owned(),
+            mtime: time::OffsetDateTime::from_unix_timestamp(1_234_567),
+        };
+
+        let mut webstore = WebStore::from_window();
+        webstore.clock = Box::from(FakeClock { now: 1_234_567 });
+        webstore.storage.clear().unwrap();
+        webstore.import("code.bas", &entry.content).unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            webstore.storage.get("endbasic-program:CODE.BAS").unwrap().unwrap()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_webstore_export_import_round_trip() {
+        let text = "' A program.\nFOR i = 1 TO 3\n    PRINT i; \"\\\"\"\nNEXT\n";
+
+        let mut webstore = WebStore::from_window();
+        webstore.storage.clear().unwrap();
+        webstore.import("round.bas", text).unwrap();
+        let exported = webstore.export("ROUND.BAS").unwrap();
+        assert_eq!(text, exported);
+
+        webstore.import("again.bas", &exported).unwrap();
+        assert_eq!(text, webstore.export("again.bas").unwrap());
+    }
 }